@@ -8,10 +8,11 @@
 //! Enables hybrid queries (SQL + vector + JSON).
 
 use my_ai_db::storage::{Document, Storage};
-use my_ai_db::tenants::{User, Tenant, Environment, Collection};
+use my_ai_db::tenants::{Role, User, Tenant, Environment, Collection};
 use my_ai_db::auth::hash_password;
 use my_ai_db::query::QueryEngine;
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[tokio::main]
@@ -24,6 +25,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         username: "admin".to_string(),
         password_hash: hash_password("admin").unwrap(),
         tenants: vec!["default_tenant".to_string()],
+        roles: vec![Role::Admin],
+        context: None,
     };
     let _ = storage.create_user(user); // Ignore if exists
 
@@ -47,6 +50,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         id: "default_collection".to_string(),
         name: "Default Collection".to_string(),
         environment_id: "default_env".to_string(),
+        vector_dim: None,
+        metric: Default::default(),
+        normalize: false,
     };
     let _ = storage.create_collection(col);
 
@@ -79,6 +85,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             category: category.to_string(),
             vector: vector.clone(),
             metadata: metadata_json,
+            vectors: HashMap::new(),
+            updated_at: None,
+            source_uri: None,
+            ingested_by: None,
         };
         storage.insert_doc(doc, collection_id)?;
     }
@@ -87,7 +97,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Demo indexing engine
     let all_vectors = storage.get_vectors_in_collection(collection_id)?;
-    let _index = my_ai_db::indexing::VectorIndex::build_from_vectors(all_vectors);
+    let _index = my_ai_db::indexing::VectorIndex::build_from_vectors(all_vectors, my_ai_db::indexing::DistanceMetric::L2);
     println!("✅ Built HNSW index for vector search");
 
     // Demo SQL/DataFusion on projection + hybrid planner
@@ -1,11 +1,197 @@
 use bcrypt::{hash, verify, DEFAULT_COST};
 use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey, Algorithm};
-use crate::tenants::AuthPayload;
+use crate::storage::Storage;
+use crate::tenants::{AuthPayload, Role};
 use crate::session::get_session_manager;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tracing::{debug, info, instrument};
+use crate::config::{AuthConfig, DEFAULT_JWT_TTL_SECS};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn, error, instrument};
 
-const SECRET_KEY: &[u8] = b"my_super_secret_key"; // In prod, use env var
+static AUTH_CONFIG: OnceLock<AuthConfig> = OnceLock::new();
+/// HMAC key signing and verifying tokens, fixed for the life of the process once first used
+static SIGNING_KEY: OnceLock<Vec<u8>> = OnceLock::new();
+/// Hash checked when a login names no user, so that costs the same bcrypt verify as a wrong password
+static DUMMY_PASSWORD_HASH: OnceLock<String> = OnceLock::new();
+
+/// Message of every rejected login, whether the user is missing or the password is wrong
+pub const INVALID_CREDENTIALS: &str = "Invalid username or password";
+
+/// No `AIDB_JWT_SECRET` in a release build, which refuses to sign with a key nobody configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingJwtSecret;
+
+impl std::fmt::Display for MissingJwtSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "AIDB_JWT_SECRET must be set: release builds have no fallback token signing key")
+    }
+}
+
+impl std::error::Error for MissingJwtSecret {}
+
+/// Install the signing key and token lifetime parsed at startup; later calls are ignored.
+/// Fails in release builds when no secret is configured, so the server refuses to boot.
+pub fn configure(config: AuthConfig) -> Result<(), MissingJwtSecret> {
+    if AUTH_CONFIG.set(config).is_err() {
+        debug!("Auth already configured; keeping the first configuration");
+    }
+    // Hash now rather than on the first unknown-user login, which would take twice as long
+    dummy_password_hash();
+    signing_key().map(|_| ())
+}
+
+/// 256 bits from the OS RNG, for debug builds started without a secret
+fn random_key() -> Vec<u8> {
+    [uuid::Uuid::new_v4().into_bytes(), uuid::Uuid::new_v4().into_bytes()].concat()
+}
+
+/// The configured secret (or `AIDB_JWT_SECRET` when `configure` wasn't called); in debug builds
+/// without one, a random key, so tokens don't survive a restart
+fn signing_key() -> Result<&'static [u8], MissingJwtSecret> {
+    if let Some(key) = SIGNING_KEY.get() {
+        return Ok(key);
+    }
+    let secret = match AUTH_CONFIG.get() {
+        Some(config) => config.jwt_secret.clone(),
+        None => AuthConfig::from_env().jwt_secret,
+    };
+    let key = match secret {
+        Some(secret) => secret.into_bytes(),
+        None if cfg!(debug_assertions) => {
+            warn!("AIDB_JWT_SECRET is unset; signing tokens with a random per-process key (debug build)");
+            random_key()
+        }
+        None => return Err(MissingJwtSecret),
+    };
+    Ok(SIGNING_KEY.get_or_init(|| key))
+}
+
+/// Claims of a token issued now and valid for `ttl_secs`
+fn claims_for(username: &str, session_id: Option<String>, ttl_secs: usize, roles: &[Role]) -> AuthPayload {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as usize;
+    AuthPayload {
+        sub: username.to_owned(),
+        exp: now + ttl_secs,
+        session_id,
+        iat: Some(now),
+        roles: roles.to_vec(),
+    }
+}
+
+/// A caller lacking the role an operation requires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forbidden {
+    pub username: String,
+    pub required: Role,
+}
+
+impl std::fmt::Display for Forbidden {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "User '{}' lacks the {:?} role", self.username, self.required)
+    }
+}
+
+impl std::error::Error for Forbidden {}
+
+impl From<Forbidden> for axum::http::StatusCode {
+    fn from(_: Forbidden) -> Self {
+        axum::http::StatusCode::FORBIDDEN
+    }
+}
+
+impl From<Forbidden> for tonic::Status {
+    fn from(e: Forbidden) -> Self {
+        tonic::Status::permission_denied(e.to_string())
+    }
+}
+
+/// `Err(Forbidden)` unless the token grants `role`; `?` turns it into a `403` or `PERMISSION_DENIED`
+pub fn require_role(claims: &AuthPayload, role: Role) -> Result<(), Forbidden> {
+    if claims.has_role(role) {
+        return Ok(());
+    }
+    warn!(username = %claims.sub, required = ?role, held = ?claims.roles, "Denied: missing role");
+    Err(Forbidden { username: claims.sub.clone(), required: role })
+}
+
+/// Why a caller may not use a collection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollectionAccessError {
+    /// The collection belongs to a tenant the caller neither owns nor was granted
+    Denied { username: String, collection_id: String },
+    /// The ownership chain couldn't be read
+    Lookup(String),
+}
+
+impl std::fmt::Display for CollectionAccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Denied { username, collection_id } => {
+                write!(f, "User '{}' has no access to collection '{}'", username, collection_id)
+            }
+            Self::Lookup(e) => write!(f, "Collection ownership lookup failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CollectionAccessError {}
+
+impl From<CollectionAccessError> for axum::http::StatusCode {
+    fn from(e: CollectionAccessError) -> Self {
+        match e {
+            CollectionAccessError::Denied { .. } => axum::http::StatusCode::FORBIDDEN,
+            CollectionAccessError::Lookup(_) => axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<CollectionAccessError> for tonic::Status {
+    fn from(e: CollectionAccessError) -> Self {
+        match e {
+            CollectionAccessError::Denied { .. } => tonic::Status::permission_denied(e.to_string()),
+            CollectionAccessError::Lookup(_) => tonic::Status::internal(e.to_string()),
+        }
+    }
+}
+
+/// `Err` unless the caller owns, or was granted, the tenant owning `collection_id` (see
+/// `Storage::can_access_collection`); `?` turns a denial into a `403` or `PERMISSION_DENIED`
+pub fn require_collection_access(storage: &Storage, claims: &AuthPayload, collection_id: &str) -> Result<(), CollectionAccessError> {
+    match storage.can_access_collection(&claims.sub, collection_id) {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            warn!(username = %claims.sub, collection_id = %collection_id, "Denied: collection owned by another tenant");
+            Err(CollectionAccessError::Denied { username: claims.sub.clone(), collection_id: collection_id.to_string() })
+        }
+        Err(e) => {
+            error!(error = %e, collection_id = %collection_id, "Collection ownership lookup failed");
+            Err(CollectionAccessError::Lookup(e.to_string()))
+        }
+    }
+}
+
+fn sign(claims: &AuthPayload, key: &[u8]) -> Result<String, jsonwebtoken::errors::Error> {
+    encode(&Header::default(), claims, &EncodingKey::from_secret(key))
+}
+
+fn verify_token(token: &str, key: &[u8]) -> Result<AuthPayload, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    // Tokens are issued and checked by the same server clock, so a short TTL means what it says
+    validation.leeway = 0;
+    Ok(decode::<AuthPayload>(token, &DecodingKey::from_secret(key), &validation)?.claims)
+}
+
+/// `signing_key` as a token error, for the functions whose signatures predate it
+fn token_key() -> Result<&'static [u8], jsonwebtoken::errors::Error> {
+    signing_key().map_err(|e| {
+        error!(error = %e, "No token signing key");
+        jsonwebtoken::errors::ErrorKind::InvalidKeyFormat.into()
+    })
+}
+
+fn jwt_ttl_secs() -> usize {
+    AUTH_CONFIG.get().map(|c| c.jwt_ttl_secs).unwrap_or(DEFAULT_JWT_TTL_SECS) as usize
+}
 
 #[instrument(skip(password))]
 pub fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
@@ -19,27 +205,47 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool, bcrypt::Bcryp
     verify(password, hash)
 }
 
+fn dummy_password_hash() -> &'static str {
+    DUMMY_PASSWORD_HASH.get_or_init(|| hash_password(&uuid::Uuid::new_v4().to_string()).unwrap_or_default())
+}
+
+/// Check a login's password against the user's hash, or against a dummy hash when `password_hash`
+/// is `None` (no such user) so both failures take a bcrypt verify. The check takes at least
+/// `AIDB_LOGIN_MIN_VERIFY_MS`; callers answer either failure with `INVALID_CREDENTIALS`.
+pub async fn verify_login(password: String, password_hash: Option<String>) -> bool {
+    let started = Instant::now();
+    let min_verify = AUTH_CONFIG.get().map(|c| c.login_min_verify).unwrap_or(Duration::ZERO);
+    let verified = tokio::task::spawn_blocking(move || match password_hash {
+        Some(hash) => verify_password(&password, &hash).unwrap_or(false),
+        None => {
+            let _ = verify_password(&password, dummy_password_hash());
+            false
+        }
+    })
+    .await
+    .unwrap_or(false);
+    tokio::time::sleep(min_verify.saturating_sub(started.elapsed())).await;
+    verified
+}
+
+/// Token without a session or roles: it can read but not write
 #[instrument(skip(username))]
 pub fn create_jwt(username: &str) -> Result<String, jsonwebtoken::errors::Error> {
-    debug!(username = %username, "Creating JWT token");
-    
-    let expiration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as usize + 3600; // 1 hour
-
-    let claims = AuthPayload {
-        sub: username.to_owned(),
-        exp: expiration,
-        session_id: None,
-    };
+    create_jwt_with_roles(username, &[])
+}
 
-    encode(&Header::default(), &claims, &EncodingKey::from_secret(SECRET_KEY))
+/// Token without a session carrying `roles`, e.g. for service accounts
+#[instrument(skip(username))]
+pub fn create_jwt_with_roles(username: &str, roles: &[Role]) -> Result<String, jsonwebtoken::errors::Error> {
+    debug!(username = %username, ?roles, "Creating JWT token");
+    
+    let claims = claims_for(username, None, jwt_ttl_secs(), roles);
+    sign(&claims, token_key()?)
 }
 
-/// Create a JWT with a new session for the user
+/// Create a JWT with a new session for the user, carrying their `roles`
 #[instrument(skip(username))]
-pub fn create_jwt_with_session(username: &str) -> Result<(String, String), jsonwebtoken::errors::Error> {
+pub fn create_jwt_with_session(username: &str, roles: &[Role]) -> Result<(String, String), jsonwebtoken::errors::Error> {
     debug!(username = %username, "Creating JWT token with session");
     
     // Create a new session
@@ -48,18 +254,8 @@ pub fn create_jwt_with_session(username: &str) -> Result<(String, String), jsonw
     
     info!(username = %username, session_id = %session_id, "New session created");
     
-    let expiration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as usize + 3600; // 1 hour
-
-    let claims = AuthPayload {
-        sub: username.to_owned(),
-        exp: expiration,
-        session_id: Some(session_id.clone()),
-    };
-
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(SECRET_KEY))?;
+    let claims = claims_for(username, Some(session_id.clone()), jwt_ttl_secs(), roles);
+    let token = sign(&claims, token_key()?)?;
     Ok((token, session_id))
 }
 
@@ -67,12 +263,57 @@ pub fn create_jwt_with_session(username: &str) -> Result<(String, String), jsonw
 pub fn validate_jwt(token: &str) -> Result<AuthPayload, jsonwebtoken::errors::Error> {
     debug!("Validating JWT token");
     
-    let token_data = decode::<AuthPayload>(
-        token,
-        &DecodingKey::from_secret(SECRET_KEY),
-        &Validation::new(Algorithm::HS256),
-    )?;
+    let claims = verify_token(token, token_key()?)?;
     
-    debug!(username = %token_data.claims.sub, "JWT token validated successfully");
-    Ok(token_data.claims)
+    debug!(username = %claims.sub, "JWT token validated successfully");
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_signed_with_one_secret_fails_under_another() {
+        let claims = AuthPayload { sub: "alice".to_string(), exp: usize::MAX / 2, session_id: None, iat: None, roles: vec![] };
+        let token = sign(&claims, b"first-deployment-secret").unwrap();
+
+        assert_eq!(verify_token(&token, b"first-deployment-secret").unwrap().sub, "alice");
+        let forged = verify_token(&token, b"second-deployment-secret").unwrap_err();
+        assert_eq!(*forged.kind(), jsonwebtoken::errors::ErrorKind::InvalidSignature);
+
+        // The debug fallback differs per process (and per call before it is pinned)
+        assert_ne!(random_key(), random_key());
+        assert_eq!(random_key().len(), 32);
+        // Whatever key the process settled on, its own tokens round-trip
+        assert_eq!(validate_jwt(&create_jwt("bob").unwrap()).unwrap().sub, "bob");
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let claims = claims_for("carol", None, 1, &[]);
+        assert_eq!(claims.exp, claims.iat.unwrap() + 1);
+        let token = sign(&claims, b"ttl-secret").unwrap();
+        let valid = verify_token(&token, b"ttl-secret").unwrap();
+        assert_eq!((valid.sub.as_str(), valid.iat), ("carol", claims.iat));
+
+        std::thread::sleep(std::time::Duration::from_millis(2100));
+        let expired = verify_token(&token, b"ttl-secret").unwrap_err();
+        assert_eq!(*expired.kind(), jsonwebtoken::errors::ErrorKind::ExpiredSignature);
+    }
+
+    #[test]
+    fn roles_travel_in_the_token_and_gate_operations() {
+        let token = create_jwt_with_roles("dave", &[Role::Writer]).unwrap();
+        let claims = validate_jwt(&token).unwrap();
+        assert_eq!(claims.roles, vec![Role::Writer]);
+        assert_eq!(require_role(&claims, Role::Reader), Ok(()));
+        assert_eq!(require_role(&claims, Role::Writer), Ok(()));
+        let denied = require_role(&claims, Role::Admin).unwrap_err();
+        assert_eq!(axum::http::StatusCode::from(denied.clone()), axum::http::StatusCode::FORBIDDEN);
+        assert_eq!(tonic::Status::from(denied).code(), tonic::Code::PermissionDenied);
+
+        let no_roles = validate_jwt(&create_jwt("erin").unwrap()).unwrap();
+        assert!(no_roles.has_role(Role::Reader) && !no_roles.has_role(Role::Writer));
+    }
 }
@@ -1,5 +1,6 @@
+use serde::Serialize;
 use std::collections::{HashMap, VecDeque};
-use tracing::{debug, trace, instrument};
+use tracing::{debug, info, trace, instrument};
 
 use crate::storage::Document;
 
@@ -9,12 +10,52 @@ pub struct CacheEntry {
     pub size_bytes: usize,
 }
 
+/// Why an entry was pushed out of the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionReason {
+    /// Making room for an insert
+    Capacity,
+    /// The capacity was lowered below the current size
+    Resize,
+}
+
+impl EvictionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EvictionReason::Capacity => "capacity",
+            EvictionReason::Resize => "resize",
+        }
+    }
+}
+
+/// Point-in-time cache occupancy plus lifetime eviction and lookup totals
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub capacity_bytes: usize,
+    pub size_bytes: usize,
+    pub entries: usize,
+    /// Entries evicted (LRU) since the cache was created; explicit removals aren't counted
+    pub evictions: u64,
+    /// Estimated bytes freed by those evictions
+    pub evicted_bytes: u64,
+    /// Lookups answered from the cache since it was created
+    pub hits: u64,
+    /// Lookups that had to go to storage
+    pub misses: u64,
+}
+
 #[derive(Debug)]
 pub struct DocCache {
     capacity_bytes: usize,
     size_bytes: usize,
     entries: HashMap<String, CacheEntry>,
     lru_order: VecDeque<String>,
+    evictions: u64,
+    evicted_bytes: u64,
+    hits: u64,
+    misses: u64,
+    /// Log every eviction at info level (otherwise only trace)
+    log_evictions: bool,
 }
 
 impl DocCache {
@@ -26,18 +67,30 @@ impl DocCache {
             size_bytes: 0,
             entries: HashMap::new(),
             lru_order: VecDeque::new(),
+            evictions: 0,
+            evicted_bytes: 0,
+            hits: 0,
+            misses: 0,
+            log_evictions: false,
         }
     }
 
+    /// Log each eviction (key, size, reason) at info level
+    pub fn set_log_evictions(&mut self, enabled: bool) {
+        self.log_evictions = enabled;
+    }
+
     #[instrument(skip(self))]
     pub fn get(&mut self, id: &str) -> Option<Document> {
         if let Some(entry) = self.entries.get(id) {
             let doc_clone = entry.doc.clone();
             let size_bytes = entry.size_bytes;
             self.touch(id);
+            self.hits += 1;
             trace!(id = %id, size_bytes = size_bytes, "Cache hit");
             return Some(doc_clone);
         }
+        self.misses += 1;
         trace!(id = %id, "Cache miss");
         None
     }
@@ -65,13 +118,10 @@ impl DocCache {
         // Evict entries if necessary
         let mut evicted_count = 0;
         while self.size_bytes + size_bytes > self.capacity_bytes {
-            if let Some(evict_id) = self.lru_order.pop_back() {
-                if let Some(evicted) = self.entries.remove(&evict_id) {
-                    self.size_bytes = self.size_bytes.saturating_sub(evicted.size_bytes);
-                    evicted_count += 1;
-                }
-            } else {
-                break;
+            match self.evict_lru(EvictionReason::Capacity) {
+                Some(true) => evicted_count += 1,
+                Some(false) => {}
+                None => break,
             }
         }
         
@@ -100,6 +150,84 @@ impl DocCache {
         }
     }
 
+    pub fn capacity_bytes(&self) -> usize {
+        self.capacity_bytes
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.size_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Drop every entry (capacity is unchanged)
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.lru_order.clear();
+        self.size_bytes = 0;
+    }
+
+    /// Change the capacity, evicting least-recently-used entries until the cache fits.
+    /// Returns the number of evicted entries.
+    #[instrument(skip(self))]
+    pub fn set_capacity(&mut self, capacity_bytes: usize) -> usize {
+        self.capacity_bytes = capacity_bytes;
+
+        let mut evicted_count = 0;
+        while self.size_bytes > self.capacity_bytes {
+            match self.evict_lru(EvictionReason::Resize) {
+                Some(true) => evicted_count += 1,
+                Some(false) => {}
+                None => break,
+            }
+        }
+
+        debug!(
+            capacity_bytes = capacity_bytes,
+            current_size_bytes = self.size_bytes,
+            evicted_count = evicted_count,
+            "Cache capacity changed"
+        );
+        evicted_count
+    }
+
+    /// Occupancy and eviction totals
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            capacity_bytes: self.capacity_bytes,
+            size_bytes: self.size_bytes,
+            entries: self.entries.len(),
+            evictions: self.evictions,
+            evicted_bytes: self.evicted_bytes,
+            hits: self.hits,
+            misses: self.misses,
+        }
+    }
+
+    /// Drop the least-recently-used entry and account for it. `None` when the LRU list is
+    /// empty; `Some(false)` when its key no longer had an entry.
+    fn evict_lru(&mut self, reason: EvictionReason) -> Option<bool> {
+        let evict_id = self.lru_order.pop_back()?;
+        let Some(evicted) = self.entries.remove(&evict_id) else {
+            return Some(false);
+        };
+        self.size_bytes = self.size_bytes.saturating_sub(evicted.size_bytes);
+        self.evictions += 1;
+        self.evicted_bytes += evicted.size_bytes as u64;
+        if self.log_evictions {
+            info!(id = %evict_id, size_bytes = evicted.size_bytes, reason = reason.as_str(), "Cache entry evicted");
+        } else {
+            trace!(id = %evict_id, size_bytes = evicted.size_bytes, reason = reason.as_str(), "Cache entry evicted");
+        }
+        Some(true)
+    }
+
     #[instrument(skip(self))]
     fn touch(&mut self, id: &str) {
         self.lru_order.retain(|key| key != id);
@@ -0,0 +1,146 @@
+//! Cancellation of blocking search work when its request goes away
+//!
+//! Searches run on the blocking pool, and `spawn_blocking` tasks keep running after the future
+//! awaiting them is dropped: a client that disconnects mid-search would still pay for the whole
+//! vector scan or index build. `run_blocking` ties the task to its future instead. Dropping the
+//! future (axum and tonic drop the handler future on disconnect) cancels a token that the task
+//! sees through `check`, which the long loops (vector scans, index builds, exact scans) call
+//! between units of work; the task then unwinds with a `Cancelled` error.
+//!
+//! The token is carried in a thread-local for the duration of the task, so storage code doesn't
+//! thread it through every signature. Outside `run_blocking`, `check` never fails.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::debug;
+
+/// Entries scanned between two `check` calls in tight loops
+pub const CHECK_INTERVAL: usize = 1024;
+
+/// Error returned by work whose request was dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Shared flag set once the work's requester is gone
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Run `f` on this thread with `self` as the token `check` consults
+    pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT.with(|current| current.replace(Some(self.clone())));
+        let _restore = RestoreOnDrop(previous);
+        f()
+    }
+}
+
+/// Cancels its token when dropped
+struct CancelOnDrop(CancelToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Puts back the token that was current before `CancelToken::scope`, also on panic
+struct RestoreOnDrop(Option<CancelToken>);
+
+impl Drop for RestoreOnDrop {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<CancelToken>> = const { RefCell::new(None) };
+}
+
+/// `Err(Cancelled)` once the request running this thread's work has been dropped
+pub fn check() -> Result<(), Cancelled> {
+    let cancelled = CURRENT.with(|current| current.borrow().as_ref().is_some_and(CancelToken::is_cancelled));
+    if cancelled {
+        debug!("Abandoning work of a dropped request");
+        return Err(Cancelled);
+    }
+    Ok(())
+}
+
+/// `spawn_blocking` that is cancelled (see `check`) when the returned future is dropped before
+/// the task finishes
+pub async fn run_blocking<T, F>(f: F) -> Result<T, tokio::task::JoinError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let token = CancelToken::new();
+    // Also fires after the task finished, when there is nothing left to stop
+    let _cancel_on_drop = CancelOnDrop(token.clone());
+    tokio::task::spawn_blocking(move || token.scope(f)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn dropping_the_future_stops_the_blocking_work() {
+        let iterations = Arc::new(AtomicUsize::new(0));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (counter, flag) = (iterations.clone(), stopped.clone());
+        let work = run_blocking(move || {
+            let outcome: Result<(), Cancelled> = loop {
+                if let Err(e) = check() {
+                    break Err(e);
+                }
+                counter.fetch_add(1, Ordering::Relaxed);
+                std::thread::sleep(Duration::from_millis(1));
+            };
+            flag.store(true, Ordering::Relaxed);
+            outcome
+        });
+
+        // The timeout drops the future, as a disconnecting client does
+        assert!(tokio::time::timeout(Duration::from_millis(30), work).await.is_err());
+        let started = Instant::now();
+        while !stopped.load(Ordering::Relaxed) {
+            assert!(started.elapsed() < Duration::from_secs(2), "blocking work kept running");
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let at_stop = iterations.load(Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(iterations.load(Ordering::Relaxed), at_stop);
+
+        // Awaited to completion, the work isn't cancelled
+        let finished = run_blocking(|| {
+            std::thread::sleep(Duration::from_millis(10));
+            check()
+        });
+        assert_eq!(finished.await.unwrap(), Ok(()));
+        assert_eq!(check(), Ok(()), "no token outside run_blocking");
+    }
+}
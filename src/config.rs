@@ -0,0 +1,460 @@
+//! Server configuration, parsed once at startup
+//!
+//! Every `AIDB_*` setting the server consults is read here into `AppConfig`, which `main` hands
+//! to `logging::init_logging`, `Storage::open_with_config`, `create_router_with_config` and
+//! `auth::configure`; modules only parse the raw values they are given and never read the process
+//! environment themselves. Handles built without a config (tests, scripts) take their settings
+//! from `StorageConfig::from_env` / `RestConfig::from_env`, which go through the same `from_lookup`.
+//!
+//! `AppConfig::from_lookup` takes the variable source as a function, so a config can be built
+//! from a controlled environment without touching the process one.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::indexing::{DistanceMetric, HnswParams};
+use crate::logging::LogConfig;
+use crate::query::sql::parse_query_timeout;
+use crate::rest::empty::EmptyResults;
+use crate::rest::envelope::ResponseMode;
+use crate::rest::params::PagePolicy;
+use crate::rest::payload::PayloadLimit;
+use crate::rest::request_id::RequestIdPolicy;
+use crate::rest::strict::StrictMode;
+use crate::storage::cache::parse_cache_autosize_fraction;
+use crate::storage::self_check::parse_self_check_mode;
+use crate::storage::vector::DEFAULT_PARALLEL_DECODE_THRESHOLD;
+use crate::storage::warm::DEFAULT_WARM_CONCURRENCY;
+use crate::storage::{IndexCheckpointPolicy, RebuildDebounce, RebuildWait, RetryPolicy};
+
+/// gRPC port used when `AIDB_GRPC_PORT` is unset
+pub const DEFAULT_GRPC_PORT: u16 = 50051;
+/// REST port used when `AIDB_REST_PORT` is unset
+pub const DEFAULT_REST_PORT: u16 = 11111;
+/// Data directory used when `AIDB_DATA_PATH` is unset
+pub const DEFAULT_DATA_PATH: &str = "aidb_data";
+/// Document cache size used when `AIDB_CACHE_MB` is unset
+pub const DEFAULT_CACHE_MB: usize = 64;
+/// JWT lifetime used when `AIDB_JWT_TTL_SECS` is unset
+pub const DEFAULT_JWT_TTL_SECS: u64 = 3600;
+/// Directory REST snapshots and restores are confined to when `AIDB_SNAPSHOT_DIR` is unset
+pub const DEFAULT_SNAPSHOT_DIR: &str = "aidb_snapshots";
+/// Bound on the final storage flush at shutdown when `AIDB_SHUTDOWN_FLUSH_TIMEOUT_SECS` is unset
+pub const DEFAULT_SHUTDOWN_FLUSH_TIMEOUT_SECS: u64 = 10;
+
+/// Source of configuration variables: the value of a variable by name, if set
+pub type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn process_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+fn parse_flag(raw: Option<&str>) -> bool {
+    raw.is_some_and(|raw| matches!(raw.trim().to_lowercase().as_str(), "1" | "true"))
+}
+
+fn parse_port(name: &str, raw: Option<String>, default: u16) -> Result<u16, Box<dyn std::error::Error>> {
+    match raw {
+        None => Ok(default),
+        Some(raw) => raw.trim().parse::<u16>().map_err(|e| format!("Invalid {} '{}': {}", name, raw, e).into()),
+    }
+}
+
+/// Storage engine settings applied by `Storage::open_with_config`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageConfig {
+    /// Document cache capacity (`AIDB_CACHE_MB`, default 64)
+    pub cache_mb: usize,
+    /// Concurrent index builds (`AIDB_MAX_INDEX_BUILDS`, default: available cores)
+    pub max_index_builds: usize,
+    /// Entries a collection scan needs before decoding in parallel (`AIDB_PARALLEL_DECODE_MIN`)
+    pub parallel_decode_threshold: usize,
+    /// zstd-compress new document values (`AIDB_DOC_COMPRESSION`)
+    pub doc_compression: bool,
+    /// Component magnitude bound for vectors (`AIDB_VECTOR_MAX_ABS`; unset = unbounded). Squared
+    /// L2 terms overflow `f32` once a component passes ~1.8e19, so a bound keeps distances finite.
+    pub vector_max_abs: Option<f32>,
+    /// Metric of collections that don't declare one (`AIDB_DEFAULT_METRIC`: `l2`, `cosine`, `dot_product`)
+    pub default_metric: DistanceMetric,
+    /// Open for reads only (`AIDB_READ_ONLY`)
+    pub read_only: bool,
+    /// Derive `category` for inserts without one using the default keyword rules (`AIDB_AUTO_CATEGORY`)
+    pub auto_category: bool,
+    /// Parameters of every index build (`AIDB_HNSW_EF_CONSTRUCTION`, `AIDB_HNSW_EF_SEARCH`,
+    /// `AIDB_HNSW_NUM_LAYERS`, `AIDB_HNSW_SEED` for reproducible builds, and
+    /// `AIDB_FLAT_INDEX_THRESHOLD` below which collections are scanned exactly)
+    pub hnsw: HnswParams,
+    /// Load or build every collection's index right after startup (`AIDB_WARM_INDEXES`)
+    pub warm_indexes: bool,
+    /// Stop warming once warmed indexes use this much memory (`AIDB_WARM_BUDGET_MB`; unset = no budget)
+    pub warm_budget_mb: Option<u64>,
+    /// Collections warmed in parallel (`AIDB_WARM_CONCURRENCY`, default 2)
+    pub warm_concurrency: usize,
+    /// Searches during another search's rebuild (`AIDB_REBUILD_WAIT`, `AIDB_REBUILD_WAIT_TIMEOUT_MS`)
+    pub rebuild_wait: RebuildWait,
+    /// Quiet period before stale indexes rebuild (`AIDB_REBUILD_DEBOUNCE_MS`, `AIDB_REBUILD_MAX_STALE_MS`)
+    pub rebuild_debounce: RebuildDebounce,
+    /// When cached indexes are persisted (`AIDB_INDEX_CHECKPOINT_SECS`,
+    /// `AIDB_INDEX_CHECKPOINT_WRITES`, `AIDB_INDEX_DIR`)
+    pub index_checkpoints: IndexCheckpointPolicy,
+    /// Retries of transient write failures (`AIDB_WRITE_RETRIES`, `AIDB_WRITE_RETRY_BASE_MS`)
+    pub write_retry: RetryPolicy,
+    /// Log every document cache eviction at info level (`AIDB_CACHE_LOG_EVICTIONS`)
+    pub cache_log_evictions: bool,
+    /// Default deadline of hybrid queries (`AIDB_QUERY_TIMEOUT_MS`; unset or 0 = none)
+    pub query_timeout: Option<Duration>,
+    /// Serve SQL and hybrid filters without DataFusion (`AIDB_SQL_FALLBACK`)
+    pub sql_fallback: bool,
+    /// Consistency scan at startup (`AIDB_SELF_CHECK`): `Some(false)` reports, `Some(true)` also repairs
+    pub self_check: Option<bool>,
+}
+
+impl StorageConfig {
+    pub fn from_env() -> Self {
+        Self::from_lookup(&process_env)
+    }
+
+    pub fn from_lookup(var: Lookup) -> Self {
+        let cache_mb = var("AIDB_CACHE_MB").and_then(|raw| raw.trim().parse::<usize>().ok()).unwrap_or(DEFAULT_CACHE_MB);
+        let max_index_builds = var("AIDB_MAX_INDEX_BUILDS")
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(2));
+        let parallel_decode_threshold = var("AIDB_PARALLEL_DECODE_MIN")
+            .and_then(|raw| raw.trim().parse::<usize>().ok())
+            .unwrap_or(DEFAULT_PARALLEL_DECODE_THRESHOLD);
+        let doc_compression = var("AIDB_DOC_COMPRESSION")
+            .is_some_and(|raw| matches!(raw.trim().to_lowercase().as_str(), "1" | "true" | "zstd"));
+        let vector_max_abs = var("AIDB_VECTOR_MAX_ABS")
+            .and_then(|raw| raw.trim().parse::<f32>().ok())
+            .filter(|max| max.is_finite() && *max > 0.0);
+        let positive = |name: &str| var(name).and_then(|raw| raw.trim().parse::<usize>().ok()).filter(|n| *n > 0);
+        let defaults = HnswParams::default();
+        let default_metric = var("AIDB_DEFAULT_METRIC")
+            .and_then(|raw| serde_json::from_value(serde_json::Value::String(raw.trim().to_lowercase())).ok())
+            .unwrap_or_default();
+        Self {
+            cache_mb,
+            max_index_builds,
+            parallel_decode_threshold,
+            doc_compression,
+            vector_max_abs,
+            default_metric,
+            read_only: parse_flag(var("AIDB_READ_ONLY").as_deref()),
+            auto_category: parse_flag(var("AIDB_AUTO_CATEGORY").as_deref()),
+            hnsw: HnswParams {
+                ef_construction: positive("AIDB_HNSW_EF_CONSTRUCTION").unwrap_or(defaults.ef_construction),
+                ef_search: positive("AIDB_HNSW_EF_SEARCH").unwrap_or(defaults.ef_search),
+                num_layers: positive("AIDB_HNSW_NUM_LAYERS"),
+                seed: var("AIDB_HNSW_SEED").and_then(|raw| raw.trim().parse::<u64>().ok()),
+                flat_threshold: positive("AIDB_FLAT_INDEX_THRESHOLD").unwrap_or(defaults.flat_threshold),
+            },
+            warm_indexes: parse_flag(var("AIDB_WARM_INDEXES").as_deref()),
+            warm_budget_mb: var("AIDB_WARM_BUDGET_MB").and_then(|raw| raw.trim().parse::<u64>().ok()),
+            warm_concurrency: var("AIDB_WARM_CONCURRENCY")
+                .and_then(|raw| raw.trim().parse::<usize>().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(DEFAULT_WARM_CONCURRENCY),
+            rebuild_wait: RebuildWait::parse(var("AIDB_REBUILD_WAIT").as_deref(), var("AIDB_REBUILD_WAIT_TIMEOUT_MS").as_deref()),
+            rebuild_debounce: RebuildDebounce::parse(
+                var("AIDB_REBUILD_DEBOUNCE_MS").as_deref(),
+                var("AIDB_REBUILD_MAX_STALE_MS").as_deref(),
+            ),
+            index_checkpoints: IndexCheckpointPolicy::parse(
+                var("AIDB_INDEX_CHECKPOINT_SECS").as_deref(),
+                var("AIDB_INDEX_CHECKPOINT_WRITES").as_deref(),
+                var("AIDB_INDEX_DIR").as_deref(),
+            ),
+            write_retry: RetryPolicy::parse(var("AIDB_WRITE_RETRIES").as_deref(), var("AIDB_WRITE_RETRY_BASE_MS").as_deref()),
+            cache_log_evictions: parse_flag(var("AIDB_CACHE_LOG_EVICTIONS").as_deref()),
+            query_timeout: parse_query_timeout(var("AIDB_QUERY_TIMEOUT_MS").as_deref()),
+            sql_fallback: parse_flag(var("AIDB_SQL_FALLBACK").as_deref()),
+            self_check: parse_self_check_mode(var("AIDB_SELF_CHECK").as_deref()),
+        }
+    }
+}
+
+/// Server-wide REST policies installed by `create_router_with_config`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestConfig {
+    pub page: PagePolicy,
+    pub response_mode: ResponseMode,
+    pub request_ids: RequestIdPolicy,
+    pub payload_limit: PayloadLimit,
+    pub strict: StrictMode,
+    pub empty_results: EmptyResults,
+    /// Share of available memory `POST /admin/cache/autosize` gives the document cache when the
+    /// request names none (`AIDB_CACHE_AUTOSIZE_FRACTION`, default 0.25)
+    pub cache_autosize_fraction: f64,
+    /// Directory `POST /admin/snapshot` and `POST /admin/restore` paths are resolved in; requests
+    /// can't name files outside it (`AIDB_SNAPSHOT_DIR`, default `aidb_snapshots`)
+    pub snapshot_dir: PathBuf,
+}
+
+impl RestConfig {
+    pub fn from_env() -> Self {
+        Self::from_lookup(&process_env)
+    }
+
+    pub fn from_lookup(var: Lookup) -> Self {
+        Self {
+            page: PagePolicy::from_vars(var("AIDB_PAGE_DEFAULT").as_deref(), var("AIDB_PAGE_MAX").as_deref()),
+            response_mode: ResponseMode::from_var(var("AIDB_RESPONSE_MODE").as_deref()),
+            request_ids: RequestIdPolicy::from_var(var("AIDB_TRUST_REQUEST_ID").as_deref()),
+            payload_limit: PayloadLimit::from_var(var("AIDB_MAX_RESPONSE_BYTES").as_deref()),
+            strict: StrictMode::from_var(var("AIDB_STRICT_JSON").as_deref()),
+            empty_results: EmptyResults::from_var(var("AIDB_EMPTY_RESULTS").as_deref()),
+            cache_autosize_fraction: parse_cache_autosize_fraction(var("AIDB_CACHE_AUTOSIZE_FRACTION").as_deref()),
+            snapshot_dir: var("AIDB_SNAPSHOT_DIR")
+                .filter(|raw| !raw.trim().is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_SNAPSHOT_DIR)),
+        }
+    }
+}
+
+/// Token signing settings installed by `auth::configure`
+#[derive(Clone, PartialEq, Eq)]
+pub struct AuthConfig {
+    /// HMAC key for issued tokens (`AIDB_JWT_SECRET`; required in release builds, a random
+    /// per-process key in debug builds when unset)
+    pub jwt_secret: Option<String>,
+    /// Token lifetime (`AIDB_JWT_TTL_SECS`, default 3600)
+    pub jwt_ttl_secs: u64,
+    /// Shortest time a login's password check may take (`AIDB_LOGIN_MIN_VERIFY_MS`, default 0:
+    /// only the bcrypt verify itself), so fast failures don't tell callers anything
+    pub login_min_verify: Duration,
+}
+
+impl std::fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("jwt_secret", &self.jwt_secret.as_ref().map(|_| "<redacted>"))
+            .field("jwt_ttl_secs", &self.jwt_ttl_secs)
+            .field("login_min_verify", &self.login_min_verify)
+            .finish()
+    }
+}
+
+impl AuthConfig {
+    pub fn from_env() -> Self {
+        Self::from_lookup(&process_env)
+    }
+
+    pub fn from_lookup(var: Lookup) -> Self {
+        Self {
+            jwt_secret: var("AIDB_JWT_SECRET").filter(|secret| !secret.is_empty()),
+            jwt_ttl_secs: var("AIDB_JWT_TTL_SECS")
+                .and_then(|raw| raw.trim().parse::<u64>().ok())
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_JWT_TTL_SECS),
+            login_min_verify: var("AIDB_LOGIN_MIN_VERIFY_MS")
+                .and_then(|raw| raw.trim().parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// PEM certificate chain and private key the gRPC server presents (`AIDB_TLS_CERT`/`AIDB_TLS_KEY`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+impl TlsConfig {
+    /// `None` (plaintext) when neither variable is set; setting only one is an error
+    pub fn from_lookup(var: Lookup) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let path = |name: &str| var(name).filter(|raw| !raw.trim().is_empty()).map(PathBuf::from);
+        match (path("AIDB_TLS_CERT"), path("AIDB_TLS_KEY")) {
+            (Some(cert_path), Some(key_path)) => Ok(Some(Self { cert_path, key_path })),
+            (None, None) => Ok(None),
+            _ => Err("AIDB_TLS_CERT and AIDB_TLS_KEY must be set together".into()),
+        }
+    }
+}
+
+/// Everything the server reads from its environment at startup
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// Sled directory (`AIDB_DATA_PATH`)
+    pub data_path: String,
+    /// gRPC listen address (`AIDB_GRPC_PORT` on `[::1]`)
+    pub grpc_addr: SocketAddr,
+    /// REST listen address (`AIDB_REST_PORT` on `0.0.0.0`)
+    pub rest_addr: SocketAddr,
+    /// Server-side TLS for gRPC; plaintext when `None`
+    pub grpc_tls: Option<TlsConfig>,
+    /// How long shutdown waits for the final storage flush (`AIDB_SHUTDOWN_FLUSH_TIMEOUT_SECS`)
+    pub shutdown_flush_timeout: Duration,
+    pub storage: StorageConfig,
+    pub rest: RestConfig,
+    pub auth: AuthConfig,
+    /// Log level and file (`AIDB_LOG_LEVEL`, `AIDB_LOG_FILE`)
+    pub log: LogConfig,
+}
+
+impl AppConfig {
+    /// Parse the process environment; fails on an unparseable port or half a TLS key pair
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_lookup(&process_env)
+    }
+
+    /// Parse variables from `var`; unset or invalid optional values keep their defaults
+    pub fn from_lookup(var: Lookup) -> Result<Self, Box<dyn std::error::Error>> {
+        let grpc_port = parse_port("AIDB_GRPC_PORT", var("AIDB_GRPC_PORT"), DEFAULT_GRPC_PORT)?;
+        let rest_port = parse_port("AIDB_REST_PORT", var("AIDB_REST_PORT"), DEFAULT_REST_PORT)?;
+        Ok(Self {
+            data_path: var("AIDB_DATA_PATH").unwrap_or_else(|| DEFAULT_DATA_PATH.to_string()),
+            grpc_addr: SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, grpc_port)),
+            rest_addr: SocketAddr::from((std::net::Ipv4Addr::UNSPECIFIED, rest_port)),
+            grpc_tls: TlsConfig::from_lookup(var)?,
+            shutdown_flush_timeout: Duration::from_secs(
+                var("AIDB_SHUTDOWN_FLUSH_TIMEOUT_SECS")
+                    .and_then(|raw| raw.trim().parse::<u64>().ok())
+                    .filter(|secs| *secs > 0)
+                    .unwrap_or(DEFAULT_SHUTDOWN_FLUSH_TIMEOUT_SECS),
+            ),
+            storage: StorageConfig::from_lookup(var),
+            rest: RestConfig::from_lookup(var),
+            auth: AuthConfig::from_lookup(var),
+            log: LogConfig::from_lookup(var),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config_from(vars: &[(&str, &str)]) -> Result<AppConfig, Box<dyn std::error::Error>> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        AppConfig::from_lookup(&|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn defaults_apply_and_variables_override_them() {
+        let defaults = config_from(&[]).unwrap();
+        assert_eq!(defaults.data_path, "aidb_data");
+        assert_eq!(defaults.grpc_addr, "[::1]:50051".parse().unwrap());
+        assert_eq!(defaults.rest_addr, "0.0.0.0:11111".parse().unwrap());
+        assert_eq!(defaults.grpc_tls, None);
+        assert_eq!(defaults.shutdown_flush_timeout, Duration::from_secs(10));
+        assert_eq!(defaults.storage.cache_mb, 64);
+        assert_eq!(defaults.storage.default_metric, DistanceMetric::L2);
+        assert_eq!(defaults.storage.hnsw, HnswParams::default());
+        assert!(!defaults.storage.read_only);
+        assert!(!defaults.storage.warm_indexes);
+        assert_eq!(defaults.storage.warm_concurrency, 2);
+        assert_eq!(defaults.storage.rebuild_wait, RebuildWait::Wait { timeout: None });
+        assert_eq!(defaults.storage.rebuild_debounce, RebuildDebounce::default());
+        assert_eq!(defaults.storage.index_checkpoints, IndexCheckpointPolicy::default());
+        assert_eq!(defaults.storage.write_retry, RetryPolicy::default());
+        assert!(!defaults.storage.cache_log_evictions);
+        assert_eq!(defaults.storage.query_timeout, None);
+        assert!(!defaults.storage.sql_fallback);
+        assert_eq!(defaults.storage.self_check, None);
+        assert_eq!(defaults.rest.page, PagePolicy::default());
+        assert_eq!(defaults.rest.empty_results, EmptyResults::EmptyList);
+        assert_eq!(defaults.rest.cache_autosize_fraction, 0.25);
+        assert_eq!(defaults.rest.snapshot_dir, PathBuf::from("aidb_snapshots"));
+        assert_eq!(defaults.auth.jwt_secret, None);
+        assert_eq!(defaults.auth.jwt_ttl_secs, 3600);
+        assert_eq!(defaults.auth.login_min_verify, Duration::ZERO);
+        assert_eq!(defaults.log, LogConfig::default());
+
+        let config = config_from(&[
+            ("AIDB_DATA_PATH", "/var/lib/aidb"),
+            ("AIDB_GRPC_PORT", "6000"),
+            ("AIDB_REST_PORT", "8080"),
+            ("AIDB_TLS_CERT", "/etc/aidb/server.pem"),
+            ("AIDB_TLS_KEY", "/etc/aidb/server.key"),
+            ("AIDB_SHUTDOWN_FLUSH_TIMEOUT_SECS", "3"),
+            ("AIDB_CACHE_MB", "256"),
+            ("AIDB_MAX_INDEX_BUILDS", "3"),
+            ("AIDB_DEFAULT_METRIC", "Cosine"),
+            ("AIDB_HNSW_EF_SEARCH", "400"),
+            ("AIDB_HNSW_NUM_LAYERS", "5"),
+            ("AIDB_FLAT_INDEX_THRESHOLD", "2000"),
+            ("AIDB_READ_ONLY", "true"),
+            ("AIDB_WARM_INDEXES", "1"),
+            ("AIDB_WARM_BUDGET_MB", "512"),
+            ("AIDB_REBUILD_WAIT_TIMEOUT_MS", "250"),
+            ("AIDB_REBUILD_DEBOUNCE_MS", "100"),
+            ("AIDB_INDEX_CHECKPOINT_SECS", "30"),
+            ("AIDB_INDEX_CHECKPOINT_WRITES", "5"),
+            ("AIDB_INDEX_DIR", "/var/lib/aidb/indexes"),
+            ("AIDB_WRITE_RETRIES", "7"),
+            ("AIDB_WRITE_RETRY_BASE_MS", "20"),
+            ("AIDB_CACHE_LOG_EVICTIONS", "true"),
+            ("AIDB_QUERY_TIMEOUT_MS", "1500"),
+            ("AIDB_SQL_FALLBACK", "1"),
+            ("AIDB_SELF_CHECK", "repair"),
+            ("AIDB_CACHE_AUTOSIZE_FRACTION", "0.5"),
+            ("AIDB_SNAPSHOT_DIR", "/backups/aidb"),
+            ("AIDB_PAGE_MAX", "50"),
+            ("AIDB_MAX_RESPONSE_BYTES", "4096"),
+            ("AIDB_STRICT_JSON", "1"),
+            ("AIDB_JWT_SECRET", "s3cret"),
+            ("AIDB_JWT_TTL_SECS", "60"),
+            ("AIDB_LOGIN_MIN_VERIFY_MS", "300"),
+            ("AIDB_LOG_LEVEL", "debug"),
+            ("AIDB_LOG_FILE", "/var/log/aidb.json"),
+        ])
+        .unwrap();
+        assert_eq!(config.data_path, "/var/lib/aidb");
+        assert_eq!(config.grpc_addr.port(), 6000);
+        assert_eq!(config.rest_addr.port(), 8080);
+        let tls = config.grpc_tls.as_ref().unwrap();
+        assert_eq!((tls.cert_path.to_str(), tls.key_path.to_str()), (Some("/etc/aidb/server.pem"), Some("/etc/aidb/server.key")));
+        assert_eq!(config.shutdown_flush_timeout, Duration::from_secs(3));
+        assert_eq!(config.storage.cache_mb, 256);
+        assert_eq!(config.storage.max_index_builds, 3);
+        assert_eq!(config.storage.default_metric, DistanceMetric::Cosine);
+        assert_eq!(config.storage.hnsw, HnswParams { ef_search: 400, num_layers: Some(5), flat_threshold: 2000, ..HnswParams::default() });
+        assert!(config.storage.read_only);
+        assert!(config.storage.warm_indexes);
+        assert_eq!(config.storage.warm_budget_mb, Some(512));
+        assert_eq!(config.storage.rebuild_wait, RebuildWait::Wait { timeout: Some(Duration::from_millis(250)) });
+        assert_eq!(config.storage.rebuild_debounce, RebuildDebounce::new(Duration::from_millis(100)));
+        assert_eq!(
+            config.storage.index_checkpoints,
+            IndexCheckpointPolicy { interval: Duration::from_secs(30), min_writes: 5, dir: Some(PathBuf::from("/var/lib/aidb/indexes")) }
+        );
+        assert_eq!(config.storage.write_retry, RetryPolicy { max_retries: 7, base_delay: Duration::from_millis(20) });
+        assert!(config.storage.cache_log_evictions);
+        assert_eq!(config.storage.query_timeout, Some(Duration::from_millis(1500)));
+        assert!(config.storage.sql_fallback);
+        assert_eq!(config.storage.self_check, Some(true));
+        assert_eq!(config.rest.cache_autosize_fraction, 0.5);
+        assert_eq!(config.rest.snapshot_dir, PathBuf::from("/backups/aidb"));
+        let serve_stale = config_from(&[("AIDB_REBUILD_WAIT", "serve_stale")]).unwrap();
+        assert_eq!(serve_stale.storage.rebuild_wait, RebuildWait::ServeStale);
+        assert_eq!(config.rest.page, PagePolicy { default_limit: 50, max_limit: 50 });
+        assert_eq!(config.rest.payload_limit.max_bytes, Some(4096));
+        assert!(config.rest.strict.enabled);
+        assert_eq!(config.auth.jwt_secret.as_deref(), Some("s3cret"));
+        assert_eq!(config.auth.jwt_ttl_secs, 60);
+        assert_eq!(config.auth.login_min_verify, Duration::from_millis(300));
+        assert!(!format!("{:?}", config.auth).contains("s3cret"));
+        assert_eq!(config.log, LogConfig { level: "debug".to_string(), file_path: PathBuf::from("/var/log/aidb.json") });
+
+        // Optional values that don't parse keep their defaults; a bad port or a lone TLS path is an error
+        let lenient = config_from(&[
+            ("AIDB_CACHE_MB", "lots"),
+            ("AIDB_DEFAULT_METRIC", "manhattan"),
+            ("AIDB_SELF_CHECK", "sometimes"),
+            ("AIDB_CACHE_AUTOSIZE_FRACTION", "1.5"),
+        ])
+        .unwrap();
+        assert_eq!(lenient.storage.cache_mb, 64);
+        assert_eq!(lenient.storage.default_metric, DistanceMetric::L2);
+        assert_eq!(lenient.storage.self_check, None);
+        assert_eq!(lenient.rest.cache_autosize_fraction, 0.25);
+        assert!(config_from(&[("AIDB_REST_PORT", "http")]).is_err());
+        assert!(config_from(&[("AIDB_TLS_CERT", "/etc/aidb/server.pem")]).is_err());
+    }
+}
@@ -1,63 +1,905 @@
 use instant_distance::{Builder, HnswMap, Point, Search};
-use tracing::{info, debug, instrument};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::mem::{size_of, size_of_val};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, debug, warn, instrument};
 
-#[derive(Clone, Debug)]
-struct VectorPoint(Vec<f32>);
+/// Default `HnswParams::flat_threshold`: collections with fewer vectors are served by an exact
+/// brute-force scan. Below this size an HNSW graph costs more to build than a scan and can miss
+/// true neighbors.
+pub const FLAT_INDEX_THRESHOLD: usize = 256;
+
+/// Points `VectorIndex::add` buffers beside an HNSW graph before rebuilding it with them.
+/// Buffered points are searched exactly, so the buffer trades query time for fewer rebuilds.
+pub const PENDING_MERGE_THRESHOLD: usize = 1024;
+
+/// instant-distance's fixed graph degree (`M`): layer-zero nodes keep `2 * M` neighbor links,
+/// upper-layer nodes `M`. Only used to estimate graph memory.
+const HNSW_M: usize = 32;
+
+/// Heap and inline bytes of a stored (id, vector) pair
+fn entry_bytes(id: &str, vector: &[f32]) -> usize {
+    size_of::<String>() + id.len() + size_of::<Vec<f32>>() + size_of_val(vector)
+}
+
+/// HNSW graph parameters passed to instant-distance's `Builder`, reported back to clients.
+///
+/// Without a `seed` every build draws a random one, so two builds over the same vectors can link
+/// different neighbors and return different approximate results. With a seed, builds over the
+/// same (id, vector) list in the same order produce the same graph and the same search results:
+/// the seed fixes each point's layer, and the build runs on a one-thread pool because
+/// instant-distance inserts a layer's points in parallel. Seeded builds are therefore slower on
+/// multi-core machines; leave the seed unset in production unless reproducibility matters more.
+/// Flat indexes (below `flat_threshold`) are exact and reproducible either way.
+///
+/// Higher `ef_construction` links each point to better neighbors (slower builds, better recall);
+/// `ef_search` is the candidate list a search keeps, stored in the graph and applied to every
+/// search of it, so a search returns at most `ef_search` results and recall drops as it nears `k`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HnswParams {
+    /// Candidates considered per insert (`AIDB_HNSW_EF_CONSTRUCTION`)
+    pub ef_construction: usize,
+    /// Candidates kept per search (`AIDB_HNSW_EF_SEARCH`)
+    pub ef_search: usize,
+    /// Target number of graph layers (`AIDB_HNSW_NUM_LAYERS`); `None` for instant-distance's
+    /// default level distribution. Converted per build into the level multiplier that gives the
+    /// built point count about this many layers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_layers: Option<usize>,
+    /// Build seed (`AIDB_HNSW_SEED`); `None` for a random one per build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Indexes with fewer vectors are an exact `FlatIndex` instead of a graph
+    /// (`AIDB_FLAT_INDEX_THRESHOLD`, default `FLAT_INDEX_THRESHOLD`)
+    #[serde(default = "default_flat_threshold")]
+    pub flat_threshold: usize,
+}
+
+fn default_flat_threshold() -> usize {
+    FLAT_INDEX_THRESHOLD
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self { ef_construction: 100, ef_search: 100, num_layers: None, seed: None, flat_threshold: FLAT_INDEX_THRESHOLD }
+    }
+}
+
+impl HnswParams {
+    /// Build `points` into a graph mapping to `values` with these parameters
+    fn build(&self, points: Vec<VectorPoint>, values: Vec<String>) -> HnswMap<VectorPoint, String> {
+        let mut builder = Builder::default().ef_construction(self.ef_construction).ef_search(self.ef_search);
+        if let Some(layers) = self.num_layers {
+            // A point reaches layer l with probability exp(-l / ml), so n points span about
+            // 1 + ml * ln(n) layers
+            let ml = layers.saturating_sub(1) as f32 / (points.len().max(2) as f32).ln();
+            builder = builder.ml(ml);
+        }
+        let Some(seed) = self.seed else {
+            return builder.build(points, values);
+        };
+        let builder = builder.seed(seed);
+        match rayon::ThreadPoolBuilder::new().num_threads(1).build() {
+            Ok(pool) => pool.install(|| builder.build(points, values)),
+            Err(e) => {
+                warn!(error = %e, "No single-thread pool for a seeded HNSW build; building in parallel");
+                builder.build(points, values)
+            }
+        }
+    }
+}
+
+/// Which index structure served a search (reported back to clients for recall debugging)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexBackend {
+    Flat,
+    Hnsw,
+}
+
+impl IndexBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndexBackend::Flat => "flat",
+            IndexBackend::Hnsw => "hnsw",
+        }
+    }
+}
+
+/// Distance metric used to rank neighbors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    #[default]
+    L2,
+    Cosine,
+    /// Negated inner product, for embeddings trained for maximum inner product search
+    DotProduct,
+}
+
+impl DistanceMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DistanceMetric::L2 => "l2",
+            DistanceMetric::Cosine => "cosine",
+            DistanceMetric::DotProduct => "dot_product",
+        }
+    }
+
+    /// Raw distance between two vectors (lower = closer for every metric)
+    pub fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            DistanceMetric::L2 => a
+                .iter()
+                .zip(b.iter())
+                .map(|(x, y)| (x - y).powi(2))
+                .sum::<f32>()
+                .sqrt(),
+            DistanceMetric::Cosine => {
+                let dot = dot(a, b);
+                let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+                let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+                if norm_a == 0.0 || norm_b == 0.0 {
+                    return 1.0;
+                }
+                1.0 - dot / (norm_a * norm_b)
+            }
+            DistanceMetric::DotProduct => -dot(a, b),
+        }
+    }
+
+    /// Convert a raw distance into a canonical similarity where higher = closer.
+    /// - `l2`: `1 / (1 + d)`, in (0, 1]; identical vectors score 1.
+    /// - `cosine`: `1 - d`, i.e. the cosine similarity in [-1, 1].
+    /// - `dot_product`: `-d`, i.e. the (unbounded) inner product.
+    pub fn to_similarity(&self, distance: f32) -> f32 {
+        match self {
+            DistanceMetric::L2 => 1.0 / (1.0 + distance),
+            DistanceMetric::Cosine => 1.0 - distance,
+            DistanceMetric::DotProduct => -distance,
+        }
+    }
+}
+
+impl DistanceMetric {
+    /// This metric as a distance function, e.g. to wrap it in a custom one
+    pub fn as_distance_fn(self) -> DistanceFn {
+        Arc::new(move |a: &[f32], b: &[f32]| self.distance(a, b))
+    }
+}
+
+/// A distance function over two vectors (lower = closer), for `VectorIndex::build_with_distance`
+pub type DistanceFn = Arc<dyn Fn(&[f32], &[f32]) -> f32 + Send + Sync>;
+
+/// A custom distance carried by an index and its graph points. Closures can't be serialized, so
+/// it is skipped by serde and indexes holding one refuse `to_bytes`.
+#[derive(Clone)]
+struct CustomDistance(DistanceFn);
+
+impl std::fmt::Debug for CustomDistance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CustomDistance")
+    }
+}
+
+/// How a search score should be read: a raw metric distance (lower = closer)
+/// or the canonical similarity from `DistanceMetric::to_similarity` (higher = closer)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreKind {
+    #[default]
+    Distance,
+    Similarity,
+}
+
+impl ScoreKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScoreKind::Distance => "distance",
+            ScoreKind::Similarity => "similarity",
+        }
+    }
+
+    /// Parse a client-supplied kind; empty selects the default (`distance`)
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_lowercase().as_str() {
+            "" | "distance" => Ok(ScoreKind::Distance),
+            "similarity" => Ok(ScoreKind::Similarity),
+            other => Err(format!("Unknown score_kind '{}' (expected distance or similarity)", other)),
+        }
+    }
+
+    /// Express a raw `distance` under `metric` in this kind
+    pub fn apply(&self, metric: DistanceMetric, distance: f32) -> f32 {
+        match self {
+            ScoreKind::Distance => distance,
+            ScoreKind::Similarity => metric.to_similarity(distance),
+        }
+    }
+
+    /// Order two scores of this kind best-first (ascending distance, descending similarity)
+    pub fn best_first(&self, a: f32, b: f32) -> std::cmp::Ordering {
+        match self {
+            ScoreKind::Distance => a.total_cmp(&b),
+            ScoreKind::Similarity => b.total_cmp(&a),
+        }
+    }
+}
+
+/// Counting semaphore bounding how many index builds run at once.
+/// Building an HNSW graph holds every vector of a collection in memory, so after a restart
+/// many cold collections searched together could otherwise exhaust RAM; extra builds queue here.
+#[derive(Debug)]
+pub struct IndexBuildLimiter {
+    max_permits: usize,
+    in_use: Mutex<usize>,
+    released: Condvar,
+}
+
+/// RAII permit returned by `IndexBuildLimiter::acquire`; the slot is freed on drop
+pub struct IndexBuildPermit<'a> {
+    limiter: &'a IndexBuildLimiter,
+}
+
+impl IndexBuildLimiter {
+    pub fn new(max_permits: usize) -> Self {
+        Self {
+            max_permits: max_permits.max(1),
+            in_use: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn max_permits(&self) -> usize {
+        self.max_permits
+    }
+
+    /// Number of builds currently holding a permit
+    pub fn in_flight(&self) -> usize {
+        *self.in_use.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Block until a build slot is free
+    pub fn acquire(&self) -> IndexBuildPermit<'_> {
+        let mut in_use = self.in_use.lock().unwrap_or_else(|e| e.into_inner());
+        while *in_use >= self.max_permits {
+            debug!(in_use = *in_use, max_permits = self.max_permits, "Index build queued");
+            in_use = self.released.wait(in_use).unwrap_or_else(|e| e.into_inner());
+        }
+        *in_use += 1;
+        IndexBuildPermit { limiter: self }
+    }
+}
+
+impl Drop for IndexBuildPermit<'_> {
+    fn drop(&mut self) {
+        let mut in_use = self.limiter.in_use.lock().unwrap_or_else(|e| e.into_inner());
+        *in_use = in_use.saturating_sub(1);
+        self.limiter.released.notify_one();
+    }
+}
+
+/// Write `bytes` to a temporary sibling of `path` and rename it into place, so readers see either
+/// the old file or the complete new one
+pub(crate) fn write_file_atomic(path: &std::path::Path, bytes: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+/// A vector in an HNSW graph, tagged with the metric (or custom distance) the graph ranks by.
+/// Cosine points are stored unit-length so the graph compares them with a plain dot product.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct VectorPoint {
+    vector: Vec<f32>,
+    metric: DistanceMetric,
+    #[serde(skip)]
+    custom: Option<CustomDistance>,
+}
+
+impl VectorPoint {
+    fn new(mut vector: Vec<f32>, metric: DistanceMetric, custom: Option<CustomDistance>) -> Self {
+        if metric == DistanceMetric::Cosine && custom.is_none() {
+            let norm = l2_norm(&vector);
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|x| *x /= norm);
+            }
+        }
+        Self { vector, metric, custom }
+    }
+}
 
 impl Point for VectorPoint {
-    /// Euclidean (L2) distance for vector similarity search
+    /// Distance under the point's custom distance, else its metric
     fn distance(&self, other: &Self) -> f32 {
-        self.0
-            .iter()
-            .zip(other.0.iter())
-            .map(|(a, b)| (a - b).powi(2))
-            .sum::<f32>()
-            .sqrt()
+        if let Some(custom) = &self.custom {
+            return (custom.0)(&self.vector, &other.vector);
+        }
+        match self.metric {
+            // Both sides are already normalized; zero vectors stay zero and score 1, as in DistanceMetric::Cosine
+            DistanceMetric::Cosine => 1.0 - dot(&self.vector, &other.vector),
+            metric => metric.distance(&self.vector, &other.vector),
+        }
     }
 }
 
+/// FlatIndex: exact nearest neighbors by scanning every vector (O(n) per query)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FlatIndex {
+    ids: Vec<String>,
+    vectors: Vec<Vec<f32>>,
+    metric: DistanceMetric,
+    /// L2 norm of each stored vector, precomputed for cosine so queries don't renormalize them
+    norms: Option<Vec<f32>>,
+    /// Ranks by this instead of `metric` when set
+    #[serde(skip)]
+    custom: Option<CustomDistance>,
+}
+
+fn l2_norm(v: &[f32]) -> f32 {
+    v.iter().map(|x| x * x).sum::<f32>().sqrt()
+}
+
+impl FlatIndex {
+    /// Exact L2 index
+    pub fn new(vectors: Vec<(String, Vec<f32>)>) -> Self {
+        Self::with_metric(vectors, DistanceMetric::L2)
+    }
+
+    /// Exact index ranking by `metric`; cosine indexes precompute each stored vector's norm once
+    pub fn with_metric(vectors: Vec<(String, Vec<f32>)>, metric: DistanceMetric) -> Self {
+        let (ids, vectors): (Vec<String>, Vec<Vec<f32>>) = vectors.into_iter().unzip();
+        let norms = match metric {
+            DistanceMetric::Cosine => Some(vectors.iter().map(|v| l2_norm(v)).collect()),
+            DistanceMetric::L2 | DistanceMetric::DotProduct => None,
+        };
+        Self { ids, vectors, metric, norms, custom: None }
+    }
+
+    /// Exact index ranking by `distance`
+    pub fn with_distance(vectors: Vec<(String, Vec<f32>)>, distance: DistanceFn) -> Self {
+        let (ids, vectors): (Vec<String>, Vec<Vec<f32>>) = vectors.into_iter().unzip();
+        Self { ids, vectors, metric: DistanceMetric::default(), norms: None, custom: Some(CustomDistance(distance)) }
+    }
+
+    /// Drop the precomputed norms so every query recomputes them (reference path for comparisons)
+    pub fn without_norm_cache(mut self) -> Self {
+        self.norms = None;
+        self
+    }
+
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Estimated bytes held by the stored IDs, vectors and norms
+    pub fn approx_memory_bytes(&self) -> usize {
+        let entries: usize = self.ids.iter().zip(&self.vectors).map(|(id, v)| entry_bytes(id, v)).sum();
+        entries + self.norms.as_ref().map_or(0, |norms| size_of_val(norms.as_slice()))
+    }
+
+    /// Append one vector
+    pub fn push(&mut self, id: String, vector: Vec<f32>) {
+        if let Some(norms) = &mut self.norms {
+            norms.push(l2_norm(&vector));
+        }
+        self.ids.push(id);
+        self.vectors.push(vector);
+    }
+
+    /// Exact top-k IDs sorted by ascending distance
+    pub fn search(&self, query_vector: &[f32], k: usize) -> Vec<String> {
+        self.search_with_distances(query_vector, k)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Exact top-k (id, distance) pairs sorted by ascending distance
+    pub fn search_with_distances(&self, query_vector: &[f32], k: usize) -> Vec<(String, f32)> {
+        let mut scored = self.distances(query_vector);
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        scored
+            .into_iter()
+            .take(k)
+            .map(|(d, i)| (self.ids[i].clone(), d))
+            .collect()
+    }
+
+    /// Every (id, distance) pair with distance <= `radius`, sorted by ascending distance
+    pub fn within_radius(&self, query_vector: &[f32], radius: f32) -> Vec<(String, f32)> {
+        let mut scored: Vec<(f32, usize)> = self
+            .distances(query_vector)
+            .into_iter()
+            .filter(|(d, _)| *d <= radius)
+            .collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        scored.into_iter().map(|(d, i)| (self.ids[i].clone(), d)).collect()
+    }
+
+    /// (distance, position) of every stored vector, unsorted
+    fn distances(&self, query_vector: &[f32]) -> Vec<(f32, usize)> {
+        match (&self.norms, self.metric) {
+            (Some(norms), DistanceMetric::Cosine) => {
+                // Same formula as DistanceMetric::Cosine, with the query norm computed once
+                let query_norm = l2_norm(query_vector);
+                self.vectors
+                    .iter()
+                    .zip(norms)
+                    .enumerate()
+                    .map(|(i, (v, norm))| {
+                        if query_norm == 0.0 || *norm == 0.0 {
+                            return (1.0, i);
+                        }
+                        (1.0 - dot(query_vector, v) / (query_norm * norm), i)
+                    })
+                    .collect()
+            }
+            _ => match &self.custom {
+                Some(custom) => self.vectors.iter().enumerate().map(|(i, v)| ((custom.0)(query_vector, v), i)).collect(),
+                None => self.vectors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (self.metric.distance(query_vector, v), i))
+                    .collect(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum Backend {
+    Flat(FlatIndex),
+    Hnsw(HnswMap<VectorPoint, String>), // Maps points to IDs
+}
+
+/// Serde for `VectorIndex::backend`: the shared backend is written as the backend itself
+mod shared_backend {
+    use super::Backend;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::sync::Arc;
+
+    pub fn serialize<S: Serializer>(backend: &Arc<Backend>, serializer: S) -> Result<S::Ok, S::Error> {
+        backend.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Arc<Backend>, D::Error> {
+        Backend::deserialize(deserializer).map(Arc::new)
+    }
+}
+
+/// Shape of a serialized `VectorIndex`, down to the graph links instant-distance keeps private
+#[derive(Deserialize)]
+struct IndexShape {
+    backend: BackendShape,
+}
+
+#[derive(Deserialize)]
+enum BackendShape {
+    Flat { ids: Vec<IgnoredAny>, vectors: Vec<IgnoredAny>, norms: Option<Vec<IgnoredAny>> },
+    Hnsw { hnsw: GraphShape, values: Vec<IgnoredAny> },
+}
+
+/// instant-distance's `Hnsw`: layer-zero links of every point, then the links of each upper
+/// layer (lowest first), whose nodes are the first points
+#[derive(Deserialize)]
+struct GraphShape {
+    points: Vec<IgnoredAny>,
+    zero: Vec<Vec<u32>>,
+    layers: Vec<Vec<Vec<u32>>>,
+}
+
+/// instant-distance's marker for an unused link slot
+const NO_NEIGHBOR: u32 = u32::MAX;
+
+/// Check that a serialized index can be searched without indexing out of bounds: flat IDs,
+/// vectors and norms line up, HNSW points, layer-zero nodes and IDs line up, each upper layer
+/// is non-empty and no larger than the one below, and every link stays within its layer.
+fn check_structure(json: &[u8]) -> Result<(), String> {
+    let shape: IndexShape = serde_json::from_slice(json).map_err(|e| e.to_string())?;
+    match shape.backend {
+        BackendShape::Flat { ids, vectors, norms } => {
+            if ids.len() != vectors.len() || norms.is_some_and(|norms| norms.len() != vectors.len()) {
+                return Err(format!("flat index has {} IDs for {} vectors", ids.len(), vectors.len()));
+            }
+        }
+        BackendShape::Hnsw { hnsw, values } => {
+            let points = hnsw.points.len();
+            if values.len() != points || hnsw.zero.len() != points {
+                return Err(format!(
+                    "graph has {} points, {} layer-zero nodes and {} IDs",
+                    points,
+                    hnsw.zero.len(),
+                    values.len()
+                ));
+            }
+            if points > 0 && hnsw.layers.iter().any(Vec::is_empty) {
+                return Err("graph has an empty upper layer".to_string());
+            }
+            let mut below = points;
+            for (depth, layer) in std::iter::once(&hnsw.zero).chain(&hnsw.layers).enumerate() {
+                if layer.len() > below {
+                    return Err(format!("graph layer {} has more nodes than the layer below", depth));
+                }
+                below = layer.len();
+                let out_of_range = layer.iter().flatten().find(|&&id| id != NO_NEIGHBOR && id as usize >= layer.len());
+                if let Some(id) = out_of_range {
+                    return Err(format!("graph layer {} links to point {}, past its {} nodes", depth, id, layer.len()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// VectorIndex wraps instant-distance HNSW for approximate nearest neighbor search
-/// This provides the advanced indexing for the vector database
+/// This provides the advanced indexing for the vector database.
+/// Small collections (below `HnswParams::flat_threshold`) transparently use an exact `FlatIndex`,
+/// searched through the same API.
+/// The HNSW graph is immutable, so points added after the build wait in `pending` (searched
+/// exactly alongside the graph) until `PENDING_MERGE_THRESHOLD` of them trigger a rebuild.
+/// Clones share the built backend, so cloning an index to `add` to it doesn't copy the graph.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VectorIndex {
-    map: HnswMap<VectorPoint, String>, // Maps points to IDs
+    #[serde(with = "shared_backend")]
+    backend: Arc<Backend>,
+    metric: DistanceMetric,
+    #[serde(default)]
+    pending: Vec<(String, Vec<f32>)>,
+    /// How long the build took, kept across checkpoints (0 for indexes saved before it was recorded)
+    #[serde(default)]
+    build_millis: u64,
+    /// Graph parameters, reused when `add` rebuilds the index
+    #[serde(default)]
+    params: HnswParams,
+    /// Set by `build_with_distance`: ranks by it instead of `metric`, which is then nominal
+    #[serde(skip)]
+    custom: Option<CustomDistance>,
 }
 
 impl VectorIndex {
-    /// Build the index from a list of (id, vector) pairs obtained from storage
+    /// Build the index from a list of (id, vector) pairs obtained from storage, ranking
+    /// neighbors by `metric`
+    pub fn build_from_vectors(vectors: Vec<(String, Vec<f32>)>, metric: DistanceMetric) -> Self {
+        Self::build_with_params(vectors, metric, HnswParams::default())
+    }
+
+    /// `build_from_vectors` with explicit HNSW parameters (see `HnswParams` for what a seed guarantees)
+    pub fn build_with_params(vectors: Vec<(String, Vec<f32>)>, metric: DistanceMetric, params: HnswParams) -> Self {
+        Self::build(vectors, metric, params, None)
+    }
+
+    /// Build the index ranking neighbors by `distance` instead of a built-in metric, e.g. a
+    /// Manhattan or Chebyshev distance. `metric()` then reports the nominal default, search
+    /// distances are whatever `distance` returns, and the index can't be serialized.
+    pub fn build_with_distance(vectors: Vec<(String, Vec<f32>)>, distance: DistanceFn) -> Self {
+        Self::build(vectors, DistanceMetric::default(), HnswParams::default(), Some(CustomDistance(distance)))
+    }
+
     #[instrument(skip(vectors))]
-    pub fn build_from_vectors(vectors: Vec<(String, Vec<f32>)>) -> Self {
-        debug!(vector_count = vectors.len(), "Building vector index");
+    fn build(vectors: Vec<(String, Vec<f32>)>, metric: DistanceMetric, params: HnswParams, custom: Option<CustomDistance>) -> Self {
+        let metric_name = if custom.is_some() { "custom" } else { metric.as_str() };
+        debug!(vector_count = vectors.len(), metric = metric_name, "Building vector index");
+        let started = Instant::now();
+        let finish = |backend: Backend, custom: Option<CustomDistance>| Self {
+            backend: Arc::new(backend),
+            metric,
+            pending: Vec::new(),
+            build_millis: started.elapsed().as_millis() as u64,
+            params,
+            custom,
+        };
+
+        if vectors.len() < params.flat_threshold {
+            debug!(vector_count = vectors.len(), "Using flat index for small collection");
+            let flat = match &custom {
+                Some(custom) => FlatIndex::with_distance(vectors, custom.0.clone()),
+                None => FlatIndex::with_metric(vectors, metric),
+            };
+            return finish(Backend::Flat(flat), custom);
+        }
         
         let points: Vec<VectorPoint> = vectors
             .iter()
-            .map(|(_, v)| VectorPoint(v.clone()))
+            .map(|(_, v)| VectorPoint::new(v.clone(), metric, custom.clone()))
             .collect();
         let values: Vec<String> = vectors.iter().map(|(id, _)| id.clone()).collect();
 
-        let map = Builder::default().build(points, values);
+        let map = params.build(points, values);
         
         debug!(vector_count = vectors.len(), "Vector index built successfully");
-        Self { map }
+        finish(Backend::Hnsw(map), custom)
+    }
+
+    /// Add one point without rebuilding the whole index. Flat indexes take it directly (and
+    /// switch to HNSW once they reach `flat_threshold`); HNSW indexes buffer it and are
+    /// rebuilt with the buffer once it holds `PENDING_MERGE_THRESHOLD` points. The point is
+    /// searchable as soon as this returns. Adding an ID that is already indexed keeps both
+    /// entries, so updated or deleted documents still need a rebuild.
+    pub fn add(&mut self, id: String, vector: Vec<f32>) {
+        let flat_threshold = self.params.flat_threshold;
+        let merge = match self.flat_mut() {
+            Some(flat) => {
+                flat.push(id, vector);
+                flat.len() >= flat_threshold
+            }
+            None => {
+                self.pending.push((id, vector));
+                self.pending.len() >= PENDING_MERGE_THRESHOLD
+            }
+        };
+        if merge {
+            debug!(vector_count = self.len(), "Rebuilding vector index with added points");
+            *self = Self::build(self.entries(), self.metric, self.params, self.custom.take());
+        }
+    }
+
+    /// The flat backend for appending, copied first if a clone of this index shares it
+    fn flat_mut(&mut self) -> Option<&mut FlatIndex> {
+        let shared = match &*self.backend {
+            Backend::Flat(flat) if Arc::strong_count(&self.backend) > 1 => Some(flat.clone()),
+            _ => None,
+        };
+        if let Some(flat) = shared {
+            self.backend = Arc::new(Backend::Flat(flat));
+        }
+        match Arc::get_mut(&mut self.backend) {
+            Some(Backend::Flat(flat)) => Some(flat),
+            _ => None,
+        }
+    }
+
+    /// Every indexed (id, vector) pair, buffered points included. Cosine graph points come
+    /// back unit-length, which ranks the same.
+    fn entries(&self) -> Vec<(String, Vec<f32>)> {
+        let mut entries: Vec<(String, Vec<f32>)> = match &*self.backend {
+            Backend::Flat(flat) => flat.ids.iter().cloned().zip(flat.vectors.iter().cloned()).collect(),
+            Backend::Hnsw(map) => map.values.iter().cloned().zip(map.iter().map(|(_, point)| point.vector.clone())).collect(),
+        };
+        entries.extend(self.pending.iter().cloned());
+        entries
+    }
+
+    /// (id, distance) of every buffered point within `radius` (unsorted)
+    fn pending_within(&self, query_vector: &[f32], radius: f32) -> impl Iterator<Item = (String, f32)> + '_ {
+        let query_vector = query_vector.to_vec();
+        self.pending
+            .iter()
+            .map(move |(id, v)| (id.clone(), self.distance(&query_vector, v)))
+            .filter(move |(_, d)| *d <= radius)
+    }
+
+    /// Distance between two vectors under the custom distance, else the metric
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        match &self.custom {
+            Some(custom) => (custom.0)(a, b),
+            None => self.metric.distance(a, b),
+        }
+    }
+
+    /// Serialized index (zstd-compressed JSON), restorable with `from_bytes` without a rebuild.
+    /// Fails for indexes built with a custom distance.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        if self.custom.is_some() {
+            return Err("Vector index with a custom distance can't be serialized".into());
+        }
+        let json = serde_json::to_vec(self)?;
+        Ok(zstd::encode_all(json.as_slice(), 3)?)
+    }
+
+    /// Index previously serialized with `to_bytes`. Fails for files whose graph links point
+    /// outside the graph or whose points and IDs don't pair up, which would panic in a search.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_bytes_within(bytes, u64::MAX)
+    }
+
+    /// `from_bytes` for files from elsewhere: fails as soon as the decompressed JSON exceeds
+    /// `max_json_bytes` instead of inflating all of it
+    pub fn from_bytes_within(bytes: &[u8], max_json_bytes: u64) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut json = Vec::new();
+        zstd::Decoder::new(bytes)?.take(max_json_bytes.saturating_add(1)).read_to_end(&mut json)?;
+        if json.len() as u64 > max_json_bytes {
+            return Err(format!("index decompresses to more than {} bytes", max_json_bytes).into());
+        }
+        check_structure(&json)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    /// Write the serialized index to `path`, replacing any previous file atomically
+    pub fn save(&self, path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        write_file_atomic(path, &self.to_bytes()?)
+    }
+
+    /// Index previously written with `save`
+    pub fn load(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Which backend serves searches on this index
+    pub fn backend(&self) -> IndexBackend {
+        match *self.backend {
+            Backend::Flat(_) => IndexBackend::Flat,
+            Backend::Hnsw(_) => IndexBackend::Hnsw,
+        }
+    }
+
+    /// Distance metric used for ranking
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    /// Number of indexed vectors, including points added since the build
+    pub fn len(&self) -> usize {
+        let built = match &*self.backend {
+            Backend::Flat(flat) => flat.len(),
+            Backend::Hnsw(map) => map.values.len(),
+        };
+        built + self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check that this index holds exactly `vectors`: each ID once, with the same vector (graph
+    /// points of cosine indexes compared after the same normalization the build applied).
+    /// The error says what differs.
+    pub fn check_contents(&self, vectors: &[(String, Vec<f32>)]) -> Result<(), String> {
+        let expected: HashMap<&str, &[f32]> = vectors.iter().map(|(id, v)| (id.as_str(), v.as_slice())).collect();
+        let built: Box<dyn Iterator<Item = (&String, &[f32], bool)>> = match &*self.backend {
+            Backend::Flat(flat) => Box::new(flat.ids.iter().zip(flat.vectors.iter()).map(|(id, v)| (id, v.as_slice(), false))),
+            Backend::Hnsw(map) => Box::new(map.values.iter().zip(map.iter()).map(|(id, (_, point))| (id, point.vector.as_slice(), true))),
+        };
+        let mut seen = HashSet::new();
+        for (id, indexed, in_graph) in built.chain(self.pending.iter().map(|(id, v)| (id, v.as_slice(), false))) {
+            let Some(stored) = expected.get(id.as_str()) else {
+                return Err(format!("index holds '{}', which the collection doesn't", id));
+            };
+            if !seen.insert(id.as_str()) {
+                return Err(format!("index holds '{}' more than once", id));
+            }
+            let same = if in_graph {
+                VectorPoint::new(stored.to_vec(), self.metric, self.custom.clone()).vector == indexed
+            } else {
+                *stored == indexed
+            };
+            if !same {
+                return Err(format!("vector of '{}' differs from the stored one", id));
+            }
+        }
+        if seen.len() != expected.len() {
+            return Err(format!("index holds {} of the collection's {} vectors", seen.len(), expected.len()));
+        }
+        Ok(())
+    }
+
+    /// Length of the indexed vectors (`None` when empty)
+    pub fn dim(&self) -> Option<usize> {
+        let built = match &*self.backend {
+            Backend::Flat(flat) => flat.vectors.first().map(Vec::len),
+            Backend::Hnsw(map) => map.iter().next().map(|(_, point)| point.vector.len()),
+        };
+        built.or_else(|| self.pending.first().map(|(_, v)| v.len()))
+    }
+
+    /// Points added since the build, searched exactly beside the graph
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Time the last (re)build of this index took
+    pub fn build_time(&self) -> Duration {
+        Duration::from_millis(self.build_millis)
+    }
+
+    /// Estimated resident size: stored vectors and IDs, plus for HNSW the neighbor links of the
+    /// graph (`2 * M` per point on layer zero and `M` per upper-layer node, about `n / (M - 1)` of
+    /// them), plus buffered points. Allocator overhead and spare capacity aren't counted, so
+    /// treat it as a lower bound for capacity planning.
+    pub fn approx_memory_bytes(&self) -> usize {
+        let built = match &*self.backend {
+            Backend::Flat(flat) => flat.approx_memory_bytes(),
+            Backend::Hnsw(map) => {
+                let n = map.values.len();
+                let points: usize = map
+                    .iter()
+                    .zip(&map.values)
+                    .map(|((_, point), id)| entry_bytes(id, &point.vector) + size_of::<DistanceMetric>())
+                    .sum();
+                let link = size_of::<u32>();
+                let links = n * 2 * HNSW_M * link + n / (HNSW_M - 1) * HNSW_M * link;
+                points + links
+            }
+        };
+        built + self.pending.iter().map(|(id, v)| entry_bytes(id, v)).sum::<usize>()
+    }
+
+    /// Name of the distance metric used for ranking (`custom` for `build_with_distance` indexes)
+    pub fn metric_name(&self) -> &'static str {
+        if self.custom.is_some() {
+            return "custom";
+        }
+        self.metric().as_str()
     }
 
     /// Search for k nearest neighbors by query vector, returns IDs
     /// This is the core indexing engine functionality
     #[instrument(skip(self, query_vector))]
     pub fn search(&self, query_vector: &[f32], k: usize) -> Vec<String> {
+        self.search_with_distances(query_vector, k)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Search for k nearest neighbors, returning (id, raw distance) pairs sorted closest first
+    #[instrument(skip(self, query_vector))]
+    pub fn search_with_distances(&self, query_vector: &[f32], k: usize) -> Vec<(String, f32)> {
         debug!(k = k, vector_len = query_vector.len(), "Searching vector index");
+
+        let map = match &*self.backend {
+            Backend::Flat(flat) => return flat.search_with_distances(query_vector, k),
+            Backend::Hnsw(map) => map,
+        };
         
-        let query_point = VectorPoint(query_vector.to_vec());
+        let query_point = VectorPoint::new(query_vector.to_vec(), self.metric, self.custom.clone());
         let mut search_state = Search::default();
         // Search returns iterator of (PointId, &Value), sorted by distance
-        let results: Vec<String> = self.map
+        let mut results: Vec<(String, f32)> = map
             .search(&query_point, &mut search_state)
             .take(k)
-            .map(|item| item.value.clone())
+            .map(|item| (item.value.clone(), item.distance))
             .collect();
+        if !self.pending.is_empty() {
+            results.extend(self.pending_within(query_vector, f32::INFINITY));
+            results.sort_by(|a, b| a.1.total_cmp(&b.1));
+            results.truncate(k);
+        }
         
         debug!(k = k, results_count = results.len(), "Vector search completed");
         results
     }
+
+    /// Every (id, distance) pair within `radius` of the query, sorted closest first.
+    /// Exact on both backends: HNSW's beam search can't enumerate a whole neighborhood, so the
+    /// graph's points are scanned instead (O(n) per query).
+    #[instrument(skip(self, query_vector))]
+    pub fn within_radius(&self, query_vector: &[f32], radius: f32) -> Vec<(String, f32)> {
+        let map = match &*self.backend {
+            Backend::Flat(flat) => return flat.within_radius(query_vector, radius),
+            Backend::Hnsw(map) => map,
+        };
+        // Graph points and values share the same order
+        let mut results: Vec<(String, f32)> = map
+            .iter()
+            .zip(&map.values)
+            .map(|((_, point), id)| (id, self.distance(query_vector, &point.vector)))
+            .filter(|(_, distance)| *distance <= radius)
+            .map(|(id, distance)| (id.clone(), distance))
+            .chain(self.pending_within(query_vector, radius))
+            .collect();
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        debug!(radius = radius, results_count = results.len(), "Range search completed");
+        results
+    }
 }
 
 #[cfg(test)]
@@ -74,7 +916,7 @@ mod tests {
         ];
 
         // Build index
-        let index = VectorIndex::build_from_vectors(vectors);
+        let index = VectorIndex::build_from_vectors(vectors, DistanceMetric::L2);
 
         // Search with query close to doc1
         let query = vec![0.9, 0.1, 0.0];
@@ -84,4 +926,358 @@ mod tests {
         assert_eq!(results[0], "doc1");
         assert_eq!(results.len(), 1);
     }
+
+    #[test]
+    fn custom_distances_rank_on_both_backends_and_survive_merges() {
+        let manhattan: DistanceFn = Arc::new(|a: &[f32], b: &[f32]| a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum());
+        // Closer by L2 (4.24 vs 5) but farther by Manhattan (6 vs 5)
+        let diagonal = ("diagonal".to_string(), vec![3.0, 3.0]);
+        let axis = ("axis".to_string(), vec![5.0, 0.0]);
+        let filler = |n: usize| (0..n).map(|i| (format!("far{}", i), vec![100.0 + i as f32, 100.0]));
+
+        let l2 = VectorIndex::build_from_vectors(vec![diagonal.clone(), axis.clone()], DistanceMetric::L2);
+        assert_eq!(l2.search(&[0.0, 0.0], 2), vec!["diagonal", "axis"]);
+        let flat = VectorIndex::build_with_distance(vec![diagonal.clone(), axis.clone()], manhattan.clone());
+        assert_eq!(flat.search_with_distances(&[0.0, 0.0], 2), vec![("axis".to_string(), 5.0), ("diagonal".to_string(), 6.0)]);
+        assert_eq!(flat.metric_name(), "custom");
+        assert!(flat.to_bytes().is_err(), "closures can't be checkpointed");
+
+        let mut vectors: Vec<(String, Vec<f32>)> = filler(FLAT_INDEX_THRESHOLD).collect();
+        vectors.extend([diagonal.clone(), axis.clone()]);
+        let hnsw = VectorIndex::build_with_distance(vectors, manhattan.clone());
+        assert_eq!(hnsw.backend(), IndexBackend::Hnsw);
+        assert_eq!(hnsw.search(&[0.0, 0.0], 2), vec!["axis", "diagonal"]);
+        assert_eq!(hnsw.within_radius(&[0.0, 0.0], 5.5), vec![("axis".to_string(), 5.0)]);
+
+        // Crossing into HNSW on `add` rebuilds with the same distance
+        let mut grown = VectorIndex::build_with_distance(filler(FLAT_INDEX_THRESHOLD - 2).chain([diagonal]).collect(), manhattan);
+        grown.add(axis.0, axis.1);
+        assert_eq!(grown.backend(), IndexBackend::Hnsw);
+        assert_eq!(grown.search(&[0.0, 0.0], 2), vec!["axis", "diagonal"]);
+
+        // Built-in metrics are available as distance functions too
+        let cosine = DistanceMetric::Cosine.as_distance_fn();
+        assert!((cosine(&[1.0, 0.0], &[0.0, 2.0]) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn added_points_are_searchable_without_a_rebuild() {
+        let point = |i: usize| (format!("doc{}", i), vec![i as f32 * 10.0, 1.0]);
+        let mut index = VectorIndex::build_from_vectors((0..5).map(point).collect(), DistanceMetric::L2);
+        assert_eq!(index.search(&[41.0, 1.0], 1), vec!["doc4"]);
+        let original = index.clone();
+
+        for i in 5..10 {
+            index.add(point(i).0, point(i).1);
+        }
+        assert_eq!(index.len(), 10);
+        // The clone shared the backend but not the added points
+        assert_eq!((original.len(), original.search(&[91.0, 1.0], 1)), (5, vec!["doc4".to_string()]));
+        for i in 5..10 {
+            assert_eq!(index.search(&[i as f32 * 10.0 + 1.0, 1.0], 1), vec![format!("doc{}", i)]);
+        }
+
+        // HNSW graphs buffer added points; searches merge them with the graph's candidates
+        let mut index = VectorIndex::build_from_vectors((0..FLAT_INDEX_THRESHOLD).map(point).collect(), DistanceMetric::L2);
+        assert_eq!(index.backend(), IndexBackend::Hnsw);
+        let original = index.clone();
+        index.add("far".to_string(), vec![-500.0, 1.0]);
+        assert_eq!(index.len(), FLAT_INDEX_THRESHOLD + 1);
+        assert_eq!(original.pending_len(), 0);
+        assert_eq!(index.backend(), IndexBackend::Hnsw);
+        assert_eq!(index.search(&[-480.0, 1.0], 1), vec!["far"]);
+        assert_eq!(index.within_radius(&[-500.0, 1.0], 1.0), vec![("far".to_string(), 0.0)]);
+    }
+
+    #[test]
+    fn memory_estimate_grows_with_indexed_vectors() {
+        let point = |i: usize| (format!("doc{}", i), vec![i as f32, 1.0, 0.5, 0.25]);
+        let build = |n: usize| VectorIndex::build_from_vectors((0..n).map(point).collect(), DistanceMetric::Cosine);
+
+        let (small, flat, hnsw, larger) = (build(10), build(100), build(FLAT_INDEX_THRESHOLD + 10), build(FLAT_INDEX_THRESHOLD * 2));
+        assert_eq!((flat.backend(), hnsw.backend()), (IndexBackend::Flat, IndexBackend::Hnsw));
+        // At least the raw vectors, and more with every step up in size
+        assert!(flat.approx_memory_bytes() >= 100 * 4 * size_of::<f32>());
+        assert!(small.approx_memory_bytes() < flat.approx_memory_bytes());
+        assert!(flat.approx_memory_bytes() < hnsw.approx_memory_bytes());
+        assert!(hnsw.approx_memory_bytes() < larger.approx_memory_bytes());
+        // Graph links cost more per vector than a flat scan list
+        assert!(hnsw.approx_memory_bytes() / hnsw.len() > flat.approx_memory_bytes() / flat.len());
+
+        let mut grown = build(FLAT_INDEX_THRESHOLD + 10);
+        grown.add("extra".to_string(), vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(grown.pending_len(), 1);
+        assert!(grown.approx_memory_bytes() > hnsw.approx_memory_bytes());
+    }
+
+    #[test]
+    fn saved_index_loads_with_the_same_results() {
+        let path = std::env::temp_dir().join("aidb_test_index_save.aidx");
+        let vectors: Vec<(String, Vec<f32>)> = (0..FLAT_INDEX_THRESHOLD + 10)
+            .map(|i| (format!("doc{}", i), vec![i as f32, (i % 7) as f32]))
+            .collect();
+        let index = VectorIndex::build_from_vectors(vectors, DistanceMetric::Cosine);
+        index.save(&path).unwrap();
+
+        let loaded = VectorIndex::load(&path).unwrap();
+        assert_eq!(loaded.backend(), IndexBackend::Hnsw);
+        assert_eq!(loaded.metric(), DistanceMetric::Cosine);
+        assert_eq!(loaded.len(), index.len());
+        assert_eq!(loaded.search(&[40.0, 3.0], 5), index.search(&[40.0, 3.0], 5));
+        assert!(VectorIndex::load(&path.with_extension("missing")).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn larger_search_candidate_lists_find_more_true_neighbors() {
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 10_000) as f32 / 100.0
+        };
+        let vectors: Vec<(String, Vec<f32>)> = (0..FLAT_INDEX_THRESHOLD * 4)
+            .map(|i| (format!("doc{}", i), (0..8).map(|_| next()).collect()))
+            .collect();
+        let queries: Vec<Vec<f32>> = (0..20).map(|_| (0..8).map(|_| next()).collect()).collect();
+        let exact = FlatIndex::new(vectors.clone());
+        let recall = |params: HnswParams| {
+            let index = VectorIndex::build_with_params(vectors.clone(), DistanceMetric::L2, params);
+            assert_eq!(index.backend(), IndexBackend::Hnsw);
+            let found: usize = queries
+                .iter()
+                .map(|q| {
+                    let truth = exact.search(q, 10);
+                    index.search(q, 10).iter().filter(|id| truth.contains(id)).count()
+                })
+                .sum();
+            found as f64 / (queries.len() * 10) as f64
+        };
+
+        let narrow = recall(HnswParams { ef_construction: 4, ef_search: 2, num_layers: Some(2), seed: Some(7), ..HnswParams::default() });
+        let wide = recall(HnswParams { ef_construction: 200, ef_search: 200, num_layers: Some(4), seed: Some(7), ..HnswParams::default() });
+        assert!(narrow <= 0.2, "at most ef_search = 2 of 10 neighbors, got {}", narrow);
+        assert!(wide >= 0.9, "wide candidate lists recall nearly every neighbor, got {}", wide);
+    }
+
+    #[test]
+    fn seeded_builds_return_identical_results() {
+        // Deterministic pseudo-random vectors, so the graph has real choices to make
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % 10_000) as f32 / 100.0
+        };
+        let vectors: Vec<(String, Vec<f32>)> = (0..FLAT_INDEX_THRESHOLD * 3)
+            .map(|i| (format!("doc{}", i), (0..8).map(|_| next()).collect()))
+            .collect();
+        let queries: Vec<Vec<f32>> = (0..20).map(|_| (0..8).map(|_| next()).collect()).collect();
+        let params = HnswParams { seed: Some(42), ..HnswParams::default() };
+
+        let a = VectorIndex::build_with_params(vectors.clone(), DistanceMetric::L2, params);
+        let b = VectorIndex::build_with_params(vectors.clone(), DistanceMetric::L2, params);
+        assert_eq!(a.backend(), IndexBackend::Hnsw);
+        for query in &queries {
+            assert_eq!(a.search_with_distances(query, 10), b.search_with_distances(query, 10));
+        }
+
+        // The seed survives the rebuild that merges added points
+        let (mut a, mut b) = (a, b);
+        for i in 0..PENDING_MERGE_THRESHOLD {
+            let point = (format!("added{}", i), (0..8).map(|_| next()).collect::<Vec<f32>>());
+            a.add(point.0.clone(), point.1.clone());
+            b.add(point.0, point.1);
+        }
+        assert_eq!(a.pending_len(), 0, "the buffer was merged into a rebuilt graph");
+        for query in &queries {
+            assert_eq!(a.search_with_distances(query, 10), b.search_with_distances(query, 10));
+        }
+    }
+
+    #[test]
+    fn test_backend_selection_by_collection_size() {
+        let small: Vec<(String, Vec<f32>)> = (0..10)
+            .map(|i| (format!("doc{}", i), vec![i as f32, 0.0]))
+            .collect();
+        assert_eq!(VectorIndex::build_from_vectors(small, DistanceMetric::L2).backend(), IndexBackend::Flat);
+
+        let large: Vec<(String, Vec<f32>)> = (0..FLAT_INDEX_THRESHOLD)
+            .map(|i| (format!("doc{}", i), vec![i as f32, 0.0]))
+            .collect();
+        let index = VectorIndex::build_from_vectors(large, DistanceMetric::L2);
+        assert_eq!(index.backend(), IndexBackend::Hnsw);
+        assert_eq!(index.metric_name(), "l2");
+    }
+
+    #[test]
+    fn collections_below_the_flat_threshold_find_the_true_nearest_neighbor() {
+        // Two tight clusters and a lone target past the far one. The graph parameters are as sparse
+        // as they get, which a flat index ignores: it scans every vector.
+        let mut vectors: Vec<(String, Vec<f32>)> = (0..300).map(|i| (format!("near{}", i), vec![i as f32 * 0.01, 0.0])).collect();
+        vectors.extend((0..300).map(|i| (format!("far{}", i), vec![1000.0 + i as f32 * 0.01, 0.0])));
+        vectors.push(("target".to_string(), vec![2000.0, 0.0]));
+        let query = [1999.0, 0.0];
+        let sparse = HnswParams { ef_construction: 1, ef_search: 1, seed: Some(3), ..HnswParams::default() };
+
+        let exact = VectorIndex::build_with_params(vectors.clone(), DistanceMetric::L2, HnswParams { flat_threshold: 5000, ..sparse });
+        assert_eq!(exact.backend(), IndexBackend::Flat);
+        assert_eq!(exact.search_with_distances(&query, 1), vec![("target".to_string(), 1.0)]);
+        assert_eq!(exact.search(&query, 3), vec!["target", "far299", "far298"], "not capped by ef_search");
+
+        // A lower threshold switches the same vectors to a graph; `add` crosses it the same way
+        let graph = VectorIndex::build_with_params(vectors.clone(), DistanceMetric::L2, HnswParams { flat_threshold: 100, ..sparse });
+        assert_eq!(graph.backend(), IndexBackend::Hnsw);
+        let mut grown = VectorIndex::build_with_params(vectors[..9].to_vec(), DistanceMetric::L2, HnswParams { flat_threshold: 10, ..sparse });
+        assert_eq!(grown.backend(), IndexBackend::Flat);
+        grown.add("extra".to_string(), vec![5.0, 5.0]);
+        assert_eq!(grown.backend(), IndexBackend::Hnsw);
+    }
+
+    #[test]
+    fn cosine_ranks_scaled_copy_nearest_unlike_l2() {
+        let base = vec![1.0, 2.0, 3.0];
+        let query_and_vectors = |n: usize| {
+            // The scaled copy sits far away in L2 terms; the distractor is close but points elsewhere
+            let mut vectors = vec![
+                ("scaled".to_string(), base.iter().map(|x| x * 10.0).collect::<Vec<f32>>()),
+                ("distractor".to_string(), vec![2.0, 2.0, 1.0]),
+            ];
+            vectors.extend((0..n).map(|i| (format!("noise{}", i), vec![-(i as f32) - 50.0, 40.0, -60.0])));
+            vectors
+        };
+
+        // Both backends: flat below the threshold, HNSW above it
+        for noise in [0, FLAT_INDEX_THRESHOLD] {
+            let cosine = VectorIndex::build_from_vectors(query_and_vectors(noise), DistanceMetric::Cosine);
+            let l2 = VectorIndex::build_from_vectors(query_and_vectors(noise), DistanceMetric::L2);
+            assert_eq!(cosine.metric(), DistanceMetric::Cosine);
+            assert_eq!(cosine.metric_name(), "cosine");
+
+            let nearest = cosine.search_with_distances(&base, 1);
+            assert_eq!(nearest[0].0, "scaled", "backend {:?}", cosine.backend());
+            assert!(nearest[0].1.abs() < 1e-5);
+            assert_eq!(l2.search(&base, 1), vec!["distractor"], "backend {:?}", l2.backend());
+        }
+    }
+
+    #[test]
+    fn dot_product_prefers_larger_aligned_vectors() {
+        let vectors = vec![
+            ("unit".to_string(), vec![1.0, 0.0]),
+            ("long".to_string(), vec![5.0, 1.0]),
+            ("opposite".to_string(), vec![-3.0, 0.0]),
+        ];
+        let index = VectorIndex::build_from_vectors(vectors, DistanceMetric::DotProduct);
+        let results = index.search_with_distances(&[1.0, 0.0], 3);
+        assert_eq!(results.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), ["long", "unit", "opposite"]);
+        assert_eq!(DistanceMetric::DotProduct.to_similarity(results[0].1), 5.0);
+        assert_eq!(serde_json::to_value(DistanceMetric::DotProduct).unwrap(), "dot_product");
+    }
+
+    #[test]
+    fn test_canonical_similarity_is_higher_for_closer_vectors() {
+        let query = [1.0, 0.0];
+        let near = [0.9, 0.1];
+        let far = [-1.0, 0.5];
+
+        for metric in [DistanceMetric::L2, DistanceMetric::Cosine] {
+            let d_near = metric.distance(&query, &near);
+            let d_far = metric.distance(&query, &far);
+            assert!(d_near < d_far, "{}: distance should grow with dissimilarity", metric.as_str());
+
+            let s_near = ScoreKind::Similarity.apply(metric, d_near);
+            let s_far = ScoreKind::Similarity.apply(metric, d_far);
+            assert!(s_near > s_far, "{}: similarity should be higher for the closer vector", metric.as_str());
+            assert!((metric.to_similarity(metric.distance(&query, &query)) - 1.0).abs() < 1e-6);
+        }
+
+        assert_eq!(ScoreKind::Distance.apply(DistanceMetric::L2, 2.5), 2.5);
+        assert_eq!(ScoreKind::parse("Similarity"), Ok(ScoreKind::Similarity));
+        assert!(ScoreKind::parse("rank").is_err());
+    }
+
+    #[test]
+    fn test_build_limiter_serializes_with_one_permit() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let limiter = Arc::new(IndexBuildLimiter::new(1));
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_seen = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let (limiter, active, max_seen) = (limiter.clone(), active.clone(), max_seen.clone());
+                std::thread::spawn(move || {
+                    let _permit = limiter.acquire();
+                    let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_seen.fetch_max(now, Ordering::SeqCst);
+                    let vectors = (0..50).map(|j| (format!("{}-{}", i, j), vec![j as f32, 1.0])).collect();
+                    let index = VectorIndex::build_from_vectors(vectors, DistanceMetric::L2);
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                    active.fetch_sub(1, Ordering::SeqCst);
+                    index.search(&[1.0, 1.0], 1)
+                })
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap().len(), 1);
+        }
+
+        assert_eq!(max_seen.load(Ordering::SeqCst), 1);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    fn cosine_vectors(n: usize, dim: usize) -> Vec<(String, Vec<f32>)> {
+        (0..n)
+            .map(|i| {
+                let v = (0..dim).map(|d| ((i * 31 + d * 7) % 97) as f32 - 48.0).collect();
+                (format!("v{}", i), v)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cached_norms_match_recomputed_cosine() {
+        let vectors = cosine_vectors(200, 16);
+        let cached = FlatIndex::with_metric(vectors.clone(), DistanceMetric::Cosine);
+        let uncached = FlatIndex::with_metric(vectors, DistanceMetric::Cosine).without_norm_cache();
+        assert_eq!(cached.metric(), DistanceMetric::Cosine);
+
+        for query in [vec![1.0; 16], vec![-3.0; 16], (0..16).map(|d| d as f32).collect()] {
+            let a = cached.search_with_distances(&query, 10);
+            let b = uncached.search_with_distances(&query, 10);
+            assert_eq!(a.iter().map(|(id, _)| id).collect::<Vec<_>>(), b.iter().map(|(id, _)| id).collect::<Vec<_>>());
+            for ((_, da), (_, db)) in a.iter().zip(&b) {
+                assert!((da - db).abs() < 1e-5, "{} vs {}", da, db);
+            }
+        }
+        // A zero query is maximally distant from everything, as in DistanceMetric::Cosine
+        assert!(cached.search_with_distances(&[0.0; 16], 3).iter().all(|(_, d)| *d == 1.0));
+    }
+
+    /// Timing-sensitive, so opt-in: `cargo test --release -- --ignored cached_norms_speed_up`
+    #[test]
+    #[ignore]
+    fn cached_norms_speed_up_repeated_queries() {
+        let vectors = cosine_vectors(20_000, 256);
+        let cached = FlatIndex::with_metric(vectors.clone(), DistanceMetric::Cosine);
+        let uncached = FlatIndex::with_metric(vectors, DistanceMetric::Cosine).without_norm_cache();
+        let query: Vec<f32> = (0..256).map(|d| d as f32).collect();
+
+        let time = |index: &FlatIndex| {
+            let start = std::time::Instant::now();
+            for _ in 0..10 {
+                index.search_with_distances(&query, 10);
+            }
+            start.elapsed()
+        };
+        let uncached_time = (0..3).map(|_| time(&uncached)).min().unwrap();
+        let cached_time = (0..3).map(|_| time(&cached)).min().unwrap();
+        assert!(cached_time < uncached_time, "cached {:?} vs uncached {:?}", cached_time, uncached_time);
+    }
 }
@@ -5,12 +5,16 @@
 //!
 //! This lib exposes the core storage and indexing engine.
 
+// Startup configuration: every AIDB_* setting parsed once into AppConfig
+pub mod config;
 pub mod cache;
 pub mod storage;
 pub mod indexing;
 // Query module for SQL engine (DataFusion) over NoSQL/Arrow projection
 // Enables multi-model: SQL on JSON/vectors with push-down
 pub mod query;
+// Cancels blocking search work when the request that started it is dropped
+pub mod cancel;
 // REST API module: Axum HTTP handlers on port 11111 (mirrors gRPC for multi-model)
 pub mod rest;
 pub mod tenants;
@@ -7,6 +7,7 @@
 use std::path::PathBuf;
 use std::io::{BufRead, BufReader};
 use std::fs::File;
+use std::sync::OnceLock;
 use tracing_subscriber::{
     fmt::{self, format::FmtSpan},
     layer::SubscriberExt,
@@ -17,6 +18,7 @@ use tracing_appender::non_blocking::WorkerGuard;
 use serde::{Deserialize, Serialize};
 
 /// Configuration for logging
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LogConfig {
     pub level: String,
     pub file_path: PathBuf,
@@ -31,24 +33,22 @@ impl Default for LogConfig {
     }
 }
 
+/// File `init_logging` writes to, read back by the session log endpoints
+static LOG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
 impl LogConfig {
-    /// Load logging configuration from environment variables
-    pub fn from_env() -> Self {
-        let level = std::env::var("AIDB_LOG_LEVEL")
-            .unwrap_or_else(|_| "info".to_string());
-        
-        let file_path = std::env::var("AIDB_LOG_FILE")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| PathBuf::from("logs/aidb.log.json"));
-        
-        Self { level, file_path }
+    /// Parse `AIDB_LOG_LEVEL` and `AIDB_LOG_FILE` (unset keeps the defaults)
+    pub fn from_lookup(var: crate::config::Lookup) -> Self {
+        let defaults = Self::default();
+        Self {
+            level: var("AIDB_LOG_LEVEL").unwrap_or(defaults.level),
+            file_path: var("AIDB_LOG_FILE").map(PathBuf::from).unwrap_or(defaults.file_path),
+        }
     }
     
-    /// Get the log file path
+    /// Get the log file path (the one logging was initialized with, else the default)
     pub fn get_log_path() -> PathBuf {
-        std::env::var("AIDB_LOG_FILE")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| PathBuf::from("logs/aidb.log.json"))
+        LOG_PATH.get().cloned().unwrap_or_else(|| Self::default().file_path)
     }
 }
 
@@ -198,9 +198,9 @@ pub fn read_all_logs() -> Result<Vec<JsonLogEntry>, Box<dyn std::error::Error>>
 
 /// Initialize the logging system with JSON file output
 /// Returns a WorkerGuard that must be kept alive for the duration of the application
-pub fn init_logging() -> Result<WorkerGuard, Box<dyn std::error::Error>> {
-    let config = LogConfig::from_env();
-    
+pub fn init_logging(config: &LogConfig) -> Result<WorkerGuard, Box<dyn std::error::Error>> {
+    let _ = LOG_PATH.set(config.file_path.clone());
+
     // Create log directory if it doesn't exist
     if let Some(parent) = config.file_path.parent() {
         std::fs::create_dir_all(parent)?;
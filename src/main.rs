@@ -11,23 +11,30 @@
 //!   cargo run --bin my_ai_db     # start server
 //!   # Then query via gRPC (see README for grpcurl/curl-like examples)
 
-use tonic::{transport::Server, Request, Response, Status};
+use tonic::{transport::{Identity, Server, ServerTlsConfig}, Request, Response, Status};
 // Axum + Tokio for REST API server (concurrent with gRPC on 11111)
 use axum;
-use std::net::SocketAddr;
+use futures::{Stream, StreamExt};
+use std::collections::HashMap;
+use std::future::{Future, IntoFuture};
+use std::time::{Duration, Instant};
 use tokio::net::TcpListener;  // For Axum bind in 0.7+
+use tokio::sync::watch;
 // tower::ServiceBuilder unused (optional layers; keep dep for future)
 use tracing::{info, warn, error, debug, instrument};
 
 // Core modules from lib (use package name for bin compatibility)
 // Enables multi-model: Storage (Sled/JSON), Indexing (HNSW), Query (DataFusion SQL)
 // + REST: Axum HTTP on port 11111 (concurrent with gRPC)
-use my_ai_db::storage::{Storage, Document};
-use my_ai_db::query::QueryEngine;
-use my_ai_db::rest::create_router;  // REST router
+use my_ai_db::storage::{Storage, Document, DimensionMismatch, InvalidVector, DEFAULT_VECTOR_FIELD};
+use my_ai_db::query::{encode_ipc_stream, QueryEngine};
+use my_ai_db::query::vector::{ScoreBoost, VectorSearchOptions, DEFAULT_EXPAND_WEIGHT};
+use my_ai_db::indexing::ScoreKind;
+use my_ai_db::rest::create_router_with_config;  // REST router
+use my_ai_db::config::{AppConfig, TlsConfig};
 use serde_json;  // For JSON in NoSQL insert_doc RPC
-use my_ai_db::tenants::{User, Tenant, Environment, Collection, AuthPayload};
-use my_ai_db::auth::{hash_password, verify_password, create_jwt_with_session, validate_jwt};
+use my_ai_db::tenants::{validate_hierarchy_id, AuthPayload, Collection, Environment, Role, Tenant, User};
+use my_ai_db::auth::{hash_password, verify_login, INVALID_CREDENTIALS, create_jwt_with_session, require_collection_access, require_role, validate_jwt};
 
 // Include generated proto code (from tonic-build on aidb package)
 // Regenerates on build for new multi-model RPCs
@@ -38,7 +45,7 @@ pub mod aidb {
 use aidb::{
     ai_db_service_server::{AiDbService, AiDbServiceServer},
     HybridRequest, HybridResponse, InsertDocRequest, InsertRequest, InsertResponse,
-    BatchInsertRequest, BatchInsertDocRequest,
+    BatchInsertRequest, BatchInsertDocRequest, ImportDocsResponse,
     SearchRequest, SearchResponse, SqlRequest, SqlResponse, VectorSearchRequest,
     TextSearchRequest, TextSearchResponse, TextSearchItem,
     RegisterRequest, RegisterResponse, LoginRequest, LoginResponse,
@@ -51,6 +58,205 @@ use aidb::{
     RagResultItem, RagChunk,
 };
 
+/// Documents buffered by `ImportDocs` before they are written as one batch
+const IMPORT_BATCH_SIZE: usize = 500;
+/// Error messages kept in an `ImportDocsResponse`
+const IMPORT_MAX_ERRORS: usize = 20;
+
+/// Metadata key naming the default tenant of a call (e.g. for `CreateEnvironment`)
+const TENANT_METADATA: &str = "tenant";
+/// Metadata key naming the default collection of a call
+const COLLECTION_METADATA: &str = "collection";
+
+/// Tenant and collection a client scoped its calls to through metadata, so it needn't repeat
+/// them in every message (the gRPC side of the REST workspace context). A non-empty
+/// `collection_id`/`tenant_id` in the message always wins over the scope.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct CallScope {
+    tenant: Option<String>,
+    collection: Option<String>,
+}
+
+impl CallScope {
+    fn from_metadata(metadata: &tonic::metadata::MetadataMap) -> Result<Self, Status> {
+        let read = |key: &str| -> Result<Option<String>, Status> {
+            match metadata.get(key) {
+                None => Ok(None),
+                Some(value) => {
+                    let value = value
+                        .to_str()
+                        .map_err(|_| Status::invalid_argument(format!("Metadata '{}' must be ASCII", key)))?
+                        .trim();
+                    Ok((!value.is_empty()).then(|| value.to_string()))
+                }
+            }
+        };
+        Ok(Self { tenant: read(TENANT_METADATA)?, collection: read(COLLECTION_METADATA)? })
+    }
+}
+
+/// Interceptor installing the call's `CallScope` as a request extension
+fn scope_interceptor(mut request: Request<()>) -> Result<Request<()>, Status> {
+    let scope = CallScope::from_metadata(request.metadata())?;
+    if scope != CallScope::default() {
+        debug!(tenant = ?scope.tenant, collection = ?scope.collection, "gRPC call scoped by metadata");
+    }
+    request.extensions_mut().insert(scope);
+    Ok(request)
+}
+
+/// Messages whose target collection (or tenant) a `CallScope` defaults
+trait Scoped {
+    fn apply_scope(&mut self, scope: &CallScope);
+
+    /// Collection the message targets, checked against the caller's tenants
+    fn collection_id(&self) -> Option<&str> {
+        None
+    }
+}
+
+fn fill_default(field: &mut String, default: &Option<String>) {
+    if let (true, Some(default)) = (field.is_empty(), default) {
+        *field = default.clone();
+    }
+}
+
+macro_rules! collection_scoped {
+    ($($message:ty),* $(,)?) => {
+        $(impl Scoped for $message {
+            fn apply_scope(&mut self, scope: &CallScope) {
+                fill_default(&mut self.collection_id, &scope.collection);
+            }
+
+            fn collection_id(&self) -> Option<&str> {
+                Some(&self.collection_id)
+            }
+        })*
+    };
+}
+
+collection_scoped!(
+    InsertRequest, InsertDocRequest, BatchInsertRequest, BatchInsertDocRequest, SearchRequest,
+    VectorSearchRequest, TextSearchRequest, SqlRequest, HybridRequest, RagIngestRequest,
+    RagSearchRequest, RagGetDocRequest, RagDeleteDocRequest, RagListDocsRequest,
+);
+
+impl Scoped for CreateEnvironmentRequest {
+    fn apply_scope(&mut self, scope: &CallScope) {
+        fill_default(&mut self.tenant_id, &scope.tenant);
+    }
+}
+
+/// The request's message with its `CallScope` (if the interceptor ran) applied
+fn into_scoped<T: Scoped>(request: Request<T>) -> T {
+    let scope = request.extensions().get::<CallScope>().cloned().unwrap_or_default();
+    let mut message = request.into_inner();
+    message.apply_scope(&scope);
+    message
+}
+
+/// Map a storage error to a gRPC status: rejected vectors (NaN/Inf or out-of-range components)
+/// are the caller's fault and become InvalidArgument, anything else Internal with `message`
+fn storage_status(e: &(dyn std::error::Error + 'static), message: String) -> Status {
+    if let Some(mismatch) = e.downcast_ref::<DimensionMismatch>() {
+        return Status::invalid_argument(mismatch.to_string());
+    }
+    match e.downcast_ref::<InvalidVector>() {
+        Some(invalid) => Status::invalid_argument(invalid.to_string()),
+        None => Status::internal(message),
+    }
+}
+
+fn record_import_error(summary: &mut ImportDocsResponse, message: String) {
+    warn!(error = %message, "ImportDocs error");
+    if summary.errors.len() < IMPORT_MAX_ERRORS {
+        summary.errors.push(message);
+    }
+}
+
+/// Write buffered import docs, one `insert_docs` batch per collection (arrival order kept within each)
+fn flush_import_batch(storage: &Storage, pending: &mut Vec<(String, Document)>, summary: &mut ImportDocsResponse) {
+    let mut by_collection: Vec<(String, Vec<Document>)> = Vec::new();
+    for (collection_id, doc) in pending.drain(..) {
+        match by_collection.iter_mut().find(|(c, _)| *c == collection_id) {
+            Some((_, docs)) => docs.push(doc),
+            None => by_collection.push((collection_id, vec![doc])),
+        }
+    }
+    for (collection_id, docs) in by_collection {
+        let count = docs.len() as u64;
+        let dim = docs[0].vector.len();
+        let result = storage
+            .insert_docs(docs, &collection_id)
+            .and_then(|_| storage.record_vector_dim(&collection_id, dim).map(|_| ()));
+        match result {
+            Ok(()) => {
+                summary.inserted += count;
+                summary.batches += 1;
+                debug!(collection_id = %collection_id, count = count, "ImportDocs batch written");
+            }
+            Err(e) => {
+                summary.failed += count;
+                record_import_error(summary, format!("Batch of {} docs for {} failed: {}", count, collection_id, e));
+            }
+        }
+    }
+}
+
+/// Consume an `ImportDocs` stream, inserting documents in batches of `batch_size`.
+/// Invalid messages and failed batches are counted and skipped; a transport error ends the
+/// import after flushing what was already received, with `completed = false`.
+async fn import_doc_stream<S>(storage: &Storage, mut stream: S, batch_size: usize) -> ImportDocsResponse
+where
+    S: Stream<Item = Result<InsertDocRequest, Status>> + Unpin,
+{
+    let mut summary = ImportDocsResponse { completed: true, ..Default::default() };
+    let mut pending: Vec<(String, Document)> = Vec::with_capacity(batch_size);
+
+    while let Some(item) = stream.next().await {
+        let req = match item {
+            Ok(req) => req,
+            Err(status) => {
+                record_import_error(&mut summary, format!("Stream interrupted: {}", status.message()));
+                summary.completed = false;
+                break;
+            }
+        };
+        summary.received += 1;
+        if req.collection_id.is_empty() {
+            summary.failed += 1;
+            record_import_error(&mut summary, format!("Document {} is missing collection_id", req.id));
+            continue;
+        }
+
+        let metadata_json: serde_json::Value = serde_json::from_str(&req.metadata_json)
+            .unwrap_or(serde_json::json!({}));
+        let doc = Document {
+            id: req.id,
+            text: req.text,
+            category: req.category,
+            vector: req.vector,
+            metadata: metadata_json,
+            vectors: HashMap::new(),
+            updated_at: None,
+            source_uri: if req.source_uri.is_empty() { None } else { Some(req.source_uri) },
+            ingested_by: if req.ingested_by.is_empty() { None } else { Some(req.ingested_by) },
+        };
+        // Reject bad vectors per document so they don't fail the whole batch
+        if let Err(e) = storage.validate_doc_vectors(&doc) {
+            summary.failed += 1;
+            record_import_error(&mut summary, format!("Document {} rejected: {}", doc.id, e));
+            continue;
+        }
+        pending.push((req.collection_id, doc));
+        if pending.len() >= batch_size {
+            flush_import_batch(storage, &mut pending, &mut summary);
+        }
+    }
+    flush_import_batch(storage, &mut pending, &mut summary);
+    summary
+}
+
 /// Service implementation for AiDbService
 /// Combines multi-model engines:
 /// - Storage: Sled (NoSQL JSON + vectors)
@@ -67,14 +273,28 @@ impl AiDbServiceImpl {
         Self { storage }
     }
 
-    fn check_auth(&self, metadata: &tonic::metadata::MetadataMap) -> Result<AuthPayload, Status> {
+    /// Claims of the call's token, which must grant `role` (`Role::Reader` for any valid token)
+    fn check_auth(&self, metadata: &tonic::metadata::MetadataMap, role: Role) -> Result<AuthPayload, Status> {
         let token = metadata.get("authorization")
             .ok_or(Status::unauthenticated("Missing token"))?
             .to_str()
             .map_err(|_| Status::unauthenticated("Invalid token"))?;
 
         let token = if token.starts_with("Bearer ") { &token[7..] } else { token };
-        validate_jwt(token).map_err(|_| Status::unauthenticated("Invalid token"))
+        let claims = validate_jwt(token).map_err(|_| Status::unauthenticated("Invalid token"))?;
+        require_role(&claims, role)?;
+        Ok(claims)
+    }
+
+    /// `check_auth`, then the message with its call scope applied, rejected unless the caller
+    /// may use the collection it targets
+    fn authorize<T: Scoped>(&self, request: Request<T>, role: Role) -> Result<T, Status> {
+        let claims = self.check_auth(request.metadata(), role)?;
+        let message = into_scoped(request);
+        if let Some(collection_id) = message.collection_id().filter(|id| !id.is_empty()) {
+            require_collection_access(&self.storage, &claims, collection_id)?;
+        }
+        Ok(message)
     }
 }
 
@@ -97,9 +317,11 @@ impl AiDbService for AiDbServiceImpl {
             username: req.username.clone(),
             password_hash: hash,
             tenants: vec![],
+            roles: vec![],
+            context: None,
         };
         
-        self.storage.create_user(user).map_err(|e| {
+        self.storage.register_user(user).map_err(|e| {
             warn!(error = %e, username = %req.username, "User already exists");
             Status::already_exists(e.to_string())
         })?;
@@ -120,18 +342,23 @@ impl AiDbService for AiDbServiceImpl {
             .map_err(|e| {
                 error!(error = %e, username = %req.username, "Database error during login");
                 Status::internal("DB error")
-            })?
-            .ok_or_else(|| {
-                warn!(username = %req.username, "User not found");
-                Status::unauthenticated("User not found")
             })?;
 
-        if !verify_password(&req.password, &user.password_hash).unwrap_or(false) {
-            warn!(username = %req.username, "Invalid password attempt");
-            return Err(Status::unauthenticated("Invalid password"));
-        }
+        // Missing users and wrong passwords get the same answer after the same work
+        let verified = verify_login(req.password, user.as_ref().map(|u| u.password_hash.clone())).await;
+        let user = match user {
+            Some(user) if verified => user,
+            Some(_) => {
+                warn!(username = %req.username, "Invalid password attempt");
+                return Err(Status::unauthenticated(INVALID_CREDENTIALS));
+            }
+            None => {
+                warn!(username = %req.username, "User not found");
+                return Err(Status::unauthenticated(INVALID_CREDENTIALS));
+            }
+        };
 
-        let (token, session_id) = create_jwt_with_session(&user.username).map_err(|e| {
+        let (token, session_id) = create_jwt_with_session(&user.username, &user.effective_roles()).map_err(|e| {
             error!(error = %e, username = %user.username, "JWT creation failed");
             Status::internal("Token gen failed")
         })?;
@@ -145,29 +372,30 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<CreateTenantRequest>,
     ) -> Result<Response<CreateTenantResponse>, Status> {
-        let claims = self.check_auth(request.metadata())?;
+        let claims = self.check_auth(request.metadata(), Role::Admin)?;
         let req = request.into_inner();
         let session_id = claims.session_id.as_deref().unwrap_or("none");
         debug!(user_id = %claims.sub, session_id = %session_id, tenant_id = %req.id, "Create tenant request");
+        let tenant_id = validate_hierarchy_id("tenant", &req.id).map_err(|e| Status::invalid_argument(e.to_string()))?;
         
         let tenant = Tenant {
-            id: req.id.clone(),
+            id: tenant_id.clone(),
             name: req.name.clone(),
             owner_id: claims.sub.clone(),
             environments: vec![],
         };
         
         self.storage.create_tenant(tenant).map_err(|e| {
-            error!(error = %e, session_id = %session_id, tenant_id = %req.id, "Failed to create tenant");
+            error!(error = %e, session_id = %session_id, tenant_id = %tenant_id, "Failed to create tenant");
             Status::internal(e.to_string())
         })?;
         
         if let Some(mut user) = self.storage.get_user(&claims.sub).unwrap() {
-             user.tenants.push(req.id.clone());
+             user.tenants.push(tenant_id.clone());
              self.storage.update_user(user).unwrap();
         }
         
-        info!(session_id = %session_id, tenant_id = %req.id, name = %req.name, owner_id = %claims.sub, "Tenant created successfully");
+        info!(session_id = %session_id, tenant_id = %tenant_id, name = %req.name, owner_id = %claims.sub, "Tenant created successfully");
         Ok(Response::new(CreateTenantResponse { success: true }))
     }
 
@@ -176,29 +404,30 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<CreateEnvironmentRequest>,
     ) -> Result<Response<CreateEnvironmentResponse>, Status> {
-        let claims = self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let claims = self.check_auth(request.metadata(), Role::Admin)?;
+        let req = into_scoped(request);
         let session_id = claims.session_id.as_deref().unwrap_or("none");
         debug!(session_id = %session_id, env_id = %req.id, tenant_id = %req.tenant_id, "Create environment request");
+        let env_id = validate_hierarchy_id("environment", &req.id).map_err(|e| Status::invalid_argument(e.to_string()))?;
         
         let env = Environment {
-            id: req.id.clone(),
+            id: env_id.clone(),
             name: req.name.clone(),
             tenant_id: req.tenant_id.clone(),
             collections: vec![],
         };
         
         self.storage.create_environment(env).map_err(|e| {
-            error!(error = %e, session_id = %session_id, env_id = %req.id, "Failed to create environment");
+            error!(error = %e, session_id = %session_id, env_id = %env_id, "Failed to create environment");
             Status::internal(e.to_string())
         })?;
         
         if let Some(mut tenant) = self.storage.get_tenant(&req.tenant_id).unwrap() {
-             tenant.environments.push(req.id.clone());
+             tenant.environments.push(env_id.clone());
              self.storage.update_tenant(tenant).unwrap();
         }
         
-        info!(session_id = %session_id, env_id = %req.id, tenant_id = %req.tenant_id, "Environment created successfully");
+        info!(session_id = %session_id, env_id = %env_id, tenant_id = %req.tenant_id, "Environment created successfully");
         Ok(Response::new(CreateEnvironmentResponse { success: true }))
     }
 
@@ -207,28 +436,32 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<CreateCollectionRequest>,
     ) -> Result<Response<CreateCollectionResponse>, Status> {
-        let claims = self.check_auth(request.metadata())?;
+        let claims = self.check_auth(request.metadata(), Role::Admin)?;
         let req = request.into_inner();
         let session_id = claims.session_id.as_deref().unwrap_or("none");
         debug!(session_id = %session_id, collection_id = %req.id, env_id = %req.env_id, "Create collection request");
+        let collection_id = validate_hierarchy_id("collection", &req.id).map_err(|e| Status::invalid_argument(e.to_string()))?;
         
         let col = Collection {
-            id: req.id.clone(),
+            id: collection_id.clone(),
             name: req.name.clone(),
             environment_id: req.env_id.clone(),
+            vector_dim: (req.vector_dim > 0).then_some(req.vector_dim as usize),
+            metric: Default::default(),
+            normalize: req.normalize,
         };
         
         self.storage.create_collection(col).map_err(|e| {
-            error!(error = %e, session_id = %session_id, collection_id = %req.id, "Failed to create collection");
+            error!(error = %e, session_id = %session_id, collection_id = %collection_id, "Failed to create collection");
             Status::internal(e.to_string())
         })?;
         
         if let Some(mut env) = self.storage.get_environment(&req.env_id).unwrap() {
-             env.collections.push(req.id.clone());
+             env.collections.push(collection_id.clone());
              self.storage.update_environment(env).unwrap();
         }
         
-        info!(session_id = %session_id, collection_id = %req.id, env_id = %req.env_id, "Collection created successfully");
+        info!(session_id = %session_id, collection_id = %collection_id, env_id = %req.env_id, "Collection created successfully");
         Ok(Response::new(CreateCollectionResponse { success: true }))
     }
 
@@ -239,8 +472,8 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<InsertRequest>,
     ) -> Result<Response<InsertResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Writer)?;
+        let started = Instant::now();
         let collection_id = req.collection_id.clone();
         if collection_id.is_empty() { 
             warn!("Insert request missing collection_id");
@@ -263,11 +496,11 @@ impl AiDbService for AiDbServiceImpl {
             .insert(&key, metadata_batch, req.vector.clone())
             .map_err(|e| {
                 error!(error = %e, key = %key, "Sled storage failed");
-                Status::internal(format!("Sled storage error: {}", e))
+                storage_status(e.as_ref(), format!("Sled storage error: {}", e))
             })?;
 
-        info!(id = %req.id, collection_id = %collection_id, vector_len = req.vector.len(), "Insert completed successfully");
-        Ok(Response::new(InsertResponse { success: true }))
+        info!(id = %req.id, collection_id = %collection_id, vector_len = req.vector.len(), latency_ms = started.elapsed().as_millis() as u64, "Insert completed successfully");
+        Ok(Response::new(InsertResponse { success: true, dim: 0, metric: String::new() }))
     }
 
     /// Search: Placeholder for text-based/hybrid search (to integrate DataFusion)
@@ -276,13 +509,21 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<SearchRequest>,
     ) -> Result<Response<SearchResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Reader)?;
         info!(query = %req.query, "Text search query received");
         // TODO: Implement robust querying with DataFusion over Arrow metadata
         // For now, stub response
         let results = vec![];
-        Ok(Response::new(SearchResponse { results }))
+        Ok(Response::new(SearchResponse {
+            results,
+            index_backend: String::new(),
+            metric: String::new(),
+            scores: vec![],
+            score_kind: String::new(),
+            exact_results: vec![],
+            exact_scores: vec![],
+            recall: 0.0,
+        }))
     }
 
     /// VectorSearch: Core indexing engine - ANN search via HNSW
@@ -292,22 +533,75 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<VectorSearchRequest>,
     ) -> Result<Response<SearchResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Reader)?;
+        let started = Instant::now();
         let collection_id = req.collection_id.clone();
         debug!(collection_id = %collection_id, top_k = req.top_k, "Vector search request");
 
         let top_k = req.top_k as usize;
-        let results = self
+        let score_kind = ScoreKind::parse(&req.score_kind).map_err(Status::invalid_argument)?;
+        let options = VectorSearchOptions {
+            field: if req.field.is_empty() { DEFAULT_VECTOR_FIELD.to_string() } else { req.field.clone() },
+            score_kind,
+            expand_tags: req.expand_tags.clone(),
+            expand_weight: if req.expand_weight > 0.0 { req.expand_weight } else { DEFAULT_EXPAND_WEIGHT },
+            boost: (!req.boost_field.is_empty()).then(|| ScoreBoost { field: req.boost_field.clone(), weight: req.boost_weight }),
+            dedupe_text: req.dedupe_text,
+        };
+        if let Some(boost) = &options.boost {
+            boost.validate().map_err(Status::invalid_argument)?;
+        }
+        if req.compare {
+            if options.dedupe_text || options.boost.is_some() {
+                return Err(Status::invalid_argument("compare cannot be combined with dedupe_text or boost_field"));
+            }
+            // ANN results plus an exact scan of the same (expanded) query
+            let comparison = self
+                .storage
+                .expand_query_with_tags(&collection_id, &req.query_vector, &options.expand_tags, options.expand_weight)
+                .and_then(|query| self.storage.vector_search_compare(&collection_id, &options.field, &query, top_k, score_kind))
+                .map_err(|e| {
+                    error!(error = %e, collection_id = %collection_id, "Vector search comparison failed");
+                    storage_status(e.as_ref(), format!("Storage retrieval error: {}", e))
+                })?;
+            let (approximate, exact) = (comparison.approximate, comparison.exact);
+            return Ok(Response::new(SearchResponse {
+                results: approximate.ids,
+                index_backend: approximate.index_backend.as_str().to_string(),
+                metric: approximate.metric,
+                scores: approximate.scores,
+                score_kind: approximate.score_kind.as_str().to_string(),
+                exact_results: exact.ids,
+                exact_scores: exact.scores,
+                recall: comparison.recall,
+            }));
+        }
+        let outcome = self
             .storage
-            .vector_search(&collection_id, &req.query_vector, top_k)
+            .vector_search_with_options(&collection_id, &req.query_vector, top_k, &options)
             .map_err(|e| {
                 error!(error = %e, collection_id = %collection_id, "Vector search failed");
-                Status::internal(format!("Storage retrieval error: {}", e))
+                storage_status(e.as_ref(), format!("Storage retrieval error: {}", e))
             })?;
 
-        info!(collection_id = %collection_id, top_k = top_k, results_count = results.len(), "Vector search completed");
-        Ok(Response::new(SearchResponse { results }))
+        info!(
+            collection_id = %collection_id,
+            top_k = top_k,
+            results_count = outcome.ids.len(),
+            index_backend = outcome.index_backend.as_str(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "Vector search completed"
+        );
+        Ok(Response::new(SearchResponse {
+            results: outcome.ids,
+            index_backend: outcome.index_backend.as_str().to_string(),
+            metric: outcome.metric,
+            scores: outcome.scores,
+            score_kind: outcome.score_kind.as_str().to_string(),
+            exact_results: vec![],
+            exact_scores: vec![],
+            recall: 0.0,
+        }))
     }
 
     #[instrument(skip(self, request), fields(collection_id))]
@@ -315,8 +609,7 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<TextSearchRequest>,
     ) -> Result<Response<TextSearchResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Reader)?;
         let collection_id = req.collection_id.clone();
 
         info!(collection_id = %collection_id, query = %req.query, "Text search request received");
@@ -358,8 +651,8 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<InsertDocRequest>,
     ) -> Result<Response<InsertResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Writer)?;
+        let started = Instant::now();
         let collection_id = req.collection_id.clone();
         if collection_id.is_empty() { 
             warn!("InsertDoc request missing collection_id");
@@ -379,17 +672,27 @@ impl AiDbService for AiDbServiceImpl {
             category: req.category.clone(),
             vector: req.vector.clone(),
             metadata: metadata_json,
+            vectors: HashMap::new(),
+            updated_at: None,
+            source_uri: if req.source_uri.is_empty() { None } else { Some(req.source_uri.clone()) },
+            ingested_by: if req.ingested_by.is_empty() { None } else { Some(req.ingested_by.clone()) },
         };
 
         // Insert to multi-model storage layer
         self.storage.insert_doc(doc, &collection_id)
             .map_err(|e| {
                 error!(error = %e, id = %req.id, collection_id = %collection_id, "NoSQL insert failed");
-                Status::internal(format!("NoSQL/JSON insert error: {}", e))
+                storage_status(e.as_ref(), format!("NoSQL/JSON insert error: {}", e))
             })?;
+        let vector_config = self.storage.record_vector_dim(&collection_id, req.vector.len())
+            .map_err(|e| Status::internal(format!("Collection config error: {}", e)))?;
 
-        info!(id = %req.id, collection_id = %collection_id, "InsertDoc completed successfully");
-        Ok(Response::new(InsertResponse { success: true }))
+        info!(id = %req.id, collection_id = %collection_id, dim = vector_config.dim, latency_ms = started.elapsed().as_millis() as u64, "InsertDoc completed successfully");
+        Ok(Response::new(InsertResponse {
+            success: true,
+            dim: vector_config.dim as u32,
+            metric: vector_config.metric.as_str().to_string(),
+        }))
     }
 
     #[instrument(skip(self, request), fields(collection_id))]
@@ -397,8 +700,8 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<BatchInsertRequest>,
     ) -> Result<Response<InsertResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Writer)?;
+        let started = Instant::now();
         let collection_id = req.collection_id;
         if collection_id.is_empty() { 
             return Err(Status::invalid_argument("Missing collection_id")); 
@@ -414,17 +717,21 @@ impl AiDbService for AiDbServiceImpl {
                 category: "vector".to_string(),
                 vector: r.vector,
                 metadata: serde_json::json!({}),
+                vectors: HashMap::new(),
+                updated_at: None,
+                source_uri: None,
+                ingested_by: None,
             });
         }
 
         self.storage.insert_docs(docs, &collection_id)
             .map_err(|e| {
                 error!(error = %e, collection_id = %collection_id, "BatchInsert failed");
-                Status::internal(format!("Batch insert error: {}", e))
+                storage_status(e.as_ref(), format!("Batch insert error: {}", e))
             })?;
 
-        info!(collection_id = %collection_id, "BatchInsert completed successfully");
-        Ok(Response::new(InsertResponse { success: true }))
+        info!(collection_id = %collection_id, latency_ms = started.elapsed().as_millis() as u64, "BatchInsert completed successfully");
+        Ok(Response::new(InsertResponse { success: true, dim: 0, metric: String::new() }))
     }
 
     #[instrument(skip(self, request), fields(collection_id))]
@@ -432,8 +739,8 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<BatchInsertDocRequest>,
     ) -> Result<Response<InsertResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Writer)?;
+        let started = Instant::now();
         let collection_id = req.collection_id;
         if collection_id.is_empty() { 
             return Err(Status::invalid_argument("Missing collection_id")); 
@@ -451,17 +758,52 @@ impl AiDbService for AiDbServiceImpl {
                 category: r.category,
                 vector: r.vector,
                 metadata: metadata_json,
+                vectors: HashMap::new(),
+                updated_at: None,
+                source_uri: None,
+                ingested_by: None,
             });
         }
 
         self.storage.insert_docs(docs, &collection_id)
             .map_err(|e| {
                 error!(error = %e, collection_id = %collection_id, "BatchInsertDoc failed");
-                Status::internal(format!("Batch insert error: {}", e))
+                storage_status(e.as_ref(), format!("Batch insert error: {}", e))
             })?;
 
-        info!(collection_id = %collection_id, "BatchInsertDoc completed successfully");
-        Ok(Response::new(InsertResponse { success: true }))
+        info!(collection_id = %collection_id, latency_ms = started.elapsed().as_millis() as u64, "BatchInsertDoc completed successfully");
+        Ok(Response::new(InsertResponse { success: true, dim: 0, metric: String::new() }))
+    }
+
+    #[instrument(skip(self, request))]
+    async fn import_docs(
+        &self,
+        request: Request<tonic::Streaming<InsertDocRequest>>,
+    ) -> Result<Response<ImportDocsResponse>, Status> {
+        let claims = self.check_auth(request.metadata(), Role::Writer)?;
+        info!("ImportDocs stream opened");
+
+        // A document for a collection the caller can't use ends the import like a transport error
+        let scope = request.extensions().get::<CallScope>().cloned().unwrap_or_default();
+        let storage = self.storage.clone();
+        let messages = request.into_inner().map(move |message| {
+            let mut message = message?;
+            message.apply_scope(&scope);
+            if !message.collection_id.is_empty() {
+                require_collection_access(&storage, &claims, &message.collection_id)?;
+            }
+            Ok(message)
+        });
+        let summary = import_doc_stream(&self.storage, messages, IMPORT_BATCH_SIZE).await;
+        info!(
+            received = summary.received,
+            inserted = summary.inserted,
+            failed = summary.failed,
+            batches = summary.batches,
+            completed = summary.completed,
+            "ImportDocs finished"
+        );
+        Ok(Response::new(summary))
     }
 
     /// ExecuteSql: SQL queries via DataFusion on Arrow projection of NoSQL data
@@ -471,8 +813,8 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<SqlRequest>,
     ) -> Result<Response<SqlResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Reader)?;
+        let started = Instant::now();
         let collection_id = req.collection_id.clone();
         info!(collection_id = %collection_id, sql = %req.sql, "SQL query request received");
 
@@ -490,13 +832,13 @@ impl AiDbService for AiDbServiceImpl {
                 Status::internal(format!("SQL execution error: {}", e))
             })?;
 
-        // Serialize Arrow results to bytes (IPC stub for response)
-        let mut arrow_buf: Vec<u8> = vec![];
-        for batch in results {
-            arrow_buf.extend(format!("{:?}", batch.schema()).as_bytes());
-        }
+        // Serialize Arrow results to one IPC stream (empty when there are no batches)
+        let arrow_buf = encode_ipc_stream(&results).map_err(|e| {
+            error!(error = %e, sql = %req.sql, "Failed to encode SQL results as Arrow IPC");
+            Status::internal(format!("Arrow IPC encoding error: {}", e))
+        })?;
 
-        info!(collection_id = %collection_id, sql = %req.sql, "SQL query completed");
+        info!(collection_id = %collection_id, sql = %req.sql, bytes = arrow_buf.len(), latency_ms = started.elapsed().as_millis() as u64, "SQL query completed");
         Ok(Response::new(SqlResponse { arrow_data: arrow_buf }))
     }
 
@@ -508,8 +850,8 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<HybridRequest>,
     ) -> Result<Response<HybridResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Reader)?;
+        let started = Instant::now();
         let collection_id = req.collection_id.clone();
         info!(collection_id = %collection_id, sql_filter = %req.sql_filter, top_k = req.top_k, "Hybrid search request");
 
@@ -521,18 +863,22 @@ impl AiDbService for AiDbServiceImpl {
                 Status::internal(format!("Planner error: {}", e))
             })?;
         
-        let docs = query_engine.hybrid_query(&req.sql_filter, &req.query_vector, req.top_k as usize).await
+        let timeout = Some(std::time::Duration::from_millis(req.timeout_ms as u64))
+            .filter(|t| !t.is_zero())
+            .or(self.storage.default_query_timeout());
+        let outcome = query_engine.hybrid_query_with_timeout(&req.sql_filter, &req.query_vector, req.top_k as usize, timeout).await
             .map_err(|e| {
                 error!(error = %e, collection_id = %collection_id, "Hybrid query failed");
-                Status::internal(format!("Hybrid query error: {}", e))
+                storage_status(e.as_ref(), format!("Hybrid query error: {}", e))
             })?;
 
         // Results as IDs (extend to full JSON for NoSQL response)
+        let docs = outcome.docs;
         let results: Vec<String> = docs.iter().map(|(doc, _)| doc.id.clone()).collect();
         let cache_hits: Vec<bool> = docs.iter().map(|(_, from_cache)| *from_cache).collect();
 
-        info!(collection_id = %collection_id, results_count = results.len(), cache_hits = ?cache_hits, "Hybrid search completed");
-        Ok(Response::new(HybridResponse { results, cache_hits }))
+        info!(collection_id = %collection_id, results_count = results.len(), cache_hits = ?cache_hits, latency_ms = started.elapsed().as_millis() as u64, "Hybrid search completed");
+        Ok(Response::new(HybridResponse { results, cache_hits, partial: outcome.partial }))
     }
 
     // === RAG System gRPC Methods ===
@@ -544,8 +890,7 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<RagIngestRequest>,
     ) -> Result<Response<RagIngestResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Writer)?;
         
         info!(
             collection_id = %req.collection_id,
@@ -599,8 +944,7 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<RagSearchRequest>,
     ) -> Result<Response<RagSearchResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Reader)?;
         
         info!(
             collection_id = %req.collection_id,
@@ -666,8 +1010,7 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<RagGetDocRequest>,
     ) -> Result<Response<RagGetDocResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Reader)?;
         
         debug!(collection_id = %req.collection_id, doc_id = %req.doc_id, "RAG get doc request");
         
@@ -711,8 +1054,7 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<RagDeleteDocRequest>,
     ) -> Result<Response<RagDeleteDocResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Writer)?;
         
         debug!(collection_id = %req.collection_id, doc_id = %req.doc_id, "RAG delete doc request");
         
@@ -736,8 +1078,7 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<RagListDocsRequest>,
     ) -> Result<Response<RagListDocsResponse>, Status> {
-        self.check_auth(request.metadata())?;
-        let req = request.into_inner();
+        let req = self.authorize(request, Role::Reader)?;
         
         debug!(collection_id = %req.collection_id, "RAG list docs request");
         
@@ -761,7 +1102,7 @@ impl AiDbService for AiDbServiceImpl {
         &self,
         request: Request<RagEmbedRequest>,
     ) -> Result<Response<RagEmbedResponse>, Status> {
-        self.check_auth(request.metadata())?;
+        self.check_auth(request.metadata(), Role::Reader)?;
         let req = request.into_inner();
         
         debug!(text_len = req.text.len(), "RAG embed request");
@@ -797,58 +1138,418 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load .env file if present
     dotenvy::dotenv().ok();
     
+    // Read configuration from environment, once (see my_ai_db::config)
+    let config = AppConfig::from_env()?;
+
     // Initialize logging (must be done early)
-    let _guard = my_ai_db::logging::init_logging()?;
-    
-    // Read configuration from environment
-    let grpc_port = std::env::var("AIDB_GRPC_PORT")
-        .unwrap_or_else(|_| "50051".to_string());
-    let rest_port = std::env::var("AIDB_REST_PORT")
-        .unwrap_or_else(|_| "11111".to_string());
-    let data_path = std::env::var("AIDB_DATA_PATH")
-        .unwrap_or_else(|_| "aidb_data".to_string());
+    let _guard = my_ai_db::logging::init_logging(&config.log)?;
     
-    // gRPC address
-    let grpc_addr: SocketAddr = format!("[::1]:{}", grpc_port).parse()?;
-    // REST API address
-    let rest_addr: SocketAddr = format!("0.0.0.0:{}", rest_port).parse()?;
-
-    info!("🚀 aiDB Hybrid Multi-Model DB starting...");
+    let grpc_addr = config.grpc_addr;
+    let rest_addr = config.rest_addr;
+    // Read replica mode: serve GET/search/SQL from a copy of the primary's data directory
+    let read_only = config.storage.read_only;
+    my_ai_db::auth::configure(config.auth.clone())?;
+
+    info!(
+        version = env!("CARGO_PKG_VERSION"),
+        storage = "sled",
+        index = "hnsw",
+        sql = "datafusion",
+        "aiDB starting"
+    );
     info!(grpc_addr = %grpc_addr, rest_addr = %rest_addr, "Server addresses configured");
-    info!("📦 Storage Engine: Sled + Arrow/JSON (NoSQL)");
-    info!("🔍 Indexing: instant-distance HNSW | SQL: DataFusion");
-    info!("🌐 Networking: gRPC + REST/HTTP (Tokio concurrent)");
-    debug!("🧪 Run `cargo run --bin load_data` first");
-    debug!("📖 See README.md for gRPC/grpcurl + REST/cURL");
 
     // Init unified storage (shared between gRPC/REST)
-    let storage = Storage::open(&data_path)?;
-    info!(data_path = %data_path, "Storage initialized");
+    let storage = Storage::open_with_config(&config.data_path, &config.storage)?;
+    info!(data_path = %config.data_path, read_only = read_only, "Storage initialized");
+
+    // Optional consistency scan of doc/vector/metadata trees (AIDB_SELF_CHECK=1 or =repair)
+    if let Some(repair) = config.storage.self_check {
+        let report = if repair { storage.self_check_and_repair()? } else { storage.self_check()? };
+        info!(
+            docs_scanned = report.docs_scanned,
+            issues = report.issue_count(),
+            repaired = report.repaired,
+            "Startup self-check finished"
+        );
+        let generations = storage.verify_generations(repair)?;
+        info!(
+            collections_checked = generations.collections_checked,
+            drifted = generations.drifted.len(),
+            repaired = generations.repaired,
+            "Startup generation check finished"
+        );
+    }
+
+    // Periodic index checkpoints so restarts skip rebuilds (AIDB_INDEX_CHECKPOINT_SECS)
+    let _index_checkpointer = if read_only { None } else { storage.spawn_index_checkpointer() };
+
+    // Load or build indexes in the background so the first searches don't (AIDB_WARM_INDEXES)
+    let _index_warmer = config.storage.warm_indexes.then(|| {
+        let budget_bytes = config.storage.warm_budget_mb.map(|mb| mb.saturating_mul(1024 * 1024));
+        storage.spawn_index_warmer(budget_bytes, config.storage.warm_concurrency)
+    });
 
     // gRPC service (multi-model: insert, vector, sql, hybrid)
     let grpc_service = AiDbServiceImpl::new(storage.clone());  // Clone for share (Sled thread-safe)
 
     // REST router (Axum: /insert_doc, /sql, /hybrid_search on :11111)
-    let rest_app = create_router(storage);
-
-    // Spawn REST server concurrently (Tokio task)
-    let _rest_server = tokio::spawn(async move {
-        let listener = TcpListener::bind(&rest_addr).await?;
-        info!(rest_addr = %rest_addr, "REST server started");
-        axum::serve(listener, rest_app.into_make_service()).await?;
-        Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
-    });
-
-    // Run gRPC server (main task)
-    info!(grpc_addr = %grpc_addr, "gRPC server starting");
-    Server::builder()
-        .add_service(AiDbServiceServer::new(grpc_service))
-        .serve(grpc_addr)
-        .await?;
+    let rest_app = create_router_with_config(storage.clone(), &config.rest);
+
+    // Both servers stop on SIGINT/SIGTERM, draining in-flight requests first
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let listener = TcpListener::bind(&rest_addr).await?;
+    info!(rest_addr = %rest_addr, "REST server started");
+    let rest_server = axum::serve(listener, rest_app.into_make_service())
+        .with_graceful_shutdown(shutdown_requested(shutdown_rx.clone()))
+        .into_future();
+
+    let mut grpc_builder = Server::builder();
+    match &config.grpc_tls {
+        Some(tls) => {
+            grpc_builder = grpc_builder.tls_config(load_grpc_tls(tls)?)?;
+            info!(grpc_addr = %grpc_addr, cert = %tls.cert_path.display(), "gRPC server starting with TLS");
+        }
+        None => info!(grpc_addr = %grpc_addr, "gRPC server starting (plaintext)"),
+    }
+    let grpc_server = grpc_builder
+        // Per-call `tenant`/`collection` metadata defaults the corresponding message fields
+        .add_service(AiDbServiceServer::with_interceptor(grpc_service, scope_interceptor))
+        .serve_with_shutdown(grpc_addr, shutdown_requested(shutdown_rx));
 
-    // Keep main alive for servers (in prod use select! or signal)
-    tokio::signal::ctrl_c().await?;
-    info!("Shutting down...");
+    let served = serve_until_shutdown(grpc_server, rest_server, shutdown_signal(), shutdown_tx).await;
 
+    // Flush even after a server failure, but never wait on it indefinitely
+    flush_with_timeout(move || storage.flush().map_err(|e| e.to_string()), config.shutdown_flush_timeout).await;
+    served?;
+    info!("Shutdown complete");
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Run the final storage `flush` on its own thread and wait at most `timeout` for it; returns
+/// whether it finished successfully in time. A flush that overruns is logged and left behind
+/// (a detached thread, so it can't hold up process exit the way a blocking-pool task would).
+async fn flush_with_timeout<F>(flush: F, timeout: Duration) -> bool
+where
+    F: FnOnce() -> Result<usize, String> + Send + 'static,
+{
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+    let started = Instant::now();
+    let spawned = std::thread::Builder::new()
+        .name("aidb-shutdown-flush".to_string())
+        .spawn(move || {
+            let _ = done_tx.send(flush());
+        });
+    if let Err(e) = spawned {
+        error!(error = %e, "Failed to start the shutdown flush");
+        return false;
+    }
+
+    match tokio::time::timeout(timeout, done_rx).await {
+        Ok(Ok(Ok(bytes))) => {
+            info!(bytes = bytes, elapsed_ms = started.elapsed().as_millis() as u64, "Storage flushed");
+            true
+        }
+        Ok(Ok(Err(e))) => {
+            error!(error = %e, "Storage flush failed during shutdown");
+            false
+        }
+        Ok(Err(_)) => {
+            error!("Storage flush panicked during shutdown");
+            false
+        }
+        Err(_) => {
+            warn!(timeout_ms = timeout.as_millis() as u64, "Storage flush timed out during shutdown; exiting without it");
+            false
+        }
+    }
+}
+
+/// Server identity from the PEM files named by `AIDB_TLS_CERT`/`AIDB_TLS_KEY`
+fn load_grpc_tls(tls: &TlsConfig) -> Result<ServerTlsConfig, Box<dyn std::error::Error>> {
+    let read = |name: &str, path: &std::path::Path| {
+        std::fs::read(path).map_err(|e| format!("Failed to read {} '{}': {}", name, path.display(), e))
+    };
+    let cert = read("AIDB_TLS_CERT", &tls.cert_path)?;
+    let key = read("AIDB_TLS_KEY", &tls.key_path)?;
+    Ok(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+}
+
+/// Resolve once a shutdown has been requested on `shutdown` (or its sender is gone)
+async fn shutdown_requested(mut shutdown: watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|requested| *requested).await;
+}
+
+/// Wait for SIGINT (Ctrl-C) or, on Unix, SIGTERM; returns the signal's name
+async fn shutdown_signal() -> &'static str {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!(error = %e, "Failed to listen for SIGINT");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
+
+/// Run both servers until `signal` resolves or either server stops, then request shutdown on
+/// `shutdown` and wait for the other to drain. A server error, or a server stopping before any
+/// signal, is returned so the process exits non-zero (gRPC's error wins if both fail).
+async fn serve_until_shutdown<G, R, GE, RE>(
+    grpc: G,
+    rest: R,
+    signal: impl Future<Output = &'static str>,
+    shutdown: watch::Sender<bool>,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    G: Future<Output = Result<(), GE>>,
+    R: Future<Output = Result<(), RE>>,
+    GE: Into<Box<dyn std::error::Error>>,
+    RE: Into<Box<dyn std::error::Error>>,
+{
+    tokio::pin!(grpc, rest, signal);
+    let (mut grpc_exit, mut rest_exit) = (None, None);
+    tokio::select! {
+        name = &mut signal => info!(signal = name, "Shutdown signal received, draining in-flight requests"),
+        result = &mut grpc => grpc_exit = Some(result.map_err(Into::into)),
+        result = &mut rest => rest_exit = Some(result.map_err(Into::into)),
+    }
+    let unexpected = grpc_exit.is_some() || rest_exit.is_some();
+    let _ = shutdown.send(true);
+
+    let grpc_result = match grpc_exit {
+        Some(result) => server_outcome("gRPC", result, true),
+        None => server_outcome("gRPC", grpc.await.map_err(Into::into), false),
+    };
+    let rest_result = match rest_exit {
+        Some(result) => server_outcome("REST", result, true),
+        None => server_outcome("REST", rest.await.map_err(Into::into), false),
+    };
+    if unexpected {
+        warn!("A server stopped without a shutdown signal; the other was shut down with it");
+    }
+    grpc_result.and(rest_result)
+}
+
+/// Log how one server stopped; stopping `early` (before any shutdown request) is an error even
+/// without one, so a supervisor restarts the process
+fn server_outcome(
+    server: &str,
+    result: Result<(), Box<dyn std::error::Error>>,
+    early: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match result {
+        Err(e) => {
+            error!(server = server, error = %e, "Server failed");
+            Err(format!("{} server failed: {}", server, e).into())
+        }
+        Ok(()) if early => {
+            error!(server = server, "Server stopped unexpectedly");
+            Err(format!("{} server stopped unexpectedly", server).into())
+        }
+        Ok(()) => {
+            info!(server = server, "Server stopped");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn doc_request(id: &str, collection_id: &str) -> InsertDocRequest {
+        InsertDocRequest {
+            id: id.to_string(),
+            text: format!("imported {}", id),
+            category: "AI".to_string(),
+            vector: vec![0.1, 0.2],
+            metadata_json: "{}".to_string(),
+            collection_id: collection_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn import_stream_inserts_in_batches_and_summarizes() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_grpc_import");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        let mut messages: Vec<Result<InsertDocRequest, Status>> =
+            (0..7).map(|i| Ok(doc_request(&format!("doc{}", i), "import_col"))).collect();
+        messages.insert(3, Ok(doc_request("orphan", "")));
+        let summary = import_doc_stream(&storage, futures::stream::iter(messages), 3).await;
+
+        assert!(summary.completed);
+        assert_eq!(summary.received, 8);
+        assert_eq!(summary.inserted, 7);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.batches, 3);
+        assert_eq!(storage.get_docs_in_collection("import_col").unwrap().len(), 7);
+
+        // A transport error keeps what arrived before it
+        let messages = vec![
+            Ok(doc_request("late1", "import_col2")),
+            Err(Status::aborted("client went away")),
+            Ok(doc_request("late2", "import_col2")),
+        ];
+        let summary = import_doc_stream(&storage, futures::stream::iter(messages), 10).await;
+        assert!(!summary.completed);
+        assert_eq!(summary.inserted, 1);
+        assert!(summary.errors[0].contains("client went away"));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn login_failures_do_not_reveal_whether_the_user_exists() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_grpc_login");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let service = AiDbServiceImpl::new(storage);
+        let register = RegisterRequest { username: "alice".to_string(), password: "right".to_string() };
+        service.register(Request::new(register)).await.unwrap();
+        let login = |username: &str, password: &str| {
+            service.login(Request::new(LoginRequest { username: username.to_string(), password: password.to_string() }))
+        };
+
+        assert!(login("alice", "right").await.is_ok());
+        let wrong_password = login("alice", "wrong").await.unwrap_err();
+        let missing_user = login("mallory", "wrong").await.unwrap_err();
+        assert_eq!(wrong_password.code(), tonic::Code::Unauthenticated);
+        assert_eq!((wrong_password.code(), wrong_password.message()), (missing_user.code(), missing_user.message()));
+        assert_eq!(missing_user.message(), INVALID_CREDENTIALS);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    /// `message` as the service receives it after `scope_interceptor`, sent with `metadata`
+    fn intercepted<T>(message: T, metadata: &[(&'static str, &str)]) -> Request<T> {
+        let mut request = Request::new(());
+        let token = my_ai_db::auth::create_jwt_with_roles("grpc_test_user", &[Role::Writer]).expect("JWT for test");
+        request.metadata_mut().insert("authorization", format!("Bearer {}", token).parse().unwrap());
+        for (key, value) in metadata {
+            request.metadata_mut().insert(*key, value.parse().unwrap());
+        }
+        let (metadata, extensions, ()) = scope_interceptor(request).expect("interceptor").into_parts();
+        Request::from_parts(metadata, extensions, message)
+    }
+
+    #[tokio::test]
+    async fn collection_metadata_defaults_the_target_collection() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_grpc_scope");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let service = AiDbServiceImpl::new(storage.clone());
+        let scoped = [("collection", "scoped_col"), ("tenant", "acme")];
+
+        service.insert_doc(intercepted(doc_request("relies_on_scope", ""), &scoped)).await.unwrap();
+        // The message's own collection_id overrides the metadata default
+        service.insert_doc(intercepted(doc_request("explicit", "other_col"), &scoped)).await.unwrap();
+        let ids = |col: &str| storage.get_docs_in_collection(col).unwrap().into_iter().map(|d| d.id).collect::<Vec<_>>();
+        assert_eq!(ids("scoped_col"), vec!["relies_on_scope"]);
+        assert_eq!(ids("other_col"), vec!["explicit"]);
+
+        let search = VectorSearchRequest { query_vector: vec![0.1, 0.2], top_k: 5, ..Default::default() };
+        let found = service.vector_search(intercepted(search, &scoped)).await.unwrap().into_inner();
+        assert_eq!(found.results, vec!["relies_on_scope"]);
+
+        // Neither metadata nor field: the call is rejected as before
+        let missing = service.insert_doc(intercepted(doc_request("nowhere", ""), &[])).await.unwrap_err();
+        assert_eq!(missing.code(), tonic::Code::InvalidArgument);
+
+        let mut environment = CreateEnvironmentRequest { id: "env".to_string(), name: "env".to_string(), ..Default::default() };
+        environment.apply_scope(&CallScope::from_metadata(intercepted((), &scoped).metadata()).unwrap());
+        assert_eq!(environment.tenant_id, "acme");
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn servers_drain_on_a_signal_and_a_failing_server_stops_the_other() {
+        let server = |shutdown: watch::Receiver<bool>, stopped: Arc<AtomicBool>| async move {
+            shutdown_requested(shutdown).await;
+            stopped.store(true, Ordering::SeqCst);
+            Ok::<(), std::io::Error>(())
+        };
+
+        // A signal stops both servers, which then finish cleanly
+        let (tx, rx) = watch::channel(false);
+        let (grpc_stopped, rest_stopped) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicBool::new(false)));
+        let grpc = server(rx.clone(), grpc_stopped.clone());
+        let rest = server(rx, rest_stopped.clone());
+        serve_until_shutdown(grpc, rest, async { "SIGTERM" }, tx).await.unwrap();
+        assert!(grpc_stopped.load(Ordering::SeqCst) && rest_stopped.load(Ordering::SeqCst));
+
+        // A server failing on its own shuts the other down and surfaces its error
+        let (tx, rx) = watch::channel(false);
+        let grpc_stopped = Arc::new(AtomicBool::new(false));
+        let grpc = server(rx, grpc_stopped.clone());
+        let rest = async { Err::<(), _>(std::io::Error::new(std::io::ErrorKind::AddrInUse, "address in use")) };
+        let err = serve_until_shutdown(grpc, rest, std::future::pending(), tx).await.unwrap_err();
+        assert_eq!(err.to_string(), "REST server failed: address in use");
+        assert!(grpc_stopped.load(Ordering::SeqCst), "gRPC drained after the REST failure");
+
+        // Stopping without a signal is an error even when the server reports none
+        let (tx, rx) = watch::channel(false);
+        let grpc = async { Ok::<(), std::io::Error>(()) };
+        let rest = server(rx, Arc::new(AtomicBool::new(false)));
+        let err = serve_until_shutdown(grpc, rest, std::future::pending(), tx).await.unwrap_err();
+        assert_eq!(err.to_string(), "gRPC server stopped unexpectedly");
+    }
+
+    #[test]
+    fn grpc_tls_rejects_missing_or_malformed_key_pairs() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_grpc_tls");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let (cert_path, key_path) = (temp_dir.join("server.pem"), temp_dir.join("server.key"));
+
+        let tls = TlsConfig { cert_path: cert_path.clone(), key_path: key_path.clone() };
+        let err = load_grpc_tls(&tls).unwrap_err().to_string();
+        assert!(err.starts_with("Failed to read AIDB_TLS_CERT") && err.contains("server.pem"), "{}", err);
+
+        // Readable but not PEM: refused when the server is configured, before it binds
+        fs::write(&cert_path, "not a certificate").unwrap();
+        fs::write(&key_path, "not a key").unwrap();
+        assert!(Server::builder().tls_config(load_grpc_tls(&tls).unwrap()).is_err());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn shutdown_flush_gives_up_after_its_timeout() {
+        assert!(flush_with_timeout(|| Ok(42), Duration::from_secs(5)).await);
+        assert!(!flush_with_timeout(|| Err("disk full".to_string()), Duration::from_secs(5)).await);
+
+        // A stalled flush doesn't hold shutdown past the timeout
+        let started = Instant::now();
+        let stalled = || {
+            std::thread::sleep(Duration::from_secs(30));
+            Ok(0)
+        };
+        assert!(!flush_with_timeout(stalled, Duration::from_millis(50)).await);
+        assert!(started.elapsed() < Duration::from_secs(5), "returned after {:?}", started.elapsed());
+    }
+}
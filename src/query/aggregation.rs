@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{debug, info, instrument};
 
-use crate::storage::Storage;
+use crate::storage::{Document, Storage};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -44,6 +44,28 @@ fn default_match_logic() -> MatchLogic {
     MatchLogic::And
 }
 
+impl MatchStage {
+    /// Whether `doc`, in the shape `pipeline_doc` gives it, passes the stage's filters
+    pub fn matches(&self, doc: &Value) -> bool {
+        match self.logic {
+            MatchLogic::And => self.filters.iter().all(|filter| evaluate_filter(doc, filter)),
+            MatchLogic::Or => self.filters.iter().any(|filter| evaluate_filter(doc, filter)),
+        }
+    }
+}
+
+/// A document as pipeline stages see it: fields are addressed as `category`, `metadata.tags`, ...
+pub fn pipeline_doc(doc: &Document, collection_id: &str) -> Value {
+    json!({
+        "id": doc.id,
+        "text": doc.text,
+        "category": doc.category,
+        "vector": doc.vector,
+        "metadata": doc.metadata,
+        "_source_collection": collection_id,
+    })
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum SortOrder {
@@ -188,16 +210,7 @@ impl AggregationEngine {
             .storage
             .get_docs_in_collection(&self.collection_id)?
             .into_iter()
-            .map(|doc| {
-                json!({
-                    "id": doc.id,
-                    "text": doc.text,
-                    "category": doc.category,
-                    "vector": doc.vector,
-                    "metadata": doc.metadata,
-                    "_source_collection": self.collection_id.clone(),
-                })
-            })
+            .map(|doc| pipeline_doc(&doc, &self.collection_id))
             .collect();
 
         for stage in pipeline.stages {
@@ -218,16 +231,7 @@ impl AggregationEngine {
                     )?;
                     results
                         .into_iter()
-                        .map(|doc| {
-                            json!({
-                                "id": doc.id,
-                                "text": doc.text,
-                                "category": doc.category,
-                                "vector": doc.vector,
-                                "metadata": doc.metadata,
-                                "_source_collection": self.collection_id.clone(),
-                            })
-                        })
+                        .map(|doc| pipeline_doc(&doc, &self.collection_id))
                         .collect()
                 }
                 AggregationStage::Lookup(lookup) => self.apply_lookup(docs, lookup)?,
@@ -254,16 +258,7 @@ impl AggregationEngine {
             .storage
             .get_docs_in_collection(&lookup.from)?
             .into_iter()
-            .map(|doc| {
-                json!({
-                    "id": doc.id,
-                    "text": doc.text,
-                    "category": doc.category,
-                    "vector": doc.vector,
-                    "metadata": doc.metadata,
-                    "_source_collection": lookup.from.clone(),
-                })
-            })
+            .map(|doc| pipeline_doc(&doc, &lookup.from))
             .collect();
 
         let mut results = Vec::new();
@@ -300,16 +295,7 @@ impl AggregationEngine {
             .storage
             .get_docs_in_collection(&join.from)?
             .into_iter()
-            .map(|doc| {
-                json!({
-                    "id": doc.id,
-                    "text": doc.text,
-                    "category": doc.category,
-                    "vector": doc.vector,
-                    "metadata": doc.metadata,
-                    "_source_collection": join.from.clone(),
-                })
-            })
+            .map(|doc| pipeline_doc(&doc, &join.from))
             .collect();
 
         let mut results = Vec::new();
@@ -385,16 +371,7 @@ impl AggregationEngine {
                 .storage
                 .get_docs_in_collection(collection)?
                 .into_iter()
-                .map(|doc| {
-                    json!({
-                        "id": doc.id,
-                        "text": doc.text,
-                        "category": doc.category,
-                        "vector": doc.vector,
-                        "metadata": doc.metadata,
-                        "_source_collection": collection.clone(),
-                    })
-                })
+                .map(|doc| pipeline_doc(&doc, collection))
                 .collect();
             all_docs.extend(collection_docs);
         }
@@ -404,18 +381,7 @@ impl AggregationEngine {
 }
 
 fn apply_match(docs: Vec<Value>, stage: MatchStage) -> Vec<Value> {
-    docs.into_iter()
-        .filter(|doc| match stage.logic {
-            MatchLogic::And => stage
-                .filters
-                .iter()
-                .all(|filter| evaluate_filter(doc, filter)),
-            MatchLogic::Or => stage
-                .filters
-                .iter()
-                .any(|filter| evaluate_filter(doc, filter)),
-        })
-        .collect()
+    docs.into_iter().filter(|doc| stage.matches(doc)).collect()
 }
 
 fn apply_sort(mut docs: Vec<Value>, sort_fields: &[SortField]) -> Vec<Value> {
@@ -297,6 +297,12 @@ impl CrossCollectionEngine {
                         category: category.to_string(),
                         vector: serde_json::from_value(vector).unwrap_or_default(),
                         metadata,
+                        vectors: doc.get("vectors")
+                            .and_then(|v| serde_json::from_value(v.clone()).ok())
+                            .unwrap_or_default(),
+                        updated_at: None,
+                        source_uri: None,
+                        ingested_by: None,
                     };
 
                     self.storage.insert_doc(document, collection)?;
@@ -316,6 +322,12 @@ impl CrossCollectionEngine {
                                         .and_then(|v| serde_json::from_value(v.clone()).ok())
                                         .unwrap_or(existing.vector),
                                     metadata: doc.get("metadata").cloned().unwrap_or(existing.metadata),
+                                    vectors: doc.get("vectors")
+                                        .and_then(|v| serde_json::from_value(v.clone()).ok())
+                                        .unwrap_or(existing.vectors),
+                                    updated_at: None,
+                                    source_uri: None,
+                                    ingested_by: None,
                                 };
                                 self.storage.update_doc(updated, collection)?;
                                 results.push(format!("{}/{}: updated", collection, id));
@@ -388,6 +400,19 @@ impl CrossCollectionPipeline {
             stages,
         })
     }
+
+    /// Every collection the pipeline reads: the source plus each stage's `from` or union members
+    pub fn collections(&self) -> Vec<&str> {
+        let mut collections = vec![self.source_collection.as_str()];
+        for stage in &self.stages {
+            match stage {
+                CrossCollectionStage::Lookup(lookup) => collections.push(&lookup.from),
+                CrossCollectionStage::Join(join) => collections.push(&join.from),
+                CrossCollectionStage::Union(union) => collections.extend(union.collections.iter().map(String::as_str)),
+            }
+        }
+        collections
+    }
 }
 
 fn parse_cross_collection_stage(
@@ -0,0 +1,343 @@
+//! DataFusion-free evaluation of simple equality queries
+//!
+//! When DataFusion can't serve a collection (projection/registration failed, or
+//! `AIDB_SQL_FALLBACK=1`), or errors on a query that is only equality filters, the query
+//! engine falls back to this module: the predicate is parsed here and evaluated directly on
+//! scanned documents. Supported shapes are
+//! `SELECT * | col, ... FROM docs [WHERE col = literal [AND ...]] [ORDER BY col [ASC|DESC], ...]
+//! [LIMIT n]` and, for hybrid search, the bare `col = literal [AND ...]` predicate. Columns are
+//! `id`, `text`, `category`, `source_uri`, `ingested_by` and, in predicates, any top-level
+//! `metadata` key; `ORDER BY` also takes `updated_at`. NULLs sort last ascending and first
+//! descending, as in DataFusion, so both paths return rows in the same order.
+
+use arrow::record_batch::RecordBatch;
+use std::cmp::Ordering;
+use tracing::debug;
+
+use crate::storage::sql::{docs_schema, docs_to_arrow};
+use crate::storage::Document;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Symbol(char),
+}
+
+fn tokenize(input: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '\'' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next()? {
+                    // '' is an escaped quote inside a string literal
+                    '\'' if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        value.push('\'');
+                    }
+                    '\'' => break,
+                    other => value.push(other),
+                }
+            }
+            tokens.push(Token::Str(value));
+        } else if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_' || c == '.' || c == '-') {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        } else if matches!(c, '=' | ',' | '*' | ';') {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return None;
+        }
+    }
+    Some(tokens)
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+}
+
+/// Right-hand side of one `column = literal` clause
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Str(String),
+    Number(f64),
+    Bool(bool),
+}
+
+/// Conjunction of `column = literal` clauses, evaluated per document
+#[derive(Debug, Clone, PartialEq)]
+pub struct EqualityFilter {
+    clauses: Vec<(String, Literal)>,
+}
+
+impl EqualityFilter {
+    /// Parse `col = literal [AND col = literal ...]`; `None` for anything else
+    pub fn parse(predicate: &str) -> Option<Self> {
+        let tokens = tokenize(predicate)?;
+        let (filter, rest) = Self::parse_tokens(&tokens)?;
+        rest.is_empty().then_some(filter)
+    }
+
+    fn parse_tokens(tokens: &[Token]) -> Option<(Self, &[Token])> {
+        let mut clauses = Vec::new();
+        let mut rest = tokens;
+        loop {
+            let (column, literal) = match rest {
+                [Token::Word(column), Token::Symbol('='), value, ..] => {
+                    let literal = match value {
+                        Token::Str(s) => Literal::Str(s.clone()),
+                        Token::Word(w) if w.eq_ignore_ascii_case("true") => Literal::Bool(true),
+                        Token::Word(w) if w.eq_ignore_ascii_case("false") => Literal::Bool(false),
+                        Token::Word(w) => Literal::Number(w.parse().ok()?),
+                        Token::Symbol(_) => return None,
+                    };
+                    (column.to_lowercase(), literal)
+                }
+                _ => return None,
+            };
+            clauses.push((column, literal));
+            rest = &rest[3..];
+            if !is_keyword(rest.first(), "and") {
+                return Some((Self { clauses }, rest));
+            }
+            rest = &rest[1..];
+        }
+    }
+
+    /// Whether every clause holds for `doc`; unknown columns and type mismatches don't match
+    pub fn matches(&self, doc: &Document) -> bool {
+        self.clauses.iter().all(|(column, literal)| {
+            let field = match column.as_str() {
+                "id" => Some(doc.id.as_str()),
+                "text" => Some(doc.text.as_str()),
+                "category" => Some(doc.category.as_str()),
+                "source_uri" => doc.provenance_source_uri(),
+                "ingested_by" => doc.provenance_ingested_by(),
+                _ => None,
+            };
+            match (field, literal) {
+                (Some(value), Literal::Str(s)) => value == s,
+                (Some(_), _) => false,
+                (None, literal) => match (doc.metadata.get(column), literal) {
+                    (Some(serde_json::Value::String(v)), Literal::Str(s)) => v == s,
+                    (Some(serde_json::Value::Number(v)), Literal::Number(n)) => v.as_f64() == Some(*n),
+                    (Some(serde_json::Value::Bool(v)), Literal::Bool(b)) => v == b,
+                    _ => false,
+                },
+            }
+        })
+    }
+}
+
+/// One `ORDER BY` key
+#[derive(Debug, Clone, PartialEq)]
+struct SortKey {
+    column: String,
+    descending: bool,
+}
+
+/// Columns `ORDER BY` can sort on without DataFusion
+const SORTABLE_COLUMNS: &[&str] = &["id", "text", "category", "source_uri", "ingested_by", "updated_at"];
+
+/// Ascending order with NULLs after every value
+fn nulls_last<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+    }
+}
+
+impl SortKey {
+    fn compare(&self, a: &Document, b: &Document) -> Ordering {
+        let ordering = match self.column.as_str() {
+            "id" => a.id.cmp(&b.id),
+            "text" => a.text.cmp(&b.text),
+            "category" => a.category.cmp(&b.category),
+            "source_uri" => nulls_last(a.provenance_source_uri(), b.provenance_source_uri()),
+            "ingested_by" => nulls_last(a.provenance_ingested_by(), b.provenance_ingested_by()),
+            "updated_at" => nulls_last(a.updated_at, b.updated_at),
+            _ => Ordering::Equal,
+        };
+        if self.descending { ordering.reverse() } else { ordering }
+    }
+}
+
+/// `SELECT ... FROM docs [WHERE ...] [ORDER BY ...] [LIMIT n]` with an equality-only predicate
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimpleQuery {
+    /// Projected `docs` columns; `None` for `*`
+    columns: Option<Vec<String>>,
+    filter: Option<EqualityFilter>,
+    order_by: Vec<SortKey>,
+    limit: Option<usize>,
+}
+
+impl SimpleQuery {
+    /// Parse a simple query; `None` when the SQL needs DataFusion
+    pub fn parse(sql: &str) -> Option<Self> {
+        let tokens = tokenize(sql)?;
+        let mut rest: &[Token] = &tokens;
+        if !is_keyword(rest.first(), "select") {
+            return None;
+        }
+        rest = &rest[1..];
+
+        let columns = if let [Token::Symbol('*'), tail @ ..] = rest {
+            rest = tail;
+            None
+        } else {
+            let schema = docs_schema();
+            let mut columns = Vec::new();
+            while let [Token::Word(column), tail @ ..] = rest {
+                let column = column.to_lowercase();
+                if column == "from" || schema.index_of(&column).is_err() {
+                    return None;
+                }
+                columns.push(column);
+                rest = tail;
+                match rest {
+                    [Token::Symbol(','), tail @ ..] => rest = tail,
+                    _ => break,
+                }
+            }
+            if columns.is_empty() {
+                return None;
+            }
+            Some(columns)
+        };
+
+        if !is_keyword(rest.first(), "from") || !is_keyword(rest.get(1), "docs") {
+            return None;
+        }
+        rest = &rest[2..];
+
+        let mut filter = None;
+        if is_keyword(rest.first(), "where") {
+            let (parsed, tail) = EqualityFilter::parse_tokens(&rest[1..])?;
+            filter = Some(parsed);
+            rest = tail;
+        }
+        let mut order_by = Vec::new();
+        if is_keyword(rest.first(), "order") && is_keyword(rest.get(1), "by") {
+            rest = &rest[2..];
+            loop {
+                let column = match rest.first() {
+                    Some(Token::Word(column)) if SORTABLE_COLUMNS.contains(&column.to_lowercase().as_str()) => column.to_lowercase(),
+                    _ => return None,
+                };
+                rest = &rest[1..];
+                let mut descending = false;
+                if is_keyword(rest.first(), "asc") || is_keyword(rest.first(), "desc") {
+                    descending = is_keyword(rest.first(), "desc");
+                    rest = &rest[1..];
+                }
+                order_by.push(SortKey { column, descending });
+                match rest {
+                    [Token::Symbol(','), tail @ ..] => rest = tail,
+                    _ => break,
+                }
+            }
+        }
+        let mut limit = None;
+        if is_keyword(rest.first(), "limit") {
+            match rest.get(1) {
+                Some(Token::Word(n)) => limit = Some(n.parse().ok()?),
+                _ => return None,
+            }
+            rest = &rest[2..];
+        }
+        if let [Token::Symbol(';')] = rest {
+            rest = &[];
+        }
+        rest.is_empty().then_some(Self { columns, filter, order_by, limit })
+    }
+
+    /// Evaluate against a collection's documents, producing batches shaped like DataFusion's output
+    pub fn execute(&self, docs: Vec<Document>) -> Result<Vec<RecordBatch>, Box<dyn std::error::Error>> {
+        let mut matched: Vec<Document> = docs
+            .into_iter()
+            .filter(|doc| self.filter.as_ref().is_none_or(|f| f.matches(doc)))
+            .collect();
+        if !self.order_by.is_empty() {
+            matched.sort_by(|a, b| {
+                self.order_by.iter().map(|key| key.compare(a, b)).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+            });
+        }
+        matched.truncate(self.limit.unwrap_or(usize::MAX));
+        debug!(rows = matched.len(), "Simple query evaluated without DataFusion");
+
+        let batch = docs_to_arrow(&matched)?;
+        let batch = match &self.columns {
+            None => batch,
+            Some(columns) => {
+                let schema = docs_schema();
+                let indices = columns
+                    .iter()
+                    .map(|c| schema.index_of(c))
+                    .collect::<Result<Vec<_>, _>>()?;
+                batch.project(&indices)?
+            }
+        };
+        Ok(vec![batch])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::QueryEngine;
+    use arrow::array::Array;
+    use crate::storage::{test_doc, Storage};
+    use std::fs;
+    use std::sync::Arc;
+
+    fn doc(id: &str, category: &str, vector: Vec<f32>) -> Document {
+        Document {
+            category: category.to_string(),
+            metadata: serde_json::json!({"source": "crawl", "rank": 2}),
+            ..test_doc(id, vector)
+        }
+    }
+
+    #[tokio::test]
+    async fn category_filter_is_served_by_the_fallback_path() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_sql_fallback");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).unwrap();
+        storage.insert_doc(doc("ai1", "AI", vec![0.0, 0.0]), "col").unwrap();
+        storage.insert_doc(doc("db1", "DB", vec![0.1, 0.0]), "col").unwrap();
+        storage.insert_doc(doc("ai2", "AI", vec![5.0, 5.0]), "col").unwrap();
+
+        let engine = QueryEngine::with_sql_fallback(Arc::new(storage), "col");
+        let batches = engine.execute_sql("SELECT id, category FROM docs WHERE category = 'AI'").await.unwrap();
+        let ids = batches[0].column(0).as_any().downcast_ref::<arrow::array::StringArray>().unwrap();
+        assert_eq!(batches[0].num_columns(), 2);
+        assert_eq!((0..ids.len()).map(|i| ids.value(i)).collect::<Vec<_>>(), vec!["ai1", "ai2"]);
+
+        let hits = engine.hybrid_query("category = 'AI' AND rank = 2", &[0.0, 0.0], 2).await.unwrap();
+        assert_eq!(hits.iter().map(|(d, _)| d.id.as_str()).collect::<Vec<_>>(), vec!["ai1", "ai2"]);
+        assert!(engine.hybrid_query("source = 'other'", &[0.0, 0.0], 2).await.unwrap().is_empty());
+
+        // Anything beyond equality filters still needs DataFusion
+        assert!(engine.execute_sql("SELECT COUNT(*) FROM docs").await.is_err());
+        assert!(SimpleQuery::parse("SELECT * FROM docs WHERE text = 'it''s' LIMIT 1;").is_some());
+        assert!(EqualityFilter::parse("category != 'AI'").is_none());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
@@ -0,0 +1,133 @@
+//! Search-time SQL filters compiled once and reused across hybrid queries
+//!
+//! A `CompiledFilter` parses a `WHERE`-style predicate (e.g. `category = 'AI'`) into a DataFusion
+//! `Expr` against the `docs` projection schema, rejects references to unknown columns, and keeps
+//! the physical expression so it can be evaluated directly on candidate batches — no SQL string
+//! is re-parsed or spliced into a query per call. Compiled filters are cached per
+//! `(collection, filter)`.
+
+use arrow::array::BooleanArray;
+use arrow::compute::filter_record_batch;
+use arrow::record_batch::RecordBatch;
+use datafusion::common::DFSchema;
+use datafusion::execution::context::SessionContext;
+use datafusion::logical_expr::Expr;
+use datafusion::physical_expr::PhysicalExpr;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::{debug, instrument};
+
+use crate::storage::sql::docs_schema;
+
+/// Most compiled filters kept in the process-wide cache before it is reset
+const FILTER_CACHE_CAPACITY: usize = 256;
+
+/// A parsed, validated filter over the `docs` projection
+#[derive(Debug)]
+pub struct CompiledFilter {
+    source: String,
+    expr: Expr,
+    physical: Arc<dyn PhysicalExpr>,
+}
+
+type FilterCache = Mutex<HashMap<(String, String), Arc<CompiledFilter>>>;
+
+fn filter_cache() -> &'static FilterCache {
+    static CACHE: OnceLock<FilterCache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+impl CompiledFilter {
+    /// Parse and validate `filter` against the `docs` schema
+    #[instrument]
+    pub fn compile(filter: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let schema = docs_schema();
+        let df_schema = DFSchema::try_from(schema.as_ref().clone())?;
+        let ctx = SessionContext::new();
+        let expr = ctx.state().create_logical_expr(filter, &df_schema)?;
+
+        let known: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        for column in expr.to_columns()? {
+            if !known.contains(&column.name.as_str()) {
+                return Err(format!(
+                    "Filter references unknown column '{}' (available: {})",
+                    column.name,
+                    known.join(", ")
+                ).into());
+            }
+        }
+
+        let physical = ctx.create_physical_expr(expr.clone(), &df_schema)?;
+        debug!(filter = %filter, expr = %expr, "Filter compiled");
+        Ok(Self { source: filter.to_string(), expr, physical })
+    }
+
+    /// Compiled filter for `(collection_id, filter)`, compiling and caching it on first use
+    pub fn cached(collection_id: &str, filter: &str) -> Result<Arc<Self>, Box<dyn std::error::Error>> {
+        let key = (collection_id.to_string(), filter.to_string());
+        if let Some(compiled) = filter_cache().lock().unwrap_or_else(|p| p.into_inner()).get(&key) {
+            return Ok(compiled.clone());
+        }
+
+        let compiled = Arc::new(Self::compile(filter)?);
+        let mut cache = filter_cache().lock().unwrap_or_else(|p| p.into_inner());
+        if cache.len() >= FILTER_CACHE_CAPACITY {
+            debug!(entries = cache.len(), "Filter cache full, clearing");
+            cache.clear();
+        }
+        Ok(cache.entry(key).or_insert(compiled).clone())
+    }
+
+    /// The filter text this was compiled from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The logical expression (e.g. for explain output)
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Keep only the rows of a `docs`-schema batch that match the filter (order preserved).
+    /// Rows where the predicate evaluates to NULL are dropped, as in SQL `WHERE`.
+    pub fn apply(&self, batch: &RecordBatch) -> Result<RecordBatch, Box<dyn std::error::Error>> {
+        let mask = self.physical.evaluate(batch)?.into_array(batch.num_rows())?;
+        let mask = mask
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .ok_or_else(|| format!("Filter '{}' does not evaluate to a boolean", self.source))?;
+        Ok(filter_record_batch(batch, mask)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sql::docs_to_arrow;
+    use crate::storage::{test_doc, Document};
+    use arrow::array::{Array, StringArray};
+
+    fn doc(id: &str, category: &str) -> Document {
+        Document { category: category.to_string(), ..test_doc(id, vec![0.0]) }
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<String> {
+        let col = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        (0..col.len()).map(|i| col.value(i).to_string()).collect()
+    }
+
+    #[test]
+    fn compiled_once_applies_to_several_candidate_sets() {
+        let filter = CompiledFilter::cached("col", "category = 'AI'").expect("compile");
+        // Same (collection, filter) reuses the compiled instance
+        assert!(Arc::ptr_eq(&filter, &CompiledFilter::cached("col", "category = 'AI'").unwrap()));
+
+        let first = docs_to_arrow(&[doc("a", "AI"), doc("b", "DB"), doc("c", "AI")]).unwrap();
+        assert_eq!(ids(&filter.apply(&first).unwrap()), vec!["a", "c"]);
+
+        let second = docs_to_arrow(&[doc("d", "DB"), doc("e", "AI")]).unwrap();
+        assert_eq!(ids(&filter.apply(&second).unwrap()), vec!["e"]);
+
+        assert!(CompiledFilter::compile("no_such_column = 1").is_err());
+    }
+}
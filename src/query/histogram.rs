@@ -0,0 +1,148 @@
+//! Distribution of k-th nearest-neighbor distances within a collection
+//!
+//! Helps pick a distance threshold: a sample of the collection's documents is searched against
+//! the collection's own index and the distance to each one's k-th neighbor (itself excluded) is
+//! bucketed into equal-width bins. Results are cached per `(collection, k, bins)` and reused
+//! until the collection's generation changes.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{info, debug, instrument};
+
+use crate::storage::{Storage, DEFAULT_VECTOR_FIELD};
+
+/// Most documents whose neighbor distance is measured for one histogram
+pub const HISTOGRAM_SAMPLE_SIZE: usize = 500;
+
+/// Histogram of k-th neighbor distances; `bin_edges` has one more entry than `counts`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DistanceHistogram {
+    pub collection_id: String,
+    pub k: usize,
+    pub metric: String,
+    /// Documents whose k-th neighbor distance was measured (equals the sum of `counts`)
+    pub sample_size: usize,
+    pub bin_edges: Vec<f32>,
+    pub counts: Vec<u64>,
+    /// Collection generation the histogram was computed at
+    pub generation: u64,
+}
+
+/// Histograms keyed by `(collection_id, k, bins)`, tagged with the generation they were computed at
+pub(crate) type HistogramCache = HashMap<(String, usize, usize), (u64, Arc<DistanceHistogram>)>;
+
+/// Bucket `distances` into `bins` equal-width bins spanning their range
+fn bucket(distances: &[f32], bins: usize) -> (Vec<f32>, Vec<u64>) {
+    if distances.is_empty() {
+        return (vec![0.0; bins + 1], vec![0; bins]);
+    }
+    let min = distances.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = distances.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let width = (max - min) / bins as f32;
+    let edges = (0..=bins)
+        .map(|i| if i == bins { max } else { min + width * i as f32 })
+        .collect();
+    let mut counts = vec![0u64; bins];
+    for d in distances {
+        let bin = if width > 0.0 { ((d - min) / width) as usize } else { 0 };
+        counts[bin.min(bins - 1)] += 1;
+    }
+    (edges, counts)
+}
+
+impl Storage {
+    /// Histogram of the distance from sampled documents to their k-th nearest neighbor
+    /// (`k` >= 1, `bins` >= 1). Documents with fewer than `k` neighbors are left out.
+    #[instrument(skip(self))]
+    pub fn distance_histogram(
+        &self,
+        collection_id: &str,
+        k: usize,
+        bins: usize,
+    ) -> Result<Arc<DistanceHistogram>, Box<dyn std::error::Error>> {
+        if k == 0 || bins == 0 {
+            return Err("k and bins must be at least 1".into());
+        }
+        let generation = self.collection_generation(collection_id)?;
+        let cache_key = (collection_id.to_string(), k, bins);
+        if let Some((computed_at, histogram)) = self.lock_histogram_cache().get(&cache_key) {
+            if *computed_at == generation {
+                debug!(collection_id = %collection_id, generation = generation, "Distance histogram cache hit");
+                return Ok(histogram.clone());
+            }
+        }
+
+        // A debounced rebuild may hand back an older index; tag the histogram with its generation
+        let (generation, index) = self.cached_field_index_at(collection_id, DEFAULT_VECTOR_FIELD)?;
+        let vectors = self.get_vectors_in_collection(collection_id)?;
+        // Evenly spaced sample so large collections cost a bounded number of searches
+        let stride = vectors.len().div_ceil(HISTOGRAM_SAMPLE_SIZE).max(1);
+        let distances: Vec<f32> = vectors
+            .iter()
+            .step_by(stride)
+            .filter_map(|(id, vector)| {
+                index
+                    .search_with_distances(vector, k + 1)
+                    .into_iter()
+                    .filter(|(neighbor, _)| neighbor != id)
+                    .nth(k - 1)
+                    .map(|(_, distance)| distance)
+            })
+            .collect();
+
+        let (bin_edges, counts) = bucket(&distances, bins);
+        let histogram = Arc::new(DistanceHistogram {
+            collection_id: collection_id.to_string(),
+            k,
+            metric: index.metric().as_str().to_string(),
+            sample_size: distances.len(),
+            bin_edges,
+            counts,
+            generation,
+        });
+        self.lock_histogram_cache().insert(cache_key, (generation, histogram.clone()));
+
+        info!(collection_id = %collection_id, k = k, bins = bins, sample_size = histogram.sample_size, "Distance histogram computed");
+        Ok(histogram)
+    }
+
+    fn lock_histogram_cache(&self) -> std::sync::MutexGuard<'_, HistogramCache> {
+        self.histogram_cache.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use std::fs;
+
+    #[test]
+    fn histogram_counts_sample_and_edges_are_monotonic() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_distance_histogram");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        let docs = (0..30)
+            .map(|i| test_doc(&format!("doc{}", i), vec![(i * i) as f32 * 0.1, 1.0]))
+            .collect();
+        storage.insert_docs(docs, "col").unwrap();
+
+        let histogram = storage.distance_histogram("col", 1, 8).unwrap();
+        assert_eq!(histogram.sample_size, 30);
+        assert_eq!(histogram.counts.iter().sum::<u64>(), 30);
+        assert_eq!(histogram.bin_edges.len(), 9);
+        assert!(histogram.bin_edges.windows(2).all(|w| w[0] <= w[1]));
+        assert!(histogram.bin_edges[0] > 0.0, "self-matches are excluded");
+
+        // Cached until the collection changes
+        assert!(Arc::ptr_eq(&histogram, &storage.distance_histogram("col", 1, 8).unwrap()));
+        storage.delete_doc("col", "doc0").unwrap();
+        let updated = storage.distance_histogram("col", 1, 8).unwrap();
+        assert_eq!(updated.sample_size, 29);
+        assert!(updated.generation > histogram.generation);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
@@ -0,0 +1,63 @@
+//! Arrow IPC encoding of SQL results
+//!
+//! Query results leave the server as one Arrow IPC stream (schema message, then one message per
+//! batch), readable by any Arrow implementation's stream reader, e.g. `pyarrow.ipc.open_stream`.
+//! A result without batches encodes to zero bytes, since there is no schema to announce.
+
+use arrow::error::ArrowError;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow::record_batch::RecordBatch;
+use std::io::Cursor;
+
+/// Serialize `batches` (which share one schema, as query results do) into an IPC stream
+pub fn encode_ipc_stream(batches: &[RecordBatch]) -> Result<Vec<u8>, ArrowError> {
+    let Some(first) = batches.first() else {
+        return Ok(Vec::new());
+    };
+    let mut buf = Vec::new();
+    {
+        let mut writer = StreamWriter::try_new(&mut buf, first.schema().as_ref())?;
+        for batch in batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+    }
+    Ok(buf)
+}
+
+/// Read back every batch of an IPC stream written by `encode_ipc_stream`
+pub fn decode_ipc_stream(bytes: &[u8]) -> Result<Vec<RecordBatch>, ArrowError> {
+    if bytes.is_empty() {
+        return Ok(Vec::new());
+    }
+    StreamReader::try_new(Cursor::new(bytes), None)?.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sql::docs_to_arrow;
+    use crate::storage::{test_doc, Document};
+
+    fn doc(i: usize) -> Document {
+        Document { updated_at: Some(1_700_000_000_000 + i as u64), ..test_doc(&format!("doc{}", i), vec![i as f32, 0.0]) }
+    }
+
+    #[test]
+    fn batches_round_trip_through_an_ipc_stream() {
+        let first = docs_to_arrow(&(0..3).map(doc).collect::<Vec<_>>()).unwrap();
+        let second = docs_to_arrow(&(3..8).map(doc).collect::<Vec<_>>()).unwrap();
+
+        let bytes = encode_ipc_stream(&[first.clone(), second.clone()]).unwrap();
+        let decoded = decode_ipc_stream(&bytes).unwrap();
+        let rows: Vec<usize> = decoded.iter().map(|b| b.num_rows()).collect();
+        assert_eq!(rows, vec![3, 5]);
+        assert_eq!(decoded[0].schema(), first.schema());
+        assert_eq!(decoded, vec![first, second]);
+
+        assert!(encode_ipc_stream(&[]).unwrap().is_empty());
+        assert!(decode_ipc_stream(&[]).unwrap().is_empty());
+        assert!(decode_ipc_stream(b"not arrow").is_err());
+    }
+}
@@ -7,17 +7,26 @@
 
 pub mod aggregation;
 pub mod cross_collection;
+pub mod fallback;
+pub mod filter;
+pub mod histogram;
+pub mod ipc;
 pub mod sql;
+pub mod udf;
 pub mod vector;
 
 pub use aggregation::AggregationEngine;
 pub use cross_collection::CrossCollectionEngine;
-pub use sql::QueryEngine;
+pub use filter::CompiledFilter;
+pub use histogram::DistanceHistogram;
+pub use ipc::{decode_ipc_stream, encode_ipc_stream};
+pub use sql::{HybridExplanation, HybridOutcome, QueryEngine};
+pub use udf::{cosine_sim_udf, cosine_similarity};
 
 #[cfg(test)]
 mod tests {
     use super::QueryEngine;
-    use crate::storage::{Document, Storage};
+    use crate::storage::{test_doc, Document, Storage};
     use std::fs;
     use serde_json;  // For json! in test doc
 
@@ -33,11 +42,9 @@ mod tests {
 
         // Insert sample multi-model doc for isolated test (NoSQL JSON + Arrow SQL)
         let doc = Document {
-            id: "test_sql_doc".to_string(),
             text: "Test for SQL/DataFusion".to_string(),
-            category: "AI".to_string(),
-            vector: vec![1.0, 0.1, 0.1, 0.1],
             metadata: serde_json::json!({"test": true}),
+            ..test_doc("test_sql_doc", vec![1.0, 0.1, 0.1, 0.1])
         };
         storage.insert_doc(doc, "test_collection")?;
 
@@ -57,4 +64,81 @@ mod tests {
         let _ = fs::remove_dir_all(temp_dir);
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn sql_filters_by_document_provenance() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = std::env::temp_dir().join("aidb_test_query_provenance");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap())?;
+
+        let doc = |id: &str, source_uri: Option<&str>, metadata: serde_json::Value| Document {
+            metadata,
+            source_uri: source_uri.map(str::to_string),
+            ingested_by: Some("nightly-crawl".to_string()),
+            ..test_doc(id, vec![0.0, 1.0])
+        };
+        storage.insert_doc(doc("manual_p1", Some("s3://bucket/manual.pdf#p1"), serde_json::json!({})), "prov")?;
+        storage.insert_doc(doc("manual_p2", Some("s3://bucket/manual.pdf#p2"), serde_json::json!({})), "prov")?;
+        storage.insert_doc(doc("faq", Some("https://example.com/faq"), serde_json::json!({})), "prov")?;
+        // Provenance recorded in metadata before the dedicated fields existed is still projected
+        storage.insert_doc(doc("legacy", None, serde_json::json!({"source_uri": "s3://bucket/manual.pdf#p1"})), "prov")?;
+        assert_eq!(
+            storage.get_doc("prov", "faq")?.source_uri.as_deref(),
+            Some("https://example.com/faq")
+        );
+
+        let engine = QueryEngine::new(std::sync::Arc::new(storage), "prov").await?;
+        let batches = engine
+            .execute_sql("SELECT id, source_uri, ingested_by FROM docs WHERE source_uri = 's3://bucket/manual.pdf#p1'")
+            .await?;
+        let mut ids = Vec::new();
+        for batch in &batches {
+            let col = batch.column(0).as_any().downcast_ref::<arrow::array::StringArray>().unwrap();
+            let by = batch.column(2).as_any().downcast_ref::<arrow::array::StringArray>().unwrap();
+            for i in 0..batch.num_rows() {
+                ids.push(col.value(i).to_string());
+                assert_eq!(by.value(i), "nightly-crawl");
+            }
+        }
+        ids.sort();
+        assert_eq!(ids, vec!["legacy", "manual_p1"]);
+
+        let _ = fs::remove_dir_all(temp_dir);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sql_orders_documents_by_cosine_similarity() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = std::env::temp_dir().join("aidb_test_query_cosine_sim");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap())?;
+
+        for (id, vector) in [("east", vec![1.0, 0.0]), ("north", vec![0.0, 1.0]), ("north_east", vec![1.0, 1.0]), ("west", vec![-1.0, 0.1])] {
+            storage.insert_doc(test_doc(id, vector), "compass")?;
+        }
+
+        let engine = QueryEngine::new(std::sync::Arc::new(storage), "compass").await?;
+        let ids = |batches: Vec<arrow::record_batch::RecordBatch>| -> Vec<String> {
+            batches
+                .iter()
+                .flat_map(|batch| {
+                    let col = batch.column(0).as_any().downcast_ref::<arrow::array::StringArray>().unwrap();
+                    (0..batch.num_rows()).map(|i| col.value(i).to_string()).collect::<Vec<_>>()
+                })
+                .collect()
+        };
+        let ranked = engine
+            .execute_sql("SELECT id FROM docs ORDER BY cosine_sim(vector, '[1.0, 0.2]') DESC")
+            .await?;
+        assert_eq!(ids(ranked), vec!["east", "north_east", "north", "west"]);
+
+        // A list literal works too, and the score can filter
+        let close = engine
+            .execute_sql("SELECT id, cosine_sim(vector, make_array(0.0, 1.0)) AS score FROM docs WHERE cosine_sim(vector, make_array(0.0, 1.0)) > 0.5 ORDER BY score DESC")
+            .await?;
+        assert_eq!(ids(close), vec!["north", "north_east"]);
+
+        let _ = fs::remove_dir_all(temp_dir);
+        Ok(())
+    }
+}
@@ -1,16 +1,119 @@
 use arrow::array::Array;
 use arrow::record_batch::RecordBatch;
 use datafusion::execution::context::SessionContext;
+use serde::Serialize;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{info, debug, warn, error, instrument};
 
-use crate::storage::{Document, Storage};
+use crate::cancel;
+use crate::query::fallback::{EqualityFilter, SimpleQuery};
+use crate::indexing::DistanceMetric;
+use crate::query::filter::CompiledFilter;
+use crate::query::udf::cosine_sim_udf;
+use crate::storage::sql::docs_to_arrow;
+use crate::storage::{Document, Storage, DEFAULT_VECTOR_FIELD};
 
 /// QueryEngine wraps DataFusion SessionContext for SQL over unified storage
 pub struct QueryEngine {
     ctx: SessionContext,
     storage: Arc<Storage>,
     collection_id: String,
+    /// Why DataFusion isn't serving this engine; simple equality queries then use `query::fallback`
+    degraded: Option<String>,
+}
+
+/// Parse `AIDB_QUERY_TIMEOUT_MS`: default deadline for hybrid queries (unset or 0 = no deadline)
+pub fn parse_query_timeout(raw: Option<&str>) -> Option<Duration> {
+    raw.and_then(|raw| raw.trim().parse::<u64>().ok())
+        .filter(|ms| *ms > 0)
+        .map(Duration::from_millis)
+}
+
+/// Result of `QueryEngine::hybrid_query_with_timeout`
+#[derive(Debug, Clone)]
+pub struct HybridOutcome {
+    /// Matching documents in rank order, each with whether it was served from the doc cache
+    pub docs: Vec<(Document, bool)>,
+    /// The deadline cut the query short: `docs` are the best candidates gathered so far and may
+    /// not all satisfy the SQL filter
+    pub partial: bool,
+    /// Metric the ANN stage ranked candidates by
+    pub metric: DistanceMetric,
+}
+
+impl HybridOutcome {
+    fn partial(docs: Vec<(Document, bool)>, top_k: usize, metric: DistanceMetric) -> Self {
+        Self { docs: docs.into_iter().take(top_k).collect(), partial: true, metric }
+    }
+}
+
+/// Why one hybrid result was returned (see `QueryEngine::explain_hybrid`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HybridExplanation {
+    /// Raw distance between the query vector and the document's vector
+    pub vector_distance: f32,
+    pub metric: String,
+    /// Fraction of the explain keywords found in the document text; `None` without keywords
+    pub keyword_score: Option<f32>,
+    /// Top-level `AND` clauses of the filter that hold for this document
+    pub matched_clauses: Vec<String>,
+    /// Clauses that don't hold (only possible in partial results)
+    pub unmatched_clauses: Vec<String>,
+}
+
+/// Top-level `AND` operands of a filter. Filters with a top-level `OR` or a `BETWEEN ... AND`
+/// are kept as a single clause rather than split incorrectly.
+fn split_conjuncts(filter: &str) -> Vec<String> {
+    let bytes = filter.as_bytes();
+    let is_boundary = |i: usize| i >= bytes.len() || !(bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_');
+    let word_at = |i: usize, word: &str| {
+        filter.get(i..i + word.len()).is_some_and(|w| w.eq_ignore_ascii_case(word)) && is_boundary(i + word.len())
+    };
+
+    let mut clauses = Vec::new();
+    let (mut depth, mut quoted, mut start, mut i) = (0usize, false, 0usize, 0usize);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' => quoted = !quoted,
+            b'(' if !quoted => depth += 1,
+            b')' if !quoted => depth = depth.saturating_sub(1),
+            _ if !quoted && depth == 0 && (i == 0 || is_boundary(i - 1)) => {
+                if word_at(i, "or") || word_at(i, "between") {
+                    return vec![filter.trim().to_string()];
+                }
+                if word_at(i, "and") {
+                    clauses.push(filter[start..i].trim().to_string());
+                    start = i + 3;
+                    i = start;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    clauses.push(filter[start..].trim().to_string());
+    clauses.retain(|c| !c.is_empty());
+    clauses
+}
+
+/// Fraction of whitespace-separated `keywords` contained in `text` (case-insensitive)
+fn keyword_score(keywords: &str, text: &str) -> Option<f32> {
+    let text = text.to_lowercase();
+    let terms: Vec<String> = keywords.split_whitespace().map(str::to_lowercase).collect();
+    if terms.is_empty() {
+        return None;
+    }
+    let found = terms.iter().filter(|t| text.contains(t.as_str())).count();
+    Some(found as f32 / terms.len() as f32)
+}
+
+/// Predicate applied to hybrid-search candidates
+enum CandidateFilter {
+    Compiled(Arc<CompiledFilter>),
+    Equality(EqualityFilter),
 }
 
 impl QueryEngine {
@@ -19,13 +122,26 @@ impl QueryEngine {
     #[instrument(skip(storage), fields(collection_id))]
     pub async fn new(storage: Arc<Storage>, collection_id: &str) -> Result<Self, Box<dyn std::error::Error>> {
         debug!(collection_id = %collection_id, "Initializing query engine");
+        if storage.sql_fallback {
+            return Ok(Self::with_sql_fallback(storage, collection_id));
+        }
         
         let ctx = SessionContext::new();
+        // `cosine_sim(vector, query)` lets hybrid ranking be written in SQL (see `query::udf`)
+        ctx.register_udf(cosine_sim_udf());
 
         // Project NoSQL JSON docs to Arrow RecordBatch (structured view)
         // Enables high-perf SQL scans, filters, agg on 'docs' table
-        let batch = storage.project_collection_to_arrow(collection_id)?;
-        ctx.register_batch("docs", batch)?;
+        let registered = storage
+            .project_collection_to_arrow(collection_id)
+            .and_then(|batch| Ok(ctx.register_batch("docs", batch)?));
+        if let Err(e) = registered {
+            // Simple equality queries can still be answered by scanning documents
+            warn!(collection_id = %collection_id, error = %e, "DataFusion unavailable, degrading to the SQL fallback");
+            let mut engine = Self::with_sql_fallback(storage, collection_id);
+            engine.degraded = Some(e.to_string());
+            return Ok(engine);
+        }
         
         info!(collection_id = %collection_id, "Query engine initialized");
 
@@ -33,84 +149,356 @@ impl QueryEngine {
             ctx,
             storage,
             collection_id: collection_id.to_string(),
+            degraded: None,
         })
     }
 
+    /// Engine that bypasses DataFusion: only equality-filter queries (see `query::fallback`) are served
+    pub fn with_sql_fallback(storage: Arc<Storage>, collection_id: &str) -> Self {
+        Self {
+            ctx: SessionContext::new(),
+            storage,
+            collection_id: collection_id.to_string(),
+            degraded: Some("SQL fallback mode".to_string()),
+        }
+    }
+
+    fn execute_simple(&self, query: &SimpleQuery) -> Result<Vec<RecordBatch>, Box<dyn std::error::Error>> {
+        query.execute(self.storage.get_docs_in_collection(&self.collection_id)?)
+    }
+
     /// Execute SQL query on projected data (e.g., relational filters on JSON fields)
     /// Supports push-down: filters applied at scan for max perf.
     #[instrument(skip(self))]
     pub async fn execute_sql(&self, sql: &str) -> Result<Vec<RecordBatch>, Box<dyn std::error::Error>> {
         debug!(sql = %sql, "Executing SQL query");
+        if let Some(reason) = &self.degraded {
+            let query = SimpleQuery::parse(sql).ok_or_else(|| {
+                format!("DataFusion unavailable ({}); only simple equality queries are supported", reason)
+            })?;
+            return self.execute_simple(&query);
+        }
         
-        let df = self.ctx.sql(sql).await?;
-        // Collect results as Arrow batches (vectorized execution)
-        let results = df.collect().await?;
+        let executed = async {
+            let df = self.ctx.sql(sql).await?;
+            // Collect results as Arrow batches (vectorized execution)
+            df.collect().await
+        }
+        .await;
+        let results = match executed {
+            Ok(results) => results,
+            Err(e) => match SimpleQuery::parse(sql) {
+                Some(query) => {
+                    warn!(sql = %sql, error = %e, "DataFusion failed on a simple query, using the SQL fallback");
+                    return self.execute_simple(&query);
+                }
+                None => return Err(e.into()),
+            },
+        };
         
         info!(sql = %sql, batch_count = results.len(), "SQL query executed");
         Ok(results)
     }
 
-    /// Hybrid query example: Combine SQL filter + vector search
-    /// Planner routes: Use index for vector, DataFusion for SQL predicate.
-    /// Benefit: No data movement between DBs.
-    #[instrument(skip(self, query_vector), fields(collection_id, sql_filter, top_k))]
+    /// Hybrid query: vector ANN for ranked candidates, then the SQL predicate as a
+    /// `CompiledFilter` evaluated directly on the candidates' Arrow projection.
+    /// Oversamples `top_k * 2` candidates and widens to the whole collection if too few match.
     pub async fn hybrid_query(
         &self,
         sql_filter: &str,  // e.g., "category = 'AI'"
         query_vector: &[f32],
         top_k: usize,
     ) -> Result<Vec<(Document, bool)>, Box<dyn std::error::Error>> {
+        Ok(self.hybrid_query_with_timeout(sql_filter, query_vector, top_k, None).await?.docs)
+    }
+
+    /// `hybrid_query` bounded by `timeout`: if the SQL stage hasn't finished in time, the
+    /// candidates gathered so far (ANN-ranked, filtered if a filter pass completed) are returned
+    /// with `partial` set instead of failing the query. The abandoned stage still runs to
+    /// completion on the blocking pool; only its result is discarded.
+    #[instrument(skip(self, query_vector), fields(collection_id, sql_filter, top_k))]
+    pub async fn hybrid_query_with_timeout(
+        &self,
+        sql_filter: &str,
+        query_vector: &[f32],
+        top_k: usize,
+        timeout: Option<Duration>,
+    ) -> Result<HybridOutcome, Box<dyn std::error::Error>> {
         debug!(
             sql_filter = %sql_filter,
             top_k = top_k,
             vector_len = query_vector.len(),
+            ?timeout,
             "Starting hybrid query"
         );
-        
-        // Step 1: Vector indexing for candidates (ANN)
-        let vectors = self.storage.get_vectors_in_collection(&self.collection_id)?;
-        let index = crate::indexing::VectorIndex::build_from_vectors(vectors);
-        let _candidate_ids = index.search(query_vector, top_k * 2);  // Oversample (unused in simplified SQL)
-
-        // Step 2: SQL filter on Arrow projection (push-down on candidates)
-        let sql = if sql_filter.is_empty() {
-            "SELECT * FROM docs".to_string()
-        } else {
-            format!("SELECT * FROM docs WHERE {}", sql_filter)
-        };
-        let sql_results = self.execute_sql(&sql).await?;
+        self.storage.check_vector("query", query_vector)?;
+        let query_vector = self.storage.prepare_query_vector(&self.collection_id, query_vector)?;
+        let filter = self.candidate_filter(sql_filter)?;
+        let stage = filter.map(|filter| move |candidates: Vec<(Document, bool)>| filter.apply(candidates));
+        let outcome = self.run_hybrid(&query_vector, top_k, timeout, stage).await?;
+        info!(
+            sql_filter = %sql_filter,
+            results = outcome.docs.len(),
+            partial = outcome.partial,
+            cache_hits = outcome.docs.iter().filter(|(_, cached)| *cached).count(),
+            "Hybrid query completed"
+        );
+        Ok(outcome)
+    }
 
-        // Step 3: Fetch full docs (NoSQL JSON) for results
-        let mut docs = vec![];
-        let mut cache_hits = 0;
-        
-        for batch in sql_results {
-            // Extract IDs from Arrow, lookup in Sled JSON
-            if let Some(id_col) = batch.column(0).as_any().downcast_ref::<arrow::array::StringArray>() {
-                for i in 0..id_col.len() {
-                    let id = id_col.value(i);
-                    let key = format!("{}/{}", self.collection_id, id);
-                    if let Ok((doc, from_cache)) = self.storage.get_doc_with_cache_status(&key) {
-                        if from_cache {
-                            cache_hits += 1;
+    /// Per-result explanations for a hybrid outcome, in result order: the vector distance under
+    /// the metric that ranked it, an optional keyword score against `keywords`, and which
+    /// top-level clauses of `sql_filter` each document satisfies
+    #[instrument(skip(self, query_vector, outcome), fields(collection_id = %self.collection_id))]
+    pub fn explain_hybrid(
+        &self,
+        sql_filter: &str,
+        query_vector: &[f32],
+        keywords: Option<&str>,
+        outcome: &HybridOutcome,
+    ) -> Result<Vec<HybridExplanation>, Box<dyn std::error::Error>> {
+        let query_vector = self.storage.prepare_query_vector(&self.collection_id, query_vector)?;
+        let mut clause_matches = Vec::new();
+        for clause in split_conjuncts(sql_filter) {
+            let matched: HashSet<String> = match self.candidate_filter(&clause)? {
+                Some(filter) => filter.apply(outcome.docs.clone())?.into_iter().map(|(d, _)| d.id).collect(),
+                None => outcome.docs.iter().map(|(d, _)| d.id.clone()).collect(),
+            };
+            clause_matches.push((clause, matched));
+        }
+
+        let explanations = outcome
+            .docs
+            .iter()
+            .map(|(doc, _)| {
+                let (matched, unmatched): (Vec<_>, Vec<_>) =
+                    clause_matches.iter().partition(|(_, ids)| ids.contains(&doc.id));
+                HybridExplanation {
+                    vector_distance: outcome.metric.distance(&query_vector, &doc.vector),
+                    metric: outcome.metric.as_str().to_string(),
+                    keyword_score: keywords.and_then(|k| keyword_score(k, &doc.text)),
+                    matched_clauses: matched.into_iter().map(|(c, _)| c.clone()).collect(),
+                    unmatched_clauses: unmatched.into_iter().map(|(c, _)| c.clone()).collect(),
+                }
+            })
+            .collect();
+        debug!(sql_filter = %sql_filter, clauses = clause_matches.len(), "Hybrid results explained");
+        Ok(explanations)
+    }
+
+    /// Candidate predicate for `sql_filter` (`None` when empty): compiled by DataFusion, or
+    /// evaluated by the equality fallback when DataFusion is unavailable or rejects it
+    fn candidate_filter(&self, sql_filter: &str) -> Result<Option<CandidateFilter>, Box<dyn std::error::Error>> {
+        if sql_filter.trim().is_empty() {
+            return Ok(None);
+        }
+        if self.degraded.is_some() {
+            let equality = EqualityFilter::parse(sql_filter)
+                .ok_or("DataFusion unavailable; only simple equality filters are supported")?;
+            return Ok(Some(CandidateFilter::Equality(equality)));
+        }
+        match CompiledFilter::cached(&self.collection_id, sql_filter) {
+            Ok(compiled) => Ok(Some(CandidateFilter::Compiled(compiled))),
+            Err(e) => match EqualityFilter::parse(sql_filter) {
+                Some(equality) => {
+                    warn!(sql_filter = %sql_filter, error = %e, "DataFusion rejected a simple filter, using the SQL fallback");
+                    Ok(Some(CandidateFilter::Equality(equality)))
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// ANN candidates in rank order, passed through the `filter_stage` (run on the blocking pool
+    /// so a deadline can cut it short). The filter only ever sees the candidate set, never the whole
+    /// collection, and results keep ANN distance order whatever order the filter matched them in.
+    /// Dropping the future cancels the blocking work (see `cancel`).
+    async fn run_hybrid<F>(
+        &self,
+        query_vector: &[f32],
+        top_k: usize,
+        timeout: Option<Duration>,
+        filter_stage: Option<F>,
+    ) -> Result<HybridOutcome, Box<dyn std::error::Error>>
+    where
+        F: Fn(Vec<(Document, bool)>) -> Result<Vec<(Document, bool)>, String> + Send + Sync + 'static,
+    {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let filter_stage = filter_stage.map(Arc::new);
+
+        // Step 1: Vector indexing for candidates (ANN), shared with vector search via the index cache
+        let storage = self.storage.clone();
+        let collection_id = self.collection_id.clone();
+        let index = cancel::run_blocking(move || {
+            storage.cached_field_index(&collection_id, DEFAULT_VECTOR_FIELD).map_err(|e| e.to_string())
+        })
+        .await??;
+        let total = index.len();
+        let metric = index.metric();
+        let mut candidate_count = top_k.saturating_mul(2).min(total);
+
+        loop {
+            // Step 2: Fetch candidate docs (NoSQL JSON) in rank order
+            let mut candidates = vec![];
+            for id in index.search(query_vector, candidate_count) {
+                let key = format!("{}/{}", self.collection_id, id);
+                if let Ok(found) = self.storage.get_doc_with_cache_status(&key) {
+                    candidates.push(found);
+                }
+            }
+
+            // Step 3: Filter candidates (SQL stage), bounded by the deadline
+            let docs = match &filter_stage {
+                None => candidates,
+                Some(stage) => {
+                    let stage = stage.clone();
+                    let gathered = candidates.clone();
+                    // Dropped on timeout, which cancels the stage's scans
+                    let run = cancel::run_blocking(move || stage(candidates));
+                    let finished = match deadline {
+                        None => Some(run.await),
+                        Some(deadline) => {
+                            let remaining = deadline.saturating_duration_since(Instant::now());
+                            tokio::time::timeout(remaining, run).await.ok()
+                        }
+                    };
+                    match finished {
+                        Some(joined) => joined.map_err(|e| e.to_string())??,
+                        None => {
+                            warn!(candidates = gathered.len(), "Hybrid SQL stage timed out, returning ANN candidates as partial results");
+                            return Ok(HybridOutcome::partial(gathered, top_k, metric));
                         }
-                        docs.push((doc, from_cache));
                     }
                 }
+            };
+
+            if docs.len() >= top_k || candidate_count >= total {
+                let docs: Vec<(Document, bool)> = docs.into_iter().take(top_k).collect();
+                return Ok(HybridOutcome { docs, partial: false, metric });
             }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                debug!(matched = docs.len(), "Deadline reached before widening, returning filtered matches as partial results");
+                return Ok(HybridOutcome::partial(docs, top_k, metric));
+            }
+            debug!(matched = docs.len(), candidates = candidate_count, "Too few filtered candidates, widening to the full collection");
+            candidate_count = total;
         }
-        
-        // Limit to top_k
-        let result_count = docs.len().min(top_k);
-        let docs: Vec<(Document, bool)> = docs.into_iter().take(top_k).collect();
-        
-        info!(
-            sql_filter = %sql_filter,
-            results = result_count,
-            cache_hits = cache_hits,
-            "Hybrid query completed"
-        );
-        
-        Ok(docs)
+    }
+}
+
+impl CandidateFilter {
+    /// Keep the candidates matching the filter, preserving rank order
+    fn apply(&self, candidates: Vec<(Document, bool)>) -> Result<Vec<(Document, bool)>, String> {
+        match self {
+            CandidateFilter::Equality(equality) => {
+                Ok(candidates.into_iter().filter(|(d, _)| equality.matches(d)).collect())
+            }
+            CandidateFilter::Compiled(filter) => {
+                let docs: Vec<Document> = candidates.iter().map(|(d, _)| d.clone()).collect();
+                let batch = docs_to_arrow(&docs).map_err(|e| e.to_string())?;
+                let matched = filter.apply(&batch).map_err(|e| e.to_string())?;
+                let matched_ids: HashSet<String> = match matched.column(0).as_any().downcast_ref::<arrow::array::StringArray>() {
+                    Some(id_col) => (0..id_col.len()).map(|i| id_col.value(i).to_string()).collect(),
+                    None => HashSet::new(),
+                };
+                Ok(candidates.into_iter().filter(|(d, _)| matched_ids.contains(&d.id)).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use std::fs;
+
+    #[tokio::test]
+    async fn slow_sql_stage_yields_partial_ann_results() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_hybrid_timeout");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).unwrap();
+        for i in 0..6 {
+            let category = if i % 2 == 0 { "AI" } else { "DB" }.to_string();
+            storage.insert_doc(Document { category, ..test_doc(&format!("doc{}", i), vec![i as f32, 0.0]) }, "col").unwrap();
+        }
+        let engine = QueryEngine::with_sql_fallback(Arc::new(storage), "col");
+        let slow_stage = |candidates: Vec<(Document, bool)>| {
+            std::thread::sleep(Duration::from_millis(300));
+            Ok(candidates.into_iter().filter(|(d, _)| d.category == "AI").collect())
+        };
+
+        let outcome = engine.run_hybrid(&[0.0, 0.0], 3, Some(Duration::from_millis(20)), Some(slow_stage)).await.unwrap();
+        assert!(outcome.partial);
+        let ids: Vec<&str> = outcome.docs.iter().map(|(d, _)| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["doc0", "doc1", "doc2"], "unfiltered ANN ranking");
+
+        // Without a deadline the same stage completes and filters
+        let outcome = engine.run_hybrid(&[0.0, 0.0], 3, None, Some(slow_stage)).await.unwrap();
+        assert!(!outcome.partial);
+        let ids: Vec<&str> = outcome.docs.iter().map(|(d, _)| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["doc0", "doc2", "doc4"]);
+
+        let outcome = engine.hybrid_query_with_timeout("category = 'DB'", &[0.0, 0.0], 2, Some(Duration::from_secs(5))).await.unwrap();
+        assert!(!outcome.partial);
+        assert_eq!(outcome.docs.len(), 2);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn hybrid_queries_share_the_cached_index() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_hybrid_index_cache");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Arc::new(Storage::open(temp_dir.to_str().unwrap()).unwrap());
+        let doc = |i: usize| test_doc(&format!("doc{}", i), vec![i as f32, 0.0]);
+        for i in 0..4 {
+            storage.insert_doc(doc(i), "col").unwrap();
+        }
+        let engine = QueryEngine::with_sql_fallback(storage.clone(), "col");
+        let ids = |outcome: HybridOutcome| outcome.docs.into_iter().map(|(d, _)| d.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(engine.hybrid_query_with_timeout("category = 'AI'", &[0.0, 0.0], 2, None).await.unwrap()), ["doc0", "doc1"]);
+        engine.hybrid_query_with_timeout("category = 'AI'", &[3.0, 0.0], 2, None).await.unwrap();
+        assert_eq!(storage.index_builds(), 1);
+
+        // A new document joins the cached index; replacing one invalidates it and the next query
+        // rebuilds it once
+        storage.insert_doc(doc(10), "col").unwrap();
+        assert_eq!(ids(engine.hybrid_query_with_timeout("category = 'AI'", &[10.0, 0.0], 1, None).await.unwrap()), ["doc10"]);
+        assert_eq!(storage.index_builds(), 1);
+        storage.insert_doc(Document { vector: vec![20.0, 0.0], ..doc(3) }, "col").unwrap();
+        assert_eq!(ids(engine.hybrid_query_with_timeout("category = 'AI'", &[20.0, 0.0], 1, None).await.unwrap()), ["doc3"]);
+        assert_eq!(storage.index_builds(), 2);
+
+        // An explicit rebuild replaces the cached index, which later searches reuse
+        assert_eq!(storage.rebuild_index("col").unwrap().len(), 5);
+        storage.vector_search("col", &[1.0, 0.0], 1).unwrap();
+        engine.hybrid_query_with_timeout("category = 'AI'", &[1.0, 0.0], 1, None).await.unwrap();
+        assert_eq!(storage.index_builds(), 3);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn filtered_results_keep_ann_distance_order() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_hybrid_pushdown");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Arc::new(Storage::open(temp_dir.to_str().unwrap()).unwrap());
+        // Inserted far-first, so id or insertion order would rank "far" ahead of "near"
+        for (id, category, x) in [("a_far", "AI", 9.0), ("b_db_nearest", "DB", 0.0), ("c_near", "AI", 1.0), ("d_mid", "AI", 4.0)] {
+            storage.insert_doc(Document { category: category.to_string(), ..test_doc(id, vec![x, 0.0]) }, "col").unwrap();
+        }
+        let ids = |docs: Vec<(Document, bool)>| docs.into_iter().map(|(d, _)| d.id).collect::<Vec<_>>();
+
+        let fallback = QueryEngine::with_sql_fallback(storage.clone(), "col");
+        assert_eq!(ids(fallback.hybrid_query("category = 'AI'", &[0.0, 0.0], 3).await.unwrap()), ["c_near", "d_mid", "a_far"]);
+
+        // Same through the compiled DataFusion filter
+        let engine = QueryEngine::new(storage, "col").await.unwrap();
+        assert_eq!(ids(engine.hybrid_query("category = 'AI'", &[0.0, 0.0], 3).await.unwrap()), ["c_near", "d_mid", "a_far"]);
+        assert_eq!(ids(engine.hybrid_query("category = 'AI'", &[10.0, 0.0], 2).await.unwrap()), ["a_far", "d_mid"]);
+
+        let _ = fs::remove_dir_all(temp_dir);
     }
 }
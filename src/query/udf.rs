@@ -0,0 +1,169 @@
+//! Scalar SQL functions registered on every `QueryEngine`
+//!
+//! `cosine_sim(vector, query)` scores each row against a query vector so hybrid search can be
+//! written as plain SQL: `SELECT id FROM docs ORDER BY cosine_sim(vector, '[0.1, 0.9]') DESC LIMIT 5`.
+//! Either argument may be a stringified vector (the `vector` column of `docs` is a JSON array
+//! string) or an Arrow list of numbers, e.g. `make_array(0.1, 0.9)`. Rows whose vector is NULL,
+//! unparseable or of a different dimension than the query score NULL rather than failing the query.
+
+use arrow::array::{Array, ArrayRef, FixedSizeListArray, Float32Array, Float64Array, LargeListArray, LargeStringArray, ListArray, StringArray};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::{ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, Volatility};
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::indexing::DistanceMetric;
+
+/// SQL name of the cosine similarity function
+pub const COSINE_SIM: &str = "cosine_sim";
+
+/// Cosine similarity of two vectors in [-1, 1]; `None` when the dimensions differ or are zero
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> Option<f64> {
+    if a.is_empty() || a.len() != b.len() {
+        return None;
+    }
+    Some(DistanceMetric::Cosine.to_similarity(DistanceMetric::Cosine.distance(a, b)) as f64)
+}
+
+/// `cosine_sim(vector, query) -> DOUBLE`
+#[derive(Debug)]
+pub struct CosineSim {
+    signature: Signature,
+}
+
+impl CosineSim {
+    pub fn new() -> Self {
+        Self { signature: Signature::any(2, Volatility::Immutable) }
+    }
+}
+
+impl Default for CosineSim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The UDF to pass to `SessionContext::register_udf`
+pub fn cosine_sim_udf() -> ScalarUDF {
+    ScalarUDF::new_from_impl(CosineSim::new())
+}
+
+/// Vector stored in row `row` of `array`, if it holds one
+fn vector_at(array: &ArrayRef, row: usize) -> Result<Option<Vec<f32>>> {
+    if array.is_null(row) {
+        return Ok(None);
+    }
+    let any = array.as_any();
+    if let Some(strings) = any.downcast_ref::<StringArray>() {
+        return Ok(serde_json::from_str(strings.value(row)).ok());
+    }
+    if let Some(strings) = any.downcast_ref::<LargeStringArray>() {
+        return Ok(serde_json::from_str(strings.value(row)).ok());
+    }
+    let values = if let Some(list) = any.downcast_ref::<ListArray>() {
+        list.value(row)
+    } else if let Some(list) = any.downcast_ref::<LargeListArray>() {
+        list.value(row)
+    } else if let Some(list) = any.downcast_ref::<FixedSizeListArray>() {
+        list.value(row)
+    } else {
+        return Err(DataFusionError::Execution(format!(
+            "{} expects vectors as JSON strings or numeric lists, got {}",
+            COSINE_SIM,
+            array.data_type()
+        )));
+    };
+    let floats = cast(&values, &DataType::Float32)?;
+    let floats = floats
+        .as_any()
+        .downcast_ref::<Float32Array>()
+        .ok_or_else(|| DataFusionError::Execution(format!("{} could not read list values as floats", COSINE_SIM)))?;
+    if floats.null_count() > 0 {
+        return Ok(None);
+    }
+    Ok(Some(floats.values().to_vec()))
+}
+
+impl ScalarUDFImpl for CosineSim {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        COSINE_SIM
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(DataType::Float64)
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        // Literal arguments arrive as scalars; broadcast them to the column's length
+        let rows = args
+            .iter()
+            .find_map(|arg| match arg {
+                ColumnarValue::Array(array) => Some(array.len()),
+                ColumnarValue::Scalar(_) => None,
+            })
+            .unwrap_or(1);
+        let arrays = args.iter().map(|arg| arg.clone().into_array(rows)).collect::<Result<Vec<_>>>()?;
+        let [vectors, queries] = arrays.as_slice() else {
+            return Err(DataFusionError::Execution(format!("{} takes 2 arguments, got {}", COSINE_SIM, arrays.len())));
+        };
+
+        // A literal query is parsed once instead of per row
+        let constant_query = match &args[1] {
+            ColumnarValue::Scalar(_) => Some(vector_at(queries, 0)?),
+            ColumnarValue::Array(_) => None,
+        };
+        let mut scores = Vec::with_capacity(rows);
+        for row in 0..rows {
+            let query = match &constant_query {
+                Some(query) => query.clone(),
+                None => vector_at(queries, row)?,
+            };
+            let score = match (vector_at(vectors, row)?, query) {
+                (Some(vector), Some(query)) => cosine_similarity(&vector, &query),
+                _ => None,
+            };
+            scores.push(score);
+        }
+        Ok(ColumnarValue::Array(Arc::new(Float64Array::from(scores))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cosine_sim_scores_string_and_list_vectors() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), Some(1.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), Some(0.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), None);
+
+        let vectors: ArrayRef = Arc::new(StringArray::from(vec![Some("[1.0, 0.0]"), Some("[0.0, 1.0]"), Some("not a vector"), None, Some("[1.0]")]));
+        let query = ColumnarValue::Scalar(datafusion::common::ScalarValue::Utf8(Some("[1.0, 1.0]".to_string())));
+        let scored = CosineSim::new().invoke(&[ColumnarValue::Array(vectors), query]).unwrap().into_array(5).unwrap();
+        let scored = scored.as_any().downcast_ref::<Float64Array>().unwrap();
+        let expected = std::f64::consts::FRAC_1_SQRT_2;
+        assert!((scored.value(0) - expected).abs() < 1e-6);
+        assert!((scored.value(1) - expected).abs() < 1e-6);
+        assert!(scored.is_null(2) && scored.is_null(3) && scored.is_null(4));
+
+        let lists: ArrayRef = Arc::new(ListArray::from_iter_primitive::<arrow::datatypes::Float64Type, _, _>(vec![
+            Some(vec![Some(0.0), Some(2.0)]),
+            Some(vec![Some(3.0), Some(0.0)]),
+        ]));
+        let query: ArrayRef = Arc::new(StringArray::from(vec!["[0.0, 1.0]", "[0.0, 1.0]"]));
+        let scored = CosineSim::new().invoke(&[ColumnarValue::Array(lists), ColumnarValue::Array(query)]).unwrap().into_array(2).unwrap();
+        let scored = scored.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!((scored.value(0), scored.value(1)), (1.0, 0.0));
+    }
+}
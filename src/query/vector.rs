@@ -1,28 +1,875 @@
-use crate::indexing::VectorIndex;
-use crate::storage::Storage;
+use crate::cancel;
+use crate::indexing::{DistanceMetric, FlatIndex, IndexBackend, ScoreKind};
+use crate::storage::{Storage, DEFAULT_VECTOR_FIELD};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
 use tracing::{info, debug, instrument};
 
+/// One hit of a multi-collection search, tagged with the collection it came from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CollectionHit {
+    pub id: String,
+    #[serde(rename = "_collection")]
+    pub collection: String,
+    pub score: f32,
+}
+
+/// Vector search results plus the index backend and metric that produced them
+#[derive(Debug, Clone)]
+pub struct VectorSearchOutcome {
+    pub ids: Vec<String>,
+    /// One score per ID, read according to `score_kind`
+    pub scores: Vec<f32>,
+    pub score_kind: ScoreKind,
+    pub index_backend: IndexBackend,
+    pub metric: String,
+}
+
+/// Documents within a radius of a query vector (see `Storage::vector_range_search`)
+#[derive(Debug, Clone)]
+pub struct VectorRangeOutcome {
+    /// How many documents lie within the radius
+    pub count: usize,
+    /// Closest-first (id, raw distance) pairs, at most the requested number
+    pub hits: Vec<(String, f32)>,
+    pub index_backend: IndexBackend,
+    pub metric: String,
+    /// Radius the count and hits were taken at: the requested one unless `min_results` relaxed it
+    pub radius: f32,
+    pub relaxed: bool,
+}
+
+/// Factor the step a range query's radius grows by is multiplied with after each relaxation
+pub const RADIUS_RELAX_FACTOR: f32 = 2.0;
+/// Relaxation steps before the radius is dropped altogether (every document counts)
+const MAX_RADIUS_RELAX_STEPS: usize = 64;
+
+/// `Err` unless `radius` is finite and, outside `dot_product` (where distances are negated inner
+/// products and so usually negative), non-negative
+pub fn check_radius(radius: f32, metric: DistanceMetric) -> Result<(), String> {
+    if !radius.is_finite() {
+        return Err(format!("radius must be finite, got {}", radius));
+    }
+    if radius < 0.0 && metric != DistanceMetric::DotProduct {
+        return Err(format!("radius must be non-negative for {} distances, got {}", metric.as_str(), radius));
+    }
+    Ok(())
+}
+
+/// ANN candidates fetched per requested result when boosting, so a boosted document ranked just
+/// outside the plain top_k can still move into it
+pub const BOOST_CANDIDATE_FACTOR: usize = 4;
+
+/// Business boost on vector scores: each candidate gains `weight * normalized(metadata[field])`,
+/// where the field's numeric values are min-max normalized to [0, 1] across the candidates.
+/// Candidates without a numeric value (or when all values are equal) gain nothing.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScoreBoost {
+    pub field: String,
+    pub weight: f32,
+}
+
+impl ScoreBoost {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.field.trim().is_empty() {
+            return Err("boost field must not be empty".to_string());
+        }
+        if !self.weight.is_finite() {
+            return Err(format!("boost weight must be finite, got {}", self.weight));
+        }
+        Ok(())
+    }
+}
+
+/// Tag-expansion weight used when a request doesn't set one
+pub const DEFAULT_EXPAND_WEIGHT: f32 = 0.5;
+
+/// How a vector search shapes its query and results, shared by the gRPC and REST endpoints
+/// (see `Storage::vector_search_with_options`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorSearchOptions {
+    /// Embedding field to search; `DEFAULT_VECTOR_FIELD` is the primary `vector`
+    pub field: String,
+    pub score_kind: ScoreKind,
+    /// Blend the query toward these tags' centroids before searching
+    pub expand_tags: Vec<String>,
+    pub expand_weight: f32,
+    pub boost: Option<ScoreBoost>,
+    /// Keep only the best result per distinct document text
+    pub dedupe_text: bool,
+}
+
+impl Default for VectorSearchOptions {
+    fn default() -> Self {
+        Self {
+            field: DEFAULT_VECTOR_FIELD.to_string(),
+            score_kind: ScoreKind::default(),
+            expand_tags: Vec::new(),
+            expand_weight: DEFAULT_EXPAND_WEIGHT,
+            boost: None,
+            dedupe_text: false,
+        }
+    }
+}
+
+/// Min-max normalize `values` to [0, 1]; missing values, and all values when they are equal, map to 0
+fn min_max_normalize(values: &[Option<f64>]) -> Vec<f32> {
+    let present = values.iter().flatten();
+    let min = present.clone().copied().fold(f64::INFINITY, f64::min);
+    let max = present.copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| match value {
+            Some(v) if max > min => ((v - min) / (max - min)) as f32,
+            _ => 0.0,
+        })
+        .collect()
+}
+
+/// Approximate (cached index) and exact results for the same query, side by side
+#[derive(Debug, Clone)]
+pub struct VectorSearchComparison {
+    pub approximate: VectorSearchOutcome,
+    pub exact: VectorSearchOutcome,
+    /// recall@k of the approximate results against the exact ones, in [0, 1]
+    pub recall: f32,
+}
+
+/// Fraction of the `exact` IDs that also appear in `approximate` (1 when `exact` is empty)
+pub fn recall_at_k(approximate: &[String], exact: &[String]) -> f32 {
+    if exact.is_empty() {
+        return 1.0;
+    }
+    let found: HashSet<&String> = approximate.iter().collect();
+    exact.iter().filter(|id| found.contains(id)).count() as f32 / exact.len() as f32
+}
+
+/// Hash of a document's `text`, used to collapse exact-duplicate search results
+pub fn text_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
 impl Storage {
     /// Vector search helper to keep vector query logic in a dedicated module.
     #[instrument(skip(self, query_vector), fields(collection_id, top_k))]
     pub fn vector_search(&self, collection_id: &str, query_vector: &[f32], top_k: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(self.vector_search_detailed(collection_id, query_vector, top_k)?.ids)
+    }
+
+    /// Vector search that also reports which backend (flat/hnsw) and metric served the query
+    #[instrument(skip(self, query_vector), fields(collection_id, top_k))]
+    pub fn vector_search_detailed(&self, collection_id: &str, query_vector: &[f32], top_k: usize) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>> {
+        self.vector_search_scored(collection_id, query_vector, top_k, ScoreKind::Distance)
+    }
+
+    /// Vector search returning per-result scores as raw distances or canonical similarities.
+    /// Similarities are comparable across collections with different metrics (higher = closer).
+    #[instrument(skip(self, query_vector), fields(collection_id, top_k))]
+    pub fn vector_search_scored(
+        &self,
+        collection_id: &str,
+        query_vector: &[f32],
+        top_k: usize,
+        score_kind: ScoreKind,
+    ) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>> {
+        self.vector_search_in_field(collection_id, DEFAULT_VECTOR_FIELD, query_vector, top_k, score_kind)
+    }
+
+    /// Vector search after blending the query toward the centroids of `expand_tags`
+    /// (see `Storage::expand_query_with_tags`); without tags this is a plain search.
+    #[instrument(skip(self, query_vector, expand_tags), fields(collection_id, top_k))]
+    pub fn vector_search_expanded(
+        &self,
+        collection_id: &str,
+        field: &str,
+        query_vector: &[f32],
+        expand_tags: &[String],
+        expand_weight: f32,
+        top_k: usize,
+        score_kind: ScoreKind,
+    ) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>> {
+        if expand_tags.is_empty() {
+            return self.vector_search_in_field(collection_id, field, query_vector, top_k, score_kind);
+        }
+        let expanded = self.expand_query_with_tags(collection_id, query_vector, expand_tags, expand_weight)?;
+        self.vector_search_in_field(collection_id, field, &expanded, top_k, score_kind)
+    }
+
+    /// Vector search with tag expansion, score boosting and text deduplication applied as
+    /// `options` ask, in that order: the expanded query is searched, boosted candidates
+    /// re-ranked, then duplicates of the same text collapsed
+    #[instrument(skip(self, query_vector, options), fields(collection_id, top_k))]
+    pub fn vector_search_with_options(
+        &self,
+        collection_id: &str,
+        query_vector: &[f32],
+        top_k: usize,
+        options: &VectorSearchOptions,
+    ) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>> {
+        if let Some(boost) = &options.boost {
+            boost.validate()?;
+        }
+        let ann = |k: usize| {
+            self.vector_search_expanded(
+                collection_id,
+                &options.field,
+                query_vector,
+                &options.expand_tags,
+                options.expand_weight,
+                k,
+                options.score_kind,
+            )
+        };
+        let search = |k: usize| match &options.boost {
+            Some(boost) => self.vector_search_boosted(collection_id, k, boost, ann),
+            None => ann(k),
+        };
+        if options.dedupe_text {
+            self.vector_search_distinct_text(collection_id, top_k, search)
+        } else {
+            search(top_k)
+        }
+    }
+
+    /// Vector search over several collections: each collection is searched with its own index
+    /// for `top_k`, then all hits are re-ranked together and the global `top_k` returned.
+    /// Scores are only comparable across collections with the same metric unless
+    /// `score_kind` is `Similarity`.
+    #[instrument(skip(self, query_vector), fields(collections = collection_ids.len(), top_k))]
+    pub fn vector_search_collections(
+        &self,
+        collection_ids: &[String],
+        query_vector: &[f32],
+        top_k: usize,
+        score_kind: ScoreKind,
+    ) -> Result<Vec<CollectionHit>, Box<dyn std::error::Error>> {
+        let mut hits = Vec::new();
+        for collection_id in collection_ids {
+            cancel::check()?;
+            let outcome = self.vector_search_scored(collection_id, query_vector, top_k, score_kind)?;
+            hits.extend(outcome.ids.into_iter().zip(outcome.scores).map(|(id, score)| CollectionHit {
+                id,
+                collection: collection_id.clone(),
+                score,
+            }));
+        }
+        hits.sort_by(|a, b| score_kind.best_first(a.score, b.score));
+        hits.truncate(top_k);
+
+        info!(collections = collection_ids.len(), results_count = hits.len(), "Multi-collection vector search completed");
+        Ok(hits)
+    }
+
+    /// Run `search` (given a candidate count) and keep only the best-scoring result per distinct
+    /// document `text`, widening the candidate count until `top_k` distinct results remain or the
+    /// collection is exhausted. Results without a stored document are never collapsed.
+    #[instrument(skip(self, search), fields(collection_id, top_k))]
+    pub fn vector_search_distinct_text(
+        &self,
+        collection_id: &str,
+        top_k: usize,
+        mut search: impl FnMut(usize) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>>,
+    ) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>> {
+        let mut candidates = top_k.saturating_mul(2).max(1);
+        loop {
+            let mut outcome = search(candidates)?;
+            let exhausted = outcome.ids.len() < candidates;
+            let fetched = outcome.ids.len();
+
+            // Results arrive best first, so the first occurrence of a text is the one to keep
+            let mut seen = HashSet::new();
+            let (ids, scores): (Vec<String>, Vec<f32>) = outcome.ids
+                .into_iter()
+                .zip(outcome.scores)
+                .filter(|(id, _)| match self.get_doc(collection_id, id) {
+                    Ok(doc) => seen.insert(text_hash(&doc.text)),
+                    Err(_) => true,
+                })
+                .take(top_k)
+                .unzip();
+
+            if ids.len() >= top_k || exhausted {
+                debug!(
+                    collection_id = %collection_id,
+                    candidates = fetched,
+                    distinct = ids.len(),
+                    "Search results deduplicated by text"
+                );
+                outcome.ids = ids;
+                outcome.scores = scores;
+                return Ok(outcome);
+            }
+            candidates = candidates.saturating_mul(2);
+        }
+    }
+
+    /// Run `search` (given a candidate count) for `top_k * BOOST_CANDIDATE_FACTOR` candidates, add
+    /// `boost` to their scores and re-rank: similarities grow by the boost, distances shrink by it.
+    #[instrument(skip(self, search), fields(collection_id, top_k, boost_field = %boost.field))]
+    pub fn vector_search_boosted(
+        &self,
+        collection_id: &str,
+        top_k: usize,
+        boost: &ScoreBoost,
+        mut search: impl FnMut(usize) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>>,
+    ) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>> {
+        boost.validate()?;
+        let mut outcome = search(top_k.saturating_mul(BOOST_CANDIDATE_FACTOR))?;
+        let values: Vec<Option<f64>> = outcome
+            .ids
+            .iter()
+            .map(|id| {
+                self.get_doc(collection_id, id)
+                    .ok()
+                    .and_then(|doc| doc.metadata.get(&boost.field).and_then(|v| v.as_f64()))
+            })
+            .collect();
+        let score_kind = outcome.score_kind;
+        let mut ranked: Vec<(String, f32)> = outcome
+            .ids
+            .into_iter()
+            .zip(outcome.scores)
+            .zip(min_max_normalize(&values))
+            .map(|((id, score), normalized)| {
+                let amount = boost.weight * normalized;
+                let boosted = match score_kind {
+                    ScoreKind::Similarity => score + amount,
+                    ScoreKind::Distance => score - amount,
+                };
+                (id, boosted)
+            })
+            .collect();
+        ranked.sort_by(|a, b| score_kind.best_first(a.1, b.1));
+        ranked.truncate(top_k);
+
         debug!(
             collection_id = %collection_id,
+            candidates = values.len(),
+            with_value = values.iter().filter(|v| v.is_some()).count(),
+            "Search results boosted"
+        );
+        (outcome.ids, outcome.scores) = ranked.into_iter().unzip();
+        Ok(outcome)
+    }
+
+    /// Vector search over one named embedding field (e.g. "title"); each field has its own index.
+    /// `DEFAULT_VECTOR_FIELD` searches the document's primary `vector`.
+    #[instrument(skip(self, query_vector), fields(collection_id, field, top_k))]
+    pub fn vector_search_in_field(
+        &self,
+        collection_id: &str,
+        field: &str,
+        query_vector: &[f32],
+        top_k: usize,
+        score_kind: ScoreKind,
+    ) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>> {
+        debug!(
+            collection_id = %collection_id,
+            field = %field,
             top_k = top_k,
             vector_len = query_vector.len(),
+            score_kind = score_kind.as_str(),
             "Starting vector search"
         );
         
-        let vectors = self.get_vectors_in_collection(collection_id)?;
-        let index = VectorIndex::build_from_vectors(vectors);
-        let results = index.search(query_vector, top_k);
+        self.check_vector("query", query_vector)?;
+        let query_vector = self.prepare_query_vector(collection_id, query_vector)?;
+        let index = self.cached_field_index(collection_id, field)?;
+        let metric = index.metric();
+        let (ids, scores): (Vec<String>, Vec<f32>) = index
+            .search_with_distances(&query_vector, top_k)
+            .into_iter()
+            .map(|(id, distance)| (id, score_kind.apply(metric, distance)))
+            .unzip();
         
         info!(
             collection_id = %collection_id,
-            results_count = results.len(),
+            results_count = ids.len(),
+            index_backend = index.backend().as_str(),
             "Vector search completed"
         );
         
-        Ok(results)
+        Ok(VectorSearchOutcome {
+            ids,
+            scores,
+            score_kind,
+            index_backend: index.backend(),
+            metric: metric.as_str().to_string(),
+        })
+    }
+
+    /// Exact k-NN over one embedding field: a brute-force scan of every stored vector, bypassing
+    /// the cached (possibly approximate) index. Each query loads the field's vectors and computes
+    /// n distances, so cost is O(n·d) per call with nothing reused between calls; intended for
+    /// ground truth and recall checks rather than serving traffic on large collections.
+    #[instrument(skip(self, query_vector), fields(collection_id, field, top_k))]
+    pub fn vector_search_exact(
+        &self,
+        collection_id: &str,
+        field: &str,
+        query_vector: &[f32],
+        top_k: usize,
+        score_kind: ScoreKind,
+    ) -> Result<VectorSearchOutcome, Box<dyn std::error::Error>> {
+        self.check_vector("query", query_vector)?;
+        let query_vector = self.prepare_query_vector(collection_id, query_vector)?;
+        let vectors = self.get_field_vectors_in_collection(collection_id, field)?;
+        cancel::check()?;
+        let scanned = vectors.len();
+        let index = FlatIndex::with_metric(vectors, self.collection_metric(collection_id)?);
+        let metric = index.metric();
+        let (ids, scores): (Vec<String>, Vec<f32>) = index
+            .search_with_distances(&query_vector, top_k)
+            .into_iter()
+            .map(|(id, distance)| (id, score_kind.apply(metric, distance)))
+            .unzip();
+
+        info!(
+            collection_id = %collection_id,
+            field = %field,
+            scanned = scanned,
+            results_count = ids.len(),
+            "Exact vector search completed"
+        );
+
+        Ok(VectorSearchOutcome {
+            ids,
+            scores,
+            score_kind,
+            index_backend: IndexBackend::Flat,
+            metric: metric.as_str().to_string(),
+        })
+    }
+
+    /// Run the same query through the cached index and an exact scan and report recall@k of the
+    /// former, for tuning the approximate index. Costs an O(n·d) scan on top of the search.
+    #[instrument(skip(self, query_vector), fields(collection_id, field, top_k))]
+    pub fn vector_search_compare(
+        &self,
+        collection_id: &str,
+        field: &str,
+        query_vector: &[f32],
+        top_k: usize,
+        score_kind: ScoreKind,
+    ) -> Result<VectorSearchComparison, Box<dyn std::error::Error>> {
+        let approximate = self.vector_search_in_field(collection_id, field, query_vector, top_k, score_kind)?;
+        let exact = self.vector_search_exact(collection_id, field, query_vector, top_k, score_kind)?;
+        let recall = recall_at_k(&approximate.ids, &exact.ids);
+
+        info!(
+            collection_id = %collection_id,
+            field = %field,
+            index_backend = approximate.index_backend.as_str(),
+            recall = recall,
+            "Approximate vs exact search compared"
+        );
+        Ok(VectorSearchComparison { approximate, exact, recall })
+    }
+
+    /// Range query: how many documents of a collection's `field` lie within raw distance
+    /// `radius` (inclusive) of the query, plus the closest `max_hits` of them.
+    /// Served by the cached index, scanning all its points so the count is exact. The radius may
+    /// be negative only on `dot_product` collections, whose distance is the negated inner product
+    /// (radius -0.8 keeps documents with a dot product of at least 0.8).
+    #[instrument(skip(self, query_vector), fields(collection_id, field, radius))]
+    pub fn vector_range_search(
+        &self,
+        collection_id: &str,
+        field: &str,
+        query_vector: &[f32],
+        radius: f32,
+        max_hits: usize,
+    ) -> Result<VectorRangeOutcome, Box<dyn std::error::Error>> {
+        self.vector_range_search_relaxed(collection_id, field, query_vector, radius, max_hits, 0)
+    }
+
+    /// Range query that backfills to at least `min_results` documents: while fewer lie within the
+    /// radius, it grows by a step that starts at `|radius|` (`f32::EPSILON` for zero) and is
+    /// multiplied by `RADIUS_RELAX_FACTOR` each time, until enough do or the whole collection is
+    /// inside. A positive radius thus doubles, and a negative `dot_product` one climbs through
+    /// zero toward +∞. The outcome reports the radius used; hits farther than the requested
+    /// `radius` are the relaxed ones. Each step is an O(n·d) scan.
+    #[instrument(skip(self, query_vector), fields(collection_id, field, radius, min_results))]
+    pub fn vector_range_search_relaxed(
+        &self,
+        collection_id: &str,
+        field: &str,
+        query_vector: &[f32],
+        radius: f32,
+        max_hits: usize,
+        min_results: usize,
+    ) -> Result<VectorRangeOutcome, Box<dyn std::error::Error>> {
+        self.check_vector("query", query_vector)?;
+        check_radius(radius, self.collection_metric(collection_id)?)?;
+        let query_vector = self.prepare_query_vector(collection_id, query_vector)?;
+        let index = self.cached_field_index(collection_id, field)?;
+        let wanted = min_results.min(index.len());
+        let mut effective = radius;
+        let mut step = radius.abs().max(f32::EPSILON);
+        let mut hits = index.within_radius(&query_vector, effective);
+        let mut steps = 0;
+        while hits.len() < wanted {
+            cancel::check()?;
+            steps += 1;
+            effective = if steps > MAX_RADIUS_RELAX_STEPS {
+                f32::INFINITY
+            } else {
+                effective + step
+            };
+            step *= RADIUS_RELAX_FACTOR;
+            hits = index.within_radius(&query_vector, effective);
+            if effective.is_infinite() {
+                // Report the farthest distance rather than an unbounded radius
+                effective = hits.last().map_or(radius, |(_, distance)| distance.max(radius));
+                break;
+            }
+        }
+        if steps > 0 {
+            debug!(collection_id = %collection_id, radius = radius, relaxed_to = effective, steps = steps, "Range search radius relaxed to reach min_results");
+        }
+        let count = hits.len();
+        hits.truncate(max_hits);
+
+        info!(collection_id = %collection_id, field = %field, radius = effective, count = count, "Range search completed");
+        Ok(VectorRangeOutcome {
+            count,
+            hits,
+            index_backend: index.backend(),
+            metric: index.metric_name().to_string(),
+            radius: effective,
+            relaxed: steps > 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recall_at_k, ScoreBoost};
+    use crate::cancel::{CancelToken, Cancelled};
+    use crate::config::StorageConfig;
+    use crate::indexing::{DistanceMetric, IndexBackend, ScoreKind, FLAT_INDEX_THRESHOLD};
+    use crate::storage::{test_doc, Document, Storage, DEFAULT_VECTOR_FIELD};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn insert_n(storage: &Storage, collection_id: &str, n: usize) {
+        let docs = (0..n)
+            .map(|i| test_doc(&format!("doc{}", i), vec![i as f32, 1.0, 0.5]))
+            .collect();
+        storage.insert_docs(docs, collection_id).expect("insert docs");
+    }
+
+    #[test]
+    fn search_reports_index_backend_and_metric() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_index_backend");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        insert_n(&storage, "tiny", 5);
+        insert_n(&storage, "large", FLAT_INDEX_THRESHOLD + 10);
+
+        let tiny = storage.vector_search_detailed("tiny", &[1.0, 1.0, 0.5], 3).unwrap();
+        assert_eq!(tiny.index_backend, IndexBackend::Flat);
+        assert_eq!(tiny.metric, "l2");
+        assert_eq!(tiny.ids[0], "doc1");
+
+        let large = storage.vector_search_detailed("large", &[1.0, 1.0, 0.5], 3).unwrap();
+        assert_eq!(large.index_backend, IndexBackend::Hnsw);
+        assert_eq!(large.metric, "l2");
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn boost_lets_a_higher_rated_doc_outrank_a_closer_one() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_search_boost");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let docs = [("recent_close", 1.0, 2024, 2.0), ("older_rated", 2.0, 2019, 4.8), ("unrated", 3.0, 2023, f64::NAN)]
+            .into_iter()
+            .map(|(id, x, year, rating)| Document {
+                metadata: if rating.is_nan() {
+                    serde_json::json!({"year": year})
+                } else {
+                    serde_json::json!({"year": year, "rating": rating})
+                },
+                ..test_doc(id, vec![x, 0.0])
+            })
+            .collect();
+        storage.insert_docs(docs, "col").unwrap();
+        let query = [0.0, 0.0];
+        let search = |k: usize| storage.vector_search_scored("col", &query, k, ScoreKind::Similarity);
+
+        assert_eq!(search(2).unwrap().ids, ["recent_close", "older_rated"]);
+        let boost = ScoreBoost { field: "rating".to_string(), weight: 1.0 };
+        let boosted = storage.vector_search_boosted("col", 2, &boost, search).unwrap();
+        assert_eq!(boosted.ids, ["older_rated", "recent_close"]);
+        // 1 / (1 + 2) plus the full boost; the lowest rating normalizes to 0
+        assert!((boosted.scores[0] - (1.0 / 3.0 + 1.0)).abs() < 1e-6);
+        assert!((boosted.scores[1] - 0.5).abs() < 1e-6);
+
+        // Distances shrink by the boost instead
+        let distance = |k: usize| storage.vector_search_scored("col", &query, k, ScoreKind::Distance);
+        let boost = ScoreBoost { field: "rating".to_string(), weight: 1.5 };
+        assert_eq!(storage.vector_search_boosted("col", 1, &boost, distance).unwrap().ids, ["older_rated"]);
+
+        let invalid = ScoreBoost { field: " ".to_string(), weight: 1.0 };
+        assert!(storage.vector_search_boosted("col", 1, &invalid, distance).is_err());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn compare_reports_approximate_and_exact_results_with_recall() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_search_compare");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        insert_n(&storage, "col", FLAT_INDEX_THRESHOLD + 50);
+
+        let query = [40.0, 1.0, 0.5];
+        let comparison = storage
+            .vector_search_compare("col", DEFAULT_VECTOR_FIELD, &query, 5, ScoreKind::Distance)
+            .unwrap();
+        assert_eq!(comparison.approximate.index_backend, IndexBackend::Hnsw);
+        assert_eq!(comparison.exact.index_backend, IndexBackend::Flat);
+        assert_eq!(comparison.approximate.ids.len(), 5);
+        assert_eq!(comparison.exact.ids.len(), 5);
+        assert_eq!(comparison.exact.ids[0], "doc40");
+        assert!((0.0..=1.0).contains(&comparison.recall));
+        assert_eq!(comparison.recall, recall_at_k(&comparison.approximate.ids, &comparison.exact.ids));
+
+        let ids = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(recall_at_k(&ids(&["a", "x"]), &ids(&["a", "b"])), 0.5);
+        assert_eq!(recall_at_k(&[], &[]), 1.0);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn range_search_counts_docs_within_radius_on_both_backends() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_range_search");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        insert_n(&storage, "tiny", 10);
+        insert_n(&storage, "large", FLAT_INDEX_THRESHOLD + 10);
+
+        // doc{i} lies at L2 distance exactly i from the query
+        for (collection_id, backend) in [("tiny", IndexBackend::Flat), ("large", IndexBackend::Hnsw)] {
+            let outcome = storage.vector_range_search(collection_id, DEFAULT_VECTOR_FIELD, &[0.0, 1.0, 0.5], 4.5, 3).unwrap();
+            assert_eq!(outcome.index_backend, backend);
+            assert_eq!(outcome.count, 5);
+            let ids: Vec<&str> = outcome.hits.iter().map(|(id, _)| id.as_str()).collect();
+            assert_eq!(ids, vec!["doc0", "doc1", "doc2"]);
+            assert_eq!(outcome.hits[2].1, 2.0);
+
+            // The radius is inclusive
+            assert_eq!(storage.vector_range_search(collection_id, DEFAULT_VECTOR_FIELD, &[0.0, 1.0, 0.5], 4.0, 0).unwrap().count, 5);
+        }
+        assert!(storage.vector_range_search("tiny", DEFAULT_VECTOR_FIELD, &[0.0, 1.0, 0.5], -1.0, 3).is_err());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn dot_product_range_search_takes_negative_radii() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_range_search_dot");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let config = StorageConfig { default_metric: DistanceMetric::DotProduct, read_only: false, ..StorageConfig::from_env() };
+        let storage = Storage::open_with_config(temp_dir.to_str().unwrap(), &config).expect("open storage");
+        insert_n(&storage, "dot", 10);
+        // Ad-hoc collections report the default metric as their vector config, as they rank by it
+        assert_eq!(storage.record_vector_dim("dot", 3).unwrap().metric, DistanceMetric::DotProduct);
+
+        // doc{i}·[0.1, 0, 0] = 0.1·i, so "dot product at least 0.65" is radius -0.65
+        let outcome = storage.vector_range_search("dot", DEFAULT_VECTOR_FIELD, &[0.1, 0.0, 0.0], -0.65, 10).unwrap();
+        assert_eq!(outcome.metric, "dot_product");
+        let ids: Vec<&str> = outcome.hits.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["doc9", "doc8", "doc7"]);
+        assert!(outcome.hits.iter().all(|(_, distance)| *distance <= -0.65));
+        assert!(storage.vector_range_search("dot", DEFAULT_VECTOR_FIELD, &[0.1, 0.0, 0.0], f32::NEG_INFINITY, 10).is_err());
+
+        // Relaxing a negative radius moves it toward +∞: -0.85 -> 0 (everything at dot >= 0)
+        let relaxed = storage
+            .vector_range_search_relaxed("dot", DEFAULT_VECTOR_FIELD, &[0.1, 0.0, 0.0], -0.85, 10, 4)
+            .unwrap();
+        assert!(relaxed.relaxed);
+        assert_eq!((relaxed.radius, relaxed.count), (0.0, 10));
+        let strict = storage
+            .vector_range_search_relaxed("dot", DEFAULT_VECTOR_FIELD, &[0.1, 0.0, 0.0], -0.65, 10, 2)
+            .unwrap();
+        assert_eq!((strict.radius, strict.count, strict.relaxed), (-0.65, 3, false));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn similarity_scores_descend_with_distance() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_score_kind");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        insert_n(&storage, "col", 6);
+
+        let by_distance = storage.vector_search_scored("col", &[2.0, 1.0, 0.5], 3, ScoreKind::Distance).unwrap();
+        let by_similarity = storage.vector_search_scored("col", &[2.0, 1.0, 0.5], 3, ScoreKind::Similarity).unwrap();
+        assert_eq!(by_distance.ids, by_similarity.ids);
+        assert_eq!(by_similarity.score_kind, ScoreKind::Similarity);
+        assert!(by_distance.scores.windows(2).all(|w| w[0] <= w[1]));
+        assert!(by_similarity.scores.windows(2).all(|w| w[0] >= w[1]));
+        assert!((by_similarity.scores[0] - 1.0).abs() < 1e-6);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn named_vector_fields_are_searched_independently() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_named_vectors");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        // "a" has a title like [1, 0] but a body like [0, 1]; "b" is the reverse
+        let doc = |id: &str, title: Vec<f32>, body: Vec<f32>| Document {
+            vectors: HashMap::from([("title".to_string(), title), ("body".to_string(), body)]),
+            ..test_doc(id, vec![0.5, 0.5])
+        };
+        storage.insert_doc(doc("a", vec![1.0, 0.0], vec![0.0, 1.0]), "col").unwrap();
+        storage.insert_doc(doc("b", vec![0.0, 1.0], vec![1.0, 0.0]), "col").unwrap();
+
+        let title = storage.vector_search_in_field("col", "title", &[1.0, 0.0], 1, ScoreKind::Distance).unwrap();
+        let body = storage.vector_search_in_field("col", "body", &[1.0, 0.0], 1, ScoreKind::Distance).unwrap();
+        assert_eq!(title.ids, vec!["a".to_string()]);
+        assert_eq!(body.ids, vec!["b".to_string()]);
+        assert_eq!(storage.vector_search("col", &[0.5, 0.5], 5).unwrap().len(), 2);
+
+        // Dropping a field on update removes it from that field's index
+        let mut updated = doc("a", vec![1.0, 0.0], vec![]);
+        updated.vectors.remove("body");
+        storage.update_doc(updated, "col").unwrap();
+        assert_eq!(storage.get_field_vectors_in_collection("col", "body").unwrap().len(), 1);
+        storage.delete_doc("col", "b").unwrap();
+        assert!(storage.get_field_vectors_in_collection("col", "body").unwrap().is_empty());
+
+        let mut invalid = doc("c", vec![1.0, 0.0], vec![0.0, 1.0]);
+        invalid.vectors.insert("title/v2".to_string(), vec![1.0, 1.0]);
+        assert!(storage.insert_doc(invalid, "col").is_err());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn tag_expansion_pulls_results_toward_tag_centroid() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_tag_expansion");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        // Two clusters: "rust" docs around (10, 10), "python" docs around (0, 0)
+        for i in 0..4 {
+            let offset = i as f32 * 0.1;
+            for (tag, base) in [("rust", 10.0), ("python", 0.0)] {
+                storage.insert_doc(Document {
+                    text: format!("{} doc", tag),
+                    metadata: serde_json::json!({"tags": [tag]}),
+                    ..test_doc(&format!("{}{}", tag, i), vec![base + offset, base - offset])
+                }, "col").unwrap();
+            }
+        }
+        let centroid = storage.tag_centroid("col", "rust").unwrap().unwrap();
+        assert!((centroid[0] - 10.15).abs() < 1e-4);
+
+        let query = [3.0, 3.0];
+        let plain = storage.vector_search("col", &query, 1).unwrap();
+        assert!(plain[0].starts_with("python"));
+
+        let tags = vec!["rust".to_string()];
+        let expanded = storage
+            .vector_search_expanded("col", DEFAULT_VECTOR_FIELD, &query, &tags, 0.6, 1, ScoreKind::Distance)
+            .unwrap();
+        assert!(expanded.ids[0].starts_with("rust"), "got {:?}", expanded.ids);
+
+        // Deleting every tagged doc drops the centroid
+        for i in 0..4 {
+            storage.delete_doc("col", &format!("rust{}", i)).unwrap();
+        }
+        assert!(storage.tag_centroid("col", "rust").unwrap().is_none());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn concurrent_searches_respect_build_permits() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_build_permits");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap())
+            .expect("open storage")
+            .with_max_index_builds(1);
+        insert_n(&storage, "col", 20);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    assert!(storage.index_build_limiter().in_flight() <= 1);
+                    storage.vector_search("col", &[3.0, 1.0, 0.5], 1).unwrap()
+                })
+            })
+            .collect();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), vec!["doc3".to_string()]);
+        }
+        assert_eq!(storage.index_build_limiter().max_permits(), 1);
+        assert_eq!(storage.index_build_limiter().in_flight(), 0);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn identical_texts_collapse_to_best_scoring_result() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_dedupe_text");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        let doc = |id: &str, text: &str, vector: Vec<f32>| Document { text: text.to_string(), ..test_doc(id, vector) };
+        storage.insert_doc(doc("orig", "same text", vec![0.0, 0.0]), "col").unwrap();
+        storage.insert_doc(doc("copy", "same text", vec![0.1, 0.0]), "col").unwrap();
+        storage.insert_doc(doc("other", "different text", vec![1.0, 0.0]), "col").unwrap();
+
+        let query = [0.0, 0.0];
+        assert_eq!(storage.vector_search("col", &query, 2).unwrap(), vec!["orig", "copy"]);
+
+        let distinct = storage
+            .vector_search_distinct_text("col", 2, |k| storage.vector_search_scored("col", &query, k, ScoreKind::Distance))
+            .unwrap();
+        assert_eq!(distinct.ids, vec!["orig", "other"]);
+        assert_eq!(distinct.scores.len(), 2);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn cancelled_searches_stop_before_scanning_or_building() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_search_cancel");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        insert_n(&storage, "col", 50);
+
+        let token = CancelToken::new();
+        token.cancel();
+        let query = [3.0, 1.0, 0.5];
+        let err = token.scope(|| storage.vector_search_exact("col", DEFAULT_VECTOR_FIELD, &query, 3, ScoreKind::Distance)).unwrap_err();
+        assert!(err.downcast_ref::<Cancelled>().is_some());
+        let err = token.scope(|| storage.vector_search("col", &query, 3)).unwrap_err();
+        assert!(err.downcast_ref::<Cancelled>().is_some());
+        assert_eq!(storage.index_builds(), 0, "no index built for a dropped request");
+
+        // A live token changes nothing
+        let ids = CancelToken::new().scope(|| storage.vector_search("col", &query, 1)).unwrap();
+        assert_eq!(ids, vec!["doc3"]);
+        assert_eq!(storage.index_builds(), 1);
+
+        let _ = fs::remove_dir_all(temp_dir);
     }
 }
@@ -4,12 +4,13 @@
 //! Provides high-level APIs for ingesting text and performing semantic search.
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tracing::{info, debug, instrument};
 
 use super::tokenizer::{TextTokenizer, TextChunk, ChunkingConfig};
 use super::embeddings::{EmbeddingModel, EmbeddingConfig};
 use crate::storage::Storage;
-use crate::indexing::VectorIndex;
+use crate::indexing::DistanceMetric;
 
 /// A RAG document with text and embedding
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -222,6 +223,10 @@ impl RagPipeline {
                 "created_at": doc.created_at,
                 "custom": doc.metadata,
             }),
+            vectors: HashMap::new(),
+            updated_at: None,
+            source_uri: None,
+            ingested_by: None,
         };
         
         storage.insert_doc(storage_doc, collection_id)?;
@@ -251,7 +256,8 @@ impl RagPipeline {
         }
         
         // Build vector index
-        let index = VectorIndex::build_from_vectors(vectors);
+        // L2 to match the distance reported as each result's score
+        let index = storage.build_index(vectors, DistanceMetric::L2);
         
         // Search for similar vectors
         let result_ids = index.search(&query_embedding, top_k);
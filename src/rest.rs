@@ -7,40 +7,75 @@
 
 use arrow::array::Array;
 use axum::{
-    extract::{Path, State, WebSocketUpgrade},
+    extract::{DefaultBodyLimit, MatchedPath, Path, Query, RawPathParams, State, WebSocketUpgrade},
     extract::ws::{WebSocket, Message},
-    http::{StatusCode, Request, header},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, Request, header},
     middleware::{self, Next},
-    response::Response,
-    routing::{delete, get, post},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
     Json, Router, Extension,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json;  // For JSON parsing in NoSQL handler
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::{info, debug, warn, error, instrument};
 use utoipa::{OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::storage::{Document, Storage};
+use crate::storage::{AuditFilter, AuditPage, AuditRecord, BatchInsertResult, BulkDeleteResult, CollectionSwap, DeleteStatus, DimensionMismatch, DocLocation, DocResync, Document, GenerationReport, IncompatibleIndex, IndexImport, IndexStats, InsertStatus, MetadataAppendError, SelfCheckReport, SnapshotReport, Storage, DEFAULT_VECTOR_FIELD};
+use crate::storage::cache::CacheResize;
+use crate::storage::snapshot::snapshot_path;
+use crate::cache::CacheStats;
+use crate::cancel;
+use crate::indexing::{DistanceMetric, IndexBackend, ScoreKind};
 use crate::query::{
-    aggregation::AggregationPipeline,
+    aggregation::{pipeline_doc, AggregationPipeline, MatchStage},
+    vector::{check_radius, CollectionHit, ScoreBoost, VectorSearchOptions, DEFAULT_EXPAND_WEIGHT},
+    DistanceHistogram,
     cross_collection::{CrossCollectionEngine, CrossCollectionPipeline, MultiCollectionOperation},
     AggregationEngine,
+    HybridExplanation,
     QueryEngine,
+    encode_ipc_stream,
 };
-use crate::tenants::{User, Tenant, Environment, Collection, AuthPayload};
-use crate::auth::{hash_password, verify_password, create_jwt_with_session, validate_jwt};
+use crate::tenants::{validate_hierarchy_id, Role, User, Tenant, Environment, Collection, CollectionUpsertError, AuthPayload, EffectiveCollectionConfig, WorkspaceContext};
+use crate::auth::{hash_password, verify_login, INVALID_CREDENTIALS, create_jwt_with_session, require_collection_access, require_role, validate_jwt};
 use crate::session::{get_session_manager, Session};
 use crate::logging::{read_logs_by_session, JsonLogEntry};
 use crate::events::{PubSubManager, CdcEvent};
+use crate::config::RestConfig;
+
+pub mod context;
+pub mod cursor;
+pub mod empty;
+pub mod envelope;
+pub mod error;
+pub mod metrics;
+pub mod params;
+pub mod payload;
+pub mod request_id;
+pub mod strict;
+use context::ResolvedCollection;
+use cursor::{RankedPage, RankedResults, SearchCursors, VectorHit};
+use empty::EmptyResults;
+use envelope::ResponseMode;
+use error::AppError;
+use params::{PagePolicy, QueryParams};
+use payload::PayloadLimit;
+use request_id::{ApiError, RequestIdPolicy};
+use strict::{unknown_keys, StrictBody, StrictJson};
 
 /// Shared app state for REST handlers (Arc-wrapped for concurrency)
 #[derive(Clone)]
 pub struct AppState {
     storage: Arc<Storage>,
     pubsub: Arc<PubSubManager>,
+    search_cursors: Arc<SearchCursors>,
+    metrics: Arc<metrics::RequestMetrics>,
+    cache_autosize_fraction: f64,
+    snapshot_dir: std::path::PathBuf,
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -69,12 +104,71 @@ pub struct InsertDocRest {
     pub category: String,
     pub vector: Vec<f32>,
     pub metadata_json: String,  // Flexible NoSQL JSON
+    /// Named embeddings (e.g. "title", "body") indexed separately from `vector`
+    #[serde(default)]
+    pub vectors: HashMap<String, Vec<f32>>,
+    /// Provenance: source file or URL (filterable as the `source_uri` SQL column)
+    #[serde(default)]
+    pub source_uri: Option<String>,
+    /// Provenance: ingestion job or client (filterable as the `ingested_by` SQL column)
+    #[serde(default)]
+    pub ingested_by: Option<String>,
 }
 
-/// DTO for batch NoSQL JSON insert
+/// DTO for batch NoSQL JSON insert: `{"documents": [...]}` or a bare array of documents
 #[derive(Deserialize, ToSchema)]
-pub struct BatchInsertDocRest {
-    pub documents: Vec<InsertDocRest>,
+#[serde(untagged)]
+pub enum BatchInsertDocRest {
+    Wrapped { documents: Vec<InsertDocRest> },
+    Array(Vec<InsertDocRest>),
+}
+
+impl BatchInsertDocRest {
+    pub fn into_documents(self) -> Vec<InsertDocRest> {
+        match self {
+            BatchInsertDocRest::Wrapped { documents } | BatchInsertDocRest::Array(documents) => documents,
+        }
+    }
+}
+
+/// Keys accepted at the top level of the wrapped batch form
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct WrappedBatchKeys {
+    documents: Vec<serde_json::Value>,
+}
+
+/// Response for POST /collections/:collection_id/docs/batch
+#[derive(Serialize, ToSchema)]
+pub struct BatchInsertDocResponse {
+    /// Whether every document was inserted
+    pub success: bool,
+    pub message: String,
+    pub inserted: usize,
+    pub rejected: usize,
+    /// One entry per document, in request order
+    #[schema(value_type = Vec<Object>)]
+    pub results: Vec<BatchInsertResult>,
+}
+
+impl StrictBody for InsertDocRest {}
+
+impl StrictBody for BatchInsertDocRest {
+    /// Top-level keys plus each document's, e.g. `documents[2].vectorz` (or `[2].vectorz` for a bare array)
+    fn unknown_fields(body: &serde_json::Value) -> Vec<String> {
+        if let Some(documents) = body.as_array() {
+            return documents.iter().enumerate()
+                .flat_map(|(i, doc)| unknown_keys::<InsertDocRest>(doc, &format!("[{}].", i)))
+                .collect();
+        }
+        let mut unknown = unknown_keys::<WrappedBatchKeys>(body, "");
+        if let Some(documents) = body.get("documents").and_then(|d| d.as_array()) {
+            for (i, doc) in documents.iter().enumerate() {
+                unknown.extend(unknown_keys::<InsertDocRest>(doc, &format!("documents[{}].", i)));
+            }
+        }
+        unknown
+    }
 }
 
 /// DTO for full-text search REST requests
@@ -84,6 +178,9 @@ pub struct TextSearchRest {
     pub partial_match: bool,
     pub case_sensitive: bool,
     pub include_metadata: bool,
+    /// Document fields to include per result (default `id`, `text`, `category`)
+    #[serde(default)]
+    pub return_fields: Option<Vec<ReturnField>>,
 }
 
 /// DTO for full-text search responses
@@ -92,13 +189,73 @@ pub struct TextSearchResponse {
     pub success: bool,
     pub message: String,
     pub results: Vec<DocumentSummary>,
+    /// Trailing results were dropped to stay under AIDB_MAX_RESPONSE_BYTES
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Pass as `?cursor=` to continue after the returned results (only when `truncated`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// A `Document` field a search request can ask for in its results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReturnField {
+    Id,
+    Text,
+    Category,
+    Vector,
+    Metadata,
+    Vectors,
+    UpdatedAt,
+    SourceUri,
+    IngestedBy,
 }
 
+/// Fields of a search result when the request names none
+const DEFAULT_RETURN_FIELDS: [ReturnField; 3] = [ReturnField::Id, ReturnField::Text, ReturnField::Category];
+
+/// A search result carrying the requested `ReturnField`s; the others are omitted
 #[derive(Serialize, ToSchema)]
 pub struct DocumentSummary {
-    pub id: String,
-    pub text: String,
-    pub category: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vector: Option<Vec<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vectors: Option<HashMap<String, Vec<f32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ingested_by: Option<String>,
+}
+
+impl DocumentSummary {
+    /// `doc` reduced to `fields` (`DEFAULT_RETURN_FIELDS` when `None`)
+    pub fn project(doc: Document, fields: Option<&[ReturnField]>) -> Self {
+        let fields = fields.unwrap_or(&DEFAULT_RETURN_FIELDS);
+        let wants = |field: ReturnField| fields.contains(&field);
+        Self {
+            id: wants(ReturnField::Id).then_some(doc.id),
+            text: wants(ReturnField::Text).then_some(doc.text),
+            category: wants(ReturnField::Category).then_some(doc.category),
+            vector: wants(ReturnField::Vector).then_some(doc.vector),
+            metadata: wants(ReturnField::Metadata).then_some(doc.metadata),
+            vectors: wants(ReturnField::Vectors).then_some(doc.vectors),
+            updated_at: doc.updated_at.filter(|_| wants(ReturnField::UpdatedAt)),
+            source_uri: doc.source_uri.filter(|_| wants(ReturnField::SourceUri)),
+            ingested_by: doc.ingested_by.filter(|_| wants(ReturnField::IngestedBy)),
+        }
+    }
 }
 
 /// Generic REST response (JSON)
@@ -111,22 +268,81 @@ pub struct RestResponse {
     pub cache_hits: Option<Vec<bool>>, // True if fetched from cache
 }
 
+/// Role a protected route requires, by method and route template. Anything not known to be
+/// read-only needs a writer, so new write routes are guarded by default.
+fn required_role(method: &Method, route: &str) -> Role {
+    if route.starts_with("/admin/") {
+        return Role::Admin;
+    }
+    match (method, route) {
+        (&Method::POST, "/tenants" | "/tenants/:tenant_id/environments" | "/environments/:env_id/collections") => Role::Admin,
+        (&Method::PUT | &Method::DELETE, "/environments/:env_id/collections/:col_id") => Role::Admin,
+        (&Method::GET | &Method::HEAD, _) => Role::Reader,
+        // A user's own workspace context isn't shared data
+        (&Method::PUT, "/me/context") => Role::Reader,
+        // Searches and queries that take their parameters as a POST body
+        (
+            &Method::POST,
+            "/search"
+            | "/similarity"
+            | "/rag/embed"
+            | "/collections/cross/query"
+            | "/collections/:collection_id/vector_search"
+            | "/collections/:collection_id/vector_search/exact"
+            | "/collections/:collection_id/vector_search/range"
+            | "/collections/:collection_id/sql"
+            | "/collections/:collection_id/search"
+            | "/collections/:collection_id/hybrid"
+            | "/collections/:collection_id/aggregate"
+            | "/collections/:collection_id/rag/search",
+        ) => Role::Reader,
+        _ => Role::Writer,
+    }
+}
+
+/// Collection named by the matched route's `:collection_id` (or `:col_id`) segment
+fn path_collection_id(params: &RawPathParams) -> Option<&str> {
+    params
+        .iter()
+        .find(|(name, _)| matches!(*name, "collection_id" | "col_id"))
+        .map(|(_, value)| value)
+}
+
+/// `require_collection_access` for collections named in a request body, which the middleware
+/// (seeing only path params) can't check; call it before doing any work
+fn require_body_collections<'a>(storage: &Storage, claims: &AuthPayload, collections: impl IntoIterator<Item = &'a str>) -> Result<(), AppError> {
+    for collection_id in collections {
+        require_collection_access(storage, claims, collection_id)?;
+    }
+    Ok(())
+}
+
 async fn auth_middleware(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
+    path_params: Option<RawPathParams>,
     mut req: Request<axum::body::Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, AppError> {
     let auth_header = req.headers()
         .get(header::AUTHORIZATION)
         .and_then(|value| value.to_str().ok())
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or_else(|| AppError::new(StatusCode::UNAUTHORIZED, "Missing Authorization header"))?;
 
     if !auth_header.starts_with("Bearer ") {
-        return Err(StatusCode::UNAUTHORIZED);
+        return Err(AppError::new(StatusCode::UNAUTHORIZED, "Authorization header is not a Bearer token"));
     }
 
     let token = &auth_header[7..];
-    let claims = validate_jwt(token).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let claims = validate_jwt(token).map_err(|e| AppError::new(StatusCode::UNAUTHORIZED, format!("Invalid token: {}", e)))?;
+    let route = req.extensions().get::<MatchedPath>().map(|route| route.as_str().to_string());
+    if let Some(route) = &route {
+        require_role(&claims, required_role(req.method(), route))?;
+    }
+    // Path params are percent-decoded, so an encoded ID can't slip past the owner lookup
+    let collection_id = path_params.as_ref().and_then(path_collection_id).map(str::to_string);
+    if let Some(collection_id) = &collection_id {
+        require_collection_access(&state.storage, &claims, collection_id)?;
+    }
 
     // Touch session to update last activity
     if let Some(ref session_id) = claims.session_id {
@@ -134,8 +350,18 @@ async fn auth_middleware(
         session_manager.touch_session(session_id);
     }
 
+    // Calls that may change state are audited once answered (see storage::audit)
+    let audited = (!matches!(*req.method(), Method::GET | Method::HEAD))
+        .then(|| (claims.sub.clone(), format!("{} {}", req.method(), route.as_deref().unwrap_or(req.uri().path()))));
     req.extensions_mut().insert(claims);
-    Ok(next.run(req).await)
+    let response = next.run(req).await;
+    if let Some((user, action)) = audited {
+        let record = AuditRecord { timestamp: 0, user, action, collection: collection_id, status: response.status().as_u16() };
+        if let Err(e) = state.storage.record_audit(record) {
+            warn!(error = %e, "Failed to record audit entry");
+        }
+    }
+    Ok(response)
 }
 
 #[derive(OpenApi)]
@@ -154,7 +380,7 @@ async fn auth_middleware(
         health_handler
     ),
     components(
-        schemas(UserRegister, UserLogin, LoginResponse, InsertDocRest, BatchInsertDocRest, TextSearchRest, TextSearchResponse, DocumentSummary, RestResponse, CreateTenantRest, CreateEnvRest, CreateCollectionRest, SqlRest, HybridRest, AggregationRest, AggregationResponse, CrossCollectionQueryRest, CrossCollectionQueryResponse, MultiCollectionOperationRest, MultiCollectionOperationResponse)
+        schemas(UserRegister, UserLogin, LoginResponse, InsertDocRest, InsertDocResponse, BatchInsertDocRest, BatchInsertDocResponse, TextSearchRest, TextSearchResponse, DocumentSummary, ReturnField, RestResponse, CreateTenantRest, CreateEnvRest, CreateCollectionRest, SqlRest, SqlRestResponse, HybridRest, HybridRestResponse, AggregationRest, AggregationResponse, CrossCollectionQueryRest, CrossCollectionQueryResponse, MultiCollectionOperationRest, MultiCollectionOperationResponse, ApiError)
     ),
     modifiers(&SecurityAddon),
     tags(
@@ -222,19 +448,70 @@ impl utoipa::Modify for SecurityAddon {
 
 /// Create Axum router with multi-model endpoints
 pub fn create_router(storage: Storage) -> Router {
+    create_router_with_config(storage, &RestConfig::from_env())
+}
+
+/// Install `config`'s policies on requests that don't already carry one (so a test or an outer
+/// layer can still override a single policy)
+async fn install_rest_config(config: Arc<RestConfig>, mut req: axum::extract::Request, next: middleware::Next) -> axum::response::Response {
+    let extensions = req.extensions_mut();
+    if extensions.get::<PagePolicy>().is_none() {
+        extensions.insert(config.page);
+    }
+    if extensions.get::<ResponseMode>().is_none() {
+        extensions.insert(config.response_mode);
+    }
+    if extensions.get::<RequestIdPolicy>().is_none() {
+        extensions.insert(config.request_ids);
+    }
+    if extensions.get::<PayloadLimit>().is_none() {
+        extensions.insert(config.payload_limit);
+    }
+    if extensions.get::<strict::StrictMode>().is_none() {
+        extensions.insert(config.strict);
+    }
+    if extensions.get::<EmptyResults>().is_none() {
+        extensions.insert(config.empty_results);
+    }
+    next.run(req).await
+}
+
+/// Router with the REST policies parsed at startup (see `crate::config`)
+pub fn create_router_with_config(storage: Storage, config: &RestConfig) -> Router {
+    let config = Arc::new(config.clone());
     let state = Arc::new(AppState {
         storage: Arc::new(storage),
         pubsub: Arc::new(PubSubManager::new(1024)),
+        search_cursors: Arc::new(SearchCursors::default()),
+        metrics: Arc::new(metrics::RequestMetrics::default()),
+        cache_autosize_fraction: config.cache_autosize_fraction,
+        snapshot_dir: config.snapshot_dir.clone(),
     });
 
     let auth_routes = Router::new()
         .route("/tenants", post(create_tenant_handler).get(get_tenants_handler))
         .route("/tenants/:tenant_id/environments", post(create_env_handler).get(get_envs_handler))
         .route("/environments/:env_id/collections", post(create_collection_handler).get(get_collections_handler))
-        .route("/environments/:env_id/collections/:col_id", delete(delete_collection_handler))
+        .route("/environments/:env_id/collections/:col_id", delete(delete_collection_handler).put(upsert_collection_handler))
+        .route("/environments/:env_id/collections/:col_id/config", get(collection_config_handler))
         .route("/collections/:collection_id/docs", post(insert_doc_handler).put(update_doc_handler).get(list_docs_handler))
+        .route("/collections/:collection_id/count", get(count_docs_handler))
         .route("/collections/:collection_id/docs/batch", post(batch_insert_doc_handler))
+        .route("/collections/:collection_id/docs/bulk_delete", post(bulk_delete_docs_handler))
+        .route("/collections/:collection_id/docs/update_by_query", post(update_by_query_handler))
         .route("/collections/:collection_id/docs/:doc_id", get(get_doc_handler).delete(delete_doc_handler))
+        .route("/collections/:collection_id/docs/:doc_id/metadata/append", post(append_metadata_handler))
+        .route("/docs", post(insert_doc_handler).get(list_docs_handler))
+        .route("/docs/:doc_id/locate", get(locate_doc_handler))
+        .route("/search", post(multi_collection_search_handler))
+        .route("/similarity", post(similarity_handler))
+        .route("/collections/:collection_id/vector_search", post(vector_search_handler).get(vector_search_page_handler))
+        .route("/collections/:collection_id/vector_search/exact", post(exact_vector_search_handler))
+        .route("/collections/:collection_id/vector_search/range", post(range_vector_search_handler))
+        .route("/collections/:collection_id/distance_histogram", get(distance_histogram_handler))
+        .route("/collections/:collection_id/index_stats", get(index_stats_handler))
+        .route("/collections/:collection_id/export", get(export_handler))
+        .route("/me/context", get(get_context_handler).put(set_context_handler))
         .route("/collections/:collection_id/sql", post(sql_handler))
         .route("/collections/:collection_id/search", post(text_search_handler))
         .route("/collections/:collection_id/hybrid", post(hybrid_handler))
@@ -252,6 +529,22 @@ pub fn create_router(storage: Storage) -> Router {
         .route("/sessions/:session_id", get(get_session_handler))
         .route("/sessions/:session_id/logs", get(get_session_logs_handler))
         .route("/sessions/:session_id/logs/:level", get(get_session_logs_by_level_handler))
+        // Admin endpoints
+        .route("/admin/self-check", post(self_check_handler))
+        .route("/admin/generations/verify", post(verify_generations_handler))
+        .route("/admin/collections/swap", post(swap_collections_handler))
+        .route("/admin/snapshot", post(snapshot_handler))
+        .route("/admin/restore", post(restore_handler))
+        .route("/admin/cache/autosize", post(cache_autosize_handler))
+        .route("/admin/cache/stats", get(cache_stats_handler))
+        .route("/admin/audit", get(audit_log_handler))
+        .route("/admin/users/:username/roles", put(set_user_roles_handler))
+        .route("/admin/collections/:collection_id/docs/:doc_id/resync", post(resync_doc_handler))
+        // Index files easily outgrow the default 2 MB body limit
+        .route(
+            "/admin/collections/:collection_id/index",
+            get(export_index_handler).put(import_index_handler).layer(DefaultBodyLimit::max(MAX_INDEX_FILE_BYTES)),
+        )
         .route_layer(middleware::from_fn_with_state(state.clone(), auth_middleware));
 
     Router::new()
@@ -259,8 +552,11 @@ pub fn create_router(storage: Storage) -> Router {
         .route("/register", post(register_handler))
         .route("/login", post(login_handler))
         .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
         .route("/ws", get(ws_handler))
         .merge(auth_routes)
+        .layer(middleware::from_fn(request_id::request_id_middleware))
+        .layer(middleware::from_fn(move |req, next| install_rest_config(config.clone(), req, next)))
         .with_state(state)
 }
 
@@ -277,23 +573,25 @@ pub fn create_router(storage: Storage) -> Router {
 async fn register_handler(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<UserRegister>,
-) -> Result<Json<RestResponse>, StatusCode> {
+) -> Result<Json<RestResponse>, AppError> {
     debug!(username = %payload.username, "REST register request");
     
     let hash = hash_password(&payload.password).map_err(|e| {
         error!(error = %e, "Password hashing failed");
-        StatusCode::INTERNAL_SERVER_ERROR
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Password hashing failed: {}", e))
     })?;
     
     let user = User {
         username: payload.username.clone(),
         password_hash: hash,
         tenants: vec![],
+        roles: vec![],
+        context: None,
     };
     
-    state.storage.create_user(user).map_err(|e| {
+    state.storage.register_user(user).map_err(|e| {
         warn!(error = %e, username = %payload.username, "User registration failed");
-        StatusCode::BAD_REQUEST
+        AppError::new(StatusCode::BAD_REQUEST, format!("User registration failed: {}", e))
     })?;
     
     info!(username = %payload.username, "User registered via REST");
@@ -318,27 +616,32 @@ async fn register_handler(
 async fn login_handler(
     State(state): State<Arc<AppState>>,
     Json(payload): Json<UserLogin>,
-) -> Result<Json<LoginResponse>, StatusCode> {
+) -> Result<Json<LoginResponse>, AppError> {
     debug!(username = %payload.username, "REST login request");
     
     let user = state.storage.get_user(&payload.username)
         .map_err(|e| {
             error!(error = %e, "Database error during login");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?
-        .ok_or_else(|| {
-            warn!(username = %payload.username, "User not found");
-            StatusCode::UNAUTHORIZED
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Database error during login: {}", e))
         })?;
 
-    if !verify_password(&payload.password, &user.password_hash).unwrap_or(false) {
-        warn!(username = %payload.username, "Invalid password attempt");
-        return Err(StatusCode::UNAUTHORIZED);
-    }
+    // Missing users and wrong passwords get the same answer after the same work
+    let verified = verify_login(payload.password, user.as_ref().map(|u| u.password_hash.clone())).await;
+    let user = match user {
+        Some(user) if verified => user,
+        Some(_) => {
+            warn!(username = %payload.username, "Invalid password attempt");
+            return Err(AppError::new(StatusCode::UNAUTHORIZED, INVALID_CREDENTIALS));
+        }
+        None => {
+            warn!(username = %payload.username, "User not found");
+            return Err(AppError::new(StatusCode::UNAUTHORIZED, INVALID_CREDENTIALS));
+        }
+    };
 
-    let (token, session_id) = create_jwt_with_session(&user.username).map_err(|e| {
+    let (token, session_id) = create_jwt_with_session(&user.username, &user.effective_roles()).map_err(|e| {
         error!(error = %e, "JWT creation failed");
-        StatusCode::INTERNAL_SERVER_ERROR
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("JWT creation failed: {}", e))
     })?;
     
     info!(username = %user.username, session_id = %session_id, "User logged in via REST");
@@ -351,6 +654,16 @@ pub struct CreateTenantRest {
     pub name: String,
 }
 
+impl StrictBody for CreateTenantRest {}
+
+/// `validate_hierarchy_id` for a new ID, rejecting unusable ones with `400`
+fn validate_id(kind: &'static str, id: &str) -> Result<String, AppError> {
+    validate_hierarchy_id(kind, id).map_err(|e| {
+        warn!(error = %e, "Rejected hierarchy ID");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected hierarchy ID: {}", e))
+    })
+}
+
 /// Handler: Create tenant
 #[utoipa::path(
     post,
@@ -367,27 +680,28 @@ pub struct CreateTenantRest {
 async fn create_tenant_handler(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<AuthPayload>,
-    Json(payload): Json<CreateTenantRest>,
-) -> Result<Json<RestResponse>, StatusCode> {
+    StrictJson(payload): StrictJson<CreateTenantRest>,
+) -> Result<Json<RestResponse>, AppError> {
     debug!(user_id = %claims.sub, tenant_id = %payload.id, "REST create tenant request");
-    
+    let tenant_id = validate_id("tenant", &payload.id)?;
+
     let tenant = Tenant {
-        id: payload.id.clone(),
+        id: tenant_id.clone(),
         name: payload.name.clone(),
         owner_id: claims.sub.clone(),
         environments: vec![],
     };
     state.storage.create_tenant(tenant).map_err(|e| {
-        error!(error = %e, tenant_id = %payload.id, "Failed to create tenant");
-        StatusCode::INTERNAL_SERVER_ERROR
+        error!(error = %e, tenant_id = %tenant_id, "Failed to create tenant");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create tenant: {}", e))
     })?;
     
     if let Some(mut user) = state.storage.get_user(&claims.sub).unwrap() {
-        user.tenants.push(payload.id.clone());
+        user.tenants.push(tenant_id.clone());
         state.storage.update_user(user).unwrap();
     }
 
-    info!(tenant_id = %payload.id, owner_id = %claims.sub, "Tenant created via REST");
+    info!(tenant_id = %tenant_id, owner_id = %claims.sub, "Tenant created via REST");
     Ok(Json(RestResponse {
         success: true,
         message: "Tenant created".to_string(),
@@ -399,7 +713,7 @@ async fn create_tenant_handler(
 async fn get_tenants_handler(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<AuthPayload>,
-) -> Result<Json<RestResponse>, StatusCode> {
+) -> Result<Json<RestResponse>, AppError> {
     debug!(user_id = %claims.sub, "REST get tenants request");
     
     let user = state.storage.get_user(&claims.sub).unwrap().unwrap();
@@ -418,30 +732,33 @@ pub struct CreateEnvRest {
     pub name: String,
 }
 
+impl StrictBody for CreateEnvRest {}
+
 async fn create_env_handler(
     State(state): State<Arc<AppState>>,
     Path(tenant_id): Path<String>,
-    Json(payload): Json<CreateEnvRest>,
-) -> Result<Json<RestResponse>, StatusCode> {
+    StrictJson(payload): StrictJson<CreateEnvRest>,
+) -> Result<Json<RestResponse>, AppError> {
     debug!(tenant_id = %tenant_id, env_id = %payload.id, "REST create environment request");
-    
+    let env_id = validate_id("environment", &payload.id)?;
+
     let env = Environment {
-        id: payload.id.clone(),
+        id: env_id.clone(),
         name: payload.name.clone(),
         tenant_id: tenant_id.clone(),
         collections: vec![],
     };
     state.storage.create_environment(env).map_err(|e| {
-        error!(error = %e, env_id = %payload.id, "Failed to create environment");
-        StatusCode::INTERNAL_SERVER_ERROR
+        error!(error = %e, env_id = %env_id, "Failed to create environment");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create environment: {}", e))
     })?;
     
     if let Some(mut tenant) = state.storage.get_tenant(&tenant_id).unwrap() {
-        tenant.environments.push(payload.id.clone());
+        tenant.environments.push(env_id.clone());
         state.storage.update_tenant(tenant).unwrap();
     }
 
-    info!(env_id = %payload.id, tenant_id = %tenant_id, "Environment created via REST");
+    info!(env_id = %env_id, tenant_id = %tenant_id, "Environment created via REST");
     Ok(Json(RestResponse {
         success: true,
         message: "Environment created".to_string(),
@@ -450,10 +767,41 @@ async fn create_env_handler(
     }))
 }
 
+/// Handler: Current workspace context of the caller
+/// GET /me/context
+async fn get_context_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+) -> Result<Json<WorkspaceContext>, AppError> {
+    debug!(user_id = %claims.sub, "REST get context request");
+
+    let user = state.storage.get_user(&claims.sub)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(user.context.unwrap_or_default()))
+}
+
+/// Handler: Set the caller's workspace context; routes like `/docs` then default to its collection
+/// PUT /me/context
+async fn set_context_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Json(payload): Json<WorkspaceContext>,
+) -> Result<Json<WorkspaceContext>, AppError> {
+    debug!(user_id = %claims.sub, ?payload, "REST set context request");
+
+    let context = state.storage.set_user_context(&claims.sub, payload).map_err(|e| {
+        warn!(error = %e, user_id = %claims.sub, "Rejected workspace context");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected workspace context: {}", e))
+    })?;
+    info!(user_id = %claims.sub, "Workspace context set via REST");
+    Ok(Json(context))
+}
+
 async fn get_envs_handler(
     State(state): State<Arc<AppState>>,
     Path(tenant_id): Path<String>,
-) -> Result<Json<RestResponse>, StatusCode> {
+) -> Result<Json<RestResponse>, AppError> {
     debug!(tenant_id = %tenant_id, "REST get environments request");
     
     let tenant = state.storage.get_tenant(&tenant_id).unwrap().unwrap();
@@ -470,31 +818,47 @@ async fn get_envs_handler(
 pub struct CreateCollectionRest {
     pub id: String,
     pub name: String,
+    /// Similarity metric ("l2", "cosine" or "dot_product"; defaults to "l2")
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub metric: Option<DistanceMetric>,
+    /// Vector dimension every document must match; recorded by the first insert when omitted
+    #[serde(default)]
+    pub vector_dim: Option<usize>,
+    /// L2-normalize document vectors on write and query vectors on search (defaults to false)
+    #[serde(default)]
+    pub normalize: bool,
 }
 
+impl StrictBody for CreateCollectionRest {}
+
 async fn create_collection_handler(
     State(state): State<Arc<AppState>>,
     Path(env_id): Path<String>,
-    Json(payload): Json<CreateCollectionRest>,
-) -> Result<Json<RestResponse>, StatusCode> {
+    StrictJson(payload): StrictJson<CreateCollectionRest>,
+) -> Result<Json<RestResponse>, AppError> {
     debug!(env_id = %env_id, collection_id = %payload.id, "REST create collection request");
-    
+    let collection_id = validate_id("collection", &payload.id)?;
+
     let col = Collection {
-        id: payload.id.clone(),
+        id: collection_id.clone(),
         name: payload.name.clone(),
         environment_id: env_id.clone(),
+        vector_dim: payload.vector_dim.filter(|dim| *dim > 0),
+        metric: payload.metric.unwrap_or_default(),
+        normalize: payload.normalize,
     };
     state.storage.create_collection(col).map_err(|e| {
-        error!(error = %e, collection_id = %payload.id, "Failed to create collection");
-        StatusCode::INTERNAL_SERVER_ERROR
+        error!(error = %e, collection_id = %collection_id, "Failed to create collection");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create collection: {}", e))
     })?;
     
     if let Some(mut env) = state.storage.get_environment(&env_id).unwrap() {
-        env.collections.push(payload.id.clone());
+        env.collections.push(collection_id.clone());
         state.storage.update_environment(env).unwrap();
     }
 
-    info!(collection_id = %payload.id, env_id = %env_id, "Collection created via REST");
+    info!(collection_id = %collection_id, env_id = %env_id, "Collection created via REST");
     Ok(Json(RestResponse {
         success: true,
         message: "Collection created".to_string(),
@@ -503,10 +867,114 @@ async fn create_collection_handler(
     }))
 }
 
+/// Handler: Effective (stored + inherited) configuration of a collection
+/// GET /environments/:env_id/collections/:col_id/config
+async fn collection_config_handler(
+    State(state): State<Arc<AppState>>,
+    Path((env_id, col_id)): Path<(String, String)>,
+) -> Result<Json<EffectiveCollectionConfig>, AppError> {
+    debug!(env_id = %env_id, collection_id = %col_id, "REST collection config request");
+
+    match state.storage.collection_config(&env_id, &col_id) {
+        Ok(Some(config)) => {
+            info!(env_id = %env_id, collection_id = %col_id, "Collection config served via REST");
+            Ok(Json(config))
+        }
+        Ok(None) => {
+            warn!(env_id = %env_id, collection_id = %col_id, "Collection not found for config");
+            Err(AppError::new(StatusCode::NOT_FOUND, "Collection not found for config"))
+        }
+        Err(e) => {
+            error!(error = %e, collection_id = %col_id, "Failed to resolve collection config");
+            Err(AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to resolve collection config: {}", e)))
+        }
+    }
+}
+
+/// DTO for idempotent collection upsert (PUT); name defaults to the path ID
+#[derive(Deserialize, ToSchema, Default)]
+pub struct UpsertCollectionRest {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Similarity metric of a collection created by this call; must match an existing one's
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    pub metric: Option<DistanceMetric>,
+    /// Vector dimension of a collection created by this call, or recorded on an existing one
+    /// without one yet; must match one already set
+    #[serde(default)]
+    pub vector_dim: Option<usize>,
+    /// Vector normalization used only when the collection is created by this call
+    #[serde(default)]
+    pub normalize: bool,
+}
+
+/// Handler: Get-or-create collection (PUT upsert; concurrent callers converge on one collection).
+/// 404 for an unknown environment; 409 if the collection lives in another environment or its
+/// metric or vector dimension differs from the requested one
+async fn upsert_collection_handler(
+    State(state): State<Arc<AppState>>,
+    Path((env_id, col_id)): Path<(String, String)>,
+    payload: Option<Json<UpsertCollectionRest>>,
+) -> Result<Json<RestResponse>, AppError> {
+    debug!(env_id = %env_id, collection_id = %col_id, "REST upsert collection request");
+    let col_id = validate_id("collection", &col_id)?;
+
+    let Json(payload) = payload.unwrap_or_default();
+    let requested_dim = payload.vector_dim.filter(|dim| *dim > 0);
+    let col = Collection {
+        id: col_id.clone(),
+        name: payload.name.unwrap_or_else(|| col_id.clone()),
+        environment_id: env_id.clone(),
+        vector_dim: requested_dim,
+        metric: payload.metric.unwrap_or_default(),
+        normalize: payload.normalize,
+    };
+    let (col, created) = state.storage.get_or_create_collection(col).map_err(|e| match e.downcast_ref::<CollectionUpsertError>() {
+        Some(rejected @ CollectionUpsertError::UnknownEnvironment { .. }) => AppError::new(StatusCode::NOT_FOUND, rejected.to_string()),
+        Some(rejected @ CollectionUpsertError::OtherEnvironment { .. }) => AppError::new(StatusCode::CONFLICT, rejected.to_string()),
+        None => {
+            error!(error = %e, collection_id = %col_id, "Failed to upsert collection");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to upsert collection: {}", e))
+        }
+    })?;
+
+    // An existing collection keeps its settings (a dimension not yet recorded is recorded now,
+    // first writer wins); report requested ones that differ rather than dropping them
+    let mut differing = Vec::new();
+    if let Some(requested) = payload.metric.filter(|metric| *metric != col.metric) {
+        differing.push(format!("metric {} (requested {})", col.metric.as_str(), requested.as_str()));
+    }
+    if let Some(requested) = requested_dim.filter(|_| !created) {
+        let existing = state.storage.record_vector_dim(&col.id, requested).map_err(|e| {
+            error!(error = %e, collection_id = %col.id, "Failed to record vector dimension");
+            AppError::internal(format!("Failed to record vector dimension: {}", e))
+        })?;
+        if existing.dim != requested {
+            differing.push(format!("vector_dim {} (requested {})", existing.dim, requested));
+        }
+    }
+    if !differing.is_empty() {
+        warn!(collection_id = %col.id, differing = ?differing, "Upsert requested settings of an existing collection");
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            format!("Collection '{}' already exists with {}", col.id, differing.join(", ")),
+        ));
+    }
+
+    info!(collection_id = %col.id, env_id = %env_id, created = created, "Collection upserted via REST");
+    Ok(Json(RestResponse {
+        success: true,
+        message: if created { "Collection created".to_string() } else { "Collection exists".to_string() },
+        results: vec![col.id],
+        cache_hits: None,
+    }))
+}
+
 async fn get_collections_handler(
     State(state): State<Arc<AppState>>,
     Path(env_id): Path<String>,
-) -> Result<Json<RestResponse>, StatusCode> {
+) -> Result<Json<RestResponse>, AppError> {
     debug!(env_id = %env_id, "REST get collections request");
     
     let env = state.storage.get_environment(&env_id).unwrap().unwrap();
@@ -519,13 +987,25 @@ async fn get_collections_handler(
     }))
 }
 
+/// Response for single-document insert: echoes the collection's effective vector config
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct InsertDocResponse {
+    pub success: bool,
+    pub message: String,
+    pub results: Vec<String>,
+    /// Vector dimension the collection now expects (set by its first insert)
+    pub dim: usize,
+    /// Similarity metric of the collection
+    pub metric: String,
+}
+
 /// Handler: Insert NoSQL Document (JSON/Serde to Sled)
 #[utoipa::path(
     post,
     path = "/collections/{collection_id}/docs",
     request_body = InsertDocRest,
     responses(
-        (status = 200, description = "Document inserted successfully", body = RestResponse),
+        (status = 200, description = "Document inserted successfully", body = InsertDocResponse),
         (status = 500, description = "Internal server error")
     ),
     params(
@@ -537,10 +1017,11 @@ async fn get_collections_handler(
 )]
 async fn insert_doc_handler(
     State(state): State<Arc<AppState>>,
-    Path(collection_id): Path<String>,
-    Json(payload): Json<InsertDocRest>,
-) -> Result<Json<RestResponse>, StatusCode> {
+    ResolvedCollection(collection_id): ResolvedCollection,
+    StrictJson(payload): StrictJson<InsertDocRest>,
+) -> Result<Json<InsertDocResponse>, AppError> {
     debug!(collection_id = %collection_id, doc_id = %payload.id, "REST insert doc request");
+    let started = std::time::Instant::now();
     
     // Parse JSON metadata for NoSQL doc
     let metadata_json: serde_json::Value = serde_json::from_str(&payload.metadata_json)
@@ -552,47 +1033,81 @@ async fn insert_doc_handler(
         category: payload.category,
         vector: payload.vector,
         metadata: metadata_json,
+        vectors: payload.vectors,
+        updated_at: None,
+        source_uri: payload.source_uri,
+        ingested_by: payload.ingested_by,
     };
 
+    state.storage.validate_doc_vectors(&doc).map_err(|e| {
+        warn!(error = %e, collection_id = %collection_id, doc_id = %payload.id, "Rejected document vectors");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected document vectors: {}", e))
+    })?;
+    reject_dimension_mismatch(&state.storage, &collection_id, std::slice::from_ref(&doc))?;
+
     // Insert to unified storage
-    if state.storage.insert_doc(doc.clone(), &collection_id).is_ok() {
-        info!(collection_id = %collection_id, doc_id = %payload.id, "Document inserted via REST");
+    match state.storage.insert_doc(doc.clone(), &collection_id) {
+        Ok(_) => {
+            state.metrics.observe(metrics::Operation::Insert, started.elapsed());
+            info!(collection_id = %collection_id, doc_id = %payload.id, latency_ms = started.elapsed().as_millis() as u64, "Document inserted via REST");
+            let vector_config = state.storage.record_vector_dim(&collection_id, doc.vector.len()).map_err(|e| {
+                error!(error = %e, collection_id = %collection_id, "Failed to record collection vector config");
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to record collection vector config: {}", e))
+            })?;
         
-        // Publish CDC event
-        let doc_json = serde_json::json!({
-            "id": doc.id,
-            "text": doc.text,
-            "category": doc.category,
-            "vector": doc.vector,
-            "metadata": doc.metadata,
-        });
-        state.pubsub.publish(CdcEvent {
-            event_type: crate::events::EventType::Insert,
-            collection: collection_id.clone(),
-            id: payload.id.clone(),
-            data: Some(doc_json),
-            timestamp: chrono::Utc::now().timestamp(),
-        });
+            // Publish CDC event
+            let doc_json = serde_json::json!({
+                "id": doc.id,
+                "text": doc.text,
+                "category": doc.category,
+                "vector": doc.vector,
+                "metadata": doc.metadata,
+            });
+            state.pubsub.publish(CdcEvent {
+                event_type: crate::events::EventType::Insert,
+                collection: collection_id.clone(),
+                id: payload.id.clone(),
+                data: Some(doc_json),
+                timestamp: chrono::Utc::now().timestamp(),
+            });
         
-        Ok(Json(RestResponse {
-            success: true,
-            message: "NoSQL JSON doc inserted to Sled".to_string(),
-            results: vec![],
-            cache_hits: None,
-        }))
-    } else {
-        error!(collection_id = %collection_id, doc_id = %payload.id, "Failed to insert document");
-        Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Ok(Json(InsertDocResponse {
+                success: true,
+                message: "NoSQL JSON doc inserted to Sled".to_string(),
+                results: vec![],
+                dim: vector_config.dim,
+                metric: vector_config.metric.as_str().to_string(),
+            }))
+        }
+        Err(e) => {
+            error!(error = %e, collection_id = %collection_id, doc_id = %payload.id, "Failed to insert document");
+            Err(AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to insert document: {}", e)))
+        }
     }
 }
 
+/// 400 when a vector's length doesn't match the collection's dimension (see `Storage::check_vector_dims`)
+fn reject_dimension_mismatch(storage: &Storage, collection_id: &str, docs: &[Document]) -> Result<(), AppError> {
+    storage.check_vector_dims(collection_id, docs).map_err(|e| {
+        if e.downcast_ref::<DimensionMismatch>().is_some() {
+            warn!(error = %e, collection_id = %collection_id, "Rejected vector of the wrong dimension");
+            AppError::bad_request(e.to_string())
+        } else {
+            error!(error = %e, collection_id = %collection_id, "Failed to check vector dimension");
+            AppError::internal(format!("Failed to check vector dimension: {}", e))
+        }
+    })
+}
+
 /// Handler: Batch Insert NoSQL Documents
+///
+/// Invalid documents are reported in `results` and skipped; the rest are still inserted.
 #[utoipa::path(
     post,
     path = "/collections/{collection_id}/docs/batch",
     request_body = BatchInsertDocRest,
     responses(
-        (status = 200, description = "Batch of documents inserted successfully", body = RestResponse),
+        (status = 200, description = "Valid documents inserted; per-document outcome in results", body = BatchInsertDocResponse),
         (status = 500, description = "Internal server error")
     ),
     params(
@@ -605,37 +1120,39 @@ async fn insert_doc_handler(
 async fn batch_insert_doc_handler(
     State(state): State<Arc<AppState>>,
     Path(collection_id): Path<String>,
-    Json(payload): Json<BatchInsertDocRest>,
-) -> Result<Json<RestResponse>, StatusCode> {
-    debug!(collection_id = %collection_id, count = payload.documents.len(), "REST batch insert doc request");
-    
-    let mut docs = Vec::new();
-    for p in &payload.documents {
-        let metadata_json: serde_json::Value = serde_json::from_str(&p.metadata_json)
-            .unwrap_or(serde_json::json!({}));
-        docs.push(Document {
-            id: p.id.clone(),
-            text: p.text.clone(),
-            category: p.category.clone(),
-            vector: p.vector.clone(),
-            metadata: metadata_json,
-        });
-    }
+    StrictJson(payload): StrictJson<BatchInsertDocRest>,
+) -> Result<Json<BatchInsertDocResponse>, AppError> {
+    let documents = payload.into_documents();
+    debug!(collection_id = %collection_id, count = documents.len(), "REST batch insert doc request");
+    let started = std::time::Instant::now();
 
-    let payload_len = payload.documents.len();
+    let docs: Vec<Document> = documents.into_iter().map(|p| Document {
+        id: p.id,
+        text: p.text,
+        category: p.category,
+        vector: p.vector,
+        metadata: serde_json::from_str(&p.metadata_json).unwrap_or(serde_json::json!({})),
+        vectors: p.vectors,
+        updated_at: None,
+        source_uri: p.source_uri,
+        ingested_by: p.ingested_by,
+    }).collect();
 
-    if state.storage.insert_docs(docs, &collection_id).is_ok() {
-        info!(collection_id = %collection_id, count = payload_len, "Batch of documents inserted via REST");
-        Ok(Json(RestResponse {
-            success: true,
-            message: format!("Batch of {} docs inserted", payload_len),
-            results: vec![],
-            cache_hits: None,
-        }))
-    } else {
-        error!(collection_id = %collection_id, "Failed to insert batch of documents");
-        Err(StatusCode::INTERNAL_SERVER_ERROR)
-    }
+    let results = state.storage.insert_docs_partial(docs, &collection_id).map_err(|e| {
+        error!(error = %e, collection_id = %collection_id, "Failed to insert batch of documents");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to insert batch of documents: {}", e))
+    })?;
+    let inserted = results.iter().filter(|r| r.status == InsertStatus::Inserted).count();
+    let rejected = results.len() - inserted;
+    state.metrics.observe(metrics::Operation::Insert, started.elapsed());
+    info!(collection_id = %collection_id, inserted = inserted, rejected = rejected, latency_ms = started.elapsed().as_millis() as u64, "Batch of documents inserted via REST");
+    Ok(Json(BatchInsertDocResponse {
+        success: rejected == 0,
+        message: format!("Batch of {} docs inserted, {} rejected", inserted, rejected),
+        inserted,
+        rejected,
+        results,
+    }))
 }
 
 /// Handler: SQL query via DataFusion (on NoSQL Arrow projection)
@@ -644,11 +1161,13 @@ async fn batch_insert_doc_handler(
     path = "/collections/{collection_id}/sql",
     request_body = SqlRest,
     responses(
-        (status = 200, description = "SQL query executed successfully", body = RestResponse),
+        (status = 200, description = "SQL query executed successfully", body = SqlRestResponse),
         (status = 400, description = "Bad request")
     ),
     params(
-        ("collection_id" = String, Path, description = "Collection ID")
+        ("collection_id" = String, Path, description = "Collection ID"),
+        ("mode" = Option<String>, Query, description = "`rows` (default) adds full row objects; `ids` returns only first-column IDs"),
+        ("format" = Option<String>, Query, description = "`json` (default) or `arrow` for the raw batches as an Arrow IPC stream; without it, `Accept: application/vnd.apache.arrow.stream` selects Arrow")
     ),
     security(
         ("bearerAuth" = [])
@@ -657,16 +1176,20 @@ async fn batch_insert_doc_handler(
 async fn sql_handler(
     State(state): State<Arc<AppState>>,
     Path(collection_id): Path<String>,
+    Query(query): Query<SqlModeQuery>,
+    headers: HeaderMap,
     Json(payload): Json<SqlRest>,
-) -> Result<Json<RestResponse>, StatusCode> {
-    debug!(collection_id = %collection_id, sql = %payload.sql, "REST SQL query request");
+) -> Result<Response, AppError> {
+    let format = query.format(&headers);
+    debug!(collection_id = %collection_id, sql = %payload.sql, mode = ?query.mode, ?format, "REST SQL query request");
+    let started = std::time::Instant::now();
 
     // Init query engine (uses fixed project_to_arrow for compat)
     let query_engine = QueryEngine::new(state.storage.clone(), &collection_id)
         .await
         .map_err(|e| {
             error!(error = %e, collection_id = %collection_id, "DataFusion init failed");
-            StatusCode::INTERNAL_SERVER_ERROR
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("DataFusion init failed: {}", e))
         })?;
 
     // Exec SQL ; catch DataFusion/Arrow errors (e.g., parse , empty , type mismatch)
@@ -674,9 +1197,29 @@ async fn sql_handler(
         .await
         .map_err(|e| {
             error!(error = %e, sql = %payload.sql, "SQL execution failed");
-            StatusCode::BAD_REQUEST
+            AppError::new(StatusCode::BAD_REQUEST, format!("SQL execution failed: {}", e))
         })?;
 
+    if format == SqlResultFormat::Arrow {
+        // Same IPC stream the gRPC SqlQuery returns in `arrow_data`
+        let bytes = encode_ipc_stream(&results).map_err(|e| {
+            error!(error = %e, sql = %payload.sql, "Failed to encode SQL batches as Arrow IPC");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to encode SQL batches as Arrow IPC: {}", e))
+        })?;
+        let row_count: usize = results.iter().map(|b| b.num_rows()).sum();
+        state.metrics.observe(metrics::Operation::Sql, started.elapsed());
+        info!(collection_id = %collection_id, sql = %payload.sql, row_count = row_count, bytes = bytes.len(), latency_ms = started.elapsed().as_millis() as u64, "SQL query executed via REST as Arrow");
+        return Ok(([(header::CONTENT_TYPE, HeaderValue::from_static(ARROW_STREAM_MEDIA_TYPE))], bytes).into_response());
+    }
+
+    let rows = match query.mode {
+        SqlResultMode::Ids => None,
+        SqlResultMode::Rows => Some(batches_to_json_rows(&results).map_err(|e| {
+            error!(error = %e, sql = %payload.sql, "Failed to serialize SQL rows");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to serialize SQL rows: {}", e))
+        })?),
+    };
+
     // Extract IDs/results from Arrow batches (robust parse ; handles SELECT)
     let mut res_ids = vec![];
     for batch in results {
@@ -691,15 +1234,33 @@ async fn sql_handler(
         }
     }
     
-    info!(collection_id = %collection_id, sql = %payload.sql, row_count = res_ids.len(), "SQL query executed via REST");
+    state.metrics.observe(metrics::Operation::Sql, started.elapsed());
+    info!(collection_id = %collection_id, sql = %payload.sql, row_count = res_ids.len(), latency_ms = started.elapsed().as_millis() as u64, "SQL query executed via REST");
 
     // Return full response (even for UPDATE/DELETE stub note ; ensures body)
-    Ok(Json(RestResponse {
-        success: true,
-        message: format!("SQL executed: {} rows", res_ids.len()),
-        results: res_ids,
-        cache_hits: None,
-    }))
+    Ok(Json(SqlRestResponse {
+        response: RestResponse {
+            success: true,
+            message: format!("SQL executed: {} rows", res_ids.len()),
+            results: res_ids,
+            cache_hits: None,
+        },
+        rows,
+    }).into_response())
+}
+
+/// Result rows of Arrow batches as JSON objects keyed by column name (NULL columns omitted)
+fn batches_to_json_rows(batches: &[arrow::record_batch::RecordBatch]) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
+    let mut writer = arrow::json::ArrayWriter::new(Vec::new());
+    for batch in batches.iter().filter(|b| b.num_rows() > 0) {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    let bytes = writer.into_inner();
+    if bytes.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(serde_json::from_slice(&bytes)?)
 }
 
 /// Handler: Aggregation pipeline
@@ -723,20 +1284,20 @@ async fn aggregate_handler(
     State(state): State<Arc<AppState>>,
     Path(collection_id): Path<String>,
     Json(payload): Json<AggregationRest>,
-) -> Result<Json<AggregationResponse>, StatusCode> {
+) -> Result<Json<AggregationResponse>, AppError> {
     debug!(collection_id = %collection_id, "REST aggregation request");
 
     let pipeline_value = serde_json::Value::Array(payload.pipeline);
     let pipeline = AggregationPipeline::from_value(pipeline_value)
         .map_err(|e| {
             error!(error = %e, collection_id = %collection_id, "Aggregation pipeline parse failed");
-            StatusCode::BAD_REQUEST
+            AppError::new(StatusCode::BAD_REQUEST, format!("Aggregation pipeline parse failed: {}", e))
         })?;
 
     let engine = AggregationEngine::new(state.storage.clone(), &collection_id);
     let results = engine.execute(pipeline).map_err(|e| {
         error!(error = %e, collection_id = %collection_id, "Aggregation execution failed");
-        StatusCode::INTERNAL_SERVER_ERROR
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Aggregation execution failed: {}", e))
     })?;
 
     Ok(Json(AggregationResponse {
@@ -762,8 +1323,9 @@ async fn aggregate_handler(
 )]
 async fn cross_collection_query_handler(
     State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
     Json(payload): Json<CrossCollectionQueryRest>,
-) -> Result<Json<CrossCollectionQueryResponse>, StatusCode> {
+) -> Result<Json<CrossCollectionQueryResponse>, AppError> {
     debug!(source = %payload.source, "REST cross-collection query request");
 
     let pipeline_value = serde_json::json!({
@@ -773,13 +1335,14 @@ async fn cross_collection_query_handler(
 
     let pipeline = CrossCollectionPipeline::from_value(pipeline_value).map_err(|e| {
         error!(error = %e, "Cross-collection pipeline parse failed");
-        StatusCode::BAD_REQUEST
+        AppError::new(StatusCode::BAD_REQUEST, format!("Cross-collection pipeline parse failed: {}", e))
     })?;
+    require_body_collections(&state.storage, &claims, pipeline.collections())?;
 
     let engine = CrossCollectionEngine::new(state.storage.clone());
     let results = engine.execute(pipeline).map_err(|e| {
         error!(error = %e, "Cross-collection execution failed");
-        StatusCode::INTERNAL_SERVER_ERROR
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Cross-collection execution failed: {}", e))
     })?;
 
     Ok(Json(CrossCollectionQueryResponse {
@@ -805,8 +1368,9 @@ async fn cross_collection_query_handler(
 )]
 async fn multi_collection_operation_handler(
     State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
     Json(payload): Json<MultiCollectionOperationRest>,
-) -> Result<Json<MultiCollectionOperationResponse>, StatusCode> {
+) -> Result<Json<MultiCollectionOperationResponse>, AppError> {
     debug!(operation = %payload.operation, "REST multi-collection operation request");
 
     let operation_value = serde_json::json!({
@@ -817,15 +1381,16 @@ async fn multi_collection_operation_handler(
 
     let operation = MultiCollectionOperation::from_value(operation_value).map_err(|e| {
         error!(error = %e, "Multi-collection operation parse failed");
-        StatusCode::BAD_REQUEST
+        AppError::new(StatusCode::BAD_REQUEST, format!("Multi-collection operation parse failed: {}", e))
     })?;
+    require_body_collections(&state.storage, &claims, operation.target_collections.iter().map(String::as_str))?;
 
     let engine = CrossCollectionEngine::new(state.storage.clone());
     let results = engine
         .execute_multi_collection_operation(operation)
         .map_err(|e| {
             error!(error = %e, "Multi-collection operation failed");
-            StatusCode::INTERNAL_SERVER_ERROR
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Multi-collection operation failed: {}", e))
         })?;
 
     Ok(Json(MultiCollectionOperationResponse {
@@ -842,10 +1407,14 @@ async fn multi_collection_operation_handler(
     request_body = TextSearchRest,
     responses(
         (status = 200, description = "Text search executed successfully", body = TextSearchResponse),
+        (status = 404, description = "No results (only with AIDB_EMPTY_RESULTS=not_found)"),
+        (status = 400, description = "Invalid query parameters"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("collection_id" = String, Path, description = "Collection ID")
+        ("collection_id" = String, Path, description = "Collection ID"),
+        ("limit" = Option<usize>, Query, description = "Maximum results to return (default AIDB_PAGE_DEFAULT or 100, capped at AIDB_PAGE_MAX or 1000)"),
+        ("offset" = Option<usize>, Query, description = "Results to skip before the page starts")
     ),
     security(
         ("bearerAuth" = [])
@@ -854,8 +1423,11 @@ async fn multi_collection_operation_handler(
 async fn text_search_handler(
     State(state): State<Arc<AppState>>,
     Path(collection_id): Path<String>,
+    params: QueryParams,
+    payload_limit: PayloadLimit,
+    empty: EmptyResults,
     Json(payload): Json<TextSearchRest>,
-) -> Result<Json<TextSearchResponse>, StatusCode> {
+) -> Result<Json<TextSearchResponse>, AppError> {
     let docs = state.storage.search_docs_text(
         &collection_id,
         &payload.query,
@@ -864,19 +1436,19 @@ async fn text_search_handler(
         payload.include_metadata,
     ).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let results: Vec<DocumentSummary> = docs
+    let results: Vec<DocumentSummary> = params.paginate(docs)
         .into_iter()
-        .map(|doc| DocumentSummary {
-            id: doc.id,
-            text: doc.text,
-            category: doc.category,
-        })
+        .map(|doc| DocumentSummary::project(doc, payload.return_fields.as_deref()))
         .collect();
+    let (results, continuation) = payload_limit.fit(results, params.offset);
+    empty.check(results.is_empty())?;
 
     Ok(Json(TextSearchResponse {
         success: true,
         message: format!("Text search matched {} documents", results.len()),
         results,
+        truncated: continuation.is_some(),
+        next_cursor: continuation.map(|c| c.next_cursor),
     }))
 }
 
@@ -887,11 +1459,13 @@ async fn text_search_handler(
     path = "/collections/{collection_id}/hybrid",
     request_body = HybridRest,
     responses(
-        (status = 200, description = "Hybrid search completed successfully", body = RestResponse),
+        (status = 200, description = "Hybrid search completed successfully", body = HybridRestResponse),
+        (status = 404, description = "No results (only with AIDB_EMPTY_RESULTS=not_found)"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("collection_id" = String, Path, description = "Collection ID")
+        ("collection_id" = String, Path, description = "Collection ID"),
+        ("explain" = Option<bool>, Query, description = "Attach a per-result explanation")
     ),
     security(
         ("bearerAuth" = [])
@@ -900,45 +1474,86 @@ async fn text_search_handler(
 async fn hybrid_handler(
     State(state): State<Arc<AppState>>,
     Path(collection_id): Path<String>,
+    Query(query): Query<HybridExplainQuery>,
+    empty: EmptyResults,
     Json(payload): Json<HybridRest>,
-) -> Result<Json<RestResponse>, StatusCode> {
+) -> Result<Json<HybridRestResponse>, AppError> {
     debug!(
         collection_id = %collection_id,
         sql_filter = %payload.sql_filter,
         top_k = payload.top_k,
         "REST hybrid search request"
     );
+    let started = std::time::Instant::now();
     
+    state.storage.check_vector("query", &payload.query_vector).map_err(|e| {
+        warn!(error = %e, collection_id = %collection_id, "Rejected hybrid query vector");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected hybrid query vector: {}", e))
+    })?;
+
     // Use hybrid planner for push-down
     let query_engine = QueryEngine::new(state.storage.clone(), &collection_id)
         .await
         .map_err(|e| {
             error!(error = %e, collection_id = %collection_id, "Query engine init failed");
-            StatusCode::INTERNAL_SERVER_ERROR
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Query engine init failed: {}", e))
         })?;
 
-    let docs: Vec<(Document, bool)> = query_engine.hybrid_query(&payload.sql_filter, &payload.query_vector, payload.top_k)
+    let timeout = payload.timeout_ms.filter(|ms| *ms > 0).map(std::time::Duration::from_millis).or(state.storage.default_query_timeout());
+    let outcome = query_engine.hybrid_query_with_timeout(&payload.sql_filter, &payload.query_vector, payload.top_k, timeout)
         .await
         .map_err(|e| {
             error!(error = %e, collection_id = %collection_id, "Hybrid query failed");
-            StatusCode::INTERNAL_SERVER_ERROR
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Hybrid query failed: {}", e))
         })?;
 
+    let explanations = if query.explain {
+        let explained = query_engine
+            .explain_hybrid(&payload.sql_filter, &payload.query_vector, payload.keywords.as_deref(), &outcome)
+            .map_err(|e| {
+                error!(error = %e, collection_id = %collection_id, "Hybrid explain failed");
+                AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Hybrid explain failed: {}", e))
+            })?;
+        Some(
+            outcome
+                .docs
+                .iter()
+                .zip(explained)
+                .map(|((doc, _), explanation)| ExplainedHybridResult { id: doc.id.clone(), explanation })
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    let docs = outcome.docs;
     let results: Vec<String> = docs.iter().map(|(doc, _)| doc.id.clone()).collect();
     let cache_hits: Vec<bool> = docs.iter().map(|(_, from_cache)| *from_cache).collect();
+    let documents = payload.return_fields.as_deref().map(|fields| {
+        docs.into_iter().map(|(doc, _)| DocumentSummary::project(doc, Some(fields))).collect::<Vec<_>>()
+    });
     
+    state.metrics.observe(metrics::Operation::Search, started.elapsed());
     info!(
         collection_id = %collection_id,
         results_count = results.len(),
         cache_hits_count = cache_hits.iter().filter(|&&h| h).count(),
+        partial = outcome.partial,
+        latency_ms = started.elapsed().as_millis() as u64,
         "Hybrid search completed via REST"
     );
+    empty.check(results.is_empty())?;
 
-    Ok(Json(RestResponse {
-        success: true,
-        message: format!("Hybrid search found {} docs", results.len()),
-        results,
-        cache_hits: Some(cache_hits),
+    Ok(Json(HybridRestResponse {
+        response: RestResponse {
+            success: true,
+            message: format!("Hybrid search found {} docs", results.len()),
+            results,
+            cache_hits: Some(cache_hits),
+        },
+        partial: outcome.partial,
+        explanations,
+        documents,
     }))
 }
 
@@ -948,14 +1563,114 @@ pub struct HybridRest {
     pub sql_filter: String,
     pub query_vector: Vec<f32>,
     pub top_k: usize,
+    /// Deadline for the query in milliseconds (defaults to AIDB_QUERY_TIMEOUT_MS); on expiry the
+    /// candidates gathered so far are returned with `partial: true`
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Keywords scored against each result's text in explanations (ranking is unaffected)
+    #[serde(default)]
+    pub keywords: Option<String>,
+    /// Also return each result's document reduced to these fields, as `documents`
+    #[serde(default)]
+    pub return_fields: Option<Vec<ReturnField>>,
+}
+
+/// Query for POST /collections/:collection_id/hybrid
+#[derive(Deserialize, Default)]
+pub struct HybridExplainQuery {
+    /// Attach an `explanations` entry per result
+    #[serde(default)]
+    pub explain: bool,
+}
+
+/// One hybrid result and why it was returned
+#[derive(Serialize)]
+pub struct ExplainedHybridResult {
+    pub id: String,
+    pub explanation: HybridExplanation,
+}
+
+/// Response for hybrid REST: the generic response plus whether a timeout cut it short
+#[derive(Serialize, ToSchema)]
+pub struct HybridRestResponse {
+    #[serde(flatten)]
+    pub response: RestResponse,
+    pub partial: bool,
+    /// Per-result explanations in result order (only with `?explain=true`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Object>>)]
+    pub explanations: Option<Vec<ExplainedHybridResult>>,
+    /// Result documents in result order, projected to `return_fields` (only when requested)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub documents: Option<Vec<DocumentSummary>>,
 }
 
 /// DTO for SQL REST
-#[derive(Deserialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct SqlRest {
     pub sql: String,
 }
 
+/// What POST /collections/:collection_id/sql returns besides the first-column IDs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlResultMode {
+    /// Only `results` (first-column IDs)
+    Ids,
+    /// `results` plus every row as an object under `rows`
+    #[default]
+    Rows,
+}
+
+/// Body encoding of POST /collections/:collection_id/sql
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SqlResultFormat {
+    /// `SqlRestResponse` as JSON
+    #[default]
+    Json,
+    /// The result batches as an Arrow IPC stream (`application/vnd.apache.arrow.stream`)
+    Arrow,
+}
+
+/// Media type of an Arrow IPC stream body
+pub const ARROW_STREAM_MEDIA_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// Query for POST /collections/:collection_id/sql
+#[derive(Deserialize, Default)]
+pub struct SqlModeQuery {
+    #[serde(default)]
+    pub mode: SqlResultMode,
+    /// Defaults to Arrow when the Accept header names the Arrow stream type, else JSON
+    #[serde(default)]
+    pub format: Option<SqlResultFormat>,
+}
+
+impl SqlModeQuery {
+    /// `?format=` when given, otherwise whatever the Accept header asks for
+    fn format(&self, headers: &HeaderMap) -> SqlResultFormat {
+        self.format.unwrap_or_else(|| {
+            let accepts_arrow = headers
+                .get_all(header::ACCEPT)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .any(|media| media.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case(ARROW_STREAM_MEDIA_TYPE));
+            if accepts_arrow { SqlResultFormat::Arrow } else { SqlResultFormat::Json }
+        })
+    }
+}
+
+/// Response for SQL REST: first-column IDs in `results`, full rows unless `?mode=ids`
+#[derive(Serialize, ToSchema)]
+pub struct SqlRestResponse {
+    #[serde(flatten)]
+    pub response: RestResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Object>>)]
+    pub rows: Option<Vec<serde_json::Value>>,
+}
+
 /// Health check handler
 #[utoipa::path(
     get,
@@ -974,6 +1689,26 @@ async fn health_handler() -> Json<RestResponse> {
     })
 }
 
+/// Handler: Prometheus metrics (index staleness, cache and request counters, see `rest::metrics`)
+/// GET /metrics
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    debug!("REST metrics scrape");
+    let storage = state.storage.clone();
+    let freshness = tokio::task::spawn_blocking(move || storage.index_freshness().map_err(|e| e.to_string()))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            error!(error = %e, "Metrics collection failed");
+            AppError::internal(format!("Metrics collection failed: {}", e))
+        })?;
+
+    let mut text = metrics::PrometheusText::default();
+    metrics::render_index_freshness(&mut text, &freshness);
+    metrics::render_cache_stats(&mut text, &state.storage.cache_stats());
+    state.metrics.render(&mut text);
+    Ok(([(header::CONTENT_TYPE, HeaderValue::from_static(metrics::PROMETHEUS_CONTENT_TYPE))], text.finish()).into_response())
+}
+
 /// WebSocket handler for real-time CDC streaming
 async fn ws_handler(
     ws: WebSocketUpgrade,
@@ -1074,8 +1809,8 @@ type UpdateDocRest = InsertDocRest;
 async fn update_doc_handler(
     State(state): State<Arc<AppState>>,
     Path(collection_id): Path<String>,
-    Json(payload): Json<UpdateDocRest>,
-) -> Result<Json<RestResponse>, StatusCode> {
+    StrictJson(payload): StrictJson<UpdateDocRest>,
+) -> Result<Json<RestResponse>, AppError> {
     debug!(collection_id = %collection_id, doc_id = %payload.id, "REST update doc request");
     
     // Parse JSON , create/update Document
@@ -1087,36 +1822,49 @@ async fn update_doc_handler(
         category: payload.category,
         vector: payload.vector,
         metadata: metadata_json,
+        vectors: payload.vectors,
+        updated_at: None,
+        source_uri: payload.source_uri,
+        ingested_by: payload.ingested_by,
     };
 
-    if state.storage.update_doc(doc.clone(), &collection_id).is_ok() {
-        info!(collection_id = %collection_id, doc_id = %payload.id, "Document updated via REST");
+    state.storage.validate_doc_vectors(&doc).map_err(|e| {
+        warn!(error = %e, collection_id = %collection_id, doc_id = %payload.id, "Rejected document vectors");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected document vectors: {}", e))
+    })?;
+    reject_dimension_mismatch(&state.storage, &collection_id, std::slice::from_ref(&doc))?;
+
+    match state.storage.update_doc(doc.clone(), &collection_id) {
+        Ok(_) => {
+            info!(collection_id = %collection_id, doc_id = %payload.id, "Document updated via REST");
         
-        // Publish CDC event
-        let doc_json = serde_json::json!({
-            "id": doc.id,
-            "text": doc.text,
-            "category": doc.category,
-            "vector": doc.vector,
-            "metadata": doc.metadata,
-        });
-        state.pubsub.publish(CdcEvent {
-            event_type: crate::events::EventType::Update,
-            collection: collection_id.clone(),
-            id: payload.id.clone(),
-            data: Some(doc_json),
-            timestamp: chrono::Utc::now().timestamp(),
-        });
+            // Publish CDC event
+            let doc_json = serde_json::json!({
+                "id": doc.id,
+                "text": doc.text,
+                "category": doc.category,
+                "vector": doc.vector,
+                "metadata": doc.metadata,
+            });
+            state.pubsub.publish(CdcEvent {
+                event_type: crate::events::EventType::Update,
+                collection: collection_id.clone(),
+                id: payload.id.clone(),
+                data: Some(doc_json),
+                timestamp: chrono::Utc::now().timestamp(),
+            });
         
-        Ok(Json(RestResponse {
-            success: true,
-            message: "NoSQL doc updated".to_string(),
-            results: vec![],
-            cache_hits: None,
-        }))
-    } else {
-        error!(collection_id = %collection_id, doc_id = %payload.id, "Failed to update document");
-        Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Ok(Json(RestResponse {
+                success: true,
+                message: "NoSQL doc updated".to_string(),
+                results: vec![],
+                cache_hits: None,
+            }))
+        }
+        Err(e) => {
+            error!(error = %e, collection_id = %collection_id, doc_id = %payload.id, "Failed to update document");
+            Err(AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update document: {}", e)))
+        }
     }
 }
 
@@ -1124,84 +1872,405 @@ async fn update_doc_handler(
 async fn delete_doc_handler(
     State(state): State<Arc<AppState>>,
     Path((collection_id, doc_id)): Path<(String, String)>,
-) -> Result<Json<RestResponse>, StatusCode> {
+) -> Result<Json<RestResponse>, AppError> {
     debug!(collection_id = %collection_id, doc_id = %doc_id, "REST delete doc request");
     
-    if state.storage.delete_doc(&collection_id, &doc_id).is_ok() {
-        info!(collection_id = %collection_id, doc_id = %doc_id, "Document deleted via REST");
+    match state.storage.delete_doc(&collection_id, &doc_id) {
+        Ok(_) => {
+            info!(collection_id = %collection_id, doc_id = %doc_id, "Document deleted via REST");
         
-        // Publish CDC event
-        state.pubsub.publish(CdcEvent {
-            event_type: crate::events::EventType::Delete,
-            collection: collection_id.clone(),
-            id: doc_id.clone(),
-            data: None,
-            timestamp: chrono::Utc::now().timestamp(),
-        });
+            // Publish CDC event
+            state.pubsub.publish(CdcEvent {
+                event_type: crate::events::EventType::Delete,
+                collection: collection_id.clone(),
+                id: doc_id.clone(),
+                data: None,
+                timestamp: chrono::Utc::now().timestamp(),
+            });
         
-        Ok(Json(RestResponse {
-            success: true,
-            message: format!("Doc {} deleted", doc_id),
-            results: vec![],
-            cache_hits: None,
-        }))
-    } else {
-        warn!(collection_id = %collection_id, doc_id = %doc_id, "Document not found for deletion");
-        Err(StatusCode::NOT_FOUND)
+            Ok(Json(RestResponse {
+                success: true,
+                message: format!("Doc {} deleted", doc_id),
+                results: vec![],
+                cache_hits: None,
+            }))
+        }
+        Err(e) => {
+            warn!(error = %e, collection_id = %collection_id, doc_id = %doc_id, "Document not found for deletion");
+            Err(AppError::new(StatusCode::NOT_FOUND, format!("Document not found for deletion: {}", e)))
+        }
+    }
+}
+
+/// Most IDs accepted by one POST /collections/:collection_id/docs/bulk_delete
+const MAX_BULK_DELETE_IDS: usize = 10_000;
+
+/// Request for POST /collections/:collection_id/docs/bulk_delete
+#[derive(Deserialize)]
+pub struct BulkDeleteRest {
+    pub ids: Vec<String>,
+}
+
+/// Response for POST /collections/:collection_id/docs/bulk_delete
+#[derive(Serialize)]
+pub struct BulkDeleteResponse {
+    pub deleted: usize,
+    pub not_found: usize,
+    /// One entry per requested ID, in request order
+    pub results: Vec<BulkDeleteResult>,
+}
+
+/// Handler: Delete many documents by ID in one batch
+/// POST /collections/:collection_id/docs/bulk_delete
+async fn bulk_delete_docs_handler(
+    State(state): State<Arc<AppState>>,
+    Path(collection_id): Path<String>,
+    Json(payload): Json<BulkDeleteRest>,
+) -> Result<Json<BulkDeleteResponse>, AppError> {
+    debug!(collection_id = %collection_id, count = payload.ids.len(), "REST bulk delete request");
+    if payload.ids.len() > MAX_BULK_DELETE_IDS {
+        warn!(count = payload.ids.len(), max = MAX_BULK_DELETE_IDS, "Rejected oversized bulk delete");
+        return Err(AppError::bad_request(format!("At most {} IDs per bulk delete, got {}", MAX_BULK_DELETE_IDS, payload.ids.len())));
+    }
+
+    let storage = state.storage.clone();
+    let col = collection_id.clone();
+    let results = tokio::task::spawn_blocking(move || {
+        storage.delete_docs_batch(&col, &payload.ids).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(collection_id = %collection_id, error = %e, "Bulk delete failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Bulk delete failed: {}", e))
+    })?;
+
+    let deleted: Vec<&BulkDeleteResult> = results.iter().filter(|r| r.status == DeleteStatus::Deleted).collect();
+    for result in &deleted {
+        state.pubsub.publish(CdcEvent {
+            event_type: crate::events::EventType::Delete,
+            collection: collection_id.clone(),
+            id: result.id.clone(),
+            data: None,
+            timestamp: chrono::Utc::now().timestamp(),
+        });
+    }
+    let deleted = deleted.len();
+    info!(collection_id = %collection_id, deleted = deleted, requested = results.len(), "Documents bulk deleted via REST");
+    Ok(Json(BulkDeleteResponse {
+        deleted,
+        not_found: results.len() - deleted,
+        results,
+    }))
+}
+
+/// Request for POST /collections/:collection_id/docs/update_by_query
+#[derive(Deserialize)]
+pub struct UpdateByQueryRest {
+    /// Which documents to patch, as in an aggregation `$match` stage: `filters` over `id`, `text`,
+    /// `category` and `metadata.<field>` paths, combined with `logic` (`and` by default)
+    #[serde(flatten)]
+    pub filter: MatchStage,
+    /// Merged into each matching document's metadata; `null` removes a field
+    pub patch: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Response for POST /collections/:collection_id/docs/update_by_query
+#[derive(Serialize)]
+pub struct UpdateByQueryResponse {
+    pub success: bool,
+    /// Documents that matched the filter
+    pub matched: usize,
+    /// Matched documents whose metadata changed (the rest already had the patched values)
+    pub updated: usize,
+}
+
+/// Handler: Patch the metadata of every document matching a filter in one batch
+/// POST /collections/:collection_id/docs/update_by_query
+async fn update_by_query_handler(
+    State(state): State<Arc<AppState>>,
+    Path(collection_id): Path<String>,
+    Json(payload): Json<UpdateByQueryRest>,
+) -> Result<Json<UpdateByQueryResponse>, AppError> {
+    debug!(collection_id = %collection_id, filters = payload.filter.filters.len(), fields = payload.patch.len(), "REST update by query request");
+    if payload.filter.filters.is_empty() || payload.patch.is_empty() {
+        warn!(collection_id = %collection_id, "Rejected update by query without filters or patch");
+        return Err(AppError::bad_request("update_by_query needs at least one filter and a non-empty patch"));
+    }
+
+    let storage = state.storage.clone();
+    let col = collection_id.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        storage
+            .patch_metadata_where(&col, |doc| payload.filter.matches(&pipeline_doc(doc, &col)), &payload.patch)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(collection_id = %collection_id, error = %e, "Update by query failed");
+        AppError::internal(format!("Update by query failed: {}", e))
+    })?;
+
+    for doc in &report.updated {
+        state.pubsub.publish(CdcEvent {
+            event_type: crate::events::EventType::Update,
+            collection: collection_id.clone(),
+            id: doc.id.clone(),
+            data: Some(serde_json::json!({
+                "id": doc.id,
+                "text": doc.text,
+                "category": doc.category,
+                "vector": doc.vector,
+                "metadata": doc.metadata,
+            })),
+            timestamp: chrono::Utc::now().timestamp(),
+        });
+    }
+    info!(collection_id = %collection_id, matched = report.matched, updated = report.updated.len(), "Documents updated by query via REST");
+    Ok(Json(UpdateByQueryResponse {
+        success: true,
+        matched: report.matched,
+        updated: report.updated.len(),
+    }))
+}
+
+/// Most values a single metadata append accepts
+const MAX_METADATA_APPEND_VALUES: usize = 1000;
+
+/// Request for POST /collections/:collection_id/docs/:doc_id/metadata/append
+#[derive(Deserialize)]
+pub struct AppendMetadataRest {
+    /// Top-level metadata field holding the array (created when missing)
+    pub field: String,
+    pub values: Vec<serde_json::Value>,
+    /// Skip values the array already contains
+    #[serde(default)]
+    pub unique: bool,
+}
+
+/// Response for POST /collections/:collection_id/docs/:doc_id/metadata/append
+#[derive(Serialize)]
+pub struct AppendMetadataResponse {
+    pub success: bool,
+    pub field: String,
+    /// The array after the append
+    pub values: serde_json::Value,
+}
+
+/// Handler: Append values to an array field of a document's metadata without rewriting the document;
+/// concurrent appends to the same document all survive
+/// POST /collections/:collection_id/docs/:doc_id/metadata/append
+async fn append_metadata_handler(
+    State(state): State<Arc<AppState>>,
+    Path((collection_id, doc_id)): Path<(String, String)>,
+    Json(payload): Json<AppendMetadataRest>,
+) -> Result<Json<AppendMetadataResponse>, AppError> {
+    debug!(collection_id = %collection_id, doc_id = %doc_id, field = %payload.field, count = payload.values.len(), "REST metadata append request");
+    if payload.field.is_empty() || payload.values.len() > MAX_METADATA_APPEND_VALUES {
+        warn!(field = %payload.field, count = payload.values.len(), "Rejected metadata append");
+        return Err(AppError::bad_request(format!("field must be non-empty, with at most {} values", MAX_METADATA_APPEND_VALUES)));
     }
+
+    let storage = state.storage.clone();
+    let (col, id, field) = (collection_id.clone(), doc_id.clone(), payload.field.clone());
+    let appended = tokio::task::spawn_blocking(move || {
+        storage
+            .append_metadata_values(&col, &id, &field, &payload.values, payload.unique)
+            .map_err(|e| match e.downcast_ref::<MetadataAppendError>() {
+                Some(rejected) => {
+                    warn!(collection_id = %col, doc_id = %id, error = %rejected, "Rejected metadata append");
+                    StatusCode::BAD_REQUEST
+                }
+                None => {
+                    error!(collection_id = %col, doc_id = %id, error = %e, "Metadata append failed");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            })
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+    let Some(doc) = appended else {
+        warn!(collection_id = %collection_id, doc_id = %doc_id, "Document not found for metadata append");
+        return Err(AppError::new(StatusCode::NOT_FOUND, "Document not found for metadata append"));
+    };
+
+    state.pubsub.publish(CdcEvent {
+        event_type: crate::events::EventType::Update,
+        collection: collection_id.clone(),
+        id: doc_id.clone(),
+        data: Some(serde_json::json!({
+            "id": doc.id,
+            "text": doc.text,
+            "category": doc.category,
+            "vector": doc.vector,
+            "metadata": doc.metadata,
+        })),
+        timestamp: chrono::Utc::now().timestamp(),
+    });
+    info!(collection_id = %collection_id, doc_id = %doc_id, field = %payload.field, "Metadata values appended via REST");
+    Ok(Json(AppendMetadataResponse {
+        success: true,
+        values: doc.metadata[&payload.field].clone(),
+        field: payload.field,
+    }))
 }
 
 async fn get_doc_handler(
     State(state): State<Arc<AppState>>,
     Path((collection_id, doc_id)): Path<(String, String)>,
-) -> Result<Json<Document>, StatusCode> {
-    debug!(collection_id = %collection_id, doc_id = %doc_id, "REST get doc request");
+    mode: ResponseMode,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    debug!(collection_id = %collection_id, doc_id = %doc_id, ?mode, "REST get doc request");
     
-    state.storage.get_doc(&collection_id, &doc_id)
-        .map(|doc| {
-            info!(collection_id = %collection_id, doc_id = %doc_id, "Document retrieved via REST");
-            Json(doc)
-        })
-        .map_err(|e| {
-            warn!(collection_id = %collection_id, doc_id = %doc_id, error = %e, "Document not found");
-            StatusCode::NOT_FOUND
-        })
+    let (doc, etag) = state.storage.get_doc_with_etag(&collection_id, &doc_id).map_err(|e| {
+        warn!(collection_id = %collection_id, doc_id = %doc_id, error = %e, "Document not found");
+        AppError::not_found(format!("Document {} not found in {}", doc_id, collection_id))
+    })?;
+    let etag_value = HeaderValue::from_str(&etag).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // The body differs between bare and envelope modes, so caches must key on Accept too
+    let vary = HeaderValue::from_static("accept");
+
+    if if_none_match(&headers, &etag) {
+        debug!(collection_id = %collection_id, doc_id = %doc_id, etag = %etag, "Document unchanged");
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag_value), (header::VARY, vary)]).into_response());
+    }
+
+    info!(collection_id = %collection_id, doc_id = %doc_id, "Document retrieved via REST");
+    let mut response = mode.respond(doc, format!("Document {} retrieved", doc_id));
+    response.headers_mut().insert(header::ETAG, etag_value);
+    response.headers_mut().insert(header::VARY, vary);
+    Ok(response)
+}
+
+/// Whether an `If-None-Match` header matches `etag` (`*`, or any listed tag; weak comparison)
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Most collections a single locate request will check
+const LOCATE_MAX_COLLECTIONS: usize = 1000;
+/// Most matching collections a single locate request returns
+const LOCATE_MAX_RESULTS: usize = 100;
+
+/// Handler: Find the collections (among those the caller can access) holding a document ID
+/// GET /docs/:doc_id/locate
+async fn locate_doc_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path(doc_id): Path<String>,
+) -> Result<Json<DocLocation>, AppError> {
+    debug!(username = %claims.sub, doc_id = %doc_id, "REST locate doc request");
+
+    let storage = state.storage.clone();
+    let username = claims.sub.clone();
+    let location = tokio::task::spawn_blocking(move || {
+        let collections = storage
+            .accessible_collections(&username, LOCATE_MAX_COLLECTIONS)
+            .map_err(|e| e.to_string())?;
+        storage.locate_doc(&doc_id, &collections, LOCATE_MAX_RESULTS).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, "Failed to locate document");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to locate document: {}", e))
+    })?;
+
+    info!(username = %claims.sub, doc_id = %location.doc_id, found = location.collections.len(), "Document located via REST");
+    Ok(Json(location))
+}
+
+/// Optional update-time window for GET /collections/:collection_id/docs
+#[derive(Deserialize, Default)]
+pub struct UpdatedRangeQuery {
+    /// Inclusive lower bound: epoch milliseconds or an RFC 3339 timestamp
+    #[serde(default)]
+    pub updated_from: Option<String>,
+    /// Inclusive upper bound: epoch milliseconds or an RFC 3339 timestamp
+    #[serde(default)]
+    pub updated_to: Option<String>,
+}
+
+/// Parse an `updated_from`/`updated_to` bound into epoch milliseconds
+fn parse_timestamp_millis(raw: &str) -> Option<u64> {
+    let raw = raw.trim();
+    raw.parse::<u64>().ok().or_else(|| {
+        chrono::DateTime::parse_from_rfc3339(raw)
+            .ok()
+            .and_then(|t| u64::try_from(t.timestamp_millis()).ok())
+    })
 }
 
 async fn list_docs_handler(
     State(state): State<Arc<AppState>>,
-    Path(collection_id): Path<String>,
-) -> Result<Json<Vec<Document>>, StatusCode> {
-    debug!(collection_id = %collection_id, "REST list docs request");
-    
-    state.storage.get_docs_in_collection(&collection_id)
-        .map(|docs| {
-            info!(collection_id = %collection_id, doc_count = docs.len(), "Documents listed via REST");
-            Json(docs)
-        })
-        .map_err(|e| {
-            error!(collection_id = %collection_id, error = %e, "Failed to list documents");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })
+    ResolvedCollection(collection_id): ResolvedCollection,
+    params: QueryParams,
+    Query(range): Query<UpdatedRangeQuery>,
+    mode: ResponseMode,
+    payload_limit: PayloadLimit,
+    empty: EmptyResults,
+) -> Result<Response, AppError> {
+    debug!(collection_id = %collection_id, limit = params.limit, offset = params.offset, ?mode, "REST list docs request");
+    let bound = |raw: Option<&str>| match raw {
+        None => Ok(None),
+        Some(raw) => parse_timestamp_millis(raw).map(Some).ok_or_else(|| {
+            warn!(value = %raw, "Rejected invalid update-time bound");
+            StatusCode::BAD_REQUEST
+        }),
+    };
+    let from = bound(range.updated_from.as_deref())?;
+    let to = bound(range.updated_to.as_deref())?;
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            warn!(from = from, to = to, "Rejected empty update-time window");
+            return Err(AppError::bad_request("updated_from is after updated_to"));
+        }
+    }
+
+    let docs = if from.is_none() && to.is_none() {
+        state.storage.get_docs_in_collection(&collection_id)
+    } else {
+        state.storage.get_docs_updated_between(&collection_id, from, to)
+    };
+    let docs = docs.map_err(|e| {
+        error!(collection_id = %collection_id, error = %e, "Failed to list documents");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list documents: {}", e))
+    })?;
+    let (page, continuation) = payload_limit.fit(params.paginate(docs), params.offset);
+    info!(collection_id = %collection_id, doc_count = page.len(), truncated = continuation.is_some(), "Documents listed via REST");
+    empty.check(page.is_empty())?;
+    let message = format!("Found {} documents", page.len());
+    Ok(mode.respond_page(page, message, continuation))
 }
 
 async fn delete_collection_handler(
     State(state): State<Arc<AppState>>,
     Path((env_id, col_id)): Path<(String, String)>,
-) -> Result<Json<RestResponse>, StatusCode> {
+) -> Result<Json<RestResponse>, AppError> {
     debug!(env_id = %env_id, col_id = %col_id, "REST delete collection request");
     
-    if state.storage.delete_collection(&env_id, &col_id).is_ok() {
-        info!(env_id = %env_id, col_id = %col_id, "Collection deleted via REST");
-        Ok(Json(RestResponse {
-            success: true,
-            message: format!("Collection {} deleted", col_id),
-            results: vec![],
-            cache_hits: None,
-        }))
-    } else {
-        error!(env_id = %env_id, col_id = %col_id, "Failed to delete collection");
-        Err(StatusCode::INTERNAL_SERVER_ERROR)
+    match state.storage.delete_collection(&env_id, &col_id) {
+        Ok(_) => {
+            info!(env_id = %env_id, col_id = %col_id, "Collection deleted via REST");
+            Ok(Json(RestResponse {
+                success: true,
+                message: format!("Collection {} deleted", col_id),
+                results: vec![],
+                cache_hits: None,
+            }))
+        }
+        Err(e) => {
+            error!(error = %e, env_id = %env_id, col_id = %col_id, "Failed to delete collection");
+            Err(AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete collection: {}", e)))
+        }
     }
 }
 
@@ -1223,7 +2292,7 @@ pub struct SessionLogsResponse {
 /// Get all sessions for the current user
 async fn get_sessions_handler(
     Extension(claims): Extension<AuthPayload>,
-) -> Result<Json<SessionsResponse>, StatusCode> {
+) -> Result<Json<SessionsResponse>, AppError> {
     debug!(username = %claims.sub, session_id = %claims.session_id.as_deref().unwrap_or("none"), "REST get sessions request");
     
     let session_manager = get_session_manager();
@@ -1237,7 +2306,7 @@ async fn get_sessions_handler(
 async fn get_session_handler(
     Extension(claims): Extension<AuthPayload>,
     Path(session_id): Path<String>,
-) -> Result<Json<Session>, StatusCode> {
+) -> Result<Json<Session>, AppError> {
     debug!(username = %claims.sub, session_id = %session_id, "REST get session request");
     
     let session_manager = get_session_manager();
@@ -1246,14 +2315,14 @@ async fn get_session_handler(
         // Verify the session belongs to the user
         if session.username != claims.sub {
             warn!(username = %claims.sub, session_id = %session_id, "Unauthorized session access attempt");
-            return Err(StatusCode::FORBIDDEN);
+            return Err(AppError::new(StatusCode::FORBIDDEN, "Session belongs to another user"));
         }
         
         info!(username = %claims.sub, session_id = %session_id, "Session retrieved");
         Ok(Json(session))
     } else {
         warn!(session_id = %session_id, "Session not found");
-        Err(StatusCode::NOT_FOUND)
+        Err(AppError::new(StatusCode::NOT_FOUND, "Session not found"))
     }
 }
 
@@ -1261,7 +2330,7 @@ async fn get_session_handler(
 async fn get_session_logs_handler(
     Extension(claims): Extension<AuthPayload>,
     Path(session_id): Path<String>,
-) -> Result<Json<SessionLogsResponse>, StatusCode> {
+) -> Result<Json<SessionLogsResponse>, AppError> {
     debug!(username = %claims.sub, session_id = %session_id, "REST get session logs request");
     
     let session_manager = get_session_manager();
@@ -1270,7 +2339,7 @@ async fn get_session_logs_handler(
     if let Some(session) = session_manager.get_session(&session_id) {
         if session.username != claims.sub {
             warn!(username = %claims.sub, session_id = %session_id, "Unauthorized session logs access attempt");
-            return Err(StatusCode::FORBIDDEN);
+            return Err(AppError::new(StatusCode::FORBIDDEN, "Session belongs to another user"));
         }
         
         // Read logs from JSON file
@@ -1280,7 +2349,7 @@ async fn get_session_logs_handler(
         Ok(Json(SessionLogsResponse { session_id, logs }))
     } else {
         warn!(session_id = %session_id, "Session not found for logs");
-        Err(StatusCode::NOT_FOUND)
+        Err(AppError::new(StatusCode::NOT_FOUND, "Session not found for logs"))
     }
 }
 
@@ -1288,7 +2357,7 @@ async fn get_session_logs_handler(
 async fn get_session_logs_by_level_handler(
     Extension(claims): Extension<AuthPayload>,
     Path((session_id, level)): Path<(String, String)>,
-) -> Result<Json<SessionLogsResponse>, StatusCode> {
+) -> Result<Json<SessionLogsResponse>, AppError> {
     debug!(username = %claims.sub, session_id = %session_id, level = %level, "REST get session logs by level request");
     
     let session_manager = get_session_manager();
@@ -1297,7 +2366,7 @@ async fn get_session_logs_by_level_handler(
     if let Some(session) = session_manager.get_session(&session_id) {
         if session.username != claims.sub {
             warn!(username = %claims.sub, session_id = %session_id, "Unauthorized session logs access attempt");
-            return Err(StatusCode::FORBIDDEN);
+            return Err(AppError::new(StatusCode::FORBIDDEN, "Session belongs to another user"));
         }
         
         // Read logs from JSON file and filter by level
@@ -1315,7 +2384,7 @@ async fn get_session_logs_by_level_handler(
         Ok(Json(SessionLogsResponse { session_id, logs }))
     } else {
         warn!(session_id = %session_id, "Session not found for logs");
-        Err(StatusCode::NOT_FOUND)
+        Err(AppError::new(StatusCode::NOT_FOUND, "Session not found for logs"))
     }
 }
 
@@ -1334,344 +2403,1470 @@ pub struct RagIngestRequest {
     pub source: Option<String>,
 }
 
-/// DTO for RAG search request
+/// Most collections one `POST /search` may span
+const MAX_SEARCH_COLLECTIONS: usize = 32;
+
+fn default_search_score_kind() -> ScoreKind {
+    ScoreKind::Similarity
+}
+
+/// Request for POST /search
 #[derive(Deserialize)]
-pub struct RagSearchRequest {
-    /// Search query text
-    pub query: String,
-    /// Number of results to return
-    pub top_k: usize,
+pub struct MultiCollectionSearchRest {
+    /// Collections to search (at most 32)
+    pub collections: Vec<String>,
+    pub query_vector: Vec<f32>,
+    /// Results after global re-ranking (defaults to 10)
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// `similarity` (default; comparable across metrics) or `distance`
+    #[serde(default = "default_search_score_kind")]
+    pub score_kind: ScoreKind,
 }
 
-/// Response for RAG search
+/// Response for POST /search: hits from all collections, best first, each tagged `_collection`
 #[derive(Serialize)]
-pub struct RagSearchResponse {
+pub struct MultiCollectionSearchResponse {
     pub success: bool,
-    pub message: String,
-    pub results: Vec<RagResultItem>,
+    pub score_kind: ScoreKind,
+    pub results: Vec<CollectionHit>,
 }
 
-/// Single result item in RAG search
-#[derive(Serialize)]
-pub struct RagResultItem {
-    pub chunk_id: String,
-    pub doc_id: String,
-    pub text: String,
-    pub score: f32,
-    pub metadata: serde_json::Value,
+/// Handler: ANN search over a caller-chosen set of collections, merged and re-ranked globally
+/// POST /search
+pub async fn multi_collection_search_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    empty: EmptyResults,
+    Json(payload): Json<MultiCollectionSearchRest>,
+) -> Result<Json<MultiCollectionSearchResponse>, AppError> {
+    let top_k = payload.top_k.unwrap_or(params::DEFAULT_TOP_K);
+    debug!(username = %claims.sub, collections = payload.collections.len(), top_k = top_k, "Multi-collection search request");
+    if payload.collections.is_empty()
+        || payload.collections.len() > MAX_SEARCH_COLLECTIONS
+        || top_k == 0
+        || top_k > params::MAX_TOP_K
+    {
+        warn!(collections = payload.collections.len(), top_k = top_k, "Rejected multi-collection search");
+        return Err(AppError::bad_request(format!(
+            "Search 1-{} collections with top_k 1-{}",
+            MAX_SEARCH_COLLECTIONS,
+            params::MAX_TOP_K
+        )));
+    }
+    require_body_collections(&state.storage, &claims, payload.collections.iter().map(String::as_str))?;
+    state.storage.check_vector("query", &payload.query_vector).map_err(|e| {
+        warn!(error = %e, "Rejected multi-collection search vector");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected multi-collection search vector: {}", e))
+    })?;
+
+    let storage = state.storage.clone();
+    let score_kind = payload.score_kind;
+    let results = cancel::run_blocking(move || {
+        storage
+            .vector_search_collections(&payload.collections, &payload.query_vector, top_k, score_kind)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, "Multi-collection search failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Multi-collection search failed: {}", e))
+    })?;
+
+    info!(username = %claims.sub, results = results.len(), "Multi-collection search completed via REST");
+    empty.check(results.is_empty())?;
+    Ok(Json(MultiCollectionSearchResponse { success: true, score_kind, results }))
 }
 
-/// Response for RAG ingestion
+fn default_exact_score_kind() -> ScoreKind {
+    ScoreKind::Distance
+}
+
+/// Request for POST /collections/:collection_id/vector_search
+#[derive(Deserialize)]
+pub struct VectorSearchRest {
+    pub query_vector: Vec<f32>,
+    /// Ranked results to compute and page through (defaults to 10)
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// Results per page (defaults to `top_k`: everything on the first page, no cursor)
+    #[serde(default)]
+    pub page_size: Option<usize>,
+    /// Named embedding field to search (defaults to the primary `vector`)
+    #[serde(default)]
+    pub field: Option<String>,
+    /// `distance` (default) or `similarity`
+    #[serde(default)]
+    pub score_kind: ScoreKind,
+    /// Blend the query toward the centroids of docs with these `metadata.tags` first
+    #[serde(default)]
+    pub expand_tags: Vec<String>,
+    /// Blend weight in [0, 1] (defaults to 0.5)
+    #[serde(default)]
+    pub expand_weight: Option<f32>,
+    /// Re-rank by a numeric metadata field: `{"field": "popularity", "weight": 0.2}`
+    #[serde(default)]
+    pub boost: Option<ScoreBoost>,
+    /// Keep only the best result per distinct document text
+    #[serde(default)]
+    pub dedupe_text: bool,
+}
+
+/// Query of GET /collections/:collection_id/vector_search
+#[derive(Deserialize)]
+pub struct VectorSearchCursorQuery {
+    pub cursor: String,
+}
+
+/// Response for POST and GET /collections/:collection_id/vector_search, best first
 #[derive(Serialize)]
-pub struct RagIngestResponse {
+pub struct VectorSearchPageResponse {
     pub success: bool,
-    pub message: String,
-    pub doc_id: String,
-    pub chunks_created: usize,
+    #[serde(flatten)]
+    pub page: RankedPage,
 }
 
-/// Handler: Ingest text into RAG system
-/// POST /collections/:collection_id/rag/ingest
-#[instrument(skip(state, payload))]
-pub async fn rag_ingest_handler(
+/// Handler: ANN vector search through the collection's cached index, with the same tag expansion,
+/// boosting and deduplication as gRPC `VectorSearch`. The `top_k` ranked results are computed
+/// once; past the first `page_size` of them the response carries a `cursor` for GET, which serves
+/// the next pages from the remembered ranking (see `rest::cursor`).
+/// POST /collections/:collection_id/vector_search
+pub async fn vector_search_handler(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<AuthPayload>,
     Path(collection_id): Path<String>,
-    Json(payload): Json<RagIngestRequest>,
-) -> Result<Json<RagIngestResponse>, StatusCode> {
-    debug!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        doc_id = %payload.doc_id,
-        text_len = payload.text.len(),
-        "RAG ingest request"
-    );
-    
-    // Parse metadata
-    let metadata = payload.metadata_json
-        .and_then(|m| serde_json::from_str(&m).ok())
-        .unwrap_or(serde_json::json!({}));
-    
-    // Create RAG pipeline with simple embedder
-    let pipeline = crate::rag::RagPipeline::simple()
-        .map_err(|e| {
-            error!(error = %e, "Failed to create RAG pipeline");
-            StatusCode::INTERNAL_SERVER_ERROR
+    empty: EmptyResults,
+    Json(payload): Json<VectorSearchRest>,
+) -> Result<Json<VectorSearchPageResponse>, AppError> {
+    let top_k = payload.top_k.unwrap_or(params::DEFAULT_TOP_K);
+    let page_size = payload.page_size.unwrap_or(top_k);
+    debug!(username = %claims.sub, collection_id = %collection_id, top_k = top_k, page_size = page_size, "REST vector search request");
+    let started = std::time::Instant::now();
+    if top_k == 0 || top_k > params::MAX_TOP_K || page_size == 0 {
+        warn!(top_k = top_k, page_size = page_size, "Rejected vector search");
+        return Err(AppError::bad_request(format!("top_k must be 1-{} and page_size at least 1", params::MAX_TOP_K)));
+    }
+    state.storage.check_vector("query", &payload.query_vector).map_err(|e| {
+        warn!(error = %e, "Rejected vector search vector");
+        AppError::bad_request(format!("Rejected vector search vector: {}", e))
+    })?;
+    if let Some(boost) = &payload.boost {
+        boost.validate().map_err(|e| {
+            warn!(error = %e, "Rejected vector search boost");
+            AppError::bad_request(e)
         })?;
-    
-    // Ingest text
-    let chunks = pipeline.ingest_text(
-        &state.storage,
-        &payload.text,
-        &payload.doc_id,
-        &collection_id,
-        Some(metadata),
-        payload.source,
-    ).await.map_err(|e| {
-        error!(error = %e, collection_id = %collection_id, doc_id = %payload.doc_id, "RAG ingestion failed");
-        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    let storage = state.storage.clone();
+    let col = collection_id.clone();
+    let score_kind = payload.score_kind;
+    let options = VectorSearchOptions {
+        field: payload.field.unwrap_or_else(|| DEFAULT_VECTOR_FIELD.to_string()),
+        score_kind,
+        expand_tags: payload.expand_tags,
+        expand_weight: payload.expand_weight.unwrap_or(DEFAULT_EXPAND_WEIGHT),
+        boost: payload.boost,
+        dedupe_text: payload.dedupe_text,
+    };
+    let outcome = cancel::run_blocking(move || {
+        storage
+            .vector_search_with_options(&col, &payload.query_vector, top_k, &options)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, collection_id = %collection_id, "Vector search failed");
+        AppError::internal(format!("Vector search failed: {}", e))
     })?;
-    
-    info!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        doc_id = %payload.doc_id,
-        chunks_created = chunks.len(),
-        "RAG ingestion completed"
-    );
-    
-    Ok(Json(RagIngestResponse {
-        success: true,
-        message: format!("Ingested {} chunks", chunks.len()),
-        doc_id: payload.doc_id,
-        chunks_created: chunks.len(),
-    }))
+    empty.check(outcome.ids.is_empty())?;
+
+    let results = RankedResults {
+        collection_id,
+        score_kind,
+        metric: outcome.metric,
+        hits: outcome.ids.into_iter().zip(outcome.scores).map(|(id, score)| VectorHit { id, score }).collect(),
+    };
+    let page = state.search_cursors.first_page(&claims.sub, results, page_size);
+    state.metrics.observe(metrics::Operation::Search, started.elapsed());
+    info!(username = %claims.sub, collection_id = %page.collection_id, results = page.results.len(), remaining = page.remaining, latency_ms = started.elapsed().as_millis() as u64, "Vector search completed via REST");
+    Ok(Json(VectorSearchPageResponse { success: true, page }))
 }
 
-/// Handler: Search RAG documents
-/// POST /collections/:collection_id/rag/search
-#[instrument(skip(state, payload))]
-pub async fn rag_search_handler(
+/// Handler: Next page of a vector search cursor; the last page carries no `cursor`. Unknown,
+/// expired, exhausted and other users' cursors are all 404.
+/// GET /collections/:collection_id/vector_search?cursor=...
+pub async fn vector_search_page_handler(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<AuthPayload>,
     Path(collection_id): Path<String>,
-    Json(payload): Json<RagSearchRequest>,
-) -> Result<Json<RagSearchResponse>, StatusCode> {
-    debug!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        query_len = payload.query.len(),
-        top_k = payload.top_k,
-        "RAG search request"
-    );
-    
-    // Create RAG pipeline
-    let pipeline = crate::rag::RagPipeline::simple()
-        .map_err(|e| {
-            error!(error = %e, "Failed to create RAG pipeline");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    
-    // Perform search
-    let results = pipeline.search(
-        &state.storage,
-        &collection_id,
-        &payload.query,
-        payload.top_k,
-    ).await.map_err(|e| {
-        error!(error = %e, collection_id = %collection_id, "RAG search failed");
-        StatusCode::INTERNAL_SERVER_ERROR
+    Query(query): Query<VectorSearchCursorQuery>,
+) -> Result<Json<VectorSearchPageResponse>, AppError> {
+    debug!(username = %claims.sub, collection_id = %collection_id, cursor = %query.cursor, "REST vector search page request");
+    let page = state.search_cursors.next_page(&query.cursor, &claims.sub, &collection_id).ok_or_else(|| {
+        warn!(collection_id = %collection_id, cursor = %query.cursor, "Unknown or expired search cursor");
+        AppError::not_found(format!("Search cursor {} not found or expired", query.cursor))
     })?;
-    
-    // Convert results
-    let result_items: Vec<RagResultItem> = results
-        .into_iter()
-        .map(|r| {
-            let chunk_id = r.chunk.id;
-            let doc_id = chunk_id
-                .split('-')
-                .next()
-                .unwrap_or(chunk_id.as_str())
-                .to_string();
-            RagResultItem {
-                chunk_id,
-                doc_id,
-                text: r.chunk.text,
-                score: r.score,
-                metadata: r.chunk.metadata,
-            }
-        })
-        .collect();
-    
-    info!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        results_count = result_items.len(),
-        "RAG search completed"
-    );
-    
-    Ok(Json(RagSearchResponse {
+    info!(username = %claims.sub, collection_id = %collection_id, results = page.results.len(), remaining = page.remaining, "Vector search page served via REST");
+    Ok(Json(VectorSearchPageResponse { success: true, page }))
+}
+
+/// Request for POST /collections/:collection_id/vector_search/exact
+#[derive(Deserialize)]
+pub struct ExactVectorSearchRest {
+    pub query_vector: Vec<f32>,
+    /// Number of nearest neighbors (defaults to 10)
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    /// Named embedding field to scan (defaults to the primary `vector`)
+    #[serde(default)]
+    pub field: Option<String>,
+    /// `distance` (default) or `similarity`
+    #[serde(default = "default_exact_score_kind")]
+    pub score_kind: ScoreKind,
+}
+
+/// One exact nearest neighbor
+#[derive(Serialize)]
+pub struct ExactVectorHit {
+    pub id: String,
+    pub score: f32,
+}
+
+/// Response for POST /collections/:collection_id/vector_search/exact, nearest first
+#[derive(Serialize)]
+pub struct ExactVectorSearchResponse {
+    pub success: bool,
+    pub score_kind: ScoreKind,
+    pub metric: String,
+    /// Always `flat`: every vector is scanned
+    pub index_backend: IndexBackend,
+    pub results: Vec<ExactVectorHit>,
+}
+
+/// Handler: Exact k-NN by brute-force scan, bypassing the approximate index. Every call scans
+/// all of the field's vectors (O(n·d)), so use it for ground truth and recall checks, not as the
+/// default search path on large collections.
+/// POST /collections/:collection_id/vector_search/exact
+pub async fn exact_vector_search_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path(collection_id): Path<String>,
+    empty: EmptyResults,
+    Json(payload): Json<ExactVectorSearchRest>,
+) -> Result<Json<ExactVectorSearchResponse>, AppError> {
+    let top_k = payload.top_k.unwrap_or(params::DEFAULT_TOP_K);
+    debug!(username = %claims.sub, collection_id = %collection_id, top_k = top_k, "Exact vector search request");
+    if top_k == 0 || top_k > params::MAX_TOP_K {
+        warn!(top_k = top_k, "Rejected exact vector search");
+        return Err(AppError::bad_request(format!("top_k must be 1-{}", params::MAX_TOP_K)));
+    }
+    state.storage.check_vector("query", &payload.query_vector).map_err(|e| {
+        warn!(error = %e, "Rejected exact vector search vector");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected exact vector search vector: {}", e))
+    })?;
+
+    let storage = state.storage.clone();
+    let field = payload.field.unwrap_or_else(|| DEFAULT_VECTOR_FIELD.to_string());
+    let score_kind = payload.score_kind;
+    let outcome = cancel::run_blocking(move || {
+        storage
+            .vector_search_exact(&collection_id, &field, &payload.query_vector, top_k, score_kind)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, "Exact vector search failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Exact vector search failed: {}", e))
+    })?;
+
+    info!(username = %claims.sub, results = outcome.ids.len(), "Exact vector search completed via REST");
+    empty.check(outcome.ids.is_empty())?;
+    Ok(Json(ExactVectorSearchResponse {
         success: true,
-        message: format!("Found {} results", result_items.len()),
-        results: result_items,
+        score_kind,
+        metric: outcome.metric,
+        index_backend: outcome.index_backend,
+        results: outcome
+            .ids
+            .into_iter()
+            .zip(outcome.scores)
+            .map(|(id, score)| ExactVectorHit { id, score })
+            .collect(),
     }))
 }
 
-/// Handler: Get RAG document chunks
-/// GET /collections/:collection_id/rag/docs/:doc_id
-pub async fn rag_get_doc_handler(
+/// Request for POST /collections/:collection_id/vector_search/range
+#[derive(Deserialize)]
+pub struct RangeVectorSearchRest {
+    pub query_vector: Vec<f32>,
+    /// Largest raw distance (inclusive) counted as a neighbor; negative only on `dot_product`
+    /// collections, e.g. -0.8 for "dot product at least 0.8"
+    pub radius: f32,
+    /// Named embedding field to search (defaults to the primary `vector`)
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Also return the IDs within the radius, closest first (at most `limit`)
+    #[serde(default)]
+    pub include_ids: bool,
+    /// Most IDs returned with `include_ids` (defaults to 10; the count is never capped)
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Widen the radius until at least this many documents are inside (or the collection runs out)
+    #[serde(default)]
+    pub min_results: Option<usize>,
+}
+
+/// One document within the radius
+#[derive(Serialize)]
+pub struct RangeVectorHit {
+    pub id: String,
+    pub distance: f32,
+    /// Outside the requested radius; included to reach `min_results`
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub relaxed: bool,
+}
+
+/// Response for POST /collections/:collection_id/vector_search/range
+#[derive(Serialize)]
+pub struct RangeVectorSearchResponse {
+    pub success: bool,
+    /// Documents within the radius
+    pub count: usize,
+    pub metric: String,
+    pub index_backend: IndexBackend,
+    /// Radius the count was taken at; larger than the requested one when `relaxed`
+    pub radius: f32,
+    /// Whether `min_results` widened the radius
+    pub relaxed: bool,
+    /// Present with `include_ids`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub results: Option<Vec<RangeVectorHit>>,
+}
+
+/// Handler: Count the documents within a distance of a query vector (optionally listing them).
+/// The count is exact on every backend, at O(n·d) per call.
+/// POST /collections/:collection_id/vector_search/range
+pub async fn range_vector_search_handler(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<AuthPayload>,
-    Path((collection_id, doc_id)): Path<(String, String)>,
-) -> Result<Json<Vec<crate::storage::RagStorageDocument>>, StatusCode> {
-    debug!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        doc_id = %doc_id,
-        "RAG get document request"
-    );
-    
-    let chunks = state.storage.get_rag_doc_chunks(&collection_id, &doc_id)
-        .map_err(|e| {
-            warn!(error = %e, collection_id = %collection_id, doc_id = %doc_id, "Document not found");
-            StatusCode::NOT_FOUND
-        })?;
-    
-    info!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        doc_id = %doc_id,
-        chunks = chunks.len(),
-        "RAG document retrieved"
-    );
-    
-    Ok(Json(chunks))
+    Path(collection_id): Path<String>,
+    Json(payload): Json<RangeVectorSearchRest>,
+) -> Result<Json<RangeVectorSearchResponse>, AppError> {
+    let limit = if payload.include_ids { payload.limit.unwrap_or(params::DEFAULT_TOP_K) } else { 0 };
+    debug!(username = %claims.sub, collection_id = %collection_id, radius = payload.radius, limit = limit, "Range vector search request");
+    let min_results = payload.min_results.unwrap_or(0);
+    if limit > params::MAX_TOP_K || min_results > params::MAX_TOP_K {
+        warn!(limit = limit, min_results = min_results, "Rejected range vector search");
+        return Err(AppError::bad_request(format!("limit and min_results must be at most {}", params::MAX_TOP_K)));
+    }
+    let metric = state.storage.collection_metric(&collection_id).map_err(|e| {
+        error!(error = %e, collection_id = %collection_id, "Failed to read collection metric");
+        AppError::internal(format!("Failed to read collection metric: {}", e))
+    })?;
+    check_radius(payload.radius, metric).map_err(|e| {
+        warn!(error = %e, radius = payload.radius, "Rejected range vector search radius");
+        AppError::bad_request(e)
+    })?;
+    state.storage.check_vector("query", &payload.query_vector).map_err(|e| {
+        warn!(error = %e, "Rejected range vector search vector");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected range vector search vector: {}", e))
+    })?;
+
+    let storage = state.storage.clone();
+    let field = payload.field.unwrap_or_else(|| DEFAULT_VECTOR_FIELD.to_string());
+    let radius = payload.radius;
+    let outcome = cancel::run_blocking(move || {
+        storage
+            .vector_range_search_relaxed(&collection_id, &field, &payload.query_vector, radius, limit, min_results)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, "Range vector search failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Range vector search failed: {}", e))
+    })?;
+
+    info!(username = %claims.sub, count = outcome.count, "Range vector search completed via REST");
+    Ok(Json(RangeVectorSearchResponse {
+        success: true,
+        count: outcome.count,
+        metric: outcome.metric,
+        index_backend: outcome.index_backend,
+        radius: outcome.radius,
+        relaxed: outcome.relaxed,
+        results: payload.include_ids.then(|| {
+            outcome
+                .hits
+                .into_iter()
+                .map(|(id, distance)| RangeVectorHit { id, relaxed: distance > radius, distance })
+                .collect()
+        }),
+    }))
 }
 
-/// Handler: Delete RAG document
-/// DELETE /collections/:collection_id/rag/docs/:doc_id
-pub async fn rag_delete_doc_handler(
+/// One side of POST /similarity: an inline vector or a stored document's embedding
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum SimilarityOperand {
+    Vector(Vec<f32>),
+    Doc {
+        collection_id: String,
+        doc_id: String,
+        /// Named embedding field (defaults to the primary `vector`)
+        #[serde(default)]
+        field: Option<String>,
+    },
+}
+
+/// Request for POST /similarity
+#[derive(Deserialize)]
+pub struct SimilarityRest {
+    pub a: SimilarityOperand,
+    pub b: SimilarityOperand,
+    /// `l2` (default), `cosine` or `dot_product`
+    #[serde(default)]
+    pub metric: DistanceMetric,
+}
+
+/// Response for POST /similarity
+#[derive(Serialize)]
+pub struct SimilarityResponse {
+    pub success: bool,
+    pub metric: DistanceMetric,
+    /// Raw distance (lower = closer)
+    pub distance: f32,
+    /// Canonical similarity (higher = closer; the cosine similarity for `cosine`)
+    pub similarity: f32,
+}
+
+/// Vector behind a similarity operand; `Err` carries the error to answer with
+fn resolve_similarity_operand(storage: &Storage, operand: SimilarityOperand) -> Result<Vec<f32>, AppError> {
+    match operand {
+        SimilarityOperand::Vector(vector) => Ok(vector),
+        SimilarityOperand::Doc { collection_id, doc_id, field } => {
+            let doc = storage.get_doc(&collection_id, &doc_id).map_err(|e| {
+                warn!(collection_id = %collection_id, doc_id = %doc_id, error = %e, "Similarity operand not found");
+                AppError::new(StatusCode::NOT_FOUND, format!("Similarity operand not found: {}", e))
+            })?;
+            match field.as_deref() {
+                None | Some(DEFAULT_VECTOR_FIELD) => Ok(doc.vector),
+                Some(field) => doc.vectors.get(field).cloned().ok_or_else(|| {
+                    warn!(collection_id = %collection_id, doc_id = %doc_id, field = %field, "Similarity operand has no such vector field");
+                    AppError::not_found(format!("Document {} has no vector field '{}'", doc_id, field))
+                }),
+            }
+        }
+    }
+}
+
+/// Handler: Distance and similarity between two vectors or stored documents; nothing is written
+/// POST /similarity
+pub async fn similarity_handler(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<AuthPayload>,
-    Path((collection_id, doc_id)): Path<(String, String)>,
-) -> Result<Json<RestResponse>, StatusCode> {
-    debug!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        doc_id = %doc_id,
-        "RAG delete document request"
-    );
-    
-    state.storage.delete_rag_doc(&collection_id, &doc_id)
-        .map_err(|e| {
-            error!(error = %e, collection_id = %collection_id, doc_id = %doc_id, "Failed to delete RAG document");
-            StatusCode::INTERNAL_SERVER_ERROR
+    Json(payload): Json<SimilarityRest>,
+) -> Result<Json<SimilarityResponse>, AppError> {
+    let operand_collections = [&payload.a, &payload.b].into_iter().filter_map(|operand| match operand {
+        SimilarityOperand::Vector(_) => None,
+        SimilarityOperand::Doc { collection_id, .. } => Some(collection_id.as_str()),
+    });
+    require_body_collections(&state.storage, &claims, operand_collections)?;
+    let a = resolve_similarity_operand(&state.storage, payload.a)?;
+    let b = resolve_similarity_operand(&state.storage, payload.b)?;
+    if a.is_empty() || a.len() != b.len() {
+        warn!(a_dim = a.len(), b_dim = b.len(), "Rejected similarity of mismatched vectors");
+        return Err(AppError::bad_request(format!("Vectors must be non-empty and of equal dimension, got {} and {}", a.len(), b.len())));
+    }
+    for vector in [&a, &b] {
+        state.storage.check_vector("similarity", vector).map_err(|e| {
+            warn!(error = %e, "Rejected similarity vector");
+            AppError::new(StatusCode::BAD_REQUEST, format!("Rejected similarity vector: {}", e))
         })?;
-    
-    info!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        doc_id = %doc_id,
-        "RAG document deleted"
-    );
-    
-    Ok(Json(RestResponse {
+    }
+
+    let metric = payload.metric;
+    let distance = metric.distance(&a, &b);
+    debug!(metric = metric.as_str(), dim = a.len(), distance = distance, "Similarity computed");
+    Ok(Json(SimilarityResponse {
         success: true,
-        message: format!("RAG document {} deleted", doc_id),
-        results: vec![],
-        cache_hits: None,
+        metric,
+        distance,
+        similarity: metric.to_similarity(distance),
     }))
 }
 
-/// Handler: List all RAG documents in collection
-/// GET /collections/:collection_id/rag/docs
-pub async fn rag_list_docs_handler(
+/// Largest `k` accepted by GET /collections/:id/distance_histogram
+const MAX_HISTOGRAM_K: usize = 100;
+/// Largest `bins` accepted by GET /collections/:id/distance_histogram
+const MAX_HISTOGRAM_BINS: usize = 1000;
+
+/// Query for GET /collections/:collection_id/distance_histogram
+#[derive(Deserialize)]
+pub struct DistanceHistogramQuery {
+    /// Which neighbor's distance to measure (defaults to 1, the nearest)
+    #[serde(default)]
+    pub k: Option<usize>,
+    /// Number of equal-width bins (defaults to 20)
+    #[serde(default)]
+    pub bins: Option<usize>,
+}
+
+/// Handler: Histogram of sampled documents' k-th nearest-neighbor distances
+/// GET /collections/:collection_id/distance_histogram?k=1&bins=20
+pub async fn distance_histogram_handler(
     State(state): State<Arc<AppState>>,
     Extension(claims): Extension<AuthPayload>,
     Path(collection_id): Path<String>,
-) -> Result<Json<Vec<String>>, StatusCode> {
-    debug!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        "RAG list documents request"
-    );
-    
-    let doc_ids = state.storage.get_rag_doc_ids(&collection_id)
-        .map_err(|e| {
-            error!(error = %e, collection_id = %collection_id, "Failed to list RAG documents");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    
-    info!(
-        username = %claims.sub,
-        collection_id = %collection_id,
-        doc_count = doc_ids.len(),
-        "RAG documents listed"
-    );
-    
-    Ok(Json(doc_ids))
+    Query(query): Query<DistanceHistogramQuery>,
+) -> Result<Json<DistanceHistogram>, AppError> {
+    let k = query.k.unwrap_or(1);
+    let bins = query.bins.unwrap_or(20);
+    debug!(username = %claims.sub, collection_id = %collection_id, k = k, bins = bins, "Distance histogram request");
+    if !(1..=MAX_HISTOGRAM_K).contains(&k) || !(1..=MAX_HISTOGRAM_BINS).contains(&bins) {
+        warn!(k = k, bins = bins, "Rejected distance histogram request");
+        return Err(AppError::bad_request(format!("k must be 1-{} and bins 1-{}", MAX_HISTOGRAM_K, MAX_HISTOGRAM_BINS)));
+    }
+
+    let storage = state.storage.clone();
+    let histogram = tokio::task::spawn_blocking(move || {
+        storage.distance_histogram(&collection_id, k, bins).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, "Distance histogram failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Distance histogram failed: {}", e))
+    })?;
+
+    info!(username = %claims.sub, collection_id = %histogram.collection_id, sample_size = histogram.sample_size, "Distance histogram served via REST");
+    Ok(Json(histogram.as_ref().clone()))
 }
 
-/// Handler: Generate embedding for text
-/// POST /rag/embed
-pub async fn rag_embed_handler(
+/// Response for GET /collections/:collection_id/index_stats
+#[derive(Serialize)]
+pub struct IndexStatsResponse {
+    pub collection_id: String,
+    /// Sum of `approx_memory_bytes` over `indexes`
+    pub approx_memory_bytes: usize,
+    pub indexes: Vec<IndexStats>,
+}
+
+/// Handler: Estimated memory, vector count and build time of a collection's vector indexes
+/// (the primary index is built if no search has built it yet)
+/// GET /collections/:collection_id/index_stats
+pub async fn index_stats_handler(
+    State(state): State<Arc<AppState>>,
     Extension(claims): Extension<AuthPayload>,
-    Json(payload): Json<RagEmbedRequest>,
-) -> Result<Json<RagEmbedResponse>, StatusCode> {
-    debug!(
-        username = %claims.sub,
-        text_len = payload.text.len(),
-        "RAG embed request"
-    );
-    
-    // Create RAG pipeline
-    let pipeline = crate::rag::RagPipeline::simple()
+    Path(collection_id): Path<String>,
+) -> Result<Json<IndexStatsResponse>, AppError> {
+    debug!(username = %claims.sub, collection_id = %collection_id, "Index stats request");
+    let storage = state.storage.clone();
+    let col = collection_id.clone();
+    let indexes = cancel::run_blocking(move || storage.index_stats(&col).map_err(|e| e.to_string()))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .map_err(|e| {
-            error!(error = %e, "Failed to create RAG pipeline");
-            StatusCode::INTERNAL_SERVER_ERROR
+            error!(error = %e, collection_id = %collection_id, "Index stats failed");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Index stats failed: {}", e))
         })?;
-    
-    // Generate embedding
-    let embedding = pipeline.embed(&payload.text)
+
+    let approx_memory_bytes = indexes.iter().map(|i| i.approx_memory_bytes).sum();
+    info!(username = %claims.sub, collection_id = %collection_id, indexes = indexes.len(), approx_memory_bytes = approx_memory_bytes, "Index stats served via REST");
+    Ok(Json(IndexStatsResponse { collection_id, approx_memory_bytes, indexes }))
+}
+
+/// Response for GET /collections/:collection_id/count
+#[derive(Serialize)]
+pub struct DocCountResponse {
+    pub collection_id: String,
+    pub count: usize,
+}
+
+/// Handler: Number of documents in a collection, without reading them
+/// GET /collections/:collection_id/count
+pub async fn count_docs_handler(
+    State(state): State<Arc<AppState>>,
+    Path(collection_id): Path<String>,
+) -> Result<Json<DocCountResponse>, AppError> {
+    debug!(collection_id = %collection_id, "REST doc count request");
+    let storage = state.storage.clone();
+    let col = collection_id.clone();
+    let count = tokio::task::spawn_blocking(move || storage.count_docs(&col).map_err(|e| e.to_string()))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .map_err(|e| {
-            error!(error = %e, "Failed to generate embedding");
-            StatusCode::INTERNAL_SERVER_ERROR
+            error!(error = %e, collection_id = %collection_id, "Doc count failed");
+            AppError::internal(format!("Doc count failed: {}", e))
         })?;
-    
-    info!(
-        username = %claims.sub,
-        embedding_dim = embedding.len(),
-        "Embedding generated"
-    );
-    
-    Ok(Json(RagEmbedResponse {
-        success: true,
-        embedding,
-        dimension: pipeline.embedding_dim(),
-    }))
+    info!(collection_id = %collection_id, count = count, "Documents counted via REST");
+    Ok(Json(DocCountResponse { collection_id, count }))
 }
 
-/// DTO for embedding request
+/// Query for GET /collections/:collection_id/export
 #[derive(Deserialize)]
-pub struct RagEmbedRequest {
-    pub text: String,
+pub struct ExportQuery {
+    /// Continue after this document ID: the `last_id` of the last checkpoint line received
+    pub resume_after: Option<String>,
+    /// Documents between checkpoint lines (default `DEFAULT_EXPORT_CHECKPOINT_EVERY`)
+    pub checkpoint_every: Option<usize>,
 }
 
-/// Response for embedding
-#[derive(Serialize)]
-pub struct RagEmbedResponse {
-    pub success: bool,
-    pub embedding: Vec<f32>,
-    pub dimension: usize,
-}
+/// Documents per export page, each followed by a checkpoint line
+pub const DEFAULT_EXPORT_CHECKPOINT_EVERY: usize = 1000;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::{body::Body, http::Request};
-    use std::fs;
-    use tower::ServiceExt;  // For .oneshot() testing
+/// One NDJSON line of an export
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportLine {
+    Doc { doc: Box<Document> },
+    /// Everything up to and including `last_id` has been sent; resume with `?resume_after=<last_id>`
+    Checkpoint { last_id: String, exported: u64 },
+    /// The export is complete; a stream ending without this line was interrupted
+    End { exported: u64 },
+}
 
-    #[tokio::test]
-    async fn test_rest_health_and_endpoints() {
-        // Temp setup for REST test (avoids lock ; tests SQL fix)
-        let temp_dir = std::env::temp_dir().join("aidb_test_rest");
-        let _ = fs::remove_dir_all(&temp_dir);
-        let temp_path = temp_dir.to_str().unwrap();
-        let storage = Storage::open(temp_path).expect("Storage for REST test");
+/// Position of an export stream between pages
+struct ExportCursor {
+    storage: Arc<Storage>,
+    collection_id: String,
+    after: Option<String>,
+    page_size: usize,
+    exported: u64,
+    done: bool,
+}
 
-        // Insert sample for endpoint test (NoSQL + SQL projection)
-        let doc = Document {
-            id: "rest_test_doc".to_string(),
-            text: "REST test".to_string(),
-            category: "AI".to_string(),
-            vector: vec![0.1, 0.1, 0.1, 0.1],
-            metadata: serde_json::json!({"test": true}),
-        };
-        storage.insert_doc(doc).expect("Insert for test");
+fn export_line(line: &ExportLine) -> String {
+    let mut out = serde_json::to_string(line).unwrap_or_default();
+    out.push('\n');
+    out
+}
 
-        // Create router
-        let app = create_router(storage);
+/// Handler: Stream every document of a collection as NDJSON in ID order, with a checkpoint line
+/// after each page so an interrupted export resumes via `?resume_after=<last_id>`
+/// GET /collections/:collection_id/export
+pub async fn export_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    ResolvedCollection(collection_id): ResolvedCollection,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, AppError> {
+    let page_size = query.checkpoint_every.unwrap_or(DEFAULT_EXPORT_CHECKPOINT_EVERY);
+    if page_size == 0 {
+        warn!(collection_id = %collection_id, "Rejected export with checkpoint_every = 0");
+        return Err(AppError::bad_request("checkpoint_every must be at least 1"));
+    }
+    debug!(username = %claims.sub, collection_id = %collection_id, resume_after = ?query.resume_after, page_size = page_size, "Export request");
+
+    let cursor = ExportCursor {
+        storage: state.storage.clone(),
+        collection_id,
+        after: query.resume_after,
+        page_size,
+        exported: 0,
+        done: false,
+    };
+    let lines = futures::stream::unfold(cursor, |mut cursor| async move {
+        if cursor.done {
+            return None;
+        }
+        let (storage, col, after, limit) = (cursor.storage.clone(), cursor.collection_id.clone(), cursor.after.clone(), cursor.page_size);
+        let page = cancel::run_blocking(move || storage.export_page(&col, after.as_deref(), limit).map_err(|e| e.to_string()))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|page| page);
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                // Ending without an `end` line tells the client to resume from its last checkpoint
+                error!(collection_id = %cursor.collection_id, error = %e, "Export page failed");
+                cursor.done = true;
+                return Some((Err(std::io::Error::other(e)), cursor));
+            }
+        };
+        let mut chunk = String::new();
+        if page.is_empty() {
+            info!(collection_id = %cursor.collection_id, exported = cursor.exported, "Export completed via REST");
+            chunk.push_str(&export_line(&ExportLine::End { exported: cursor.exported }));
+            cursor.done = true;
+            return Some((Ok(chunk), cursor));
+        }
+        let last_id = page.last().map(|d| d.id.clone()).unwrap_or_default();
+        cursor.exported += page.len() as u64;
+        for doc in page {
+            chunk.push_str(&export_line(&ExportLine::Doc { doc: Box::new(doc) }));
+        }
+        chunk.push_str(&export_line(&ExportLine::Checkpoint { last_id: last_id.clone(), exported: cursor.exported }));
+        cursor.after = Some(last_id);
+        Some((Ok(chunk), cursor))
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, HeaderValue::from_static("application/x-ndjson"))],
+        axum::body::Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+/// DTO for RAG search request
+#[derive(Deserialize)]
+pub struct RagSearchRequest {
+    /// Search query text
+    pub query: String,
+    /// Number of results to return
+    pub top_k: usize,
+    /// `distance` (default, L2; lower = closer) or `similarity` (canonical, higher = closer)
+    #[serde(default)]
+    pub score_kind: ScoreKind,
+}
+
+/// Response for RAG search
+#[derive(Serialize)]
+pub struct RagSearchResponse {
+    pub success: bool,
+    pub message: String,
+    pub results: Vec<RagResultItem>,
+}
+
+/// Single result item in RAG search
+#[derive(Serialize)]
+pub struct RagResultItem {
+    pub chunk_id: String,
+    pub doc_id: String,
+    pub text: String,
+    pub score: f32,
+    pub score_kind: ScoreKind,
+    pub metadata: serde_json::Value,
+}
+
+/// Response for RAG ingestion
+#[derive(Serialize)]
+pub struct RagIngestResponse {
+    pub success: bool,
+    pub message: String,
+    pub doc_id: String,
+    pub chunks_created: usize,
+}
+
+/// Handler: Ingest text into RAG system
+/// POST /collections/:collection_id/rag/ingest
+#[instrument(skip(state, payload))]
+pub async fn rag_ingest_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path(collection_id): Path<String>,
+    Json(payload): Json<RagIngestRequest>,
+) -> Result<Json<RagIngestResponse>, AppError> {
+    debug!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        doc_id = %payload.doc_id,
+        text_len = payload.text.len(),
+        "RAG ingest request"
+    );
+    
+    // Parse metadata
+    let metadata = payload.metadata_json
+        .and_then(|m| serde_json::from_str(&m).ok())
+        .unwrap_or(serde_json::json!({}));
+    
+    // Create RAG pipeline with simple embedder
+    let pipeline = crate::rag::RagPipeline::simple()
+        .map_err(|e| {
+            error!(error = %e, "Failed to create RAG pipeline");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create RAG pipeline: {}", e))
+        })?;
+    
+    // Ingest text
+    let chunks = pipeline.ingest_text(
+        &state.storage,
+        &payload.text,
+        &payload.doc_id,
+        &collection_id,
+        Some(metadata),
+        payload.source,
+    ).await.map_err(|e| {
+        error!(error = %e, collection_id = %collection_id, doc_id = %payload.doc_id, "RAG ingestion failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("RAG ingestion failed: {}", e))
+    })?;
+    
+    info!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        doc_id = %payload.doc_id,
+        chunks_created = chunks.len(),
+        "RAG ingestion completed"
+    );
+    
+    Ok(Json(RagIngestResponse {
+        success: true,
+        message: format!("Ingested {} chunks", chunks.len()),
+        doc_id: payload.doc_id,
+        chunks_created: chunks.len(),
+    }))
+}
+
+/// Handler: Search RAG documents
+/// POST /collections/:collection_id/rag/search
+#[instrument(skip(state, payload))]
+pub async fn rag_search_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path(collection_id): Path<String>,
+    empty: EmptyResults,
+    Json(payload): Json<RagSearchRequest>,
+) -> Result<Json<RagSearchResponse>, AppError> {
+    debug!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        query_len = payload.query.len(),
+        top_k = payload.top_k,
+        "RAG search request"
+    );
+    
+    // Create RAG pipeline
+    let pipeline = crate::rag::RagPipeline::simple()
+        .map_err(|e| {
+            error!(error = %e, "Failed to create RAG pipeline");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create RAG pipeline: {}", e))
+        })?;
+    
+    // Perform search
+    let results = pipeline.search(
+        &state.storage,
+        &collection_id,
+        &payload.query,
+        payload.top_k,
+    ).await.map_err(|e| {
+        error!(error = %e, collection_id = %collection_id, "RAG search failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("RAG search failed: {}", e))
+    })?;
+    
+    // Convert results
+    let result_items: Vec<RagResultItem> = results
+        .into_iter()
+        .map(|r| {
+            let chunk_id = r.chunk.id;
+            let doc_id = chunk_id
+                .split('-')
+                .next()
+                .unwrap_or(chunk_id.as_str())
+                .to_string();
+            RagResultItem {
+                chunk_id,
+                doc_id,
+                text: r.chunk.text,
+                score: payload.score_kind.apply(DistanceMetric::L2, r.score),
+                score_kind: payload.score_kind,
+                metadata: r.chunk.metadata,
+            }
+        })
+        .collect();
+    
+    info!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        results_count = result_items.len(),
+        "RAG search completed"
+    );
+    empty.check(result_items.is_empty())?;
+    
+    Ok(Json(RagSearchResponse {
+        success: true,
+        message: format!("Found {} results", result_items.len()),
+        results: result_items,
+    }))
+}
+
+/// Query for POST /admin/self-check
+#[derive(Deserialize, Default)]
+pub struct SelfCheckQuery {
+    /// Remove orphan vector/metadata entries and rebuild missing ones from documents
+    #[serde(default)]
+    pub repair: bool,
+}
+
+/// Handler: Verify doc/vector/metadata consistency, optionally repairing
+/// POST /admin/self-check?repair=true
+pub async fn self_check_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Query(query): Query<SelfCheckQuery>,
+) -> Result<Json<SelfCheckReport>, AppError> {
+    debug!(username = %claims.sub, repair = query.repair, "Self-check request");
+
+    let storage = state.storage.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        let result = if query.repair { storage.self_check_and_repair() } else { storage.self_check() };
+        result.map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, "Self-check failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Self-check failed: {}", e))
+    })?;
+
+    info!(
+        username = %claims.sub,
+        issues = report.issue_count(),
+        repaired = report.repaired,
+        "Self-check completed via REST"
+    );
+    Ok(Json(report))
+}
+
+/// Handler: Check per-collection generation counters against stored documents, optionally repairing
+/// POST /admin/generations/verify?repair=true
+pub async fn verify_generations_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Query(query): Query<SelfCheckQuery>,
+) -> Result<Json<GenerationReport>, AppError> {
+    debug!(username = %claims.sub, repair = query.repair, "Generation verification request");
+
+    let storage = state.storage.clone();
+    let report = tokio::task::spawn_blocking(move || storage.verify_generations(query.repair).map_err(|e| e.to_string()))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            error!(error = %e, "Generation verification failed");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Generation verification failed: {}", e))
+        })?;
+
+    info!(
+        username = %claims.sub,
+        drifted = report.drifted.len(),
+        repaired = report.repaired,
+        "Generation verification completed via REST"
+    );
+    Ok(Json(report))
+}
+
+/// Request for POST /admin/collections/swap
+#[derive(Deserialize)]
+pub struct SwapCollectionsRest {
+    pub first: String,
+    pub second: String,
+}
+
+/// Handler: Atomically exchange two collections' data (rebuild-then-swap)
+/// POST /admin/collections/swap
+pub async fn swap_collections_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Json(payload): Json<SwapCollectionsRest>,
+) -> Result<Json<CollectionSwap>, AppError> {
+    debug!(username = %claims.sub, first = %payload.first, second = %payload.second, "Collection swap request");
+    if payload.first.is_empty() || payload.second.is_empty() || payload.first == payload.second {
+        warn!(first = %payload.first, second = %payload.second, "Rejected collection swap");
+        return Err(AppError::bad_request("first and second must be two different collections"));
+    }
+
+    let storage = state.storage.clone();
+    let swap = tokio::task::spawn_blocking(move || {
+        storage.swap_collections(&payload.first, &payload.second).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, "Collection swap failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Collection swap failed: {}", e))
+    })?;
+
+    info!(username = %claims.sub, first = %swap.first, second = %swap.second, "Collections swapped via REST");
+    Ok(Json(swap))
+}
+
+/// Request for POST /admin/snapshot
+#[derive(Deserialize)]
+pub struct SnapshotRest {
+    /// Archive file to write (replaced if present), relative to the snapshot directory
+    pub path: String,
+}
+
+/// Handler: Write a point-in-time snapshot of every tree to one archive file
+/// POST /admin/snapshot
+pub async fn snapshot_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Json(payload): Json<SnapshotRest>,
+) -> Result<Json<SnapshotReport>, AppError> {
+    debug!(username = %claims.sub, path = %payload.path, "Snapshot request");
+    let path = snapshot_path(&state.snapshot_dir, &payload.path).map_err(|e| {
+        warn!(error = %e, "Rejected snapshot path");
+        AppError::bad_request(e)
+    })?;
+
+    let storage = state.storage.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        storage.snapshot(&path).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        error!(error = %e, "Snapshot failed");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Snapshot failed: {}", e))
+    })?;
+
+    info!(username = %claims.sub, path = %report.path, entries = report.entries, "Snapshot written via REST");
+    Ok(Json(report))
+}
+
+/// Request for POST /admin/restore
+#[derive(Deserialize)]
+pub struct RestoreRest {
+    /// Archive written by POST /admin/snapshot, relative to the snapshot directory
+    pub path: String,
+    /// New, empty data directory to load it into, relative to the snapshot directory (the
+    /// running database is left untouched)
+    pub data_path: String,
+}
+
+/// Handler: Load a snapshot archive into a fresh database directory
+/// POST /admin/restore
+pub async fn restore_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Json(payload): Json<RestoreRest>,
+) -> Result<Json<SnapshotReport>, AppError> {
+    debug!(username = %claims.sub, path = %payload.path, data_path = %payload.data_path, "Restore request");
+    let (archive, data_path) = snapshot_path(&state.snapshot_dir, &payload.path)
+        .and_then(|archive| Ok((archive, snapshot_path(&state.snapshot_dir, &payload.data_path)?)))
+        .map_err(|e| {
+            warn!(error = %e, "Rejected restore path");
+            AppError::bad_request(e)
+        })?;
+
+    let report = tokio::task::spawn_blocking(move || {
+        Storage::restore_snapshot(&archive, &data_path.to_string_lossy()).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        warn!(error = %e, "Restore failed");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Restore failed: {}", e))
+    })?;
+
+    info!(username = %claims.sub, path = %report.path, entries = report.entries, "Snapshot restored via REST");
+    Ok(Json(report))
+}
+
+/// Handler: Rewrite one document's vector/metadata entries from its stored JSON
+/// POST /admin/collections/:collection_id/docs/:doc_id/resync
+pub async fn resync_doc_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path((collection_id, doc_id)): Path<(String, String)>,
+) -> Result<Json<DocResync>, AppError> {
+    debug!(username = %claims.sub, collection_id = %collection_id, doc_id = %doc_id, "Document resync request");
+
+    let resync = state.storage.resync_doc(&collection_id, &doc_id)
+        .map_err(|e| {
+            error!(error = %e, collection_id = %collection_id, doc_id = %doc_id, "Document resync failed");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Document resync failed: {}", e))
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    info!(username = %claims.sub, key = %resync.key, vector_changed = resync.vector_changed, "Document resynced via REST");
+    Ok(Json(resync))
+}
+
+/// Handler: Download a collection's primary vector index as a file `PUT` can load elsewhere
+/// GET /admin/collections/:collection_id/index
+pub async fn export_index_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path(collection_id): Path<String>,
+) -> Result<Response, AppError> {
+    debug!(username = %claims.sub, collection_id = %collection_id, "Index export request");
+
+    let storage = state.storage.clone();
+    let col_id = collection_id.clone();
+    let bytes = tokio::task::spawn_blocking(move || storage.export_index(&col_id).map_err(|e| e.to_string()))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            error!(error = %e, collection_id = %collection_id, "Index export failed");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Index export failed: {}", e))
+        })?;
+
+    info!(username = %claims.sub, collection_id = %collection_id, bytes = bytes.len(), "Index exported via REST");
+    Ok(([(header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream"))], bytes).into_response())
+}
+
+/// Largest index file `PUT /admin/collections/:collection_id/index` accepts (1 GiB)
+const MAX_INDEX_FILE_BYTES: usize = 1 << 30;
+
+/// Handler: Serve a collection's searches from an uploaded index file instead of rebuilding
+/// PUT /admin/collections/:collection_id/index
+pub async fn import_index_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path(collection_id): Path<String>,
+    body: axum::body::Bytes,
+) -> Result<Json<IndexImport>, AppError> {
+    debug!(username = %claims.sub, collection_id = %collection_id, bytes = body.len(), "Index import request");
+
+    let storage = state.storage.clone();
+    let col_id = collection_id.clone();
+    let import = tokio::task::spawn_blocking(move || {
+        storage.import_index(&col_id, &body).map_err(|e| {
+            if e.downcast_ref::<IncompatibleIndex>().is_some() {
+                warn!(error = %e, collection_id = %col_id, "Rejected incompatible index file");
+                AppError::bad_request(e.to_string())
+            } else {
+                error!(error = %e, collection_id = %col_id, "Index import failed");
+                AppError::internal(format!("Index import failed: {}", e))
+            }
+        })
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    info!(username = %claims.sub, collection_id = %collection_id, vectors = import.vectors, generation = import.generation, "Index imported via REST");
+    Ok(Json(import))
+}
+
+/// Request for POST /admin/cache/autosize
+#[derive(Deserialize, Default)]
+pub struct CacheAutosizeRest {
+    /// Fraction of available memory to give the document cache (defaults to AIDB_CACHE_AUTOSIZE_FRACTION or 0.25)
+    #[serde(default)]
+    pub fraction: Option<f64>,
+}
+
+/// Handler: Recompute the document cache capacity from currently available memory
+/// POST /admin/cache/autosize
+pub async fn cache_autosize_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    payload: Option<Json<CacheAutosizeRest>>,
+) -> Result<Json<CacheResize>, AppError> {
+    let fraction = payload
+        .and_then(|Json(p)| p.fraction)
+        .unwrap_or(state.cache_autosize_fraction);
+    debug!(username = %claims.sub, fraction = fraction, "Cache autosize request");
+
+    let resize = state.storage.autosize_cache(fraction).map_err(|e| {
+        warn!(error = %e, fraction = fraction, "Rejected cache autosize request");
+        AppError::new(StatusCode::BAD_REQUEST, format!("Rejected cache autosize request: {}", e))
+    })?;
+
+    info!(
+        username = %claims.sub,
+        new_capacity_bytes = resize.new_capacity_bytes,
+        evicted = resize.evicted,
+        "Cache autosized via REST"
+    );
+    Ok(Json(resize))
+}
+
+/// Handler: Document cache occupancy and eviction totals
+/// GET /admin/cache/stats
+pub async fn cache_stats_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+) -> Json<CacheStats> {
+    let stats = state.storage.cache_stats();
+    debug!(username = %claims.sub, evictions = stats.evictions, "Cache stats request");
+    Json(stats)
+}
+
+/// Query of GET /admin/audit; every filter is optional
+#[derive(Deserialize, Default)]
+pub struct AuditLogQuery {
+    /// Exact username
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Prefix of `METHOD /route`, e.g. `DELETE` or `POST /admin`
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Inclusive lower bound: epoch milliseconds or an RFC 3339 timestamp
+    #[serde(default)]
+    pub since: Option<String>,
+    /// Inclusive upper bound: epoch milliseconds or an RFC 3339 timestamp
+    #[serde(default)]
+    pub until: Option<String>,
+    /// Page size (default and cap from AIDB_PAGE_DEFAULT / AIDB_PAGE_MAX)
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// Handler: Audited calls (every authenticated non-GET request), oldest first, filtered while
+/// the audit tree is scanned
+/// GET /admin/audit?user=...&action=...&collection=...&since=...&until=...&limit=...&cursor=...
+pub async fn audit_log_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    page_policy: Option<Extension<PagePolicy>>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Json<AuditPage>, AppError> {
+    debug!(admin = %claims.sub, user = ?query.user, action = ?query.action, collection = ?query.collection, "Audit log request");
+    let policy = page_policy.map(|Extension(policy)| policy).unwrap_or_default();
+    let limit = query.limit.unwrap_or(policy.default_limit);
+    if limit == 0 || limit > policy.max_limit {
+        return Err(AppError::bad_request(format!("limit must be 1-{}", policy.max_limit)));
+    }
+    let bound = |name: &str, raw: Option<&String>| -> Result<Option<u64>, AppError> {
+        raw.map(|raw| {
+            parse_timestamp_millis(raw)
+                .ok_or_else(|| AppError::bad_request(format!("Invalid {} '{}': expected epoch milliseconds or RFC 3339", name, raw)))
+        })
+        .transpose()
+    };
+    if let Some(cursor) = &query.cursor {
+        crate::storage::audit::decode_cursor(cursor).map_err(|e| AppError::bad_request(e.to_string()))?;
+    }
+    let filter = AuditFilter {
+        since: bound("since", query.since.as_ref())?,
+        until: bound("until", query.until.as_ref())?,
+        user: query.user,
+        action: query.action,
+        collection: query.collection,
+    };
+
+    let storage = state.storage.clone();
+    let cursor = query.cursor;
+    let page = tokio::task::spawn_blocking(move || storage.audit_log(&filter, cursor.as_deref(), limit).map_err(|e| e.to_string()))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|e| {
+            error!(error = %e, "Audit log scan failed");
+            AppError::internal(format!("Audit log scan failed: {}", e))
+        })?;
+    info!(admin = %claims.sub, records = page.records.len(), "Audit log served via REST");
+    Ok(Json(page))
+}
+
+/// Body of PUT /admin/users/:username/roles
+#[derive(Deserialize)]
+pub struct SetUserRolesRest {
+    pub roles: Vec<Role>,
+}
+
+impl StrictBody for SetUserRolesRest {}
+
+/// Handler: Replace a user's roles; they apply to tokens issued from the user's next login
+/// PUT /admin/users/:username/roles
+pub async fn set_user_roles_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path(username): Path<String>,
+    StrictJson(payload): StrictJson<SetUserRolesRest>,
+) -> Result<Json<RestResponse>, AppError> {
+    debug!(admin = %claims.sub, username = %username, roles = ?payload.roles, "Set user roles request");
+    if payload.roles.is_empty() {
+        warn!(username = %username, "Rejected empty role list");
+        return Err(AppError::bad_request("A user needs at least one role"));
+    }
+    let user = state.storage.set_user_roles(&username, payload.roles).map_err(|e| {
+        error!(error = %e, username = %username, "Failed to set user roles");
+        AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to set user roles: {}", e))
+    })?;
+    let Some(user) = user else {
+        warn!(username = %username, "Set roles for unknown user");
+        return Err(AppError::not_found(format!("User {} not found", username)));
+    };
+
+    info!(admin = %claims.sub, username = %username, roles = ?user.roles, "User roles updated via REST");
+    Ok(Json(RestResponse {
+        success: true,
+        message: format!("Roles of {} updated", username),
+        results: vec![],
+        cache_hits: None,
+    }))
+}
+
+/// Handler: Get RAG document chunks
+/// GET /collections/:collection_id/rag/docs/:doc_id
+pub async fn rag_get_doc_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path((collection_id, doc_id)): Path<(String, String)>,
+) -> Result<Json<Vec<crate::storage::RagStorageDocument>>, AppError> {
+    debug!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        doc_id = %doc_id,
+        "RAG get document request"
+    );
+    
+    let chunks = state.storage.get_rag_doc_chunks(&collection_id, &doc_id)
+        .map_err(|e| {
+            warn!(error = %e, collection_id = %collection_id, doc_id = %doc_id, "Document not found");
+            AppError::not_found(format!("Document {} not found in {}", doc_id, collection_id))
+        })?;
+    
+    info!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        doc_id = %doc_id,
+        chunks = chunks.len(),
+        "RAG document retrieved"
+    );
+    
+    Ok(Json(chunks))
+}
+
+/// Handler: Delete RAG document
+/// DELETE /collections/:collection_id/rag/docs/:doc_id
+pub async fn rag_delete_doc_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path((collection_id, doc_id)): Path<(String, String)>,
+) -> Result<Json<RestResponse>, AppError> {
+    debug!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        doc_id = %doc_id,
+        "RAG delete document request"
+    );
+    
+    state.storage.delete_rag_doc(&collection_id, &doc_id)
+        .map_err(|e| {
+            error!(error = %e, collection_id = %collection_id, doc_id = %doc_id, "Failed to delete RAG document");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete RAG document: {}", e))
+        })?;
+    
+    info!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        doc_id = %doc_id,
+        "RAG document deleted"
+    );
+    
+    Ok(Json(RestResponse {
+        success: true,
+        message: format!("RAG document {} deleted", doc_id),
+        results: vec![],
+        cache_hits: None,
+    }))
+}
+
+/// Handler: List all RAG documents in collection
+/// GET /collections/:collection_id/rag/docs
+pub async fn rag_list_docs_handler(
+    State(state): State<Arc<AppState>>,
+    Extension(claims): Extension<AuthPayload>,
+    Path(collection_id): Path<String>,
+    params: QueryParams,
+    empty: EmptyResults,
+) -> Result<Json<Vec<String>>, AppError> {
+    debug!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        "RAG list documents request"
+    );
+    
+    let doc_ids = state.storage.get_rag_doc_ids(&collection_id)
+        .map(|ids| params.paginate(ids))
+        .map_err(|e| {
+            error!(error = %e, collection_id = %collection_id, "Failed to list RAG documents");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list RAG documents: {}", e))
+        })?;
+    
+    info!(
+        username = %claims.sub,
+        collection_id = %collection_id,
+        doc_count = doc_ids.len(),
+        "RAG documents listed"
+    );
+    empty.check(doc_ids.is_empty())?;
+    
+    Ok(Json(doc_ids))
+}
+
+/// Handler: Generate embedding for text
+/// POST /rag/embed
+pub async fn rag_embed_handler(
+    Extension(claims): Extension<AuthPayload>,
+    Json(payload): Json<RagEmbedRequest>,
+) -> Result<Json<RagEmbedResponse>, AppError> {
+    debug!(
+        username = %claims.sub,
+        text_len = payload.text.len(),
+        "RAG embed request"
+    );
+    
+    // Create RAG pipeline
+    let pipeline = crate::rag::RagPipeline::simple()
+        .map_err(|e| {
+            error!(error = %e, "Failed to create RAG pipeline");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create RAG pipeline: {}", e))
+        })?;
+    
+    // Generate embedding
+    let embedding = pipeline.embed(&payload.text)
+        .map_err(|e| {
+            error!(error = %e, "Failed to generate embedding");
+            AppError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate embedding: {}", e))
+        })?;
+    
+    info!(
+        username = %claims.sub,
+        embedding_dim = embedding.len(),
+        "Embedding generated"
+    );
+    
+    Ok(Json(RagEmbedResponse {
+        success: true,
+        embedding,
+        dimension: pipeline.embedding_dim(),
+    }))
+}
+
+/// DTO for embedding request
+#[derive(Deserialize)]
+pub struct RagEmbedRequest {
+    pub text: String,
+}
+
+/// Response for embedding
+#[derive(Serialize)]
+pub struct RagEmbedResponse {
+    pub success: bool,
+    pub embedding: Vec<f32>,
+    pub dimension: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use axum::{body::Body, http::Request};
+    use std::fs;
+    use tower::ServiceExt;  // For .oneshot() testing
+
+    #[tokio::test]
+    async fn test_rest_health_and_endpoints() {
+        // Temp setup for REST test (avoids lock ; tests SQL fix)
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let temp_path = temp_dir.to_str().unwrap();
+        let storage = Storage::open(temp_path).expect("Storage for REST test");
+
+        // Insert sample for endpoint test (NoSQL + SQL projection)
+        let doc = Document {
+            text: "REST test".to_string(),
+            metadata: serde_json::json!({"test": true}),
+            ..test_doc("rest_test_doc", vec![0.1, 0.1, 0.1, 0.1])
+        };
+        storage.insert_doc(doc, "rest_test").expect("Insert for test");
+
+        // Create router
+        let app = create_router(storage);
 
         // Test /health GET
         let response = app
@@ -1687,26 +3882,1613 @@ mod tests {
             .expect("Health request");
         assert_eq!(response.status(), StatusCode::OK);
 
-        // Test /sql POST (fixed: now returns response body ; SELECT on NoSQL)
-        // Uses full body for test (addresses no-response issue)
-        let sql_body = axum::body::Body::from(serde_json::to_string(&SqlRest {
-            sql: "SELECT id, category FROM docs WHERE category = 'AI'".to_string(),
-        }).unwrap());
-        let sql_request = Request::builder()
-            .uri("/sql")
-            .method("POST")
-            .header("content-type", "application/json")
-            .body(sql_body)
-            .unwrap();
-        let sql_response = app
-            .oneshot(sql_request)
-            .await
-            .expect("SQL request");
-        assert_eq!(sql_response.status(), StatusCode::OK);  // Ensures body/response
+        // Test /sql POST (fixed: now returns response body ; SELECT on NoSQL)
+        // Uses full body for test (addresses no-response issue)
+        let sql_body = axum::body::Body::from(serde_json::to_string(&SqlRest {
+            sql: "SELECT id, category FROM docs WHERE category = 'AI'".to_string(),
+        }).unwrap());
+        let token = crate::auth::create_jwt_with_roles("rest_test_user", &[Role::Admin]).expect("JWT for test");
+        let sql_request = Request::builder()
+            .uri("/collections/rest_test/sql")
+            .method("POST")
+            .header("content-type", "application/json")
+            .header("authorization", format!("Bearer {}", token))
+            .body(sql_body)
+            .unwrap();
+        let sql_response = app
+            .oneshot(sql_request)
+            .await
+            .expect("SQL request");
+        assert_eq!(sql_response.status(), StatusCode::OK);  // Ensures body/response
+
+        // Test /insert_doc POST (NoSQL) stub (extend reqwest for full)
+        // ...
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    /// POST a JSON body to a protected route with a freshly minted token
+    async fn post_json(app: &Router, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        send_json(app, "POST", uri, body).await
+    }
+
+    async fn send_json(app: &Router, method: &str, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        send_json_as(app, "rest_test_user", &[Role::Admin], method, uri, body).await
+    }
+
+    /// `send_json` with a token for `username` carrying `roles`
+    async fn send_json_as(app: &Router, username: &str, roles: &[Role], method: &str, uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let token = crate::auth::create_jwt_with_roles(username, roles).expect("JWT for test");
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .method(method)
+                    .header("content-type", "application/json")
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .expect("request");
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    /// GET a protected route with a freshly minted token and an optional Accept header
+    async fn get_json(app: &Router, uri: &str, accept: Option<&str>) -> (StatusCode, serde_json::Value) {
+        let token = crate::auth::create_jwt_with_roles("rest_test_user", &[Role::Admin]).expect("JWT for test");
+        let mut builder = Request::builder()
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", token));
+        if let Some(accept) = accept {
+            builder = builder.header("accept", accept);
+        }
+        let response = app.clone().oneshot(builder.body(Body::empty()).unwrap()).await.expect("request");
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+    }
+
+    #[tokio::test]
+    async fn oversized_list_is_truncated_and_continues_from_cursor() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_payload_cap");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for i in 0..20 {
+            storage.insert_doc(Document {
+                text: "x".repeat(100),
+                ..test_doc(&format!("doc{:02}", i), vec![i as f32])
+            }, "capped").unwrap();
+        }
+        let app = create_router(storage).layer(Extension(PayloadLimit { max_bytes: Some(1200) }));
+
+        let mut ids = Vec::new();
+        let mut pages = 0;
+        let mut uri = "/collections/capped/docs".to_string();
+        loop {
+            let (status, body) = get_json(&app, &uri, Some(envelope::ENVELOPE_MEDIA_TYPE)).await;
+            assert_eq!(status, StatusCode::OK);
+            pages += 1;
+            let data = body["data"].as_array().unwrap();
+            assert!(!data.is_empty());
+            assert!(serde_json::to_vec(data).unwrap().len() <= 1200);
+            ids.extend(data.iter().map(|d| d["id"].as_str().unwrap().to_string()));
+            if body.get("truncated").is_none() {
+                assert!(body.get("next_cursor").is_none());
+                break;
+            }
+            assert_eq!(body["truncated"], true);
+            uri = format!("/collections/capped/docs?cursor={}", body["next_cursor"].as_str().unwrap());
+        }
+        assert!(pages > 1, "expected the list to be split");
+        assert_eq!(ids, (0..20).map(|i| format!("doc{:02}", i)).collect::<Vec<_>>());
+
+        // Bare arrays signal truncation through headers
+        let token = crate::auth::create_jwt_with_roles("rest_test_user", &[Role::Admin]).unwrap();
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/collections/capped/docs")
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[payload::TRUNCATED_HEADER], "true");
+        assert!(response.headers().contains_key(payload::NEXT_CURSOR_HEADER));
+
+        let (status, _) = get_json(&app, "/collections/capped/docs?cursor=bogus", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn conditional_get_returns_304_until_the_doc_changes() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_etag");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let doc = |text: &str| Document { text: text.to_string(), ..test_doc("cached", vec![0.1]) };
+        storage.insert_doc(doc("v1"), "etag_col").unwrap();
+        let app = create_router(storage.clone());
+
+        let token = crate::auth::create_jwt_with_roles("rest_test_user", &[Role::Admin]).unwrap();
+        let get = |if_none_match: Option<String>| {
+            let mut builder = Request::builder()
+                .uri("/collections/etag_col/docs/cached")
+                .header("authorization", format!("Bearer {}", token));
+            if let Some(tag) = if_none_match {
+                builder = builder.header(header::IF_NONE_MATCH, tag);
+            }
+            app.clone().oneshot(builder.body(Body::empty()).unwrap())
+        };
+
+        let first = get(None).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        let unchanged = get(Some(etag.clone())).await.unwrap();
+        assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(unchanged.headers()[header::ETAG], etag.as_str());
+        assert!(axum::body::to_bytes(unchanged.into_body(), usize::MAX).await.unwrap().is_empty());
+
+        storage.update_doc(doc("v2"), "etag_col").unwrap();
+        let changed = get(Some(etag.clone())).await.unwrap();
+        assert_eq!(changed.status(), StatusCode::OK);
+        let new_etag = changed.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert_ne!(new_etag, etag);
+        let body = axum::body::to_bytes(changed.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["text"], "v2");
+        assert_eq!(get(Some(format!("W/{}", new_etag))).await.unwrap().status(), StatusCode::NOT_MODIFIED);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn get_doc_supports_bare_and_envelope_modes() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_envelope");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.insert_doc(Document { text: "enveloped".to_string(), ..test_doc("d1", vec![0.1]) }, "env_col").unwrap();
+        let app = create_router(storage);
+
+        // Default: the bare Document
+        let (status, body) = get_json(&app, "/collections/env_col/docs/d1", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["id"], "d1");
+        assert!(body.get("data").is_none());
+
+        // Accept header opts into the envelope
+        let (status, body) = get_json(&app, "/collections/env_col/docs/d1", Some(envelope::ENVELOPE_MEDIA_TYPE)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["success"], true);
+        assert_eq!(body["data"]["id"], "d1");
+        assert_eq!(body["data"]["text"], "enveloped");
+        let (_, body) = get_json(&app, "/collections/env_col/docs", Some(envelope::ENVELOPE_MEDIA_TYPE)).await;
+        assert_eq!(body["data"][0]["id"], "d1");
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn list_docs_filters_by_update_time_window() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_updated_window");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let mut stamps = Vec::new();
+        for id in ["early", "middle", "late"] {
+            storage.insert_doc(test_doc(id, vec![0.1]), "timed").unwrap();
+            stamps.push(storage.get_doc("timed", id).unwrap().updated_at.expect("stamped on insert"));
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let app = create_router(storage);
+
+        let ids = |body: &serde_json::Value| -> Vec<String> {
+            body.as_array().unwrap().iter().map(|d| d["id"].as_str().unwrap().to_string()).collect()
+        };
+        let uri = format!("/collections/timed/docs?updated_from={}&updated_to={}", stamps[1], stamps[1]);
+        let (status, body) = get_json(&app, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&body), vec!["middle"]);
+
+        let (_, body) = get_json(&app, &format!("/collections/timed/docs?updated_from={}", stamps[1]), None).await;
+        assert_eq!(ids(&body), vec!["late", "middle"]);
+        let from = chrono::DateTime::from_timestamp_millis(stamps[0] as i64).unwrap().to_rfc3339();
+        let uri = format!("/collections/timed/docs?updated_from={}&updated_to={}", from.replace('+', "%2B"), stamps[0]);
+        let (_, body) = get_json(&app, &uri, None).await;
+        assert_eq!(ids(&body), vec!["early"]);
+
+        let (status, _) = get_json(&app, "/collections/timed/docs?updated_from=yesterday", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let uri = format!("/collections/timed/docs?updated_from={}&updated_to={}", stamps[2], stamps[0]);
+        assert_eq!(get_json(&app, &uri, None).await.0, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn bulk_delete_endpoint_reports_deleted_and_missing_ids() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_bulk_delete");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for id in ["a", "b"] {
+            storage.insert_doc(test_doc(id, vec![0.1]), "bulk").unwrap();
+        }
+        let app = create_router(storage);
+        let (_, body) = get_json(&app, "/collections/bulk/count", None).await;
+        assert_eq!(body, serde_json::json!({"collection_id": "bulk", "count": 2}));
+
+        let (status, body) = post_json(&app, "/collections/bulk/docs/bulk_delete", serde_json::json!({"ids": ["a", "nope", "b"]})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["deleted"].as_u64(), body["not_found"].as_u64()), (Some(2), Some(1)));
+        assert_eq!(body["results"][1], serde_json::json!({"id": "nope", "status": "not_found"}));
+        assert_eq!(body["results"][2]["status"], "deleted");
+        let (_, body) = get_json(&app, "/collections/bulk/docs", None).await;
+        assert_eq!(body, serde_json::json!([]));
+        let (_, body) = get_json(&app, "/collections/bulk/count", None).await;
+        assert_eq!(body["count"], 0);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn update_by_query_patches_only_matching_docs() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_update_by_query");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for (id, category) in [("a1", "AI"), ("a2", "AI"), ("m1", "ML")] {
+            storage.insert_doc(Document {
+                category: category.to_string(),
+                metadata: serde_json::json!({"batch": 7, "draft": true, "review": {"by": "nobody"}}),
+                ..test_doc(id, vec![0.1])
+            }, "ubq").unwrap();
+        }
+        let ml_before = storage.get_doc("ubq", "m1").unwrap();
+        let app = create_router(storage.clone());
+
+        let request = serde_json::json!({
+            "filters": [{"field": "category", "op": "eq", "value": "AI"}, {"field": "metadata.batch", "op": "eq", "value": 7}],
+            "patch": {"reviewed": true, "draft": null, "review": {"at": "2026-10-16"}}
+        });
+        let (status, body) = post_json(&app, "/collections/ubq/docs/update_by_query", request.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["matched"].as_u64(), body["updated"].as_u64()), (Some(2), Some(2)));
+        for id in ["a1", "a2"] {
+            let doc = storage.get_doc("ubq", id).unwrap();
+            assert_eq!(doc.metadata, serde_json::json!({"batch": 7, "reviewed": true, "review": {"by": "nobody", "at": "2026-10-16"}}));
+        }
+        let ml_after = storage.get_doc("ubq", "m1").unwrap();
+        assert_eq!((ml_after.metadata, ml_after.updated_at), (ml_before.metadata, ml_before.updated_at), "non-matching docs are untouched");
+        assert!(storage.verify_generations(false).unwrap().drifted.is_empty());
+
+        // Re-running matches the same documents but has nothing left to change
+        let (_, body) = post_json(&app, "/collections/ubq/docs/update_by_query", request).await;
+        assert_eq!((body["matched"].as_u64(), body["updated"].as_u64()), (Some(2), Some(0)));
+
+        let (status, _) = post_json(&app, "/collections/ubq/docs/update_by_query", serde_json::json!({"filters": [], "patch": {"x": 1}})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "an empty filter would patch the whole collection");
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn roles_gate_writes_and_administration() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_roles");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        let doc = serde_json::json!({"id": "d1", "text": "t", "category": "AI", "vector": [0.1, 0.2], "metadata_json": "{}"});
+
+        // A reader is denied inserts but may search
+        let (status, _) = send_json_as(&app, "rest_test_user", &[Role::Reader], "POST", "/collections/rbac/docs", doc.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(storage.get_docs_in_collection("rbac").unwrap().is_empty());
+        let (status, _) = send_json_as(&app, "rest_test_user", &[], "POST", "/collections/rbac/docs/bulk_delete", serde_json::json!({"ids": ["d1"]})).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "a token without roles only reads");
+
+        // A writer inserts but can't manage tenants or reach /admin
+        let (status, _) = send_json_as(&app, "rest_test_user", &[Role::Writer], "POST", "/collections/rbac/docs", doc).await;
+        assert_eq!(status, StatusCode::OK);
+        let search = serde_json::json!({"query": "t", "partial_match": true, "case_sensitive": false, "include_metadata": false});
+        let (status, _) = send_json_as(&app, "rest_test_user", &[Role::Reader], "POST", "/collections/rbac/search", search).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_json_as(&app, "rest_test_user", &[Role::Writer], "POST", "/tenants", serde_json::json!({"id": "t1", "name": "t"})).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_json_as(&app, "rest_test_user", &[Role::Writer], "POST", "/admin/self-check", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // The first registered user administers the database, later ones read; admins set others' roles
+        let (status, _) = send_json(&app, "POST", "/register", serde_json::json!({"username": "ann", "password": "pw"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(storage.get_user("ann").unwrap().unwrap().effective_roles(), vec![Role::Admin]);
+        let (status, _) = send_json(&app, "POST", "/register", serde_json::json!({"username": "bob", "password": "pw"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(storage.get_user("bob").unwrap().unwrap().effective_roles(), vec![Role::Reader]);
+        let legacy = User { username: "old".to_string(), password_hash: String::new(), tenants: vec![], roles: vec![], context: None };
+        assert_eq!(legacy.effective_roles(), vec![Role::Writer], "role-less users don't administer");
+        let demote = serde_json::json!({"roles": ["reader"]});
+        let (status, _) = send_json_as(&app, "rest_test_user", &[Role::Writer], "PUT", "/admin/users/bob/roles", demote.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(send_json(&app, "PUT", "/admin/users/bob/roles", demote.clone()).await.0, StatusCode::OK);
+        assert_eq!(storage.get_user("bob").unwrap().unwrap().effective_roles(), vec![Role::Reader]);
+        assert_eq!(send_json(&app, "PUT", "/admin/users/nobody/roles", demote).await.0, StatusCode::NOT_FOUND);
+        assert_eq!(send_json(&app, "PUT", "/admin/users/bob/roles", serde_json::json!({"roles": []})).await.0, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn collections_are_limited_to_their_tenant_owner_and_grantees() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_ownership");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for username in ["alice", "bob"] {
+            storage.create_user(User {
+                username: username.to_string(),
+                password_hash: String::new(),
+                tenants: vec![],
+                roles: vec![Role::Writer],
+                context: None,
+            }).unwrap();
+        }
+        storage.create_tenant(Tenant {
+            id: "acme".to_string(),
+            name: "acme".to_string(),
+            owner_id: "alice".to_string(),
+            environments: vec!["acme_env".to_string()],
+        }).unwrap();
+        storage.create_environment(Environment {
+            id: "acme_env".to_string(),
+            name: "acme_env".to_string(),
+            tenant_id: "acme".to_string(),
+            collections: vec!["acme_col".to_string()],
+        }).unwrap();
+        storage.create_collection(Collection {
+            id: "acme_col".to_string(),
+            name: "acme_col".to_string(),
+            environment_id: "acme_env".to_string(),
+            vector_dim: None,
+            metric: DistanceMetric::L2,
+            normalize: false,
+        }).unwrap();
+        assert_eq!(storage.collection_owner("acme_col").unwrap().map(|t| t.owner_id).as_deref(), Some("alice"));
+        assert!(storage.collection_owner("scratch").unwrap().is_none());
+        let app = create_router(storage.clone());
+        let doc = serde_json::json!({"id": "d1", "text": "t", "category": "AI", "vector": [0.1, 0.2], "metadata_json": "{}"});
+        let sql = serde_json::json!({"sql": "SELECT id FROM docs"});
+        let writer = &[Role::Writer];
+
+        let (status, _) = send_json_as(&app, "alice", writer, "POST", "/collections/acme_col/docs", doc.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Bob neither owns acme nor was granted it
+        let (status, _) = send_json_as(&app, "bob", writer, "GET", "/collections/acme_col/docs/d1", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_json_as(&app, "bob", writer, "POST", "/collections/acme_col/sql", sql.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_json_as(&app, "bob", writer, "POST", "/collections/acme_col/docs", doc.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        // Percent-encoding the ID doesn't dodge the check
+        let (status, _) = send_json_as(&app, "bob", writer, "POST", "/collections/%61cme_col/sql", sql.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        // Unowned collections stay open to everyone
+        let (status, _) = send_json_as(&app, "bob", writer, "POST", "/collections/scratch/docs", doc).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Collections named in the body are checked as well, before anything runs
+        let body_routes = [
+            ("/search", serde_json::json!({"collections": ["scratch", "acme_col"], "query_vector": [0.1, 0.2]})),
+            ("/similarity", serde_json::json!({"a": {"collection_id": "acme_col", "doc_id": "d1"}, "b": [0.1, 0.2]})),
+            (
+                "/collections/cross/query",
+                serde_json::json!({"source": "scratch", "stages": [{"lookup": {"from": "acme_col", "local_field": "id", "foreign_field": "id", "as": "acme"}}]}),
+            ),
+            ("/collections/cross/operation", serde_json::json!({"operation": "delete", "collections": ["acme_col"], "documents": [{"id": "d1"}]})),
+        ];
+        for (uri, body) in body_routes {
+            let (status, _) = send_json_as(&app, "bob", writer, "POST", uri, body).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+        }
+        assert!(storage.get_doc("acme_col", "d1").is_ok(), "the denied delete removed nothing");
+
+        // A workspace context pointing at acme (e.g. set before a grant was revoked) is checked per request
+        let mut bob = storage.get_user("bob").unwrap().unwrap();
+        bob.context = Some(WorkspaceContext { collection_id: Some("acme_col".to_string()), ..Default::default() });
+        storage.update_user(bob).unwrap();
+        let (status, _) = send_json_as(&app, "bob", writer, "GET", "/docs", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // A grant on the tenant opens its collections
+        let mut bob = storage.get_user("bob").unwrap().unwrap();
+        bob.tenants.push("acme".to_string());
+        storage.update_user(bob).unwrap();
+        let (status, _) = send_json_as(&app, "bob", writer, "GET", "/collections/acme_col/docs/d1", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn hierarchy_ids_must_be_slugs_on_create() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_hierarchy_ids");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+
+        for bad in ["a/b", "has space", ""] {
+            let (status, _) = post_json(&app, "/tenants", serde_json::json!({"id": bad, "name": "t"})).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "tenant {:?}", bad);
+            let (status, _) = post_json(&app, "/tenants/acme/environments", serde_json::json!({"id": bad, "name": "e"})).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "environment {:?}", bad);
+            let (status, _) = post_json(&app, "/environments/prod/collections", serde_json::json!({"id": bad, "name": "c"})).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "collection {:?}", bad);
+        }
+        let (status, _) = send_json(&app, "PUT", "/environments/prod/collections/has%20space", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(storage.get_tenant("a/b").unwrap().is_none());
+
+        // Valid slugs are created, surrounding whitespace trimmed
+        assert_eq!(post_json(&app, "/tenants", serde_json::json!({"id": " acme ", "name": "Acme"})).await.0, StatusCode::OK);
+        assert!(storage.get_tenant("acme").unwrap().is_some());
+        assert_eq!(post_json(&app, "/tenants/acme/environments", serde_json::json!({"id": "prod-eu", "name": "Prod"})).await.0, StatusCode::OK);
+        assert_eq!(post_json(&app, "/environments/prod-eu/collections", serde_json::json!({"id": "docs_v2.1", "name": "Docs"})).await.0, StatusCode::OK);
+        assert_eq!(storage.get_environment("prod-eu").unwrap().unwrap().collections, vec!["docs_v2.1"]);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn upsert_collection_rejects_unknown_environments_and_differing_settings() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_upsert_collection");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for env in ["prod", "dev"] {
+            storage.create_environment(Environment { id: env.to_string(), name: env.to_string(), tenant_id: "t1".to_string(), collections: vec![] }).unwrap();
+        }
+        let app = create_router(storage.clone());
+
+        let (status, _) = send_json(&app, "PUT", "/environments/nowhere/collections/docs", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(storage.get_collection("docs").unwrap().is_none());
+
+        let (status, body) = send_json(&app, "PUT", "/environments/prod/collections/docs", serde_json::json!({"metric": "cosine"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["message"], "Collection created");
+        let (status, body) = send_json(&app, "PUT", "/environments/prod/collections/docs", serde_json::json!({"metric": "cosine"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["message"], "Collection exists");
+
+        // Another environment's collection isn't handed out
+        let (status, body) = send_json(&app, "PUT", "/environments/dev/collections/docs", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body["error"].as_str().unwrap().contains("environment 'prod'"), "{}", body);
+        assert!(storage.get_environment("dev").unwrap().unwrap().collections.is_empty());
+
+        // A dimension is recorded on a collection without one, then has to match
+        let (status, _) = send_json(&app, "PUT", "/environments/prod/collections/docs", serde_json::json!({"vector_dim": 3})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(storage.get_collection("docs").unwrap().unwrap().vector_dim, Some(3));
+        let (status, body) = send_json(&app, "PUT", "/environments/prod/collections/docs", serde_json::json!({"vector_dim": 4, "metric": "l2"})).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let message = body["error"].as_str().unwrap();
+        assert!(message.contains("metric cosine (requested l2)"), "{}", message);
+        assert!(message.contains("vector_dim 3 (requested 4)"), "{}", message);
+        let stored = storage.get_collection("docs").unwrap().unwrap();
+        assert_eq!((stored.metric, stored.vector_dim), (DistanceMetric::Cosine, Some(3)));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn collection_config_reports_stored_and_inherited_settings() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_collection_config");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.create_environment(Environment { id: "env1".to_string(), name: "env1".to_string(), tenant_id: "t1".to_string(), collections: vec![] }).unwrap();
+        let app = create_router(storage);
+
+        let (status, _) = send_json(&app, "PUT", "/environments/env1/collections/cosine_col", serde_json::json!({"metric": "cosine"})).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, config) = get_json(&app, "/environments/env1/collections/cosine_col/config", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(config["metric"], "cosine");
+        assert_eq!(config["sources"]["metric"], "collection");
+        assert!(config["vector_dim"].is_null());
+        assert!(config["sources"].get("vector_dim").is_none());
+        assert_eq!(config["hnsw"]["ef_search"], 100);
+        assert_eq!(config["read_only"], false);
+        assert_eq!(config["normalize"], false);
+        assert_eq!(config["sources"]["normalize"], "collection");
+        for inherited in ["hnsw", "read_only"] {
+            assert_eq!(config["sources"][inherited], "server");
+        }
+
+        // The first insert records the dimension on the collection
+        let doc = serde_json::json!({"id": "d1", "text": "t", "category": "AI", "vector": [0.1, 0.2, 0.3], "metadata_json": "{}"});
+        assert_eq!(post_json(&app, "/collections/cosine_col/docs", doc).await.0, StatusCode::OK);
+        let (_, config) = get_json(&app, "/environments/env1/collections/cosine_col/config", None).await;
+        assert_eq!(config["vector_dim"], 3);
+        assert_eq!(config["sources"]["vector_dim"], "collection");
+
+        let (status, _) = get_json(&app, "/environments/other_env/collections/cosine_col/config", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn locate_finds_doc_in_every_accessible_collection() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_locate");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.create_user(User {
+            username: "rest_test_user".to_string(),
+            password_hash: String::new(),
+            tenants: vec!["loc_tenant".to_string()],
+            roles: vec![],
+            context: None,
+        }).unwrap();
+        storage.create_tenant(Tenant {
+            id: "loc_tenant".to_string(),
+            name: "loc_tenant".to_string(),
+            owner_id: "rest_test_user".to_string(),
+            environments: vec!["loc_env".to_string()],
+        }).unwrap();
+        storage.create_environment(Environment {
+            id: "loc_env".to_string(),
+            name: "loc_env".to_string(),
+            tenant_id: "loc_tenant".to_string(),
+            collections: vec!["loc_a".to_string(), "loc_b".to_string(), "loc_empty".to_string()],
+        }).unwrap();
+        // "loc_hidden" is not reachable from the caller's tenants
+        for col in ["loc_a", "loc_b", "loc_hidden"] {
+            storage.insert_doc(Document { text: "same id".to_string(), ..test_doc("shared", vec![0.1]) }, col).unwrap();
+        }
+        let app = create_router(storage);
+
+        let (status, body) = get_json(&app, "/docs/shared/locate", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["collections"], serde_json::json!(["loc_a", "loc_b"]));
+        assert_eq!(body["scanned"], 3);
+        assert_eq!(body["truncated"], false);
+
+        let (_, body) = get_json(&app, "/docs/missing/locate", None).await;
+        assert_eq!(body["collections"], serde_json::json!([]));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn context_collection_is_used_when_path_omits_it() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_context");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.create_user(User {
+            username: "rest_test_user".to_string(),
+            password_hash: String::new(),
+            tenants: vec!["ctx_tenant".to_string()],
+            roles: vec![],
+            context: None,
+        }).unwrap();
+        storage.create_tenant(Tenant {
+            id: "ctx_tenant".to_string(),
+            name: "ctx_tenant".to_string(),
+            owner_id: "rest_test_user".to_string(),
+            environments: vec!["ctx_env".to_string()],
+        }).unwrap();
+        storage.create_environment(Environment {
+            id: "ctx_env".to_string(),
+            name: "ctx_env".to_string(),
+            tenant_id: "ctx_tenant".to_string(),
+            collections: vec!["ctx_col".to_string()],
+        }).unwrap();
+        let app = create_router(storage.clone());
+        let doc = serde_json::json!({
+            "id": "ctx_doc", "text": "t", "category": "AI", "vector": [0.1, 0.2], "metadata_json": "{}"
+        });
+
+        // No context yet: the shortcut route can't pick a collection
+        let (status, _) = post_json(&app, "/docs", doc.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Collections outside the user's workspace are rejected
+        let (status, _) = send_json(&app, "PUT", "/me/context", serde_json::json!({"collection_id": "other"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let context = serde_json::json!({
+            "tenant_id": "ctx_tenant", "environment_id": "ctx_env", "collection_id": "ctx_col"
+        });
+        let (status, body) = send_json(&app, "PUT", "/me/context", context.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, context);
+
+        let (status, _) = post_json(&app, "/docs", doc).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(storage.get_doc("ctx_col", "ctx_doc").unwrap().text, "t");
+        let (_, listed) = get_json(&app, "/docs", None).await;
+        assert_eq!(listed[0]["id"], "ctx_doc");
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn multi_collection_search_merges_and_tags_results() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_multi_search");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for (col, id, vector) in [
+            ("ms_a", "a_near", vec![0.0, 0.0]),
+            ("ms_a", "a_far", vec![5.0, 5.0]),
+            ("ms_b", "b_mid", vec![1.0, 0.0]),
+            ("ms_b", "b_farthest", vec![10.0, 10.0]),
+        ] {
+            storage.insert_doc(test_doc(id, vector), col).unwrap();
+        }
+        let app = create_router(storage);
+
+        let (status, body) = post_json(&app, "/search", serde_json::json!({
+            "collections": ["ms_a", "ms_b"], "query_vector": [0.0, 0.0], "top_k": 3
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["score_kind"], "similarity");
+        let results = body["results"].as_array().unwrap();
+        let hits: Vec<(&str, &str)> = results
+            .iter()
+            .map(|r| (r["id"].as_str().unwrap(), r["_collection"].as_str().unwrap()))
+            .collect();
+        assert_eq!(hits, vec![("a_near", "ms_a"), ("b_mid", "ms_b"), ("a_far", "ms_a")]);
+        let scores: Vec<f64> = results.iter().map(|r| r["score"].as_f64().unwrap()).collect();
+        assert!(scores.windows(2).all(|w| w[0] >= w[1]), "scores not descending: {:?}", scores);
+
+        let (status, _) = post_json(&app, "/search", serde_json::json!({
+            "collections": [], "query_vector": [0.0, 0.0]
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn range_search_counts_docs_within_radius() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_range_search");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        // Points on a line at distances 0, 1, 2, ... from the origin
+        let docs = (0..8)
+            .map(|i| test_doc(&format!("doc{}", i), vec![i as f32, 0.0]))
+            .collect();
+        storage.insert_docs(docs, "range_col").unwrap();
+        let app = create_router(storage);
+
+        let (status, body) = post_json(&app, "/collections/range_col/vector_search/range", serde_json::json!({
+            "query_vector": [0.0, 0.0], "radius": 2.5
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["count"], 3);
+        assert!(body.get("results").is_none());
+
+        let (status, body) = post_json(&app, "/collections/range_col/vector_search/range", serde_json::json!({
+            "query_vector": [3.0, 0.0], "radius": 1.0, "include_ids": true, "limit": 2
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["count"], 3);
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["id"], "doc3");
+        assert_eq!(results[0]["distance"], 0.0);
+
+        let (status, _) = post_json(&app, "/collections/range_col/vector_search/range", serde_json::json!({
+            "query_vector": [0.0, 0.0], "radius": -1.0
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Too strict for min_results: the radius doubles (0.5 -> 1 -> 2 -> 4) until 4 docs are inside
+        let (status, body) = post_json(&app, "/collections/range_col/vector_search/range", serde_json::json!({
+            "query_vector": [0.0, 0.0], "radius": 0.5, "min_results": 4, "include_ids": true
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["count"].as_u64(), body["radius"].as_f64(), body["relaxed"].as_bool()), (Some(5), Some(4.0), Some(true)));
+        let relaxed: Vec<bool> = body["results"].as_array().unwrap().iter().map(|r| r.get("relaxed").is_some()).collect();
+        assert_eq!(relaxed, vec![false, true, true, true, true]);
+
+        // Already satisfied, or more than the collection holds: nothing left to relax toward
+        let (_, body) = post_json(&app, "/collections/range_col/vector_search/range", serde_json::json!({
+            "query_vector": [0.0, 0.0], "radius": 2.5, "min_results": 2
+        })).await;
+        assert_eq!((body["count"].as_u64(), body["relaxed"].as_bool()), (Some(3), Some(false)));
+        let (_, body) = post_json(&app, "/collections/range_col/vector_search/range", serde_json::json!({
+            "query_vector": [0.0, 0.0], "radius": 0.0, "min_results": 50
+        })).await;
+        assert_eq!((body["count"].as_u64(), body["radius"].as_f64()), (Some(8), Some(8.0)));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn index_stats_report_memory_vectors_and_build_time() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_index_stats");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        let docs = |range: std::ops::Range<usize>| range.map(|i| test_doc(&format!("doc{}", i), vec![i as f32, 1.0])).collect::<Vec<_>>();
+
+        storage.insert_docs(docs(0..20), "stats_col").unwrap();
+        let (status, small) = get_json(&app, "/collections/stats_col/index_stats", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let index = &small["indexes"][0];
+        assert_eq!((index["field"].as_str(), index["backend"].as_str(), index["vectors"].as_u64()), (Some("vector"), Some("flat"), Some(20)));
+        assert!(index["build_ms"].is_u64());
+
+        storage.insert_docs(docs(20..crate::indexing::FLAT_INDEX_THRESHOLD + 20), "stats_col").unwrap();
+        let (_, large) = get_json(&app, "/collections/stats_col/index_stats", None).await;
+        assert_eq!(large["indexes"][0]["backend"], "hnsw");
+        assert!(large["approx_memory_bytes"].as_u64().unwrap() > small["approx_memory_bytes"].as_u64().unwrap());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn interrupted_export_resumes_from_its_last_checkpoint() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_export");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        storage.insert_docs((0..23).map(|i| test_doc(&format!("doc{:02}", i), vec![i as f32, 1.0])).collect(), "export_col").unwrap();
+
+        let export = |uri: String| {
+            let app = app.clone();
+            async move {
+                let token = crate::auth::create_jwt_with_roles("rest_test_user", &[Role::Admin]).expect("JWT for test");
+                let request = Request::builder().uri(uri).header("authorization", format!("Bearer {}", token));
+                let response = app.oneshot(request.body(Body::empty()).unwrap()).await.expect("request");
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                String::from_utf8(bytes.to_vec()).unwrap()
+                    .lines()
+                    .map(|line| serde_json::from_str::<ExportLine>(line).unwrap())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // The connection drops partway into the third page: the client keeps what its last
+        // checkpoint covers and discards the unconfirmed documents after it
+        let full = export("/collections/export_col/export?checkpoint_every=5".to_string()).await;
+        assert_eq!(full.iter().filter(|l| matches!(l, ExportLine::Checkpoint { .. })).count(), 5);
+        assert!(matches!(full.last(), Some(ExportLine::End { exported: 23 })));
+        let cut = full.iter().position(|l| matches!(l, ExportLine::Doc { doc } if doc.id == "doc12")).unwrap();
+        let received = &full[..cut];
+        let checkpoint = received.iter().rposition(|l| matches!(l, ExportLine::Checkpoint { .. })).unwrap();
+        assert!(matches!(&received[checkpoint], ExportLine::Checkpoint { last_id, exported: 10 } if last_id == "doc09"));
+        let mut ids: Vec<String> = received[..checkpoint].iter().filter_map(|l| match l {
+            ExportLine::Doc { doc } => Some(doc.id.clone()),
+            _ => None,
+        }).collect();
+
+        let resumed = export("/collections/export_col/export?checkpoint_every=5&resume_after=doc09".to_string()).await;
+        assert!(matches!(resumed.last(), Some(ExportLine::End { exported: 13 })));
+        ids.extend(resumed.into_iter().filter_map(|l| match l {
+            ExportLine::Doc { doc } => Some(doc.id),
+            _ => None,
+        }));
+        assert_eq!(ids, (0..23).map(|i| format!("doc{:02}", i)).collect::<Vec<_>>(), "complete, in order, no duplicates");
+
+        let (status, _) = get_json(&app, "/collections/export_col/export?checkpoint_every=0", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn exact_vector_search_matches_hand_computed_top_k() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_exact_search");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        // Large enough that the regular search path is served by HNSW
+        let n = crate::indexing::FLAT_INDEX_THRESHOLD + 44;
+        let vector = |i: usize| vec![(i % 17) as f32 * 0.7, (i * 31 % 23) as f32 * 0.4, (i % 5) as f32];
+        let docs = (0..n)
+            .map(|i| test_doc(&format!("doc{}", i), vector(i)))
+            .collect();
+        storage.insert_docs(docs, "exact_col").unwrap();
+        let app = create_router(storage.clone());
+
+        let query = [4.1f32, 3.3, 2.0];
+        let mut expected: Vec<(String, f32)> = (0..n)
+            .map(|i| {
+                let d = vector(i).iter().zip(&query).map(|(a, b)| (a - b) * (a - b)).sum::<f32>().sqrt();
+                (format!("doc{}", i), d)
+            })
+            .collect();
+        expected.sort_by(|a, b| a.1.total_cmp(&b.1));
+        expected.truncate(10);
+
+        let (status, body) = post_json(&app, "/collections/exact_col/vector_search/exact", serde_json::json!({
+            "query_vector": query, "top_k": 10
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["index_backend"], "flat");
+        assert_eq!(body["score_kind"], "distance");
+        let results = body["results"].as_array().unwrap();
+        assert_eq!(results.len(), 10);
+        for (hit, (id, distance)) in results.iter().zip(&expected) {
+            assert!((hit["score"].as_f64().unwrap() as f32 - distance).abs() < 1e-5);
+            // Ties may come back in any order; the distance must still match the exact ranking
+            if hit["id"] != *id {
+                let tied = expected.iter().any(|(i, d)| hit["id"] == *i && d == distance);
+                assert!(tied, "unexpected exact hit {}", hit["id"]);
+            }
+        }
+
+        // The approximate path can only match or trail the exact distances rank for rank
+        let approx = storage.vector_search_detailed("exact_col", &query, 10).unwrap();
+        assert_eq!(approx.index_backend, crate::indexing::IndexBackend::Hnsw);
+        for (approx_distance, (_, exact_distance)) in approx.scores.iter().zip(&expected) {
+            assert!(*approx_distance >= exact_distance - 1e-5);
+        }
+
+        let (status, _) = post_json(&app, "/collections/exact_col/vector_search/exact", serde_json::json!({
+            "query_vector": query, "top_k": 0
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn hybrid_explain_reports_distance_and_matched_clauses() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_hybrid_explain");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for (id, category, vector) in [
+            ("near_ai", "AI", vec![0.0, 0.0]),
+            ("near_db", "DB", vec![1.0, 0.0]),
+            ("far_ai", "AI", vec![3.0, 4.0]),
+        ] {
+            storage.insert_doc(Document {
+                text: format!("{} about vectors", id),
+                category: category.to_string(),
+                metadata: serde_json::json!({"rank": 2}),
+                ..test_doc(id, vector)
+            }, "explain_col").unwrap();
+        }
+        let app = create_router(storage);
+
+        let request = serde_json::json!({
+            "sql_filter": "category = 'AI' AND rank = 2",
+            "query_vector": [0.0, 0.0],
+            "top_k": 2,
+            "keywords": "vectors graphs"
+        });
+        let (status, body) = post_json(&app, "/collections/explain_col/hybrid?explain=true", request.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], serde_json::json!(["near_ai", "far_ai"]));
+        let explanations = body["explanations"].as_array().unwrap();
+        assert_eq!(explanations[1]["id"], "far_ai");
+        let far = &explanations[1]["explanation"];
+        assert!((far["vector_distance"].as_f64().unwrap() - 5.0).abs() < 1e-6);
+        assert_eq!(far["metric"], "l2");
+        assert_eq!(far["keyword_score"], 0.5);
+        assert_eq!(far["matched_clauses"], serde_json::json!(["category = 'AI'", "rank = 2"]));
+        assert_eq!(far["unmatched_clauses"], serde_json::json!([]));
+
+        // Explanations are opt-in
+        let (_, body) = post_json(&app, "/collections/explain_col/hybrid", request).await;
+        assert!(body.get("explanations").is_none());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn similarity_of_known_vectors_and_stored_docs() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_similarity");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.insert_doc(Document {
+            vectors: HashMap::from([("title".to_string(), vec![0.0, 2.0])]),
+            ..test_doc("stored", vec![1.0, 1.0])
+        }, "sim_col").unwrap();
+        let app = create_router(storage);
+
+        // cos([1, 0], [1, 1]) = 1 / sqrt(2)
+        let (status, body) = post_json(&app, "/similarity", serde_json::json!({
+            "a": [1.0, 0.0], "b": [1.0, 1.0], "metric": "cosine"
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["metric"], "cosine");
+        let expected = 1.0 / 2f64.sqrt();
+        assert!((body["similarity"].as_f64().unwrap() - expected).abs() < 1e-6);
+        assert!((body["distance"].as_f64().unwrap() - (1.0 - expected)).abs() < 1e-6);
+
+        // Stored documents by reference, including a named field; l2 is the default metric
+        let (status, body) = post_json(&app, "/similarity", serde_json::json!({
+            "a": {"collection_id": "sim_col", "doc_id": "stored"},
+            "b": {"collection_id": "sim_col", "doc_id": "stored", "field": "title"}
+        })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["metric"], "l2");
+        assert!((body["distance"].as_f64().unwrap() - 2f64.sqrt()).abs() < 1e-6);
+
+        let (status, _) = post_json(&app, "/similarity", serde_json::json!({"a": [1.0], "b": [1.0, 0.0]})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = post_json(&app, "/similarity", serde_json::json!({
+            "a": {"collection_id": "sim_col", "doc_id": "missing"}, "b": [1.0, 0.0]
+        })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn sql_mode_controls_ids_vs_rows_payload() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_sql_mode");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for (id, category) in [("a", "AI"), ("b", "AI"), ("c", "ML")] {
+            storage.insert_doc(Document {
+                category: category.to_string(),
+                ..test_doc(id, vec![0.1, 0.2])
+            }, "sql_mode").unwrap();
+        }
+        let app = create_router(storage);
+        let sql = serde_json::json!({"sql": "SELECT id, category FROM docs WHERE category = 'AI'"});
+
+        // Rows is the default: IDs in `results` plus one object per row
+        let (status, body) = post_json(&app, "/collections/sql_mode/sql", sql.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], serde_json::json!(["a", "b"]));
+        assert_eq!(body["rows"], serde_json::json!([
+            {"id": "a", "category": "AI"},
+            {"id": "b", "category": "AI"}
+        ]));
+        let (_, explicit) = post_json(&app, "/collections/sql_mode/sql?mode=rows", sql.clone()).await;
+        assert_eq!(explicit, body);
+
+        // Ids mode leaves the rows out
+        let (status, body) = post_json(&app, "/collections/sql_mode/sql?mode=ids", sql.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], serde_json::json!(["a", "b"]));
+        assert!(body.get("rows").is_none());
+
+        let (status, _) = post_json(&app, "/collections/sql_mode/sql?mode=columns", sql).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn sql_format_arrow_returns_an_ipc_stream() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_sql_arrow");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        for (id, category) in [("a", "AI"), ("b", "AI"), ("c", "ML")] {
+            storage.insert_doc(Document {
+                category: category.to_string(),
+                ..test_doc(id, vec![0.1, 0.2])
+            }, "sql_arrow").unwrap();
+        }
+        let app = create_router(storage);
+        let sql = serde_json::json!({"sql": "SELECT id, category FROM docs WHERE category = 'AI'"});
+
+        let fetch = |uri: &'static str, accept: Option<&'static str>| {
+            let app = app.clone();
+            let sql = sql.clone();
+            async move {
+                let token = crate::auth::create_jwt_with_roles("rest_test_user", &[Role::Reader]).expect("JWT for test");
+                let mut request = Request::builder()
+                    .uri(uri)
+                    .method("POST")
+                    .header("content-type", "application/json")
+                    .header("authorization", format!("Bearer {}", token));
+                if let Some(accept) = accept {
+                    request = request.header("accept", accept);
+                }
+                let response = app.oneshot(request.body(Body::from(sql.to_string())).unwrap()).await.expect("request");
+                assert_eq!(response.status(), StatusCode::OK);
+                let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+                (content_type, axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap())
+            }
+        };
+
+        let (content_type, bytes) = fetch("/collections/sql_arrow/sql?format=arrow", None).await;
+        assert_eq!(content_type, ARROW_STREAM_MEDIA_TYPE);
+        let reader = arrow::ipc::reader::StreamReader::try_new(std::io::Cursor::new(bytes.to_vec()), None).unwrap();
+        assert_eq!(reader.schema().fields().len(), 2);
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+
+        // The Accept header selects Arrow too, while an explicit format wins over it
+        let (content_type, _) = fetch("/collections/sql_arrow/sql", Some(ARROW_STREAM_MEDIA_TYPE)).await;
+        assert_eq!(content_type, ARROW_STREAM_MEDIA_TYPE);
+        let (content_type, bytes) = fetch("/collections/sql_arrow/sql?format=json", Some(ARROW_STREAM_MEDIA_TYPE)).await;
+        assert_eq!(content_type, "application/json");
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body["results"], serde_json::json!(["a", "b"]));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn invalid_sql_returns_a_json_error_body() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_sql_error");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage);
+
+        let (status, body) = post_json(&app, "/collections/sql_error/sql", serde_json::json!({"sql": "SELEC nonsense FROM"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["success"], false);
+        assert_eq!(body["status"], 400);
+        assert_eq!(body["code"], "bad_request");
+        let error = body["error"].as_str().expect("error message");
+        assert!(error.starts_with("SQL execution failed: "), "got {}", error);
+        assert!(error.len() > "SQL execution failed: ".len(), "the engine's reason is included");
+        assert!(body["request_id"].as_str().is_some_and(|id| !id.is_empty()));
+
+        // Lookups say what was missing
+        let (status, body) = get_json(&app, "/collections/sql_error/docs/missing", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["error"], "Document missing not found in sql_error");
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn sql_orders_by_category_then_newest_first() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_sql_order_by");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        // Inserted oldest first; insert_doc stamps updated_at
+        for (id, category) in [("ml1", "ML"), ("ai1", "AI"), ("ai2", "AI"), ("ml2", "ML")] {
+            storage.insert_doc(Document {
+                category: category.to_string(),
+                ..test_doc(id, vec![0.1, 0.2])
+            }, "sql_order").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let app = create_router(storage);
+
+        let sql = serde_json::json!({"sql": "SELECT id, category, updated_at FROM docs ORDER BY category, updated_at DESC"});
+        let (status, body) = post_json(&app, "/collections/sql_order/sql", sql).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], serde_json::json!(["ai2", "ai1", "ml2", "ml1"]));
+        let rows = body["rows"].as_array().unwrap();
+        assert_eq!(rows.iter().map(|r| r["id"].clone()).collect::<Vec<_>>(), vec!["ai2", "ai1", "ml2", "ml1"]);
+        assert!(rows.iter().all(|r| r["updated_at"].is_string()));
+
+        let sql = serde_json::json!({"sql": "SELECT id FROM docs ORDER BY category DESC, updated_at ASC LIMIT 3"});
+        let (_, body) = post_json(&app, "/collections/sql_order/sql?mode=ids", sql).await;
+        assert_eq!(body["results"], serde_json::json!(["ml1", "ml2", "ai1"]));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn concurrent_metadata_appends_both_survive() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_metadata_append");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.insert_doc(Document {
+            metadata: serde_json::json!({"title": "x"}),
+            ..test_doc("doc", vec![1.0, 0.0])
+        }, "append_col").unwrap();
+        let app = create_router(storage.clone());
+        let uri = "/collections/append_col/docs/doc/metadata/append";
+
+        let ((status_a, _), (status_b, _)) = tokio::join!(
+            post_json(&app, uri, serde_json::json!({"field": "tags", "values": ["rag"]})),
+            post_json(&app, uri, serde_json::json!({"field": "tags", "values": ["llm"]}))
+        );
+        assert_eq!((status_a, status_b), (StatusCode::OK, StatusCode::OK));
+        let tags = storage.get_doc("append_col", "doc").unwrap().metadata["tags"].clone();
+        let mut tags: Vec<&str> = tags.as_array().unwrap().iter().map(|t| t.as_str().unwrap()).collect();
+        tags.sort();
+        assert_eq!(tags, vec!["llm", "rag"]);
+
+        let (status, body) = post_json(&app, uri, serde_json::json!({"field": "tags", "values": ["rag", "new"], "unique": true})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["values"].as_array().unwrap().len(), 3);
+
+        let (status, _) = post_json(&app, uri, serde_json::json!({"field": "title", "values": ["y"]})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = post_json(&app, "/collections/append_col/docs/missing/metadata/append", serde_json::json!({
+            "field": "tags", "values": ["x"]
+        })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn strict_mode_rejects_unknown_body_fields() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_strict_json");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let strict = create_router(storage.clone()).layer(Extension(strict::StrictMode { enabled: true }));
+        let lenient = create_router(storage.clone()).layer(Extension(strict::StrictMode { enabled: false }));
+        // `vectorz` is a typo for `vectors`
+        let doc = serde_json::json!({
+            "id": "typo", "text": "t", "category": "AI", "vector": [1.0, 0.0], "metadata_json": "{}",
+            "vectorz": {"title": [0.0, 1.0]}
+        });
+
+        let (status, body) = post_json(&strict, "/collections/strict_col/docs", doc.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("vectorz"));
+        assert!(storage.get_doc("strict_col", "typo").is_err());
+
+        // Nested documents of a batch are checked too
+        let (status, body) = post_json(&strict, "/collections/strict_col/docs/batch", serde_json::json!({"documents": [doc.clone()]})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("documents[0].vectorz"));
+
+        // Without strict mode the unknown field is ignored, as before
+        let (status, _) = post_json(&lenient, "/collections/strict_col/docs", doc).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(storage.get_doc("strict_col", "typo").unwrap().vectors.is_empty());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn batch_insert_reports_each_document() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_batch_insert");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        let doc = |id: String, vector: Vec<f32>| serde_json::json!({
+            "id": id, "text": "batch doc", "category": "AI", "vector": vector, "metadata_json": "{}"
+        });
+
+        // A bare array of 100 documents
+        let docs: Vec<serde_json::Value> = (0..100).map(|i| doc(format!("doc{}", i), vec![i as f32, 1.0])).collect();
+        let (status, body) = post_json(&app, "/collections/batch_col/docs/batch", serde_json::Value::Array(docs)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["success"].as_bool(), body["inserted"].as_u64(), body["rejected"].as_u64()), (Some(true), Some(100), Some(0)));
+        assert_eq!(body["results"].as_array().unwrap().len(), 100);
+        assert_eq!(storage.get_docs_in_collection("batch_col").unwrap().len(), 100);
+
+        // Bad documents are reported and skipped; the rest still go in
+        let (status, body) = post_json(&app, "/collections/batch_col/docs/batch", serde_json::json!({"documents": [
+            doc("good".to_string(), vec![0.5, 0.5]),
+            doc("short".to_string(), vec![1.0]),
+            doc("".to_string(), vec![1.0, 0.0]),
+            serde_json::json!({"id": "inf", "text": "t", "category": "AI", "vector": [1e39, 0.0], "metadata_json": "{}"}),
+        ]})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["success"].as_bool(), body["inserted"].as_u64(), body["rejected"].as_u64()), (Some(false), Some(1), Some(3)));
+        let statuses: Vec<&str> = body["results"].as_array().unwrap().iter().map(|r| r["status"].as_str().unwrap()).collect();
+        assert_eq!(statuses, vec!["inserted", "rejected", "rejected", "rejected"]);
+        assert!(body["results"][1]["error"].as_str().unwrap().contains("expected dim 2, got 1"));
+        assert!(body["results"][0].get("error").is_none());
+        assert_eq!(storage.get_docs_in_collection("batch_col").unwrap().len(), 101);
+        assert!(storage.get_doc("batch_col", "short").is_err());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn non_finite_vectors_are_rejected_with_400() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_non_finite");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+
+        // 1e39 overflows f32 to infinity when the JSON is parsed
+        let (status, _) = post_json(&app, "/collections/nf_col/docs", serde_json::json!({
+            "id": "inf", "text": "t", "category": "AI", "vector": [1e39, 0.0], "metadata_json": "{}"
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(storage.get_doc("nf_col", "inf").is_err());
+
+        let (status, _) = post_json(&app, "/search", serde_json::json!({
+            "collections": ["nf_col"], "query_vector": [0.0, -1e39]
+        })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn insert_response_echoes_inferred_dim_and_metric() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_insert_dim");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.create_collection(Collection {
+            id: "dim_col".to_string(),
+            name: "dim_col".to_string(),
+            environment_id: "env".to_string(),
+            vector_dim: None,
+            metric: DistanceMetric::Cosine,
+            normalize: false,
+        }).unwrap();
+        let app = create_router(storage.clone());
+
+        let doc = |id: &str, vector: Vec<f32>| serde_json::json!({
+            "id": id, "text": "t", "category": "AI", "vector": vector, "metadata_json": "{}"
+        });
+        let (status, body) = post_json(&app, "/collections/dim_col/docs", doc("a", vec![0.1, 0.2, 0.3])).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["dim"], 3);
+        assert_eq!(body["metric"], "cosine");
+        assert_eq!(storage.get_collection("dim_col").unwrap().unwrap().vector_dim, Some(3));
+
+        // The first insert fixed the dimension; later inserts echo it
+        let (_, body) = post_json(&app, "/collections/dim_col/docs", doc("b", vec![0.4, 0.5, 0.6])).await;
+        assert_eq!(body["dim"], 3);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn resync_endpoint_rewrites_drifted_vector() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_resync");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.insert_doc(test_doc("d1", vec![1.0, 2.0]), "rs_col").unwrap();
+        storage.vector_tree.insert(b"rs_col/d1", vec![0u8; 8]).unwrap();
+        let app = create_router(storage.clone());
+
+        let (status, body) = post_json(&app, "/admin/collections/rs_col/docs/d1/resync", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["vector_changed"], true);
+        assert_eq!(storage.get_vectors_in_collection("rs_col").unwrap(), vec![("d1".to_string(), vec![1.0, 2.0])]);
+
+        let (status, _) = post_json(&app, "/admin/collections/rs_col/docs/nope/resync", serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn empty_results_answer_empty_list_or_not_found_per_setting() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_empty_results");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.insert_doc(Document {
+            text: "rust databases".to_string(),
+            ..test_doc("doc", vec![0.1, 0.2])
+        }, "filled").unwrap();
+        let search = |query: &str| serde_json::json!({
+            "query": query, "partial_match": true, "case_sensitive": false, "include_metadata": false
+        });
+
+        // Default: an empty list is still a 200
+        let app = create_router(storage.clone());
+        let (status, body) = get_json(&app, "/collections/empty/docs", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!([]));
+        let (status, body) = post_json(&app, "/collections/filled/search", search("python")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], serde_json::json!([]));
+
+        // not_found: the same requests answer 404, while non-empty results are unaffected
+        let app = create_router(storage).layer(Extension(EmptyResults::NotFound));
+        let (status, _) = get_json(&app, "/collections/empty/docs", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = post_json(&app, "/collections/filled/search", search("python")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = post_json(&app, "/collections/filled/search", search("rust")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"][0]["id"], "doc");
+        let (status, _) = get_json(&app, "/collections/filled/docs", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn search_return_fields_project_each_result() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_return_fields");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        storage.insert_doc(Document {
+            text: "a long body about rust databases".to_string(),
+            metadata: serde_json::json!({"title": "Rust"}),
+            ..test_doc("doc", vec![0.5, 0.25])
+        }, "proj").unwrap();
+        let app = create_router(storage);
+        let search = |return_fields: serde_json::Value| {
+            let mut request = serde_json::json!({"query": "rust", "partial_match": true, "case_sensitive": false, "include_metadata": false});
+            if !return_fields.is_null() {
+                request["return_fields"] = return_fields;
+            }
+            request
+        };
+
+        let (status, body) = post_json(&app, "/collections/proj/search", search(serde_json::json!(["id", "category"]))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], serde_json::json!([{"id": "doc", "category": "AI"}]), "text and vector are omitted");
+
+        let (_, body) = post_json(&app, "/collections/proj/search", search(serde_json::json!(["metadata", "vector"]))).await;
+        assert_eq!(body["results"], serde_json::json!([{"vector": [0.5, 0.25], "metadata": {"title": "Rust"}}]));
+
+        // Without return_fields the summary is unchanged
+        let (_, body) = post_json(&app, "/collections/proj/search", search(serde_json::Value::Null)).await;
+        assert_eq!(body["results"], serde_json::json!([{"id": "doc", "text": "a long body about rust databases", "category": "AI"}]));
+
+        let (status, body) = post_json(&app, "/collections/proj/search", search(serde_json::json!(["id", "txt"]))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["error"].as_str().unwrap().contains("unknown variant `txt`"), "{}", body);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn metrics_stale_gauge_flips_on_write_and_clears_on_rebuild() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_metrics_staleness");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        let doc = |id: &str| test_doc(id, vec![1.0, 0.0]);
+        // No token: scrapers aren't users
+        let scrape = || async {
+            let response = app.clone().oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/plain; version=0.0.4"));
+            String::from_utf8(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+        };
+
+        storage.insert_doc(doc("a"), "metrics_col").unwrap();
+        storage.vector_search("metrics_col", &[1.0, 0.0], 1).unwrap();
+        let text = scrape().await;
+        let generation = storage.collection_generation("metrics_col").unwrap();
+        assert!(text.contains(&format!("aidb_collection_generation{{collection=\"metrics_col\"}} {}\n", generation)));
+        assert!(text.contains(&format!("aidb_index_built_generation{{collection=\"metrics_col\"}} {}\n", generation)));
+        assert!(text.contains("aidb_index_stale{collection=\"metrics_col\"} 0\n"));
+
+        // Replacing a document moves the generation past the cached index
+        storage.insert_doc(Document { vector: vec![0.0, 1.0], ..doc("a") }, "metrics_col").unwrap();
+        let text = scrape().await;
+        assert!(text.contains("aidb_index_stale{collection=\"metrics_col\"} 1\n"));
+        assert!(text.contains(&format!("aidb_index_built_generation{{collection=\"metrics_col\"}} {}\n", generation)));
+
+        storage.rebuild_index("metrics_col").unwrap();
+        assert!(scrape().await.contains("aidb_index_stale{collection=\"metrics_col\"} 0\n"));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests_by_operation_and_report_cache_lookups() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_metrics_requests");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        for id in ["a", "b"] {
+            let doc = serde_json::json!({"id": id, "text": id, "category": "AI", "vector": [1.0, 0.0], "metadata_json": "{}"});
+            assert_eq!(post_json(&app, "/collections/counted/docs", doc).await.0, StatusCode::OK);
+        }
+        let search = serde_json::json!({"query_vector": [1.0, 0.0], "top_k": 2});
+        assert_eq!(post_json(&app, "/collections/counted/vector_search", search).await.0, StatusCode::OK);
+        storage.get_doc("counted", "a").unwrap();
+        storage.get_doc("counted", "a").unwrap();
+
+        let response = app.clone().oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let text = String::from_utf8(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap();
+        assert!(text.contains("aidb_requests_total{operation=\"insert\"} 2\n"), "{}", text);
+        assert!(text.contains("aidb_requests_total{operation=\"search\"} 1\n"));
+        assert!(text.contains("aidb_requests_total{operation=\"sql\"} 0\n"));
+        assert!(text.contains("aidb_request_duration_seconds_count{operation=\"insert\"} 2\n"));
+        let hits: u64 = text
+            .lines()
+            .find_map(|line| line.strip_prefix("aidb_cache_hits_total "))
+            .and_then(|value| value.parse().ok())
+            .expect("cache hit counter");
+        assert!(hits >= 1, "the second read of the same document is a hit");
+        assert!(text.contains("# TYPE aidb_cache_misses_total counter\n"));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
 
-        // Test /insert_doc POST (NoSQL) stub (extend reqwest for full)
-        // ...
+    #[tokio::test]
+    async fn vector_search_cursor_pages_through_the_ranking_without_duplicates() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_vector_search_cursor");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        for i in 0..4 {
+            let doc = serde_json::json!({"id": format!("d{}", i), "text": "t", "category": "AI", "vector": [i as f32, 0.0], "metadata_json": "{}"});
+            assert_eq!(post_json(&app, "/collections/paged/docs", doc).await.0, StatusCode::OK);
+        }
+
+        // One page with everything, for comparison
+        let (status, whole) = post_json(&app, "/collections/paged/vector_search", serde_json::json!({"query_vector": [0.0, 0.0], "top_k": 4})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(whole.get("cursor").is_none());
+        let ids = |page: &serde_json::Value| -> Vec<String> {
+            page["results"].as_array().unwrap().iter().map(|hit| hit["id"].as_str().unwrap().to_string()).collect()
+        };
+        assert_eq!(ids(&whole), ["d0", "d1", "d2", "d3"]);
+
+        let (status, first) = post_json(&app, "/collections/paged/vector_search", serde_json::json!({"query_vector": [0.0, 0.0], "top_k": 4, "page_size": 2})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((ids(&first), first["remaining"].as_u64()), (vec!["d0".to_string(), "d1".to_string()], Some(2)));
+        let cursor = first["cursor"].as_str().unwrap().to_string();
+
+        // Later writes don't reorder a cursor's ranking
+        let closer = serde_json::json!({"id": "new", "text": "t", "category": "AI", "vector": [0.0, 0.0], "metadata_json": "{}"});
+        assert_eq!(post_json(&app, "/collections/paged/docs", closer).await.0, StatusCode::OK);
+
+        let uri = format!("/collections/paged/vector_search?cursor={}", cursor);
+        let (status, second) = get_json(&app, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&second), ["d2", "d3"]);
+        assert_eq!(second["remaining"], 0);
+        assert!(second.get("cursor").is_none());
+        assert_eq!([ids(&first), ids(&second)].concat(), ids(&whole), "pages cover the ranking once");
+
+        // Exhausted cursors are gone; so are other users'
+        assert_eq!(get_json(&app, &uri, None).await.0, StatusCode::NOT_FOUND);
+        let (_, other) = post_json(&app, "/collections/paged/vector_search", serde_json::json!({"query_vector": [0.0, 0.0], "top_k": 4, "page_size": 1})).await;
+        let uri = format!("/collections/paged/vector_search?cursor={}", other["cursor"].as_str().unwrap());
+        let (status, _) = send_json_as(&app, "someone_else", &[Role::Reader], "GET", &uri, serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn vector_search_ranks_with_scores_and_applies_boost_and_dedupe() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_vector_search");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        for (id, text, vector, popularity) in [("a", "same", [1.0, 0.0], 0), ("b", "same", [0.9, 0.1], 1), ("c", "other", [0.0, 1.0], 10)] {
+            let doc = serde_json::json!({"id": id, "text": text, "category": "AI", "vector": vector, "metadata_json": format!("{{\"popularity\": {}}}", popularity)});
+            assert_eq!(post_json(&app, "/collections/vs/docs", doc).await.0, StatusCode::OK);
+        }
+        let search = |body: serde_json::Value| post_json(&app, "/collections/vs/vector_search", body);
+        let ids = |body: &serde_json::Value| -> Vec<String> {
+            body["results"].as_array().unwrap().iter().map(|hit| hit["id"].as_str().unwrap().to_string()).collect()
+        };
+
+        let (status, body) = search(serde_json::json!({"query_vector": [1.0, 0.0], "top_k": 2, "score_kind": "similarity"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&body), ["a", "b"]);
+        assert_eq!((body["score_kind"].as_str(), body["metric"].as_str()), (Some("similarity"), Some("l2")));
+        let scores: Vec<f64> = body["results"].as_array().unwrap().iter().map(|hit| hit["score"].as_f64().unwrap()).collect();
+        assert!((scores[0] - 1.0).abs() < 1e-6 && scores[0] > scores[1], "{:?}", scores);
+
+        // Same options as gRPC VectorSearch
+        let (_, body) = search(serde_json::json!({"query_vector": [1.0, 0.0], "top_k": 2, "dedupe_text": true})).await;
+        assert_eq!(ids(&body), ["a", "c"]);
+        let (_, body) = search(serde_json::json!({"query_vector": [1.0, 0.0], "top_k": 1, "boost": {"field": "popularity", "weight": 10.0}})).await;
+        assert_eq!(ids(&body), ["c"]);
+
+        let (status, body) = search(serde_json::json!({"query_vector": [1.0, 0.0], "boost": {"field": " ", "weight": 1.0}})).await;
+        assert_eq!((status, body["error"].as_str()), (StatusCode::BAD_REQUEST, Some("boost field must not be empty")));
+        let (status, _) = search(serde_json::json!({"query_vector": [1.0, 0.0], "top_k": 0})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn audit_log_filters_by_user_and_action_and_pages() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_audit_log");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let app = create_router(storage.clone());
+        let doc = |id: &str| serde_json::json!({"id": id, "text": "t", "category": "AI", "vector": [0.1, 0.2], "metadata_json": "{}"});
+        let writer = [Role::Writer];
+        for (user, id) in [("alice", "a1"), ("bob", "b1"), ("bob", "b2")] {
+            let (status, _) = send_json_as(&app, user, &writer, "POST", "/collections/audited/docs", doc(id)).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (status, _) = send_json_as(&app, "bob", &writer, "DELETE", "/collections/audited/docs/b1", serde_json::Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_json_as(&app, "alice", &writer, "POST", "/collections/audited/docs", serde_json::json!({"id": "bad"})).await;
+        assert!(status.is_client_error());
+        // Reads aren't audited
+        assert_eq!(get_json(&app, "/collections/audited/docs/a1", None).await.0, StatusCode::OK);
+
+        let (status, body) = get_json(&app, "/admin/audit?user=bob&action=DELETE", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let records = body["records"].as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["user"], "bob");
+        assert_eq!(records[0]["action"], "DELETE /collections/:collection_id/docs/:doc_id");
+        assert_eq!((records[0]["collection"].as_str(), records[0]["status"].as_u64()), (Some("audited"), Some(200)));
+
+        let (_, body) = get_json(&app, "/admin/audit?user=alice", None).await;
+        let statuses: Vec<u64> = body["records"].as_array().unwrap().iter().map(|r| r["status"].as_u64().unwrap()).collect();
+        assert_eq!(statuses.len(), 2);
+        assert_eq!(statuses[0], 200, "oldest first");
+        assert!(statuses[1] >= 400, "failed calls are audited too");
+
+        // Pages of one, continuing from the cursor
+        let (_, first) = get_json(&app, "/admin/audit?user=bob&limit=1", None).await;
+        assert_eq!(first["records"][0]["action"], "POST /collections/:collection_id/docs");
+        let uri = format!("/admin/audit?user=bob&limit=1&cursor={}", first["next_cursor"].as_str().unwrap());
+        let (_, second) = get_json(&app, &uri, None).await;
+        assert_eq!(second["records"][0]["action"], "POST /collections/:collection_id/docs");
+        assert_ne!(first["next_cursor"], second["next_cursor"]);
+        let uri = format!("/admin/audit?user=bob&limit=1&cursor={}", second["next_cursor"].as_str().unwrap());
+        let (_, third) = get_json(&app, &uri, None).await;
+        assert_eq!(third["records"][0]["action"], "DELETE /collections/:collection_id/docs/:doc_id");
+        assert!(third.get("next_cursor").is_none());
+
+        // A time range past every record is empty; bad bounds and cursors are rejected
+        let (_, body) = get_json(&app, "/admin/audit?since=4102444800000", None).await;
+        assert_eq!(body["records"].as_array().unwrap().len(), 0);
+        assert_eq!(get_json(&app, "/admin/audit?since=yesterday", None).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(get_json(&app, "/admin/audit?cursor=zz", None).await.0, StatusCode::BAD_REQUEST);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn index_file_round_trips_between_instances() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_index_file");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let docs = || (0..5).map(|i| test_doc(&format!("d{}", i), vec![i as f32, 1.0])).collect::<Vec<_>>();
+        let source = Storage::open(temp_dir.join("source").to_str().unwrap()).expect("open storage");
+        let target = Storage::open(temp_dir.join("target").to_str().unwrap()).expect("open storage");
+        source.insert_docs(docs(), "idx").unwrap();
+        target.insert_docs(docs(), "idx").unwrap();
+        let token = crate::auth::create_jwt_with_roles("rest_test_user", &[Role::Admin]).expect("JWT for test");
+        let request = |method: &str, body: Vec<u8>| {
+            Request::builder()
+                .uri("/admin/collections/idx/index")
+                .method(method)
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = create_router(source.clone()).oneshot(request("GET", Vec::new())).await.expect("request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/octet-stream");
+        let exported = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec();
+
+        let app = create_router(target.clone());
+        let response = app.clone().oneshot(request("PUT", exported)).await.expect("request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let import: serde_json::Value = serde_json::from_slice(&axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()).unwrap();
+        assert_eq!(import["vectors"], 5);
+        assert_eq!(target.vector_search("idx", &[3.0, 1.0], 1).unwrap(), vec!["d3".to_string()]);
+        assert_eq!(target.index_builds(), 0);
+
+        let response = app.oneshot(request("PUT", b"not an index".to_vec())).await.expect("request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        drop((source, target));
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[tokio::test]
+    async fn snapshots_are_confined_to_the_snapshot_directory() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_rest_snapshot_dir");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.join("data").to_str().unwrap()).expect("open storage");
+        storage.insert_doc(test_doc("d1", vec![1.0, 0.0]), "snap").unwrap();
+        let snapshot_dir = temp_dir.join("snapshots");
+        let app = create_router_with_config(storage.clone(), &RestConfig { snapshot_dir: snapshot_dir.clone(), ..RestConfig::from_env() });
+
+        for path in ["../escape.snap", "/tmp/aidb_escape.snap", ""] {
+            let (status, _) = post_json(&app, "/admin/snapshot", serde_json::json!({"path": path})).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{:?}", path);
+        }
+        let (status, report) = post_json(&app, "/admin/snapshot", serde_json::json!({"path": "nightly/aidb.snap"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(snapshot_dir.join("nightly/aidb.snap").exists());
+
+        let (status, _) = post_json(&app, "/admin/restore", serde_json::json!({"path": "nightly/aidb.snap", "data_path": "../restored"})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, restored) = post_json(&app, "/admin/restore", serde_json::json!({"path": "nightly/aidb.snap", "data_path": "restored"})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(restored["entries"], report["entries"]);
+        assert!(snapshot_dir.join("restored").is_dir());
 
+        drop(storage);
         let _ = fs::remove_dir_all(temp_dir);
     }
 }
\ No newline at end of file
@@ -0,0 +1,66 @@
+//! Collection resolution for routes that may omit the hierarchy
+//!
+//! Document routes exist both as `/collections/:collection_id/...` and as context-relative
+//! shortcuts (e.g. `/docs`). `ResolvedCollection` takes the `collection_id` path segment when
+//! present and otherwise falls back to the caller's workspace context (`PUT /me/context`).
+//! Access to a context collection is checked on every request, not only when the context was
+//! set, so losing a tenant grant also closes its collections to the shortcuts.
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Path},
+    http::{request::Parts, StatusCode},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, error};
+
+use crate::auth::require_collection_access;
+use crate::rest::AppState;
+use crate::tenants::AuthPayload;
+
+/// Target collection of a request: explicit path segment or the user's context collection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedCollection(pub String);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ResolvedCollection {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        // Routes without path params reject the extractor; that simply means "no explicit collection"
+        let explicit = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .ok()
+            .and_then(|Path(mut params)| params.remove("collection_id"));
+        let claims = parts
+            .extensions
+            .get::<AuthPayload>()
+            .cloned()
+            .ok_or((StatusCode::UNAUTHORIZED, "Missing credentials".to_string()))?;
+        let username = claims.sub.clone();
+
+        let resolved = state
+            .storage
+            .resolve_collection(&username, explicit.as_deref())
+            .map_err(|e| {
+                error!(error = %e, username = %username, "Failed to resolve collection");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to resolve collection".to_string())
+            })?;
+        match resolved {
+            Some(collection_id) => {
+                if explicit.is_none() {
+                    // Path IDs were checked by auth_middleware; the context may have gone stale
+                    require_collection_access(&state.storage, &claims, &collection_id)
+                        .map_err(|e| (StatusCode::from(e.clone()), e.to_string()))?;
+                    debug!(username = %username, collection_id = %collection_id, "Collection resolved from user context");
+                }
+                Ok(ResolvedCollection(collection_id))
+            }
+            None => Err((
+                StatusCode::BAD_REQUEST,
+                "No collection in the path and no workspace context set (PUT /me/context)".to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Server-side cursors over ranked vector search results
+//!
+//! `POST /collections/:id/vector_search` ranks `top_k` candidates once and returns the first
+//! page; the rest stay here under an opaque cursor ID so `GET .../vector_search?cursor=` serves
+//! the following pages without searching again. A cursor belongs to the user and collection that
+//! created it, expires `SEARCH_CURSOR_TTL` after its last page was served, and is dropped once
+//! its last page has been read. At most `MAX_SEARCH_CURSORS` are kept; beyond that the one
+//! closest to expiry is evicted. Cursors live in memory only, so a restart invalidates them.
+
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::indexing::ScoreKind;
+
+/// How long an unused cursor stays valid
+pub const SEARCH_CURSOR_TTL: Duration = Duration::from_secs(300);
+/// Cursors kept at once across all users
+pub const MAX_SEARCH_CURSORS: usize = 1024;
+
+/// One ranked result
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VectorHit {
+    pub id: String,
+    pub score: f32,
+}
+
+/// A page of ranked results plus how to continue
+#[derive(Debug, Clone, Serialize)]
+pub struct RankedPage {
+    pub collection_id: String,
+    pub score_kind: ScoreKind,
+    pub metric: String,
+    pub results: Vec<VectorHit>,
+    /// Pass as `?cursor=` for the next page; absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Ranked results after this page
+    pub remaining: usize,
+}
+
+/// Ranked results of one search, before paging
+#[derive(Debug, Clone)]
+pub struct RankedResults {
+    pub collection_id: String,
+    pub score_kind: ScoreKind,
+    pub metric: String,
+    pub hits: Vec<VectorHit>,
+}
+
+#[derive(Debug)]
+struct CursorEntry {
+    owner: String,
+    collection_id: String,
+    score_kind: ScoreKind,
+    metric: String,
+    page_size: usize,
+    hits: VecDeque<VectorHit>,
+    expires_at: Instant,
+}
+
+/// Open cursors by ID
+#[derive(Debug)]
+pub struct SearchCursors {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CursorEntry>>,
+}
+
+impl Default for SearchCursors {
+    fn default() -> Self {
+        Self::with_ttl(SEARCH_CURSOR_TTL)
+    }
+}
+
+impl SearchCursors {
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self { ttl, entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// First `page_size` results; the rest are kept under a new cursor owned by `owner`
+    pub fn first_page(&self, owner: &str, results: RankedResults, page_size: usize) -> RankedPage {
+        let mut entry = CursorEntry {
+            owner: owner.to_string(),
+            collection_id: results.collection_id,
+            score_kind: results.score_kind,
+            metric: results.metric,
+            page_size: page_size.max(1),
+            hits: results.hits.into(),
+            expires_at: Instant::now() + self.ttl,
+        };
+        let page = take_page(&mut entry);
+        if entry.hits.is_empty() {
+            return page;
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut entries = self.lock_entries();
+        let now = Instant::now();
+        entries.retain(|_, e| e.expires_at > now);
+        if entries.len() >= MAX_SEARCH_CURSORS {
+            if let Some(oldest) = entries.iter().min_by_key(|(_, e)| e.expires_at).map(|(id, _)| id.clone()) {
+                debug!(cursor = %oldest, "Search cursor evicted to make room");
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(id.clone(), entry);
+        RankedPage { cursor: Some(id), ..page }
+    }
+
+    /// Next page of `cursor` for `owner` in `collection_id`; `None` if it is unknown, expired,
+    /// exhausted or another user's or collection's
+    pub fn next_page(&self, cursor: &str, owner: &str, collection_id: &str) -> Option<RankedPage> {
+        let mut entries = self.lock_entries();
+        let now = Instant::now();
+        let entry = entries
+            .get_mut(cursor)
+            .filter(|e| e.expires_at > now && e.owner == owner && e.collection_id == collection_id)?;
+        entry.expires_at = now + self.ttl;
+        let page = take_page(entry);
+        if entry.hits.is_empty() {
+            entries.remove(cursor);
+            return Some(page);
+        }
+        Some(RankedPage { cursor: Some(cursor.to_string()), ..page })
+    }
+
+    fn lock_entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, CursorEntry>> {
+        self.entries.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+/// Remove the next page from `entry` (without a cursor ID)
+fn take_page(entry: &mut CursorEntry) -> RankedPage {
+    let count = entry.page_size.min(entry.hits.len());
+    let results: Vec<VectorHit> = entry.hits.drain(..count).collect();
+    RankedPage {
+        collection_id: entry.collection_id.clone(),
+        score_kind: entry.score_kind,
+        metric: entry.metric.clone(),
+        results,
+        cursor: None,
+        remaining: entry.hits.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(count: usize) -> RankedResults {
+        RankedResults {
+            collection_id: "col".to_string(),
+            score_kind: ScoreKind::Distance,
+            metric: "l2".to_string(),
+            hits: (0..count).map(|i| VectorHit { id: format!("d{}", i), score: i as f32 }).collect(),
+        }
+    }
+
+    #[test]
+    fn cursors_are_scoped_to_their_owner_and_expire() {
+        let cursors = SearchCursors::default();
+        let first = cursors.first_page("alice", ranked(5), 2);
+        let cursor = first.cursor.clone().unwrap();
+        assert_eq!(first.remaining, 3);
+        assert!(cursors.next_page(&cursor, "bob", "col").is_none(), "another user's cursor");
+        assert!(cursors.next_page(&cursor, "alice", "other").is_none(), "another collection");
+
+        let second = cursors.next_page(&cursor, "alice", "col").unwrap();
+        assert_eq!(second.results.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), ["d2", "d3"]);
+        let last = cursors.next_page(&cursor, "alice", "col").unwrap();
+        assert_eq!((last.results.len(), last.remaining, last.cursor), (1, 0, None));
+        assert!(cursors.next_page(&cursor, "alice", "col").is_none(), "dropped once exhausted");
+
+        // Everything fits on one page: no cursor is opened
+        assert!(cursors.first_page("alice", ranked(2), 2).cursor.is_none());
+
+        let expired = SearchCursors::with_ttl(Duration::ZERO);
+        let cursor = expired.first_page("alice", ranked(5), 2).cursor.unwrap();
+        assert!(expired.next_page(&cursor, "alice", "col").is_none());
+    }
+}
@@ -0,0 +1,55 @@
+//! Status of search and list endpoints that found nothing
+//!
+//! An empty result is a `200` with an empty list by default. Clients that would rather branch on
+//! the status can set `AIDB_EMPTY_RESULTS=not_found`, and searches and listings with no results
+//! answer `404` instead. Counts and aggregates are unaffected: zero is an answer, not a miss.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
+use std::convert::Infallible;
+use tracing::debug;
+
+/// How search and list endpoints report an empty result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyResults {
+    /// `200` with an empty list
+    #[default]
+    EmptyList,
+    /// `404`
+    NotFound,
+}
+
+impl EmptyResults {
+    /// Parse `AIDB_EMPTY_RESULTS` (`not_found`/`404`, or `empty_list`, the default)
+    pub(crate) fn from_var(raw: Option<&str>) -> Self {
+        match raw {
+            Some(raw) if matches!(raw.trim().to_lowercase().as_str(), "not_found" | "404") => EmptyResults::NotFound,
+            _ => EmptyResults::EmptyList,
+        }
+    }
+
+    /// `Err(404)` when the result is empty and empty results are reported as not found
+    pub fn check(self, is_empty: bool) -> Result<(), StatusCode> {
+        if is_empty && self == EmptyResults::NotFound {
+            debug!("Empty result reported as not found");
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for EmptyResults
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    /// The mode installed as a request extension, else the default
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<EmptyResults>().copied().unwrap_or_default())
+    }
+}
@@ -0,0 +1,161 @@
+//! Response shape for REST resource endpoints: bare resource vs `{success, message, data}` envelope
+//!
+//! Resource endpoints (`GET /collections/:id/docs`, `GET /collections/:id/docs/:doc_id`) return
+//! the bare `Document`/array by default. Clients that prefer a uniform envelope can ask for one:
+//! - server-wide with `AIDB_RESPONSE_MODE=envelope` (or `bare`, the default), or
+//! - per request with `Accept: application/vnd.aidb.envelope+json` / `application/vnd.aidb.bare+json`,
+//!   which overrides the server default.
+//!
+//! Action endpoints (inserts, deletes, admin operations) keep returning `RestResponse` in both modes.
+//! A list cut short by the payload cap (see `payload`) carries `truncated`/`next_cursor` in the
+//! envelope, or `X-Truncated`/`X-Next-Cursor` headers on a bare array.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::convert::Infallible;
+
+use crate::rest::payload::{Continuation, NEXT_CURSOR_HEADER, TRUNCATED_HEADER};
+
+/// Accept media type selecting the envelope for one request
+pub const ENVELOPE_MEDIA_TYPE: &str = "application/vnd.aidb.envelope+json";
+/// Accept media type selecting the bare resource for one request
+pub const BARE_MEDIA_TYPE: &str = "application/vnd.aidb.bare+json";
+
+/// How resource endpoints shape their JSON body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResponseMode {
+    #[default]
+    Bare,
+    Envelope,
+}
+
+/// Envelope wrapped around a resource in `ResponseMode::Envelope`
+#[derive(Debug, Serialize)]
+pub struct ResourceEnvelope<T> {
+    pub success: bool,
+    pub message: String,
+    pub data: T,
+    /// Set when the payload cap dropped trailing results
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Cursor continuing after the returned results (only when `truncated`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl ResponseMode {
+    /// Parse `AIDB_RESPONSE_MODE` (`envelope` or `bare`; anything else keeps the bare default)
+    pub(crate) fn from_var(raw: Option<&str>) -> Self {
+        match raw {
+            Some(raw) if raw.trim().eq_ignore_ascii_case("envelope") => ResponseMode::Envelope,
+            _ => ResponseMode::Bare,
+        }
+    }
+
+    /// Mode requested by an `Accept` header, if it names one of the aidb media types
+    fn from_accept(accept: &str) -> Option<Self> {
+        accept.split(',').find_map(|part| {
+            match part.split(';').next().unwrap_or("").trim() {
+                ENVELOPE_MEDIA_TYPE => Some(ResponseMode::Envelope),
+                BARE_MEDIA_TYPE => Some(ResponseMode::Bare),
+                _ => None,
+            }
+        })
+    }
+
+    /// Render a resource in this mode; `message` is only used by the envelope
+    pub fn respond<T: Serialize>(self, data: T, message: impl Into<String>) -> Response {
+        self.respond_page(data, message, None)
+    }
+
+    /// Render a (possibly truncated) result list in this mode
+    pub fn respond_page<T: Serialize>(
+        self,
+        data: T,
+        message: impl Into<String>,
+        continuation: Option<Continuation>,
+    ) -> Response {
+        match self {
+            ResponseMode::Bare => {
+                let mut response = Json(data).into_response();
+                if let Some(next) = continuation.and_then(|c| HeaderValue::from_str(&c.next_cursor).ok()) {
+                    response.headers_mut().insert(TRUNCATED_HEADER, HeaderValue::from_static("true"));
+                    response.headers_mut().insert(NEXT_CURSOR_HEADER, next);
+                }
+                response
+            }
+            ResponseMode::Envelope => Json(ResourceEnvelope {
+                success: true,
+                message: message.into(),
+                data,
+                truncated: continuation.is_some(),
+                next_cursor: continuation.map(|c| c.next_cursor),
+            })
+            .into_response(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ResponseMode
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let requested = parts
+            .headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .and_then(ResponseMode::from_accept);
+        let server_default = parts.extensions.get::<ResponseMode>().copied().unwrap_or_default();
+        Ok(requested.unwrap_or(server_default))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Extension, Router};
+    use tower::ServiceExt;
+
+    async fn call(default_mode: ResponseMode, accept: Option<&str>) -> String {
+        let app = Router::new()
+            .route("/", get(|mode: ResponseMode| async move { mode.respond(vec![1, 2], "two items") }))
+            .layer(Extension(default_mode));
+        let mut request = Request::builder().uri("/");
+        if let Some(accept) = accept {
+            request = request.header(header::ACCEPT, accept);
+        }
+        let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn server_default_applies_unless_accept_overrides() {
+        assert_eq!(call(ResponseMode::Bare, None).await, "[1,2]");
+        assert_eq!(
+            call(ResponseMode::Envelope, None).await,
+            r#"{"success":true,"message":"two items","data":[1,2]}"#
+        );
+        assert_eq!(call(ResponseMode::Envelope, Some(BARE_MEDIA_TYPE)).await, "[1,2]");
+    }
+
+    #[test]
+    fn accept_header_selects_mode() {
+        assert_eq!(ResponseMode::from_accept(ENVELOPE_MEDIA_TYPE), Some(ResponseMode::Envelope));
+        assert_eq!(
+            ResponseMode::from_accept(&format!("text/html, {}; q=0.9", BARE_MEDIA_TYPE)),
+            Some(ResponseMode::Bare)
+        );
+        assert_eq!(ResponseMode::from_accept("application/json"), None);
+    }
+}
@@ -0,0 +1,95 @@
+//! Handler errors that explain themselves
+//!
+//! Handlers fail with an `AppError`: a status plus the reason, e.g. the DataFusion message for a
+//! bad SQL query. `request_id_middleware` turns it into the `ApiError` JSON body
+//! (`{"success": false, "status": 400, "code": "bad_request", "error": "...", "request_id": "..."}`),
+//! so clients see why a request failed and not just that it did. A bare `StatusCode` converts into
+//! an `AppError` whose reason is the status's canonical phrase, so `?` on helpers that still fail
+//! with a status keeps working.
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::auth::{CollectionAccessError, Forbidden};
+
+/// A failed request: the status to answer with and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl AppError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+/// Machine-readable name of a status for the `code` field, e.g. `bad_request`, `not_found`
+pub fn error_code(status: StatusCode) -> String {
+    status
+        .canonical_reason()
+        .map(|reason| reason.to_lowercase().replace(|c: char| !c.is_ascii_alphanumeric(), "_"))
+        .unwrap_or_else(|| format!("http_{}", status.as_u16()))
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.status)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<StatusCode> for AppError {
+    fn from(status: StatusCode) -> Self {
+        Self::new(status, status.canonical_reason().unwrap_or("error"))
+    }
+}
+
+impl From<Forbidden> for AppError {
+    fn from(e: Forbidden) -> Self {
+        Self::new(StatusCode::FORBIDDEN, e.to_string())
+    }
+}
+
+impl From<CollectionAccessError> for AppError {
+    fn from(e: CollectionAccessError) -> Self {
+        Self::new(StatusCode::from(e.clone()), e.to_string())
+    }
+}
+
+impl IntoResponse for AppError {
+    /// The reason as a plain-text body; `request_id_middleware` wraps it into an `ApiError`
+    fn into_response(self) -> Response {
+        (self.status, self.message).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_name_the_status() {
+        assert_eq!(error_code(StatusCode::BAD_REQUEST), "bad_request");
+        assert_eq!(error_code(StatusCode::NOT_FOUND), "not_found");
+        assert_eq!(error_code(StatusCode::INTERNAL_SERVER_ERROR), "internal_server_error");
+        assert_eq!(error_code(StatusCode::PAYLOAD_TOO_LARGE), "payload_too_large");
+        assert_eq!(AppError::from(StatusCode::UNAUTHORIZED).message, "Unauthorized");
+    }
+}
@@ -0,0 +1,242 @@
+//! Prometheus text exposition for `GET /metrics`
+//!
+//! Served outside the auth routes so a scraper needs no token. Per collection it reports the
+//! current write generation, the generation its cached primary vector index was built at (only
+//! once one is cached) and whether that index is stale, i.e. built before the latest write.
+//! It also reports document cache hits, misses and evictions, and per operation (insert, search,
+//! SQL) the number of REST requests served and a histogram of their latency. Request metrics
+//! live in memory and start from zero at every restart, as Prometheus counters expect.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::cache::CacheStats;
+use crate::storage::IndexFreshness;
+
+/// Content type of the Prometheus text format
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Accumulates metric families in the Prometheus text format
+#[derive(Debug, Default)]
+pub struct PrometheusText {
+    out: String,
+}
+
+impl PrometheusText {
+    /// One gauge family: `samples` are (label value, sample value) pairs for the `collection` label
+    pub fn gauge(&mut self, name: &str, help: &str, samples: impl IntoIterator<Item = (String, u64)>) -> &mut Self {
+        self.header(name, help, "gauge");
+        for (collection, value) in samples {
+            let _ = writeln!(self.out, "{}{{collection=\"{}\"}} {}", name, escape_label(&collection), value);
+        }
+        self
+    }
+
+    /// One counter family: `samples` are (label value, sample value) pairs for `label`
+    pub fn counter(&mut self, name: &str, help: &str, label: &str, samples: impl IntoIterator<Item = (String, u64)>) -> &mut Self {
+        self.header(name, help, "counter");
+        for (label_value, value) in samples {
+            let _ = writeln!(self.out, "{}{{{}=\"{}\"}} {}", name, label, escape_label(&label_value), value);
+        }
+        self
+    }
+
+    /// A family with a single unlabelled sample; `kind` is `counter` or `gauge`
+    pub fn single(&mut self, name: &str, help: &str, kind: &str, value: u64) -> &mut Self {
+        self.header(name, help, kind);
+        let _ = writeln!(self.out, "{} {}", name, value);
+        self
+    }
+
+    /// One histogram family in seconds: per `label` value, cumulative `_bucket`s, `_sum` and `_count`
+    pub fn histogram(&mut self, name: &str, help: &str, label: &str, samples: impl IntoIterator<Item = (String, HistogramSnapshot)>) -> &mut Self {
+        self.header(name, help, "histogram");
+        for (label_value, snapshot) in samples {
+            let label_value = escape_label(&label_value);
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS_SECONDS.iter().zip(snapshot.buckets) {
+                cumulative += count;
+                let _ = writeln!(self.out, "{}_bucket{{{}=\"{}\",le=\"{}\"}} {}", name, label, label_value, bound, cumulative);
+            }
+            let _ = writeln!(self.out, "{}_bucket{{{}=\"{}\",le=\"+Inf\"}} {}", name, label, label_value, snapshot.count);
+            let _ = writeln!(self.out, "{}_sum{{{}=\"{}\"}} {}", name, label, label_value, snapshot.sum_seconds);
+            let _ = writeln!(self.out, "{}_count{{{}=\"{}\"}} {}", name, label, label_value, snapshot.count);
+        }
+        self
+    }
+
+    fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.out, "# HELP {} {}", name, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", name, kind);
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+/// Escape a label value: backslash, double quote and newline
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Generation and index staleness gauges of every written collection
+pub fn render_index_freshness(text: &mut PrometheusText, freshness: &[IndexFreshness]) {
+    text.gauge(
+        "aidb_collection_generation",
+        "Current write generation of the collection",
+        freshness.iter().map(|f| (f.collection_id.clone(), f.generation)),
+    )
+    .gauge(
+        "aidb_index_built_generation",
+        "Generation the cached primary vector index was built at",
+        freshness.iter().filter_map(|f| Some((f.collection_id.clone(), f.built_generation?))),
+    )
+    .gauge(
+        "aidb_index_stale",
+        "1 if the cached primary vector index predates the collection's latest write",
+        freshness.iter().map(|f| (f.collection_id.clone(), f.stale as u64)),
+    );
+}
+
+/// Document cache lookup and eviction counters plus its current occupancy
+pub fn render_cache_stats(text: &mut PrometheusText, stats: &CacheStats) {
+    text.single("aidb_cache_hits_total", "Document lookups answered from the cache", "counter", stats.hits)
+        .single("aidb_cache_misses_total", "Document lookups that went to storage", "counter", stats.misses)
+        .single("aidb_cache_evictions_total", "Documents evicted from the cache to make room", "counter", stats.evictions)
+        .single("aidb_cache_size_bytes", "Estimated size of the cached documents", "gauge", stats.size_bytes as u64)
+        .single("aidb_cache_entries", "Documents currently cached", "gauge", stats.entries as u64);
+}
+
+/// Upper bounds of the latency histogram buckets, in seconds
+pub const LATENCY_BUCKETS_SECONDS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// REST operations whose requests are counted and timed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Single and batch document inserts
+    Insert,
+    /// Vector and hybrid searches
+    Search,
+    /// SQL queries
+    Sql,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 3] = [Operation::Insert, Operation::Search, Operation::Sql];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Insert => "insert",
+            Operation::Search => "search",
+            Operation::Sql => "sql",
+        }
+    }
+}
+
+/// Latency distribution of one operation at the time of a scrape
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HistogramSnapshot {
+    /// Observations per bucket of `LATENCY_BUCKETS_SECONDS` (not cumulative); slower ones only
+    /// show up in `count`
+    pub buckets: [u64; LATENCY_BUCKETS_SECONDS.len()],
+    pub count: u64,
+    pub sum_seconds: f64,
+}
+
+#[derive(Debug, Default)]
+struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_SECONDS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+/// Request counters and latency histograms of the REST handlers, shared through `AppState`
+#[derive(Debug, Default)]
+pub struct RequestMetrics {
+    histograms: [LatencyHistogram; Operation::ALL.len()],
+}
+
+impl RequestMetrics {
+    /// Count one served request of `operation` that took `elapsed`
+    pub fn observe(&self, operation: Operation, elapsed: Duration) {
+        let histogram = &self.histograms[operation as usize];
+        let seconds = elapsed.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS_SECONDS.iter().position(|bound| seconds <= *bound) {
+            histogram.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        histogram.sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        histogram.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, operation: Operation) -> HistogramSnapshot {
+        let histogram = &self.histograms[operation as usize];
+        HistogramSnapshot {
+            buckets: std::array::from_fn(|i| histogram.buckets[i].load(Ordering::Relaxed)),
+            count: histogram.count.load(Ordering::Relaxed),
+            sum_seconds: histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+        }
+    }
+
+    /// Request counters and latency histograms of every operation
+    pub fn render(&self, text: &mut PrometheusText) {
+        let snapshots: Vec<(String, HistogramSnapshot)> =
+            Operation::ALL.iter().map(|op| (op.as_str().to_string(), self.snapshot(*op))).collect();
+        text.counter(
+            "aidb_requests_total",
+            "REST requests served successfully, by operation",
+            "operation",
+            snapshots.iter().map(|(op, snapshot)| (op.clone(), snapshot.count)),
+        )
+        .histogram("aidb_request_duration_seconds", "Latency of successful REST requests, by operation", "operation", snapshots);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gauges_escape_label_values_and_skip_unbuilt_indexes() {
+        let freshness = vec![
+            IndexFreshness { collection_id: "a\"b".to_string(), generation: 3, built_generation: Some(2), stale: true },
+            IndexFreshness { collection_id: "cold".to_string(), generation: 1, built_generation: None, stale: false },
+        ];
+        let mut text = PrometheusText::default();
+        render_index_freshness(&mut text, &freshness);
+        let text = text.finish();
+
+        assert!(text.contains("# TYPE aidb_index_stale gauge\n"));
+        assert!(text.contains("aidb_collection_generation{collection=\"a\\\"b\"} 3\n"));
+        assert!(text.contains("aidb_index_built_generation{collection=\"a\\\"b\"} 2\n"));
+        assert!(!text.contains("aidb_index_built_generation{collection=\"cold\"}"));
+        assert!(text.contains("aidb_index_stale{collection=\"cold\"} 0\n"));
+    }
+
+    #[test]
+    fn request_histograms_are_cumulative_and_cache_counters_are_reported() {
+        let metrics = RequestMetrics::default();
+        metrics.observe(Operation::Search, Duration::from_millis(3));
+        metrics.observe(Operation::Search, Duration::from_millis(40));
+        metrics.observe(Operation::Search, Duration::from_secs(30));
+        let snapshot = metrics.snapshot(Operation::Search);
+        assert_eq!((snapshot.count, snapshot.buckets.iter().sum::<u64>()), (3, 2), "30s is past the last bucket");
+        assert_eq!(metrics.snapshot(Operation::Sql).count, 0);
+
+        let mut text = PrometheusText::default();
+        metrics.render(&mut text);
+        render_cache_stats(&mut text, &CacheStats { hits: 7, misses: 2, ..CacheStats::default() });
+        let text = text.finish();
+
+        assert!(text.contains("# TYPE aidb_request_duration_seconds histogram\n"));
+        assert!(text.contains("aidb_requests_total{operation=\"search\"} 3\n"));
+        assert!(text.contains("aidb_requests_total{operation=\"insert\"} 0\n"));
+        assert!(text.contains("aidb_request_duration_seconds_bucket{operation=\"search\",le=\"0.005\"} 1\n"));
+        assert!(text.contains("aidb_request_duration_seconds_bucket{operation=\"search\",le=\"0.05\"} 2\n"));
+        assert!(text.contains("aidb_request_duration_seconds_bucket{operation=\"search\",le=\"+Inf\"} 3\n"));
+        assert!(text.contains("aidb_request_duration_seconds_sum{operation=\"search\"} 30.043\n"));
+        assert!(text.contains("# TYPE aidb_cache_hits_total counter\naidb_cache_hits_total 7\n"));
+        assert!(text.contains("aidb_cache_misses_total 2\n"));
+    }
+}
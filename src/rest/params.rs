@@ -0,0 +1,235 @@
+//! Typed query-string parameters shared by REST list/search handlers
+//!
+//! Centralizes defaults and bounds for `limit`, `offset`/`cursor`, `top_k`, `fields` and `format`
+//! so handlers don't parse the query string ad hoc. Invalid values are rejected with 400.
+//! Page size default and cap come from `PagePolicy` (`AIDB_PAGE_DEFAULT` / `AIDB_PAGE_MAX`).
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{request::Parts, StatusCode},
+};
+use serde::Deserialize;
+
+/// Page size used when `limit` is omitted (unless `AIDB_PAGE_DEFAULT` overrides it)
+pub const DEFAULT_LIMIT: usize = 100;
+/// Largest page a client may request (unless `AIDB_PAGE_MAX` overrides it)
+pub const MAX_LIMIT: usize = 1000;
+/// Neighbors returned when `top_k` is omitted
+pub const DEFAULT_TOP_K: usize = 10;
+/// Largest `top_k` a client may request
+pub const MAX_TOP_K: usize = 1000;
+
+/// Server-wide paging policy applied by the `QueryParams` extractor.
+/// `create_router_with_config` installs the configured policy as a request extension; requests
+/// without one get the default policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PagePolicy {
+    pub default_limit: usize,
+    pub max_limit: usize,
+}
+
+impl Default for PagePolicy {
+    fn default() -> Self {
+        Self { default_limit: DEFAULT_LIMIT, max_limit: MAX_LIMIT }
+    }
+}
+
+impl PagePolicy {
+    /// Parse `AIDB_PAGE_DEFAULT` / `AIDB_PAGE_MAX` (positive integers; invalid values keep the built-in defaults)
+    pub(crate) fn from_vars(default_limit: Option<&str>, max_limit: Option<&str>) -> Self {
+        let parse = |raw: Option<&str>| raw.and_then(|v| v.trim().parse::<usize>().ok()).filter(|n| *n > 0);
+        let max_limit = parse(max_limit).unwrap_or(MAX_LIMIT);
+        // A default above the cap would make every defaulted request invalid
+        let default_limit = parse(default_limit).unwrap_or(DEFAULT_LIMIT).min(max_limit);
+        Self { default_limit, max_limit }
+    }
+
+}
+
+/// Response encodings a handler may offer via `?format=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    Ndjson,
+    Arrow,
+}
+
+/// Raw query string; signed integers so negative values get a clear message instead of a parse error
+#[derive(Debug, Deserialize, Default)]
+struct RawQueryParams {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    top_k: Option<i64>,
+    fields: Option<String>,
+    format: Option<String>,
+    /// `next_cursor` of a truncated response; takes precedence over `offset`
+    cursor: Option<String>,
+}
+
+/// Validated query parameters with defaults applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryParams {
+    pub limit: usize,
+    pub offset: usize,
+    pub top_k: usize,
+    /// Comma-separated `fields=` list (empty = all fields)
+    pub fields: Vec<String>,
+    pub format: ResponseFormat,
+}
+
+impl Default for QueryParams {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_LIMIT,
+            offset: 0,
+            top_k: DEFAULT_TOP_K,
+            fields: vec![],
+            format: ResponseFormat::Json,
+        }
+    }
+}
+
+fn bounded(name: &str, value: Option<i64>, default: usize, min: usize, max: usize) -> Result<usize, String> {
+    match value {
+        None => Ok(default),
+        Some(v) if v < 0 => Err(format!("{} must be non-negative, got {}", name, v)),
+        Some(v) if (v as u64) < min as u64 => Err(format!("{} must be at least {}, got {}", name, min, v)),
+        Some(v) if (v as u64) > max as u64 => Err(format!("{} must be at most {}, got {}", name, max, v)),
+        Some(v) => Ok(v as usize),
+    }
+}
+
+impl RawQueryParams {
+    fn validate(self, policy: &PagePolicy) -> Result<QueryParams, String> {
+        let format = match self.format.as_deref().map(|f| f.trim().to_lowercase()) {
+            None => ResponseFormat::Json,
+            Some(f) if f.is_empty() || f == "json" => ResponseFormat::Json,
+            Some(f) if f == "ndjson" => ResponseFormat::Ndjson,
+            Some(f) if f == "arrow" => ResponseFormat::Arrow,
+            Some(other) => return Err(format!("Unsupported format '{}' (expected json, ndjson or arrow)", other)),
+        };
+        let fields = self.fields
+            .map(|raw| {
+                raw.split(',')
+                    .map(|f| f.trim().to_string())
+                    .filter(|f| !f.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let offset = match self.cursor.as_deref() {
+            Some(cursor) => cursor
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid cursor '{}' (pass a next_cursor from a previous response)", cursor))?,
+            None => bounded("offset", self.offset, 0, 0, usize::MAX)?,
+        };
+
+        Ok(QueryParams {
+            limit: bounded("limit", self.limit, policy.default_limit, 0, policy.max_limit)?,
+            offset,
+            top_k: bounded("top_k", self.top_k, DEFAULT_TOP_K, 1, MAX_TOP_K)?,
+            fields,
+            format,
+        })
+    }
+}
+
+impl QueryParams {
+    /// Apply `offset`/`limit` to an already-ordered result list
+    pub fn paginate<T>(&self, items: Vec<T>) -> Vec<T> {
+        items.into_iter().skip(self.offset).take(self.limit).collect()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for QueryParams
+where
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(raw) = Query::<RawQueryParams>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.body_text()))?;
+        let policy = parts.extensions.get::<PagePolicy>().copied().unwrap_or_default();
+        raw.validate(&policy).map_err(|msg| (StatusCode::BAD_REQUEST, msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Extension, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new().route(
+            "/",
+            get(|p: QueryParams| async move {
+                format!("{} {} {} {:?} {:?}", p.limit, p.offset, p.top_k, p.fields, p.format)
+            }),
+        )
+    }
+
+    async fn call(uri: &str) -> (StatusCode, String) {
+        call_with(app().layer(Extension(PagePolicy::default())), uri).await
+    }
+
+    async fn call_with(app: Router, uri: &str) -> (StatusCode, String) {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn defaults_are_applied() {
+        let (status, body) = call("/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, format!("{} 0 {} [] Json", DEFAULT_LIMIT, DEFAULT_TOP_K));
+
+        let (status, body) = call("/?limit=5&offset=2&fields=id,%20category&format=arrow").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, format!("5 2 {} [\"id\", \"category\"] Arrow", DEFAULT_TOP_K));
+    }
+
+    #[tokio::test]
+    async fn invalid_params_are_rejected() {
+        let (status, body) = call("/?limit=-1").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("limit must be non-negative"));
+
+        assert_eq!(call(&format!("/?limit={}", MAX_LIMIT + 1)).await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(call("/?top_k=0").await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(call("/?limit=abc").await.0, StatusCode::BAD_REQUEST);
+        assert_eq!(call("/?format=xml").await.0, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn page_policy_override_changes_default_and_cap() {
+        let policy = PagePolicy::from_vars(Some("25"), Some("50"));
+        assert_eq!(policy, PagePolicy { default_limit: 25, max_limit: 50 });
+
+        let (status, body) = call_with(app().layer(Extension(policy)), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.starts_with("25 0 "), "unexpected body: {}", body);
+        assert_eq!(call_with(app().layer(Extension(policy)), "/?limit=51").await.0, StatusCode::BAD_REQUEST);
+
+        // Invalid values fall back; a default above the cap is clamped
+        assert_eq!(PagePolicy::from_vars(Some("abc"), None), PagePolicy::default());
+        assert_eq!(PagePolicy::from_vars(Some("500"), Some("200")).default_limit, 200);
+    }
+
+    #[test]
+    fn paginate_applies_offset_and_limit() {
+        let params = QueryParams { limit: 2, offset: 1, ..QueryParams::default() };
+        assert_eq!(params.paginate(vec![1, 2, 3, 4]), vec![2, 3]);
+    }
+}
@@ -0,0 +1,82 @@
+//! Byte-size cap on list/search result payloads
+//!
+//! A page that fits the `limit` may still be too large for a client (long texts, big metadata).
+//! With `AIDB_MAX_RESPONSE_BYTES` set, list/search handlers keep only the longest prefix of the
+//! page whose serialized results fit the cap, flag the response `truncated: true` and hand back a
+//! `next_cursor`; passing it as `?cursor=` continues right after the last returned item. At least
+//! one item is always returned so a single oversized item can't stall pagination.
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::request::Parts,
+};
+use serde::Serialize;
+use std::convert::Infallible;
+use tracing::debug;
+
+/// Response header flagging a truncated bare (non-envelope) list
+pub const TRUNCATED_HEADER: &str = "x-truncated";
+/// Response header carrying the continuation cursor of a truncated bare list
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
+/// Cap on the serialized size of a response's result list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PayloadLimit {
+    /// `None` = unlimited
+    pub max_bytes: Option<usize>,
+}
+
+/// Where a truncated result list stops
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Continuation {
+    /// Pass as `?cursor=` to fetch the items after the returned ones
+    pub next_cursor: String,
+}
+
+impl PayloadLimit {
+    /// Parse `AIDB_MAX_RESPONSE_BYTES` (positive integer; unset, 0 or invalid = unlimited)
+    pub(crate) fn from_var(raw: Option<&str>) -> Self {
+        let max_bytes = raw.and_then(|raw| raw.trim().parse::<usize>().ok()).filter(|n| *n > 0);
+        Self { max_bytes }
+    }
+
+    /// Longest prefix of `items` (which start at `offset` in the full result list) whose JSON
+    /// array encoding fits the cap, plus the continuation when items were dropped
+    pub fn fit<T: Serialize>(&self, mut items: Vec<T>, offset: usize) -> (Vec<T>, Option<Continuation>) {
+        let Some(max_bytes) = self.max_bytes else {
+            return (items, None);
+        };
+        // Surrounding brackets, then each item plus its separating comma
+        let mut used = 2;
+        let mut keep = 0;
+        for item in &items {
+            let size = serde_json::to_vec(item).map(|v| v.len()).unwrap_or(0) + usize::from(keep > 0);
+            if keep > 0 && used + size > max_bytes {
+                break;
+            }
+            used += size;
+            keep += 1;
+        }
+        if keep == items.len() {
+            return (items, None);
+        }
+        debug!(returned = keep, dropped = items.len() - keep, max_bytes = max_bytes, "Result payload truncated");
+        items.truncate(keep);
+        let next_cursor = (offset + keep).to_string();
+        (items, Some(Continuation { next_cursor }))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for PayloadLimit
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    /// The limit installed as a request extension, else the default
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<PayloadLimit>().copied().unwrap_or_default())
+    }
+}
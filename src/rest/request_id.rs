@@ -0,0 +1,206 @@
+//! Request IDs for client-side correlation
+//!
+//! Every REST request gets an ID: the caller's `X-Request-Id` when it is present, well-formed and
+//! trusted, otherwise a freshly generated UUID. The ID is echoed in the `X-Request-Id` response
+//! header, recorded on a tracing span around the request (so every log line for it carries the
+//! ID), and included in error bodies. Handlers fail with an `AppError` (a status and its reason),
+//! a bare `StatusCode` or a plain-text rejection; those responses are rewritten into an
+//! `ApiError` JSON body carrying the reason, a `code` naming the status, and the ID.
+//!
+//! `AIDB_TRUST_REQUEST_ID=0` ignores incoming IDs and always generates one (e.g. when the server
+//! is exposed directly and client-chosen IDs shouldn't end up in logs).
+
+use axum::{
+    body::Body,
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use tracing::{info_span, warn, Instrument};
+use utoipa::ToSchema;
+
+use crate::rest::error::error_code;
+
+/// Header carrying the request ID in both directions
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+/// Longest incoming ID that is honored; longer ones are replaced
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+/// Largest non-JSON error body folded into an `ApiError` message
+const MAX_ERROR_BODY_BYTES: usize = 64 * 1024;
+
+/// How request IDs are assigned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestIdPolicy {
+    /// Reuse a well-formed incoming `X-Request-Id` instead of generating one
+    pub trust_incoming: bool,
+}
+
+impl Default for RequestIdPolicy {
+    fn default() -> Self {
+        Self { trust_incoming: true }
+    }
+}
+
+impl RequestIdPolicy {
+    /// Parse `AIDB_TRUST_REQUEST_ID` (`0`/`false` always generates; default honors incoming IDs)
+    pub(crate) fn from_var(raw: Option<&str>) -> Self {
+        match raw {
+            Some(raw) if matches!(raw.trim().to_lowercase().as_str(), "0" | "false") => Self { trust_incoming: false },
+            _ => Self::default(),
+        }
+    }
+
+    /// ID for a request carrying `incoming` (the raw `X-Request-Id` header, if any)
+    fn assign(&self, incoming: Option<&HeaderValue>) -> String {
+        incoming
+            .filter(|_| self.trust_incoming)
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+    }
+}
+
+/// Non-empty, bounded, and limited to characters safe to log and echo
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+/// ID of the current request, available to handlers as `Extension<RequestId>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+/// JSON body of REST error responses
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    pub success: bool,
+    /// HTTP status code
+    pub status: u16,
+    /// Snake-case name of the status, e.g. `bad_request`, `not_found`
+    pub code: String,
+    /// Why the request failed (the status's canonical phrase when the handler gave no reason)
+    pub error: String,
+    /// Same value as the `X-Request-Id` response header
+    pub request_id: String,
+}
+
+/// Assign the request ID, run the request inside a span carrying it, echo it in the response
+/// header and attach it to error bodies
+pub async fn request_id_middleware(mut req: Request<Body>, next: Next) -> Response {
+    let policy = req.extensions().get::<RequestIdPolicy>().copied().unwrap_or_default();
+    let request_id = policy.assign(req.headers().get(REQUEST_ID_HEADER));
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let span = info_span!("request", request_id = %request_id, method = %req.method(), path = %req.uri().path());
+    let response = next.run(req).instrument(span).await;
+
+    let mut response = if response.status().is_client_error() || response.status().is_server_error() {
+        into_api_error(response, &request_id).await
+    } else {
+        response
+    };
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Rewrite an error response without a JSON body into an `ApiError`; JSON bodies pass through
+async fn into_api_error(response: Response, request_id: &str) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if is_json {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let message = match axum::body::to_bytes(body, MAX_ERROR_BODY_BYTES).await {
+        Ok(bytes) if !bytes.is_empty() => String::from_utf8_lossy(&bytes).into_owned(),
+        Ok(_) => parts.status.canonical_reason().unwrap_or("error").to_string(),
+        Err(e) => {
+            warn!(request_id = %request_id, error = %e, "Could not read error body");
+            parts.status.canonical_reason().unwrap_or("error").to_string()
+        }
+    };
+    let mut rewritten = (
+        parts.status,
+        Json(ApiError {
+            success: false,
+            status: parts.status.as_u16(),
+            code: error_code(parts.status),
+            error: message,
+            request_id: request_id.to_string(),
+        }),
+    )
+        .into_response();
+    // Keep headers such as WWW-Authenticate or Retry-After set by the handler
+    for (name, value) in parts.headers.iter() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            rewritten.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, middleware, routing::get, Extension, Router};
+    use tower::ServiceExt;
+
+    fn app(policy: RequestIdPolicy) -> Router {
+        Router::new()
+            .route("/ok", get(|Extension(id): Extension<RequestId>| async move { id.0 }))
+            .route("/missing", get(|| async { StatusCode::NOT_FOUND }))
+            .layer(middleware::from_fn(request_id_middleware))
+            .layer(Extension(policy))
+    }
+
+    async fn call(app: Router, uri: &str, request_id: Option<&str>) -> (StatusCode, String, String) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(id) = request_id {
+            request = request.header(REQUEST_ID_HEADER, id);
+        }
+        let response = app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let echoed = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, echoed, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn responses_carry_a_request_id_and_echo_a_provided_one() {
+        // Generated when absent, and the same ID the handler saw
+        let (status, generated, body) = call(app(RequestIdPolicy::default()), "/ok", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+        assert_eq!(body, generated);
+
+        let (_, echoed, body) = call(app(RequestIdPolicy::default()), "/ok", Some("client-42")).await;
+        assert_eq!((echoed.as_str(), body.as_str()), ("client-42", "client-42"));
+
+        // Errors get an ApiError body with the same ID
+        let (status, echoed, body) = call(app(RequestIdPolicy::default()), "/missing", Some("client-43")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(echoed, "client-43");
+        let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(error["request_id"], "client-43");
+        assert_eq!(error["status"], 404);
+        assert_eq!(error["code"], "not_found");
+        assert_eq!(error["error"], "Not Found");
+
+        // Malformed or untrusted incoming IDs are replaced
+        let (_, replaced, _) = call(app(RequestIdPolicy::default()), "/ok", Some("has spaces")).await;
+        assert_ne!(replaced, "has spaces");
+        let (_, replaced, _) = call(app(RequestIdPolicy { trust_incoming: false }), "/ok", Some("client-44")).await;
+        assert_ne!(replaced, "client-44");
+    }
+}
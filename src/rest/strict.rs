@@ -0,0 +1,112 @@
+//! Strict request bodies: reject JSON fields the endpoint doesn't know
+//!
+//! Serde ignores unknown fields, so a typo such as `vectors` for `vector` or `metadata` for
+//! `metadata_json` is silently dropped and the write succeeds without it. With
+//! `AIDB_STRICT_JSON=1`, create/insert endpoints (`StrictJson` bodies) answer 400 naming the
+//! unexpected fields instead. Off by default so existing clients sending extra fields keep working.
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde_json::Value;
+use std::cell::Cell;
+use tracing::warn;
+
+/// Whether unknown fields in `StrictJson` bodies are rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StrictMode {
+    pub enabled: bool,
+}
+
+impl StrictMode {
+    /// Parse `AIDB_STRICT_JSON` (`1`/`true` rejects unknown fields; default accepts them)
+    pub(crate) fn from_var(raw: Option<&str>) -> Self {
+        let enabled = raw.is_some_and(|raw| matches!(raw.trim().to_lowercase().as_str(), "1" | "true"));
+        Self { enabled }
+    }
+}
+
+/// Deserializer that only records the field names a derived struct asks for
+struct FieldNames<'a>(&'a Cell<Option<&'static [&'static str]>>);
+
+impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.set(Some(fields));
+        Err(de::Error::custom("field names recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// Field names `T` declares, or `None` when it isn't a plain derived struct (e.g. uses `flatten`)
+fn declared_fields<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let fields = Cell::new(None);
+    let _ = T::deserialize(FieldNames(&fields));
+    fields.get()
+}
+
+/// Keys of the JSON object `body` that `T` doesn't declare, each prefixed with `path`
+pub fn unknown_keys<T: DeserializeOwned>(body: &Value, path: &str) -> Vec<String> {
+    let (Some(fields), Some(object)) = (declared_fields::<T>(), body.as_object()) else {
+        return Vec::new();
+    };
+    object
+        .keys()
+        .filter(|key| !fields.contains(&key.as_str()))
+        .map(|key| format!("{}{}", path, key))
+        .collect()
+}
+
+/// A request body that can be checked for unknown fields
+pub trait StrictBody: DeserializeOwned {
+    /// Unknown fields in `body`, as paths; top-level keys only unless overridden
+    fn unknown_fields(body: &Value) -> Vec<String> {
+        unknown_keys::<Self>(body, "")
+    }
+}
+
+/// `Json` that, in strict mode, rejects bodies with unknown fields (400)
+pub struct StrictJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for StrictJson<T>
+where
+    T: StrictBody,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let mode = req.extensions().get::<StrictMode>().copied().unwrap_or_default();
+        let Json(body) = Json::<Value>::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        if mode.enabled {
+            let unknown = T::unknown_fields(&body);
+            if !unknown.is_empty() {
+                warn!(fields = ?unknown, "Rejected request body with unknown fields");
+                return Err((StatusCode::BAD_REQUEST, format!("Unknown field(s) in request body: {}", unknown.join(", "))).into_response());
+            }
+        }
+        serde_json::from_value(body).map(StrictJson).map_err(|e| {
+            (StatusCode::UNPROCESSABLE_ENTITY, format!("Failed to deserialize the JSON body into the target type: {}", e)).into_response()
+        })
+    }
+}
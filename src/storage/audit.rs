@@ -0,0 +1,132 @@
+//! Audit log of state-changing API calls
+//!
+//! Every authenticated REST request other than GET/HEAD is recorded once it has been answered:
+//! who made it, the matched route, the collection it named and the response status. Records are
+//! keyed by big-endian timestamp plus a Sled-generated ID, so a key-order scan is a time-order
+//! scan and a time range is a key range. `Storage::audit_log` filters while scanning and pages
+//! with an opaque cursor (the hex key of the last record returned).
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
+
+use crate::storage::nosql::now_millis;
+use crate::storage::Storage;
+
+/// One audited call
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub user: String,
+    /// Method and matched route, e.g. `DELETE /collections/:collection_id/docs/:doc_id`
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// HTTP status of the response
+    pub status: u16,
+}
+
+/// Which records `Storage::audit_log` returns; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditFilter {
+    pub user: Option<String>,
+    /// Prefix of the action: `DELETE` for every delete, `POST /admin` for admin calls
+    pub action: Option<String>,
+    pub collection: Option<String>,
+    /// Inclusive lower bound on `timestamp`
+    pub since: Option<u64>,
+    /// Inclusive upper bound on `timestamp`
+    pub until: Option<u64>,
+}
+
+impl AuditFilter {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.user.as_ref().is_none_or(|user| *user == record.user)
+            && self.action.as_ref().is_none_or(|action| record.action.starts_with(action.as_str()))
+            && self.collection.as_ref().is_none_or(|col| record.collection.as_ref() == Some(col))
+    }
+}
+
+/// One page of `Storage::audit_log`, oldest first
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuditPage {
+    pub records: Vec<AuditRecord>,
+    /// Pass back as `after` for the next page; `None` once the range is exhausted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+fn encode_cursor(key: &[u8]) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Key a cursor from `AuditPage::next_cursor` points at; errors on anything else
+pub fn decode_cursor(cursor: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if cursor.len() != 32 || !cursor.is_ascii() {
+        return Err(format!("Invalid audit cursor '{}'", cursor).into());
+    }
+    (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16).map_err(|_| format!("Invalid audit cursor '{}'", cursor).into()))
+        .collect()
+}
+
+impl Storage {
+    /// Append `record` with the current time (its `timestamp` is overwritten). Read-only
+    /// handles record nothing: every call that would be audited fails on them anyway.
+    pub fn record_audit(&self, mut record: AuditRecord) -> Result<(), Box<dyn std::error::Error>> {
+        if self.read_only {
+            return Ok(());
+        }
+        record.timestamp = now_millis();
+        let mut key = record.timestamp.to_be_bytes().to_vec();
+        key.extend_from_slice(&self.db.generate_id()?.to_be_bytes());
+        let value = serde_json::to_vec(&record)?;
+        self.retry_write("record_audit", || self.audit_tree.insert(key.as_slice(), value.as_slice()))?;
+        Ok(())
+    }
+
+    /// Up to `limit` records matching `filter`, oldest first, starting after the `after` cursor
+    #[instrument(skip(self))]
+    pub fn audit_log(&self, filter: &AuditFilter, after: Option<&str>, limit: usize) -> Result<AuditPage, Box<dyn std::error::Error>> {
+        let start = match after {
+            Some(cursor) => {
+                let mut key = decode_cursor(cursor)?;
+                key.push(0); // first key after the cursor
+                key
+            }
+            None => filter.since.unwrap_or(0).to_be_bytes().to_vec(),
+        };
+        let end = filter.until.map(|until| until.saturating_add(1).to_be_bytes().to_vec());
+        if end.as_ref().is_some_and(|end| start >= *end) {
+            return Ok(AuditPage::default());
+        }
+        let range = match &end {
+            Some(end) => self.audit_tree.range(start.as_slice()..end.as_slice()),
+            None => self.audit_tree.range(start.as_slice()..),
+        };
+
+        let mut page = AuditPage::default();
+        let mut last_key = None;
+        let mut more = false;
+        let mut scanned = 0;
+        for item in range {
+            let (key, value) = item?;
+            scanned += 1;
+            let record: AuditRecord = serde_json::from_slice(&value)?;
+            if !filter.matches(&record) {
+                continue;
+            }
+            if page.records.len() == limit {
+                more = true;
+                break;
+            }
+            page.records.push(record);
+            last_key = Some(key);
+        }
+        // A cursor only when another match exists past the last returned record
+        page.next_cursor = last_key.filter(|_| more).map(|key| encode_cursor(&key));
+        debug!(scanned = scanned, returned = page.records.len(), "Audit log scanned");
+        Ok(page)
+    }
+}
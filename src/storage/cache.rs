@@ -0,0 +1,209 @@
+use serde::Serialize;
+use std::sync::MutexGuard;
+use sysinfo::System;
+use tracing::{info, debug, warn, instrument};
+
+use crate::cache::{CacheStats, DocCache};
+use crate::storage::Storage;
+
+/// Fraction of available memory used by `autosize_cache` when none is given
+pub const DEFAULT_CACHE_AUTOSIZE_FRACTION: f64 = 0.25;
+
+/// Parse `AIDB_CACHE_AUTOSIZE_FRACTION` (in (0, 1]; unset or invalid values fall back to the default)
+pub fn parse_cache_autosize_fraction(raw: Option<&str>) -> f64 {
+    raw.and_then(|raw| raw.trim().parse::<f64>().ok())
+        .filter(|f| *f > 0.0 && *f <= 1.0)
+        .unwrap_or(DEFAULT_CACHE_AUTOSIZE_FRACTION)
+}
+
+/// Memory currently available to this process, in bytes.
+/// Inside a container the cgroup limit wins over host-wide free memory.
+pub fn available_memory_bytes() -> u64 {
+    let mut sys = System::new();
+    sys.refresh_memory();
+    let host_available = sys.available_memory();
+    match sys.cgroup_limits() {
+        Some(limits) => limits.free_memory.min(host_available),
+        None => host_available,
+    }
+}
+
+/// Result of a cache capacity change
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheResize {
+    pub old_capacity_bytes: usize,
+    pub new_capacity_bytes: usize,
+    pub size_bytes: usize,
+    pub entries: usize,
+    pub evicted: usize,
+    /// Available memory the new capacity was derived from (autosize only)
+    pub available_memory_bytes: Option<u64>,
+}
+
+impl Storage {
+    /// Lock the document cache, recovering it if a panic poisoned the mutex.
+    /// The cache only holds copies of stored documents, so a recovered cache is cleared
+    /// (a panic mid-update may have left its bookkeeping inconsistent) and keeps working
+    /// instead of being silently bypassed for the rest of the process lifetime.
+    pub(crate) fn lock_cache(&self) -> MutexGuard<'_, DocCache> {
+        self.doc_cache.lock().unwrap_or_else(|poisoned| {
+            warn!("Document cache mutex poisoned by a panic; clearing and recovering the cache");
+            self.doc_cache.clear_poison();
+            let mut cache = poisoned.into_inner();
+            cache.clear();
+            cache
+        })
+    }
+
+    /// Log every document cache eviction at info level (overrides AIDB_CACHE_LOG_EVICTIONS)
+    pub fn with_cache_eviction_logging(self, enabled: bool) -> Self {
+        self.lock_cache().set_log_evictions(enabled);
+        self
+    }
+
+    /// Document cache occupancy and eviction totals
+    pub fn cache_stats(&self) -> CacheStats {
+        self.lock_cache().stats()
+    }
+
+    /// Set the document cache capacity, evicting LRU entries if it no longer fits
+    #[instrument(skip(self))]
+    pub fn resize_cache(&self, capacity_bytes: usize) -> CacheResize {
+        let mut cache = self.lock_cache();
+        let old_capacity_bytes = cache.capacity_bytes();
+        let evicted = cache.set_capacity(capacity_bytes);
+        let resize = CacheResize {
+            old_capacity_bytes,
+            new_capacity_bytes: capacity_bytes,
+            size_bytes: cache.size_bytes(),
+            entries: cache.len(),
+            evicted,
+            available_memory_bytes: None,
+        };
+        info!(
+            old_capacity_bytes = old_capacity_bytes,
+            new_capacity_bytes = capacity_bytes,
+            evicted = evicted,
+            "Document cache resized"
+        );
+        resize
+    }
+
+    /// Resize the document cache to `fraction` of currently available memory
+    #[instrument(skip(self))]
+    pub fn autosize_cache(&self, fraction: f64) -> Result<CacheResize, Box<dyn std::error::Error>> {
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            return Err(format!("Cache fraction must be in (0, 1], got {}", fraction).into());
+        }
+        let available = available_memory_bytes();
+        let capacity_bytes = (available as f64 * fraction) as usize;
+        debug!(available_bytes = available, fraction = fraction, capacity_bytes = capacity_bytes, "Autosizing document cache");
+
+        let mut resize = self.resize_cache(capacity_bytes);
+        resize.available_memory_bytes = Some(available);
+        Ok(resize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{test_doc, Document};
+    use std::fs;
+
+    #[test]
+    fn resize_changes_capacity_and_evicts() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_cache_resize");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        for i in 0..10 {
+            storage.insert_doc(Document {
+                text: "x".repeat(100),
+                ..test_doc(&format!("doc{}", i), vec![0.0; 4])
+            }, "col").expect("insert");
+        }
+        let before = storage.doc_cache.lock().unwrap().len();
+        assert_eq!(before, 10);
+
+        let resize = storage.resize_cache(300);
+        assert_ne!(resize.old_capacity_bytes, resize.new_capacity_bytes);
+        assert_eq!(resize.new_capacity_bytes, 300);
+        assert!(resize.size_bytes <= 300);
+        assert_eq!(resize.entries, before - resize.evicted);
+        assert!(resize.entries < before);
+        // Most recently inserted docs survive
+        assert!(storage.get_doc_with_cache_status("col/doc9").unwrap().1);
+
+        let auto = storage.autosize_cache(0.01).expect("autosize");
+        assert_eq!(auto.old_capacity_bytes, 300);
+        let available = auto.available_memory_bytes.unwrap();
+        assert_eq!(auto.new_capacity_bytes, (available as f64 * 0.01) as usize);
+        assert!(storage.autosize_cache(1.5).is_err());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn evictions_are_counted_with_freed_bytes() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_cache_evictions");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap())
+            .expect("open storage")
+            .with_cache_eviction_logging(true);
+        storage.resize_cache(1000);
+        assert_eq!(storage.cache_stats().evictions, 0);
+
+        // Each doc is ~110 estimated bytes, so 20 of them overflow a 1000-byte cache
+        for i in 0..20 {
+            storage.insert_doc(Document {
+                text: "x".repeat(100),
+                ..test_doc(&format!("doc{:02}", i), vec![0.0; 1])
+            }, "col").expect("insert");
+        }
+        let stats = storage.cache_stats();
+        assert!(stats.evictions > 0);
+        assert_eq!(stats.evictions as usize, 20 - stats.entries);
+        assert!(stats.size_bytes <= stats.capacity_bytes);
+        // Everything inserted is either still cached or was evicted
+        let per_doc = stats.size_bytes / stats.entries;
+        assert_eq!(stats.evicted_bytes as usize, per_doc * stats.evictions as usize);
+
+        // Shrinking the cache evicts too, and is accounted the same way
+        let resize = storage.resize_cache(per_doc * 2);
+        let after = storage.cache_stats();
+        assert_eq!(after.evictions, stats.evictions + resize.evicted as u64);
+        assert_eq!(after.evicted_bytes, stats.evicted_bytes + (per_doc * resize.evicted) as u64);
+        assert_eq!(after.entries, 2);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn poisoned_cache_mutex_recovers() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_cache_poison");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let doc = |id: &str| Document { text: "cached".to_string(), ..test_doc(id, vec![0.0; 2]) };
+        storage.insert_doc(doc("before"), "col").expect("insert");
+
+        let poisoner = storage.clone();
+        let result = std::thread::spawn(move || {
+            let _guard = poisoner.doc_cache.lock().unwrap();
+            panic!("poison the cache mutex");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(storage.doc_cache.is_poisoned());
+
+        // Reads fall through to storage, and caching resumes after recovery
+        let (_, cached) = storage.get_doc_with_cache_status("col/before").expect("get");
+        assert!(!cached);
+        assert!(!storage.doc_cache.is_poisoned());
+        storage.insert_doc(doc("after"), "col").expect("insert");
+        assert!(storage.get_doc_with_cache_status("col/after").unwrap().1);
+        assert!(storage.get_doc_with_cache_status("col/before").unwrap().1);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
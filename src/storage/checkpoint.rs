@@ -0,0 +1,648 @@
+//! Vector index checkpoints
+//!
+//! Built indexes live in memory only, so after a restart the first search on every collection
+//! pays for a full rebuild. With `AIDB_INDEX_CHECKPOINT_SECS` set, a background task periodically
+//! serializes each cached index that is current into the `index_checkpoints` tree, skipping
+//! indexes fewer than `AIDB_INDEX_CHECKPOINT_WRITES` writes newer than their last checkpoint so
+//! write-heavy collections aren't re-serialized on every tick. On a cache miss a checkpoint is
+//! loaded instead of rebuilding when it was taken at the collection's current generation and
+//! content fingerprint; any other checkpoint is stale and ignored.
+//!
+//! With `AIDB_INDEX_DIR` set, checkpoints are written as sidecar files in that directory instead
+//! of the tree (one `<hex of collection_id/field>.aidx` file per index, replaced atomically), and
+//! `Storage::open` loads every sidecar that is still current straight into the index cache.
+//! Invalidation is the same either way: each checkpoint carries the generation and fingerprint
+//! it was taken at, so a sidecar from before later writes (or from another database) never
+//! matches and is skipped until the next pass overwrites it; deleting a collection removes its
+//! checkpoints.
+//!
+//! New documents are added to the cached index rather than invalidating it, so a checkpoint can
+//! fall behind the index it was taken from. Those additions are logged to the index WAL (see
+//! `storage::wal`) and replayed onto a checkpoint older than its collection when they account for
+//! every write since; otherwise the checkpoint is stale. A write is acknowledged only once it is
+//! in `doc_tree`, so writes since the last checkpoint cost at most a rebuild, never data.
+//!
+//! To move an index between instances without rebuilding it, `export_index` returns a
+//! collection's current primary index serialized, and `import_index` installs such a file into
+//! another instance's cache (and checkpoints it there). Generations are per instance, so the file
+//! carries only the index; the import instead checks it against the receiving collection: same
+//! metric, same dimension and exactly the IDs of the vectors stored there.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, debug, warn, error, instrument};
+
+use crate::indexing::{write_file_atomic, IndexBackend, VectorIndex};
+use crate::storage::{Storage, DEFAULT_VECTOR_FIELD};
+
+/// Extension of index sidecar files in `AIDB_INDEX_DIR`
+const SIDECAR_EXTENSION: &str = "aidx";
+
+/// Generation of the last checkpoint written or loaded, per index cache key
+pub(crate) type CheckpointedGenerations = HashMap<String, u64>;
+
+/// When cached indexes are checkpointed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexCheckpointPolicy {
+    /// Time between checkpoint passes (zero disables the background task)
+    pub interval: Duration,
+    /// Writes (generation bumps) since its last checkpoint before an index is persisted again
+    pub min_writes: u64,
+    /// Directory of sidecar index files (`None` = the `index_checkpoints` tree)
+    pub dir: Option<PathBuf>,
+}
+
+impl Default for IndexCheckpointPolicy {
+    fn default() -> Self {
+        Self { interval: Duration::ZERO, min_writes: 1, dir: None }
+    }
+}
+
+impl IndexCheckpointPolicy {
+    /// Parse the interval in seconds (default 0 = disabled), the writes between checkpoints
+    /// (default 1) and the sidecar directory (unset = checkpoint into the database)
+    pub fn parse(interval_secs: Option<&str>, min_writes: Option<&str>, dir: Option<&str>) -> Self {
+        let number = |raw: Option<&str>| raw.and_then(|raw| raw.trim().parse::<u64>().ok());
+        Self {
+            interval: Duration::from_secs(number(interval_secs).unwrap_or(0)),
+            min_writes: number(min_writes).unwrap_or(1).max(1),
+            dir: dir.filter(|raw| !raw.trim().is_empty()).map(PathBuf::from),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.interval.is_zero()
+    }
+}
+
+/// Outcome of one `Storage::checkpoint_indexes` pass
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CheckpointReport {
+    /// Index cache keys (`collection_id/field`) written
+    pub persisted: Vec<String>,
+    /// Cached indexes left alone (stale, or too few writes since their last checkpoint)
+    pub skipped: usize,
+}
+
+/// An imported index file that doesn't fit the receiving collection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompatibleIndex {
+    pub collection_id: String,
+    pub reason: String,
+}
+
+impl std::fmt::Display for IncompatibleIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Index doesn't fit collection '{}': {}", self.collection_id, self.reason)
+    }
+}
+
+impl std::error::Error for IncompatibleIndex {}
+
+/// Outcome of `Storage::import_index`
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexImport {
+    pub collection_id: String,
+    pub backend: IndexBackend,
+    pub metric: String,
+    pub vectors: usize,
+    /// Generation of the receiving collection the index now serves
+    pub generation: u64,
+}
+
+/// Checkpoint value: generation and fingerprint (u64 LE each), then the serialized index
+fn encode_checkpoint(generation: u64, fingerprint: u64, index: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16 + index.len());
+    out.extend_from_slice(&generation.to_le_bytes());
+    out.extend_from_slice(&fingerprint.to_le_bytes());
+    out.extend_from_slice(index);
+    out
+}
+
+fn decode_checkpoint(bytes: &[u8]) -> Option<(u64, u64, &[u8])> {
+    if bytes.len() < 16 {
+        return None;
+    }
+    let generation = u64::from_le_bytes(bytes[..8].try_into().ok()?);
+    let fingerprint = u64::from_le_bytes(bytes[8..16].try_into().ok()?);
+    Some((generation, fingerprint, &bytes[16..]))
+}
+
+/// Most JSON any exported index of `vectors` can decompress to: each ID (escaped, at worst six
+/// bytes a byte) and vector (at most 16 characters a component, doubled for the graph's copy),
+/// plus a point's graph links (64 layer-zero IDs of up to 11 characters, and upper layers)
+fn max_index_json_bytes(vectors: &[(String, Vec<f32>)]) -> u64 {
+    let per_point = |id: &str, vector: &[f32]| 6 * id.len() as u64 + 32 * vector.len() as u64 + 4096;
+    64 * 1024 + vectors.iter().map(|(id, v)| per_point(id, v)).sum::<u64>()
+}
+
+/// Sidecar file name for an index cache key: the key hex-encoded, so any collection ID is a
+/// valid file name
+fn sidecar_name(cache_key: &str) -> String {
+    let hex: String = cache_key.bytes().map(|b| format!("{:02x}", b)).collect();
+    format!("{}.{}", hex, SIDECAR_EXTENSION)
+}
+
+/// Index cache key of a sidecar file name, if it is one
+fn sidecar_key(file_name: &str) -> Option<String> {
+    let hex = file_name.strip_suffix(SIDECAR_EXTENSION)?.strip_suffix('.')?;
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+impl Storage {
+    /// Checkpoint cached indexes on this schedule and to this location (overrides
+    /// AIDB_INDEX_CHECKPOINT_SECS / AIDB_INDEX_CHECKPOINT_WRITES / AIDB_INDEX_DIR)
+    pub fn with_index_checkpoints(mut self, policy: IndexCheckpointPolicy) -> Self {
+        self.checkpoint_policy = policy;
+        self
+    }
+
+    /// Load every sidecar in the checkpoint directory that still matches its collection into the
+    /// index cache; stale and unreadable ones are skipped. Returns how many were loaded.
+    #[instrument(skip(self))]
+    pub fn load_index_sidecars(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let Some(dir) = &self.checkpoint_policy.dir else {
+            return Ok(0);
+        };
+        if !dir.exists() {
+            return Ok(0);
+        }
+        let mut loaded = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let Some(cache_key) = entry.file_name().to_str().and_then(sidecar_key) else {
+                continue;
+            };
+            let collection_id = cache_key.split('/').next().unwrap_or_default();
+            let generation = self.collection_generation(collection_id)?;
+            if let Some(index) = self.load_index_checkpoint(collection_id, &cache_key, generation)? {
+                self.lock_index_cache().insert(cache_key, (generation, std::time::Instant::now(), index));
+                loaded += 1;
+            }
+        }
+        info!(dir = %dir.display(), loaded = loaded, "Index sidecars loaded");
+        Ok(loaded)
+    }
+
+    /// Raw checkpoint stored for `cache_key`, from the sidecar directory or the tree
+    fn read_checkpoint(&self, cache_key: &str) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+        match &self.checkpoint_policy.dir {
+            Some(dir) => match std::fs::read(dir.join(sidecar_name(cache_key))) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            None => Ok(self.index_checkpoint_tree.get(cache_key.as_bytes())?.map(|v| v.to_vec())),
+        }
+    }
+
+    /// Drop every checkpoint of a collection (they could never match again once it is deleted)
+    pub(crate) fn remove_index_checkpoints(&self, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let prefix = format!("{}/", collection_id);
+        for key in self.index_checkpoint_tree.scan_prefix(prefix.as_bytes()).keys() {
+            self.index_checkpoint_tree.remove(key?)?;
+        }
+        let Some(dir) = self.checkpoint_policy.dir.as_ref().filter(|dir| dir.exists()) else {
+            return Ok(());
+        };
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let matches = entry.file_name().to_str().and_then(sidecar_key).is_some_and(|key| key.starts_with(&prefix));
+            if matches {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Persist every cached index that is current and at least `min_writes` generations newer
+    /// than its last checkpoint
+    #[instrument(skip(self))]
+    pub fn checkpoint_indexes(&self) -> Result<CheckpointReport, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        if let Some(dir) = &self.checkpoint_policy.dir {
+            std::fs::create_dir_all(dir)?;
+        }
+        let cached: Vec<(String, u64, Arc<VectorIndex>)> = self
+            .lock_index_cache()
+            .iter()
+            .map(|(key, (generation, _, index))| (key.clone(), *generation, index.clone()))
+            .collect();
+
+        let mut report = CheckpointReport::default();
+        for (cache_key, generation, index) in cached {
+            let collection_id = cache_key.split('/').next().unwrap_or_default();
+            let (current, fingerprint) = self.collection_state(collection_id)?;
+            let last = self.lock_checkpointed().get(&cache_key).copied();
+            let due = last.is_none_or(|last| generation.saturating_sub(last) >= self.checkpoint_policy.min_writes);
+            if generation != current || !due {
+                report.skipped += 1;
+                continue;
+            }
+            self.write_checkpoint(&cache_key, generation, fingerprint, &index.to_bytes()?)?;
+            report.persisted.push(cache_key);
+        }
+        if !report.persisted.is_empty() && self.checkpoint_policy.dir.is_none() {
+            self.index_checkpoint_tree.flush()?;
+        }
+
+        info!(persisted = report.persisted.len(), skipped = report.skipped, "Index checkpoint pass completed");
+        Ok(report)
+    }
+
+    /// Store one serialized index as the checkpoint of `cache_key` (flushing is up to the caller)
+    fn write_checkpoint(&self, cache_key: &str, generation: u64, fingerprint: u64, index: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let value = encode_checkpoint(generation, fingerprint, index);
+        match &self.checkpoint_policy.dir {
+            Some(dir) => write_file_atomic(&dir.join(sidecar_name(cache_key)), &value)?,
+            None => {
+                self.index_checkpoint_tree.insert(cache_key.as_bytes(), value)?;
+            }
+        }
+        self.lock_checkpointed().insert(cache_key.to_string(), generation);
+        self.prune_index_wal(cache_key, generation)?;
+        debug!(cache_key = %cache_key, generation = generation, "Index checkpointed");
+        Ok(())
+    }
+
+    /// Whether a checkpoint of `cache_key` was written or loaded by this handle
+    pub(crate) fn is_checkpointed(&self, cache_key: &str) -> bool {
+        self.lock_checkpointed().contains_key(cache_key)
+    }
+
+    /// The collection's current primary index, serialized (built first if no current one is
+    /// cached); `import_index` loads it into another instance
+    #[instrument(skip(self))]
+    pub fn export_index(&self, collection_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let index = self.cached_field_index(collection_id, DEFAULT_VECTOR_FIELD)?;
+        let bytes = index.to_bytes()?;
+        info!(collection_id = %collection_id, vectors = index.len(), bytes = bytes.len(), "Vector index exported");
+        Ok(bytes)
+    }
+
+    /// Serve `collection_id`'s searches from an index file written by `export_index` instead of
+    /// building one. Fails with `IncompatibleIndex` unless the index ranks by the collection's
+    /// metric, has its dimension and holds exactly its stored vectors, ID for ID.
+    #[instrument(skip(self, bytes), fields(bytes = bytes.len()))]
+    pub fn import_index(&self, collection_id: &str, bytes: &[u8]) -> Result<IndexImport, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let incompatible = |reason: String| -> Box<dyn std::error::Error> {
+            Box::new(IncompatibleIndex { collection_id: collection_id.to_string(), reason })
+        };
+        // Generation first, as in cached_field_index_at
+        let generation = self.collection_generation(collection_id)?;
+        let stored = self.get_vectors_in_collection(collection_id)?;
+        let index = VectorIndex::from_bytes_within(bytes, max_index_json_bytes(&stored))
+            .map_err(|e| incompatible(format!("not an index file ({})", e)))?;
+
+        let metric = self.collection_metric(collection_id)?;
+        if index.metric() != metric {
+            return Err(incompatible(format!("index ranks by {}, collection by {}", index.metric_name(), metric.as_str())));
+        }
+        let expected_dim = self.get_collection(collection_id)?.and_then(|col| col.vector_dim).or_else(|| stored.first().map(|(_, v)| v.len()));
+        if let (Some(expected), Some(actual)) = (expected_dim, index.dim()) {
+            if expected != actual {
+                return Err(incompatible(format!("expected dim {}, index has {}", expected, actual)));
+            }
+        }
+        index.check_contents(&stored).map_err(incompatible)?;
+
+        let cache_key = format!("{}/{}", collection_id, DEFAULT_VECTOR_FIELD);
+        let (_, fingerprint) = self.collection_state(collection_id)?;
+        self.write_checkpoint(&cache_key, generation, fingerprint, bytes)?;
+        let import = IndexImport {
+            collection_id: collection_id.to_string(),
+            backend: index.backend(),
+            metric: index.metric_name().to_string(),
+            vectors: index.len(),
+            generation,
+        };
+        self.lock_index_cache().insert(cache_key, (generation, std::time::Instant::now(), Arc::new(index)));
+        info!(collection_id = %collection_id, vectors = import.vectors, generation = generation, "Vector index imported");
+        Ok(import)
+    }
+
+    /// Index checkpointed for `cache_key`, if it matches the collection at `generation`: taken at
+    /// that generation with the same contents, or older with every write since logged in the
+    /// index WAL, which is then replayed onto it. Unreadable checkpoints are logged and ignored.
+    pub(crate) fn load_index_checkpoint(
+        &self,
+        collection_id: &str,
+        cache_key: &str,
+        generation: u64,
+    ) -> Result<Option<Arc<VectorIndex>>, Box<dyn std::error::Error>> {
+        let Some(bytes) = self.read_checkpoint(cache_key)? else {
+            return Ok(None);
+        };
+        let Some((checkpointed, checkpoint_fingerprint, index_bytes)) = decode_checkpoint(&bytes) else {
+            warn!(cache_key = %cache_key, "Ignoring malformed index checkpoint");
+            return Ok(None);
+        };
+        let current = self.collection_state(collection_id)?;
+        let replay = if current.0 != generation || checkpointed > generation {
+            None
+        } else if checkpointed == generation {
+            (checkpoint_fingerprint == current.1).then(Vec::new)
+        } else {
+            self.replay_index_wal(cache_key, (checkpointed, checkpoint_fingerprint), current)?
+        };
+        let Some(replay) = replay else {
+            debug!(cache_key = %cache_key, checkpointed = checkpointed, generation = generation, "Index checkpoint is stale");
+            return Ok(None);
+        };
+        match VectorIndex::from_bytes(index_bytes) {
+            Ok(mut index) => {
+                let replayed = replay.len();
+                for (id, vector) in replay {
+                    index.add(id, vector);
+                }
+                self.lock_checkpointed().insert(cache_key.to_string(), checkpointed);
+                info!(cache_key = %cache_key, checkpointed = checkpointed, generation = generation, replayed = replayed, "Vector index loaded from checkpoint");
+                Ok(Some(Arc::new(index)))
+            }
+            Err(e) => {
+                warn!(cache_key = %cache_key, error = %e, "Ignoring unreadable index checkpoint");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Start the background checkpoint task on the current Tokio runtime (`None` when disabled)
+    pub fn spawn_index_checkpointer(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.checkpoint_policy.is_enabled() {
+            return None;
+        }
+        let storage = self.clone();
+        let interval = self.checkpoint_policy.interval;
+        info!(interval_secs = interval.as_secs(), min_writes = self.checkpoint_policy.min_writes, "Index checkpointing enabled");
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick fires immediately; nothing is cached yet at startup
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let storage = storage.clone();
+                match tokio::task::spawn_blocking(move || storage.checkpoint_indexes().map_err(|e| e.to_string())).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => error!(error = %e, "Index checkpoint pass failed"),
+                    Err(e) => error!(error = %e, "Index checkpoint task panicked"),
+                }
+            }
+        }))
+    }
+
+    fn lock_checkpointed(&self) -> std::sync::MutexGuard<'_, CheckpointedGenerations> {
+        self.checkpointed.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexing::{IndexBackend, FLAT_INDEX_THRESHOLD};
+    use crate::storage::{test_doc, Document};
+    use std::fs;
+
+    fn doc(i: usize) -> Document {
+        test_doc(&format!("doc{}", i), vec![(i % 13) as f32, (i % 7) as f32, i as f32 * 0.01])
+    }
+
+    #[test]
+    fn checkpointed_index_serves_searches_after_restart_without_rebuild() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_index_checkpoint");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.to_str().unwrap();
+        let query = [3.0, 2.0, 0.5];
+
+        let expected = {
+            let storage = Storage::open(path).unwrap();
+            storage.insert_docs((0..FLAT_INDEX_THRESHOLD + 50).map(doc).collect(), "col").unwrap();
+            let outcome = storage.vector_search_detailed("col", &query, 5).unwrap();
+            assert_eq!(outcome.index_backend, IndexBackend::Hnsw);
+            assert_eq!(storage.checkpoint_indexes().unwrap().persisted, vec!["col/vector"]);
+            // Nothing written since: the next pass has nothing to do
+            assert!(storage.checkpoint_indexes().unwrap().persisted.is_empty());
+            outcome.ids
+        };
+
+        // Restart: the checkpoint is loaded instead of rebuilding
+        let storage = Storage::open(path).unwrap();
+        assert_eq!(storage.vector_search("col", &query, 5).unwrap(), expected);
+        assert_eq!(storage.index_builds(), 0);
+
+        // A new document is added to the loaded index; replacing one makes it stale, so the
+        // next search rebuilds
+        storage.insert_doc(doc(10_000), "col").unwrap();
+        assert_eq!(storage.vector_search("col", &doc(10_000).vector, 1).unwrap(), vec!["doc10000"]);
+        assert_eq!(storage.index_builds(), 0);
+        storage.insert_doc(Document { vector: vec![-5.0, -5.0, -5.0], ..doc(3) }, "col").unwrap();
+        storage.vector_search("col", &query, 5).unwrap();
+        assert_eq!(storage.index_builds(), 1);
+
+        drop(storage);
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn writes_after_the_last_checkpoint_are_searchable_after_restart() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_index_recovery");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.to_str().unwrap();
+        let late = Document { vector: vec![50.0, 50.0, 50.0], ..doc(20_000) };
+        let batch = vec![Document { vector: vec![-50.0, -50.0, -50.0], ..doc(20_001) }, doc(20_002)];
+
+        {
+            let storage = Storage::open(path).unwrap();
+            storage.insert_docs((0..FLAT_INDEX_THRESHOLD + 50).map(doc).collect(), "col").unwrap();
+            storage.vector_search("col", &[3.0, 2.0, 0.5], 5).unwrap();
+            storage.checkpoint_indexes().unwrap();
+            // Added to the cached index and logged, never merged into any persisted index
+            storage.insert_doc(late.clone(), "col").unwrap();
+            storage.insert_docs(batch.clone(), "col").unwrap();
+            assert_eq!(storage.index_wal_tree.len(), 2);
+        }
+
+        // Restart: the logged deltas are replayed onto the checkpoint instead of rebuilding
+        let storage = Storage::open(path).unwrap();
+        assert_eq!(storage.vector_search("col", &late.vector, 1).unwrap(), vec![late.id.clone()]);
+        assert_eq!(storage.vector_search("col", &batch[0].vector, 1).unwrap(), vec![batch[0].id.clone()]);
+        assert_eq!(storage.index_builds(), 0);
+        let index = storage.cached_field_index("col", DEFAULT_VECTOR_FIELD).unwrap();
+        index.check_contents(&storage.get_vectors_in_collection("col").unwrap()).unwrap();
+
+        // The next checkpoint covers the replayed deltas and drops their records
+        assert_eq!(storage.checkpoint_indexes().unwrap().persisted, vec!["col/vector"]);
+        assert!(storage.index_wal_tree.is_empty());
+
+        // A logged insert, then an update the log can't express: the chain no longer accounts
+        // for every write since the checkpoint, so the next restart rebuilds
+        storage.insert_doc(doc(20_003), "col").unwrap();
+        storage.insert_doc(Document { vector: vec![-9.0, -9.0, -9.0], ..doc(5) }, "col").unwrap();
+        assert_eq!(storage.index_wal_tree.len(), 1);
+        drop(storage);
+        let storage = Storage::open(path).unwrap();
+        let index = storage.cached_field_index("col", DEFAULT_VECTOR_FIELD).unwrap();
+        assert_eq!(storage.index_builds(), 1);
+        index.check_contents(&storage.get_vectors_in_collection("col").unwrap()).unwrap();
+
+        drop(storage);
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn sidecars_load_on_open_unless_stale() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_index_sidecars");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let path = temp_dir.join("db");
+        let path = path.to_str().unwrap();
+        let policy = IndexCheckpointPolicy { dir: Some(temp_dir.join("indexes")), ..Default::default() };
+        let open = || Storage::open(path).unwrap().with_index_checkpoints(policy.clone());
+        let query = [3.0, 2.0, 0.5];
+
+        let expected = {
+            let storage = open();
+            storage.insert_docs((0..FLAT_INDEX_THRESHOLD + 50).map(doc).collect(), "col").unwrap();
+            let ids = storage.vector_search("col", &query, 5).unwrap();
+            assert_eq!(storage.checkpoint_indexes().unwrap().persisted, vec!["col/vector"]);
+            assert!(storage.index_checkpoint_tree.is_empty());
+            ids
+        };
+        let sidecars: Vec<_> = fs::read_dir(temp_dir.join("indexes")).unwrap().collect();
+        assert_eq!(sidecars.len(), 1);
+        assert_eq!(sidecar_key(&sidecar_name("col/vector")).as_deref(), Some("col/vector"));
+
+        // Restart: the sidecar is current, so it is loaded into the cache and searches need no build
+        let storage = open();
+        assert_eq!(storage.load_index_sidecars().unwrap(), 1);
+        assert_eq!(storage.vector_search("col", &query, 5).unwrap(), expected);
+        assert_eq!(storage.index_builds(), 0);
+
+        // A new document after the checkpoint is replayed from the index WAL on the next start...
+        storage.insert_doc(doc(10_000), "col").unwrap();
+        drop(storage);
+        let storage = open();
+        assert_eq!(storage.load_index_sidecars().unwrap(), 1);
+        assert_eq!(storage.vector_search("col", &doc(10_000).vector, 1).unwrap(), vec!["doc10000"]);
+        assert_eq!(storage.index_builds(), 0);
+
+        // ...but a replaced one leaves the sidecar stale: skipped on the next start
+        storage.insert_doc(Document { vector: vec![-5.0, -5.0, -5.0], ..doc(3) }, "col").unwrap();
+        drop(storage);
+        let storage = open();
+        assert_eq!(storage.load_index_sidecars().unwrap(), 0);
+        storage.vector_search("col", &query, 5).unwrap();
+        assert_eq!(storage.index_builds(), 1);
+
+        drop(storage);
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn exported_index_imports_into_a_fresh_instance_without_rebuild() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_index_export");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let docs = || (0..FLAT_INDEX_THRESHOLD + 50).map(doc).collect::<Vec<_>>();
+        let query = [3.0, 2.0, 0.5];
+
+        let source = Storage::open(temp_dir.join("source").to_str().unwrap()).unwrap();
+        source.insert_docs(docs(), "col").unwrap();
+        let expected = source.vector_search("col", &query, 5).unwrap();
+        let exported = source.export_index("col").unwrap();
+
+        let target = Storage::open(temp_dir.join("target").to_str().unwrap()).unwrap();
+        target.insert_docs(docs(), "col").unwrap();
+        let import = target.import_index("col", &exported).unwrap();
+        assert_eq!((import.backend, import.vectors), (IndexBackend::Hnsw, FLAT_INDEX_THRESHOLD + 50));
+        assert_eq!(target.vector_search("col", &query, 5).unwrap(), expected);
+        assert_eq!(target.index_builds(), 0, "the imported index serves searches");
+        // ...and is checkpointed, so a checkpoint pass has nothing left to write
+        assert!(target.checkpoint_indexes().unwrap().persisted.is_empty());
+
+        let rejected = |collection_id: &str, bytes: &[u8]| {
+            let err = target.import_index(collection_id, bytes).unwrap_err();
+            assert!(err.downcast_ref::<IncompatibleIndex>().is_some(), "{}", err);
+        };
+        // Another dimension, another metric, other documents, not an index at all
+        target.insert_docs(vec![Document { vector: vec![1.0, 0.0], ..doc(0) }], "flat2d").unwrap();
+        rejected("flat2d", &exported);
+        target
+            .create_collection(crate::tenants::Collection {
+                id: "cosine".to_string(),
+                name: "cosine".to_string(),
+                environment_id: "env".to_string(),
+                vector_dim: Some(3),
+                metric: crate::indexing::DistanceMetric::Cosine,
+                normalize: false,
+            })
+            .unwrap();
+        target.insert_docs(docs(), "cosine").unwrap();
+        rejected("cosine", &exported);
+        target.insert_docs(docs()[..10].to_vec(), "partial").unwrap();
+        rejected("partial", &exported);
+        // Same IDs, one vector changed
+        let mut shifted = docs();
+        shifted[7].vector = vec![9.0, 9.0, 9.0];
+        target.insert_docs(shifted, "shifted").unwrap();
+        rejected("shifted", &exported);
+        rejected("col", b"garbage");
+        // Cosine graph points are stored unit-length and still match their raw stored vectors
+        let cosine_index = target.export_index("cosine").unwrap();
+        assert_eq!(target.import_index("cosine", &cosine_index).unwrap().vectors, FLAT_INDEX_THRESHOLD + 50);
+
+        drop((source, target));
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn tampered_index_files_are_rejected_before_they_load() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_index_tampered");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).unwrap();
+        storage.insert_docs((0..FLAT_INDEX_THRESHOLD + 50).map(doc).collect(), "col").unwrap();
+        let exported = storage.export_index("col").unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&zstd::decode_all(exported.as_slice()).unwrap()).unwrap();
+        let points = FLAT_INDEX_THRESHOLD + 50;
+
+        let rejected = |tamper: &dyn Fn(&mut serde_json::Value), reason: &str| {
+            let mut tampered = json.clone();
+            tamper(&mut tampered["backend"]["Hnsw"]);
+            let bytes = zstd::encode_all(serde_json::to_vec(&tampered).unwrap().as_slice(), 3).unwrap();
+            let err = storage.import_index("col", &bytes).unwrap_err();
+            assert!(err.downcast_ref::<IncompatibleIndex>().is_some(), "{}", err);
+            assert!(err.to_string().contains(reason), "{}", err);
+        };
+        // A layer-zero link past the last point, an upper-layer link past its layer
+        rejected(&|map| map["hnsw"]["zero"][3][0] = serde_json::json!(points + 7), "past its");
+        rejected(
+            &|map| {
+                let upper = map["hnsw"]["layers"][0].as_array().unwrap().len();
+                map["hnsw"]["layers"][0][0][0] = serde_json::json!(upper);
+            },
+            "past its",
+        );
+        // One ID fewer than points
+        rejected(&|map| drop(map["values"].as_array_mut().unwrap().pop()), "IDs");
+        // A small file that inflates far past anything an index of the collection could need
+        let mut padded = serde_json::to_vec(&json).unwrap();
+        padded.resize(padded.len() + (16 << 20), b' ');
+        let err = storage.import_index("col", &zstd::encode_all(padded.as_slice(), 3).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("decompresses to more than"), "{}", err);
+
+        // The untampered file still imports and the collection still searches
+        assert_eq!(storage.import_index("col", &exported).unwrap().vectors, points);
+        assert_eq!(storage.vector_search("col", &[3.0, 2.0, 0.5], 5).unwrap().len(), 5);
+
+        drop(storage);
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
@@ -0,0 +1,177 @@
+//! Category derivation for documents inserted without one
+//!
+//! When a classifier is installed, `insert_doc` and `insert_docs` (and so every REST/gRPC insert
+//! path) ask it for a category whenever a document arrives with an empty `category`; a document
+//! that names one keeps it. `AIDB_AUTO_CATEGORY=1` installs `KeywordClassifier::default()`.
+//!
+//! To plug in a custom classifier, implement `Classifier` and install it on the handle before it
+//! is shared with the servers:
+//!
+//! ```ignore
+//! struct ByLanguage;
+//!
+//! impl Classifier for ByLanguage {
+//!     fn classify(&self, doc: &Document) -> Option<String> {
+//!         doc.metadata.get("lang")?.as_str().map(|lang| format!("lang_{}", lang))
+//!     }
+//! }
+//!
+//! let storage = Storage::open_with_config(path, &config)?.with_classifier(Some(Arc::new(ByLanguage)));
+//! ```
+//!
+//! Returning `None` leaves the category empty. Classifiers run on the writing thread, so they
+//! should be cheap; anything slow (a model call) belongs in the ingestion pipeline instead.
+
+use std::sync::Arc;
+use tracing::debug;
+
+use crate::storage::{Document, Storage};
+
+/// Derives a category for a document that was inserted without one
+pub trait Classifier: Send + Sync {
+    /// Category for `doc`, or `None` to leave it uncategorized
+    fn classify(&self, doc: &Document) -> Option<String>;
+}
+
+/// Assigns `category` to documents mentioning any of `keywords`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeywordRule {
+    pub category: String,
+    /// Lowercase words or phrases, matched case-insensitively on word boundaries
+    pub keywords: Vec<String>,
+}
+
+impl KeywordRule {
+    pub fn new(category: &str, keywords: &[&str]) -> Self {
+        Self {
+            category: category.to_string(),
+            keywords: keywords.iter().map(|k| k.to_lowercase()).collect(),
+        }
+    }
+}
+
+/// Picks the rule whose keywords occur most often in the document's text and string metadata
+/// values; ties go to the earlier rule, and no match leaves the document uncategorized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeywordClassifier {
+    pub rules: Vec<KeywordRule>,
+}
+
+impl KeywordClassifier {
+    pub fn new(rules: Vec<KeywordRule>) -> Self {
+        Self { rules }
+    }
+}
+
+impl Default for KeywordClassifier {
+    /// Rules for the categories the examples and load script use
+    fn default() -> Self {
+        Self::new(vec![
+            KeywordRule::new("AI", &["ai", "artificial intelligence", "llm", "language model", "neural", "transformer", "rag", "embedding"]),
+            KeywordRule::new("ML", &["machine learning", "ml", "training", "regression", "classifier", "clustering", "gradient"]),
+            KeywordRule::new("Database", &["database", "sql", "query", "index", "storage", "nosql"]),
+        ])
+    }
+}
+
+/// Lowercased words of `text`, split on anything that isn't alphanumeric
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Occurrences of the (possibly multi-word) `keyword` in `words`
+fn occurrences(words: &[String], keyword: &str) -> usize {
+    let phrase: Vec<&str> = keyword.split_whitespace().collect();
+    if phrase.is_empty() || phrase.len() > words.len() {
+        return 0;
+    }
+    words.windows(phrase.len()).filter(|window| window.iter().zip(&phrase).all(|(w, p)| w == p)).count()
+}
+
+impl Classifier for KeywordClassifier {
+    fn classify(&self, doc: &Document) -> Option<String> {
+        let mut content = words(&doc.text);
+        if let Some(metadata) = doc.metadata.as_object() {
+            for value in metadata.values() {
+                match value {
+                    serde_json::Value::String(s) => content.extend(words(s)),
+                    serde_json::Value::Array(items) => content.extend(items.iter().filter_map(|i| i.as_str()).flat_map(words)),
+                    _ => {}
+                }
+            }
+        }
+        let mut best: Option<(&KeywordRule, usize)> = None;
+        for rule in &self.rules {
+            let hits: usize = rule.keywords.iter().map(|k| occurrences(&content, k)).sum();
+            if hits > 0 && best.is_none_or(|(_, most)| hits > most) {
+                best = Some((rule, hits));
+            }
+        }
+        best.map(|(rule, _)| rule.category.clone())
+    }
+}
+
+impl Storage {
+    /// This handle with `classifier` deriving categories on insert (`None` turns derivation off)
+    pub fn with_classifier(mut self, classifier: Option<Arc<dyn Classifier>>) -> Self {
+        self.classifier = classifier;
+        self
+    }
+
+    /// Fill in `doc.category` from the classifier when it is empty
+    pub(crate) fn classify_if_uncategorized(&self, doc: &mut Document) {
+        let Some(classifier) = &self.classifier else {
+            return;
+        };
+        if !doc.category.trim().is_empty() {
+            return;
+        }
+        if let Some(category) = classifier.classify(doc) {
+            debug!(id = %doc.id, category = %category, "Derived document category");
+            doc.category = category;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use std::fs;
+
+    fn doc(id: &str, text: &str, category: &str) -> Document {
+        Document { text: text.to_string(), category: category.to_string(), ..test_doc(id, vec![1.0, 0.0]) }
+    }
+
+    #[test]
+    fn keyword_rules_categorize_uncategorized_inserts() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_classify");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap())
+            .unwrap()
+            .with_classifier(Some(Arc::new(KeywordClassifier::default())));
+
+        storage.insert_doc(doc("d1", "Fine-tuning a large language model with RAG", ""), "col").unwrap();
+        storage.insert_docs(vec![
+            doc("d2", "Gradient boosting beats linear regression on tabular training data", ""),
+            doc("d3", "A SQL query planner for the database", "Manual"),
+            doc("d4", "Notes from the weekly meeting", ""),
+        ], "col").unwrap();
+
+        let category = |id: &str| storage.get_doc("col", id).unwrap().category;
+        assert_eq!(category("d1"), "AI");
+        assert_eq!(category("d2"), "ML");
+        assert_eq!(category("d3"), "Manual", "an explicit category is kept");
+        assert_eq!(category("d4"), "", "no rule matched");
+
+        // Keywords match whole words only, and metadata strings count too
+        let mut tagged = doc("d5", "Trainingsdaten", "");
+        tagged.metadata = serde_json::json!({"tags": ["nosql"]});
+        assert_eq!(KeywordClassifier::default().classify(&tagged).as_deref(), Some("Database"));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
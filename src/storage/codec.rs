@@ -0,0 +1,115 @@
+use tracing::{debug, trace};
+
+use crate::storage::{Document, Storage};
+
+/// Header byte marking a `doc_tree` value as plain JSON.
+pub(crate) const DOC_ENCODING_RAW: u8 = 0x00;
+/// Header byte marking a `doc_tree` value as zstd-compressed JSON.
+pub(crate) const DOC_ENCODING_ZSTD: u8 = 0x01;
+
+/// zstd level used for document values (fast, reasonable ratio).
+const ZSTD_LEVEL: i32 = 3;
+
+impl Storage {
+    /// Enable or disable zstd compression for document values written from now on.
+    /// Existing values keep their own header byte, so reads stay transparent either way.
+    pub fn with_doc_compression(mut self, enabled: bool) -> Self {
+        self.doc_compression = enabled;
+        self
+    }
+
+    /// Whether newly written document values are zstd-compressed
+    pub fn doc_compression_enabled(&self) -> bool {
+        self.doc_compression
+    }
+
+    /// Serialize a Document for `doc_tree`: one header byte followed by JSON (raw or zstd)
+    pub(crate) fn encode_doc(&self, doc: &Document) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let json_bytes = serde_json::to_vec(doc)?;
+        if !self.doc_compression {
+            let mut out = Vec::with_capacity(json_bytes.len() + 1);
+            out.push(DOC_ENCODING_RAW);
+            out.extend_from_slice(&json_bytes);
+            return Ok(out);
+        }
+
+        let compressed = zstd::encode_all(json_bytes.as_slice(), ZSTD_LEVEL)?;
+        trace!(
+            id = %doc.id,
+            json_bytes = json_bytes.len(),
+            compressed_bytes = compressed.len(),
+            "Document compressed"
+        );
+        let mut out = Vec::with_capacity(compressed.len() + 1);
+        out.push(DOC_ENCODING_ZSTD);
+        out.extend_from_slice(&compressed);
+        Ok(out)
+    }
+}
+
+/// Decode a `doc_tree` value written by `encode_doc`.
+/// Values stored before the header byte existed are bare JSON objects and start with `{`.
+pub(crate) fn decode_doc(bytes: &[u8]) -> Result<Document, Box<dyn std::error::Error>> {
+    match bytes.first() {
+        Some(&DOC_ENCODING_RAW) => Ok(serde_json::from_slice(&bytes[1..])?),
+        Some(&DOC_ENCODING_ZSTD) => {
+            let json_bytes = zstd::decode_all(&bytes[1..])?;
+            Ok(serde_json::from_slice(&json_bytes)?)
+        }
+        Some(b'{') => {
+            debug!("Decoding legacy header-less document value");
+            Ok(serde_json::from_slice(bytes)?)
+        }
+        Some(other) => Err(format!("Unknown document encoding header: {:#04x}", other).into()),
+        None => Err("Empty document value".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use std::fs;
+
+    fn sample_doc() -> Document {
+        Document {
+            text: "vector databases store embeddings ".repeat(64),
+            metadata: serde_json::json!({"source": "test", "tags": ["a", "b", "c"]}),
+            ..test_doc("compressed_doc", vec![0.25; 16])
+        }
+    }
+
+    #[test]
+    fn compressed_doc_is_smaller_and_round_trips() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_doc_compression");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap())
+            .expect("open storage")
+            .with_doc_compression(true);
+
+        let doc = sample_doc();
+        let json_len = serde_json::to_vec(&doc).unwrap().len();
+        storage.insert_doc(doc.clone(), "col").expect("insert");
+
+        let stored = storage.doc_tree.get(b"col/compressed_doc").unwrap().unwrap();
+        assert_eq!(stored[0], DOC_ENCODING_ZSTD);
+        assert!(stored.len() < json_len, "stored {} >= json {}", stored.len(), json_len);
+
+        // Bypass the cache so the read goes through the codec
+        storage.doc_cache.lock().unwrap().remove("col/compressed_doc");
+        let read_back = storage.get_doc("col", "compressed_doc").expect("get");
+        assert_eq!(read_back.text, doc.text);
+        assert_eq!(read_back.vector, doc.vector);
+        assert_eq!(read_back.metadata, doc.metadata);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn legacy_header_less_values_still_decode() {
+        let doc = sample_doc();
+        let legacy = serde_json::to_vec(&doc).unwrap();
+        let decoded = decode_doc(&legacy).expect("legacy decode");
+        assert_eq!(decoded.id, doc.id);
+    }
+}
@@ -0,0 +1,244 @@
+//! Debounced vector-index rebuilds under write bursts
+//!
+//! Without debouncing, every write bumps the collection generation and the next search rebuilds
+//! the index, so a bulk ingest interleaved with searches rebuilds once per write. With a quiet
+//! period configured, a stale cached index keeps serving (the last-good index) until writes to
+//! the collection have settled for that long; the next search then rebuilds once. Each index's
+//! quiet period is stretched by a stable per-index jitter of up to 25% so collections ingested
+//! together don't all rebuild at the same moment, and staleness is bounded: an index older than
+//! `max_stale` is rebuilt even while writes continue.
+//!
+//! Once a rebuild does start, `RebuildWait` decides what other searches of that index get while
+//! it runs: the last-good index right away (`serve_stale`), or the fresh index once the build
+//! finishes (`wait`, optionally bounded by a timeout after which the last-good index serves).
+
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::storage::Storage;
+
+/// Last write per collection, consulted when deciding whether to defer a rebuild
+pub(crate) type WriteTimes = HashMap<String, Instant>;
+
+/// When a stale cached index may keep serving instead of being rebuilt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RebuildDebounce {
+    /// How long writes must have settled before a stale index is rebuilt (zero disables debouncing)
+    pub quiet: Duration,
+    /// Oldest a stale index may get before it is rebuilt regardless of ongoing writes
+    pub max_stale: Duration,
+}
+
+impl RebuildDebounce {
+    /// Debounce with `quiet` and staleness bounded by ten quiet periods
+    pub fn new(quiet: Duration) -> Self {
+        Self { quiet, max_stale: quiet * 10 }
+    }
+
+    /// Parse the quiet period (default 0 = rebuild on the next search) and the staleness bound
+    /// (default ten quiet periods), both in milliseconds
+    pub fn parse(quiet_ms: Option<&str>, max_stale_ms: Option<&str>) -> Self {
+        let millis = |raw: Option<&str>| {
+            raw.and_then(|raw| raw.trim().parse::<u64>().ok())
+                .map(Duration::from_millis)
+        };
+        let mut debounce = Self::new(millis(quiet_ms).unwrap_or_default());
+        if let Some(max_stale) = millis(max_stale_ms) {
+            debounce.max_stale = max_stale;
+        }
+        debounce
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.quiet.is_zero()
+    }
+
+    /// Quiet period for one index, stretched by a stable jitter in [0%, 25%) derived from its key
+    fn quiet_for(&self, cache_key: &str) -> Duration {
+        let hash = cache_key
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+        self.quiet + self.quiet.mul_f64((hash % 1000) as f64 / 4000.0)
+    }
+
+    /// Whether a stale index built `index_age` ago should keep serving, given the time since the
+    /// collection's last write (`None` when no write has been seen by this process)
+    pub(crate) fn should_defer(&self, cache_key: &str, since_last_write: Option<Duration>, index_age: Duration) -> bool {
+        match since_last_write {
+            Some(since) if self.is_enabled() => since < self.quiet_for(cache_key) && index_age < self.max_stale,
+            _ => false,
+        }
+    }
+}
+
+/// What a search gets when another search is already rebuilding the stale index it needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebuildWait {
+    /// The last-good index, without waiting (cold indexes have none, so those searches wait)
+    ServeStale,
+    /// The rebuilt index. With a `timeout`, a search still waiting when it elapses gets the
+    /// last-good index instead, or an error if there is none.
+    Wait { timeout: Option<Duration> },
+}
+
+impl Default for RebuildWait {
+    fn default() -> Self {
+        RebuildWait::Wait { timeout: None }
+    }
+}
+
+impl RebuildWait {
+    /// Parse `AIDB_REBUILD_WAIT` (`serve_stale` or `wait`, default `wait`) and
+    /// `AIDB_REBUILD_WAIT_TIMEOUT_MS` (wait mode only; unset or 0 = no limit)
+    pub fn parse(mode: Option<&str>, timeout_ms: Option<&str>) -> Self {
+        if mode.is_some_and(|raw| raw.trim().eq_ignore_ascii_case("serve_stale")) {
+            return RebuildWait::ServeStale;
+        }
+        let timeout = timeout_ms
+            .and_then(|raw| raw.trim().parse::<u64>().ok())
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis);
+        RebuildWait::Wait { timeout }
+    }
+}
+
+impl Storage {
+    /// What searches get while another search rebuilds their index (overrides AIDB_REBUILD_WAIT)
+    pub fn with_rebuild_wait(mut self, wait: RebuildWait) -> Self {
+        self.rebuild_wait = wait;
+        self
+    }
+
+    /// Debounce index rebuilds during write bursts (overrides AIDB_REBUILD_DEBOUNCE_MS / AIDB_REBUILD_MAX_STALE_MS)
+    pub fn with_rebuild_debounce(mut self, debounce: RebuildDebounce) -> Self {
+        self.rebuild_debounce = debounce;
+        self
+    }
+
+    /// Remember when `collection_id` was last written (only tracked while debouncing is enabled)
+    pub(crate) fn note_collection_write(&self, collection_id: &str) {
+        if self.rebuild_debounce.is_enabled() {
+            self.lock_write_times().insert(collection_id.to_string(), Instant::now());
+        }
+    }
+
+    /// Whether the stale cached index under `cache_key` should keep serving for now
+    pub(crate) fn defer_rebuild(&self, collection_id: &str, cache_key: &str, built: Instant) -> bool {
+        if !self.rebuild_debounce.is_enabled() {
+            return false;
+        }
+        let since_last_write = self.lock_write_times().get(collection_id).map(|t| t.elapsed());
+        let defer = self.rebuild_debounce.should_defer(cache_key, since_last_write, built.elapsed());
+        if defer {
+            debug!(cache_key = %cache_key, ?since_last_write, "Serving last-good index while writes settle");
+        }
+        defer
+    }
+
+    /// Vector indexes built by this handle so far
+    pub fn index_builds(&self) -> u64 {
+        self.index_builds.load(Ordering::Relaxed)
+    }
+
+    fn lock_write_times(&self) -> std::sync::MutexGuard<'_, WriteTimes> {
+        self.last_writes.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexing::DistanceMetric;
+    use crate::storage::flight::BuildRole;
+    use crate::storage::{test_doc, Document, DEFAULT_VECTOR_FIELD};
+    use std::fs;
+    use std::sync::Arc;
+
+    fn doc(i: usize) -> Document {
+        test_doc(&format!("doc{:02}", i), vec![i as f32, 0.0])
+    }
+
+    /// `doc(i)` far from every unparked document; rewriting it with `doc(i)` makes indexes stale
+    /// (new documents would just be added to them)
+    fn parked(i: usize) -> Document {
+        Document { vector: vec![1000.0 + i as f32, 0.0], ..doc(i) }
+    }
+
+    #[test]
+    fn write_burst_triggers_one_rebuild_after_it_settles() {
+        let path = std::env::temp_dir().join("aidb_test_rebuild_debounce");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap())
+            .unwrap()
+            .with_rebuild_debounce(RebuildDebounce::new(Duration::from_millis(400)));
+
+        // Documents after the first start out parked far away; the burst moves them into place
+        storage.insert_docs((0..20).map(|i| if i == 0 { doc(0) } else { parked(i) }).collect(), "col").unwrap();
+        assert_eq!(storage.vector_search("col", &[19.0, 0.0], 1).unwrap(), vec!["doc00"]);
+        let builds = storage.index_builds();
+
+        // Searches during the burst are served by the last-good index
+        for i in 1..20 {
+            storage.insert_doc(doc(i), "col").unwrap();
+            assert_eq!(storage.vector_search("col", &[19.0, 0.0], 1).unwrap(), vec!["doc00"]);
+        }
+        assert_eq!(storage.index_builds(), builds);
+
+        // Quiet period plus the largest jitter
+        std::thread::sleep(Duration::from_millis(550));
+        assert_eq!(storage.vector_search("col", &[0.0, 0.0], 2).unwrap(), vec!["doc00", "doc01"]);
+        assert_eq!(storage.vector_search("col", &[19.0, 0.0], 1).unwrap(), vec!["doc19"]);
+        assert_eq!(storage.index_builds(), builds + 1);
+
+        // Bounded staleness: an index older than max_stale is rebuilt despite ongoing writes
+        let bounded = RebuildDebounce { quiet: Duration::from_secs(60), max_stale: Duration::ZERO };
+        assert!(!bounded.should_defer("col/vector", Some(Duration::ZERO), Duration::from_millis(1)));
+        assert!(RebuildDebounce::new(Duration::from_secs(60)).should_defer("col/vector", Some(Duration::ZERO), Duration::ZERO));
+
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn searches_during_a_rebuild_serve_stale_or_wait_per_policy() {
+        let path = std::env::temp_dir().join("aidb_test_rebuild_wait");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap().with_rebuild_wait(RebuildWait::ServeStale);
+        let cache_key = format!("col/{}", DEFAULT_VECTOR_FIELD);
+        let rebuild = |storage: &Storage| {
+            let index = storage.build_index(storage.get_vectors_in_collection("col").unwrap(), DistanceMetric::L2);
+            (storage.collection_generation("col").unwrap(), Arc::new(index))
+        };
+
+        storage.insert_docs(vec![doc(0), parked(5), parked(9)], "col").unwrap();
+        assert_eq!(storage.vector_search("col", &[5.0, 0.0], 1).unwrap(), vec!["doc00"]);
+        storage.insert_doc(doc(5), "col").unwrap();
+
+        // Another search is rebuilding: serve_stale answers at once from the last-good index
+        let BuildRole::Leader(leader) = storage.join_index_build(&cache_key) else { panic!("no build in flight") };
+        assert_eq!(storage.vector_search("col", &[5.0, 0.0], 1).unwrap(), vec!["doc00"]);
+
+        // wait blocks until the rebuild lands and answers from the fresh index
+        let waiting = storage.clone().with_rebuild_wait(RebuildWait::Wait { timeout: Some(Duration::from_secs(10)) });
+        std::thread::scope(|scope| {
+            let search = scope.spawn(|| waiting.vector_search("col", &[5.0, 0.0], 1).unwrap());
+            std::thread::sleep(Duration::from_millis(100));
+            assert!(!search.is_finished(), "waits for the build");
+            let (generation, index) = rebuild(&storage);
+            storage.lock_index_cache().insert(cache_key.clone(), (generation, Instant::now(), index.clone()));
+            leader.complete(generation, index);
+            assert_eq!(search.join().unwrap(), vec!["doc05"]);
+        });
+
+        // A wait that times out falls back to the last-good index
+        storage.insert_doc(doc(9), "col").unwrap();
+        let _leader = storage.join_index_build(&cache_key);
+        let impatient = storage.clone().with_rebuild_wait(RebuildWait::Wait { timeout: Some(Duration::from_millis(50)) });
+        let started = Instant::now();
+        assert_eq!(impatient.vector_search("col", &[9.0, 0.0], 1).unwrap(), vec!["doc05"]);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        let _ = fs::remove_dir_all(&path);
+    }
+}
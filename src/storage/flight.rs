@@ -0,0 +1,142 @@
+//! Single-flight index builds
+//!
+//! The global `IndexBuildLimiter` bounds how many builds run at once, but N searches arriving
+//! together on a cold collection would still queue N identical builds. Builds are coordinated per
+//! index (collection and field): the first search to miss the cache becomes the leader and builds,
+//! later ones wait for it and share the index it produced. If the leader fails, or produced the
+//! index for a generation the waiter has already moved past, the waiter retries (and may lead).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::indexing::VectorIndex;
+use crate::storage::Storage;
+
+/// Builds in progress keyed by index cache key (`collection_id/field`)
+pub(crate) type BuildFlights = HashMap<String, Arc<BuildFlight>>;
+
+/// An index and the generation it was built at
+pub(crate) type BuiltIndex = (u64, Arc<VectorIndex>);
+
+/// One in-progress build that other searches can wait on
+#[derive(Default)]
+pub(crate) struct BuildFlight {
+    /// `None` while building; then the built index and its generation, or `None` if the build failed
+    outcome: Mutex<Option<Option<BuiltIndex>>>,
+    finished: Condvar,
+}
+
+impl BuildFlight {
+    /// Block until the leader finishes; `None` if its build failed
+    pub(crate) fn wait(&self) -> Option<BuiltIndex> {
+        let mut outcome = self.outcome.lock().unwrap_or_else(|p| p.into_inner());
+        while outcome.is_none() {
+            outcome = self.finished.wait(outcome).unwrap_or_else(|p| p.into_inner());
+        }
+        outcome.clone().flatten()
+    }
+
+    /// `wait` for at most `timeout` (`None` = no limit); `Err(())` if the leader is still building
+    pub(crate) fn wait_for(&self, timeout: Option<Duration>) -> Result<Option<BuiltIndex>, ()> {
+        let Some(timeout) = timeout else {
+            return Ok(self.wait());
+        };
+        let deadline = Instant::now() + timeout;
+        let mut outcome = self.outcome.lock().unwrap_or_else(|p| p.into_inner());
+        while outcome.is_none() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(());
+            }
+            outcome = self.finished.wait_timeout(outcome, left).unwrap_or_else(|p| p.into_inner()).0;
+        }
+        Ok(outcome.clone().flatten())
+    }
+}
+
+/// Held by the search building an index; dropping it (also on error) wakes the waiters and
+/// retires the flight
+pub(crate) struct FlightLeader<'a> {
+    storage: &'a Storage,
+    cache_key: String,
+    flight: Arc<BuildFlight>,
+    built: Option<BuiltIndex>,
+}
+
+impl FlightLeader<'_> {
+    /// Hand the built index to the waiters
+    pub(crate) fn complete(mut self, generation: u64, index: Arc<VectorIndex>) {
+        self.built = Some((generation, index));
+    }
+}
+
+impl Drop for FlightLeader<'_> {
+    fn drop(&mut self) {
+        self.storage.lock_build_flights().remove(&self.cache_key);
+        *self.flight.outcome.lock().unwrap_or_else(|p| p.into_inner()) = Some(self.built.take());
+        self.flight.finished.notify_all();
+    }
+}
+
+/// Either lead the build of an index or wait for the one already running
+pub(crate) enum BuildRole<'a> {
+    Leader(FlightLeader<'a>),
+    Waiter(Arc<BuildFlight>),
+}
+
+impl Storage {
+    /// Join the build of `cache_key`: lead it if none is running, otherwise wait on the running one
+    pub(crate) fn join_index_build(&self, cache_key: &str) -> BuildRole<'_> {
+        let mut flights = self.lock_build_flights();
+        if let Some(flight) = flights.get(cache_key) {
+            debug!(cache_key = %cache_key, "Waiting for in-flight index build");
+            return BuildRole::Waiter(flight.clone());
+        }
+        let flight = Arc::new(BuildFlight::default());
+        flights.insert(cache_key.to_string(), flight.clone());
+        BuildRole::Leader(FlightLeader { storage: self, cache_key: cache_key.to_string(), flight, built: None })
+    }
+
+    fn lock_build_flights(&self) -> std::sync::MutexGuard<'_, BuildFlights> {
+        self.build_flights.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{test_doc, Storage};
+    use std::fs;
+    use std::sync::{Arc, Barrier};
+
+    #[test]
+    fn simultaneous_cold_searches_build_once() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_single_flight_build");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let docs = (0..2000)
+            .map(|i| test_doc(&format!("doc{}", i), (0..16).map(|d| ((i * 31 + d * 7) % 97) as f32).collect()))
+            .collect();
+        storage.insert_docs(docs, "col").unwrap();
+
+        let searches = 8;
+        let barrier = Arc::new(Barrier::new(searches));
+        let handles: Vec<_> = (0..searches)
+            .map(|_| {
+                let storage = storage.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    storage.vector_search("col", &[0.0; 16], 3).unwrap()
+                })
+            })
+            .collect();
+        let results: Vec<Vec<String>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(storage.index_builds(), 1);
+        assert!(results.iter().all(|r| r == &results[0]));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
@@ -0,0 +1,555 @@
+//! Per-collection generation counters used to invalidate derived state (vector indexes)
+//!
+//! Every write that changes what a collection's index would contain bumps the collection's
+//! generation; cached indexes remember the generation they were built at and are rebuilt once
+//! it moves. Alongside the counter each record keeps the document count and an order-independent
+//! fingerprint of the collection's `doc_tree` entries, so `verify_generations` can detect a
+//! counter that no longer matches the data (e.g. a write that landed before a crash while its
+//! bump did not) and repair it by recomputing from the tree.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Weak};
+use std::time::Instant;
+use tracing::{info, debug, warn, instrument};
+
+use crate::indexing::{IndexBackend, VectorIndex};
+use crate::storage::flight::BuildRole;
+use crate::storage::{RebuildWait, Storage, DEFAULT_VECTOR_FIELD};
+
+/// Generation, document count and content fingerprint of one collection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct GenerationRecord {
+    generation: u64,
+    doc_count: u64,
+    fingerprint: u64,
+}
+
+impl GenerationRecord {
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 24 {
+            return None;
+        }
+        let word = |i: usize| u64::from_le_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap());
+        Some(Self { generation: word(0), doc_count: word(1), fingerprint: word(2) })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(24);
+        out.extend_from_slice(&self.generation.to_le_bytes());
+        out.extend_from_slice(&self.doc_count.to_le_bytes());
+        out.extend_from_slice(&self.fingerprint.to_le_bytes());
+        out
+    }
+}
+
+/// Change to a collection's document count and fingerprint caused by one or more doc writes
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DocDelta {
+    count: i64,
+    fingerprint: u64,
+}
+
+impl DocDelta {
+    /// Account for `key` changing from `previous` to `current` (raw `doc_tree` values)
+    pub(crate) fn record(&mut self, key: &[u8], previous: Option<&[u8]>, current: Option<&[u8]>) {
+        if let Some(previous) = previous {
+            self.count -= 1;
+            self.fingerprint ^= entry_hash(key, previous);
+        }
+        if let Some(current) = current {
+            self.count += 1;
+            self.fingerprint ^= entry_hash(key, current);
+        }
+    }
+}
+
+/// Generation and content fingerprint a write moved a collection to, and the fingerprint it
+/// moved it from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GenerationBump {
+    pub generation: u64,
+    pub fingerprint: u64,
+    pub previous_fingerprint: u64,
+}
+
+/// Stable FNV-1a hash of one `doc_tree` entry; XOR-combined so adds and removes cancel out
+pub(crate) fn entry_hash(key: &[u8], value: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.iter().chain(std::iter::once(&0xff)).chain(value) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Collection part of a `"collection_id/doc_id"` key
+fn collection_of(key: &[u8]) -> Option<&str> {
+    let slash = key.iter().position(|b| *b == b'/')?;
+    std::str::from_utf8(&key[..slash]).ok()
+}
+
+/// Outcome of `Storage::verify_generations`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GenerationReport {
+    pub collections_checked: usize,
+    /// Collections whose recorded count/fingerprint did not match `doc_tree`
+    pub drifted: Vec<String>,
+    /// Whether drifted records were rewritten (and their generation bumped)
+    pub repaired: bool,
+}
+
+/// Size and shape of one cached vector index (see `Storage::index_stats`)
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexStats {
+    pub field: String,
+    pub backend: IndexBackend,
+    pub metric: String,
+    /// Indexed vectors, including points added since the build
+    pub vectors: usize,
+    /// Points added since the build, not yet merged into the graph
+    pub pending: usize,
+    /// Estimate from `VectorIndex::approx_memory_bytes`
+    pub approx_memory_bytes: usize,
+    /// How long the last build took
+    pub build_ms: u64,
+    /// Collection generation the index was built at
+    pub generation: u64,
+}
+
+/// Write generation of one collection next to the generation its cached primary index was
+/// built at (see `Storage::index_freshness`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexFreshness {
+    pub collection_id: String,
+    /// Current generation
+    pub generation: u64,
+    /// Generation of the cached primary index; `None` until a search (or warm-up) builds it
+    pub built_generation: Option<u64>,
+    /// A cached index exists but predates the current generation, so the next search rebuilds
+    /// it (or, with rebuild debouncing, is served from it)
+    pub stale: bool,
+}
+
+/// Vector indexes keyed by `"collection_id/field"`, tagged with the generation and time they were built at
+pub(crate) type IndexCache = HashMap<String, (u64, Instant, Arc<VectorIndex>)>;
+
+impl Storage {
+    fn update_generation(&self, collection_id: &str, delta: DocDelta) -> Result<GenerationBump, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let updated = self.generation_tree.update_and_fetch(collection_id.as_bytes(), |old| {
+            let mut record = old.and_then(GenerationRecord::decode).unwrap_or_default();
+            record.generation += 1;
+            record.doc_count = (record.doc_count as i64 + delta.count).max(0) as u64;
+            record.fingerprint ^= delta.fingerprint;
+            Some(record.encode())
+        })?;
+        self.note_collection_write(collection_id);
+        let record = updated.and_then(|bytes| GenerationRecord::decode(&bytes)).unwrap_or_default();
+        Ok(GenerationBump {
+            generation: record.generation,
+            fingerprint: record.fingerprint,
+            previous_fingerprint: record.fingerprint ^ delta.fingerprint,
+        })
+    }
+
+    /// Bump the generation of the collection owning `key` after a `doc_tree` change, returning
+    /// the bump (`None` for keys outside any collection)
+    pub(crate) fn note_doc_write(
+        &self,
+        key: &str,
+        previous: Option<&[u8]>,
+        current: Option<&[u8]>,
+    ) -> Result<Option<GenerationBump>, Box<dyn std::error::Error>> {
+        let Some(collection_id) = collection_of(key.as_bytes()) else {
+            return Ok(None);
+        };
+        let mut delta = DocDelta::default();
+        delta.record(key.as_bytes(), previous, current);
+        Ok(Some(self.update_generation(collection_id, delta)?))
+    }
+
+    /// Apply the accumulated document changes of a batch with a single generation bump
+    pub(crate) fn note_doc_batch(&self, collection_id: &str, delta: DocDelta) -> Result<GenerationBump, Box<dyn std::error::Error>> {
+        self.update_generation(collection_id, delta)
+    }
+
+    /// Bump a collection's generation for changes that leave its documents as they are
+    /// (vector/metadata rewrites, repairs)
+    pub fn bump_generation(&self, collection_id: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(self.update_generation(collection_id, DocDelta::default())?.generation)
+    }
+
+    /// Bump the generation of the collection owning a `"collection_id/doc_id"` key, if any
+    pub(crate) fn bump_generation_for_key(&self, key: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(collection_id) = collection_of(key.as_bytes()) {
+            self.update_generation(collection_id, DocDelta::default())?;
+        }
+        Ok(())
+    }
+
+    /// Forget a deleted collection's documents; the generation keeps increasing so indexes
+    /// cached for an earlier collection of the same name are never reused
+    pub(crate) fn reset_generation(&self, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.generation_tree.update_and_fetch(collection_id.as_bytes(), |old| {
+            let generation = old.and_then(GenerationRecord::decode).map(|r| r.generation).unwrap_or(0);
+            Some(GenerationRecord { generation: generation + 1, doc_count: 0, fingerprint: 0 }.encode())
+        })?;
+        let prefix = format!("{}/", collection_id);
+        self.lock_index_cache().retain(|key, _| !key.starts_with(&prefix));
+        // Checkpoints and their logged deltas could never match again; drop them rather than
+        // leave them on disk
+        self.remove_index_wal(collection_id)?;
+        self.remove_index_checkpoints(collection_id)
+    }
+
+    /// Overwrite a collection's document count and fingerprint with `contents` (a delta from an
+    /// empty collection) and bump its generation. Cached indexes are dropped rather than left to
+    /// serve while a rebuild is debounced, since the contents were replaced wholesale.
+    pub(crate) fn replace_generation_contents(&self, collection_id: &str, contents: DocDelta) -> Result<(), Box<dyn std::error::Error>> {
+        self.generation_tree.update_and_fetch(collection_id.as_bytes(), |old| {
+            let generation = old.and_then(GenerationRecord::decode).map(|r| r.generation).unwrap_or(0);
+            Some(GenerationRecord {
+                generation: generation + 1,
+                doc_count: contents.count.max(0) as u64,
+                fingerprint: contents.fingerprint,
+            }.encode())
+        })?;
+        let prefix = format!("{}/", collection_id);
+        self.lock_index_cache().retain(|key, _| !key.starts_with(&prefix));
+        Ok(())
+    }
+
+    /// Current generation of a collection (0 if it has never been written)
+    pub fn collection_generation(&self, collection_id: &str) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(self.generation_tree
+            .get(collection_id.as_bytes())?
+            .and_then(|bytes| GenerationRecord::decode(&bytes))
+            .map(|r| r.generation)
+            .unwrap_or(0))
+    }
+
+    /// Current generation and content fingerprint of a collection; together they identify
+    /// exactly which documents an index built now would contain
+    pub(crate) fn collection_state(&self, collection_id: &str) -> Result<(u64, u64), Box<dyn std::error::Error>> {
+        Ok(self.generation_tree
+            .get(collection_id.as_bytes())?
+            .and_then(|bytes| GenerationRecord::decode(&bytes))
+            .map(|r| (r.generation, r.fingerprint))
+            .unwrap_or((0, 0)))
+    }
+
+    /// Collections holding at least one document, with their document counts, in ID order
+    pub fn collections_with_docs(&self) -> Result<Vec<(String, u64)>, Box<dyn std::error::Error>> {
+        let mut collections = Vec::new();
+        for item in self.generation_tree.iter() {
+            let (k, v) = item?;
+            let doc_count = GenerationRecord::decode(&v).unwrap_or_default().doc_count;
+            if doc_count > 0 {
+                collections.push((String::from_utf8_lossy(&k).to_string(), doc_count));
+            }
+        }
+        Ok(collections)
+    }
+
+    /// Generation and primary-index staleness of every collection that has been written, in ID order
+    pub fn index_freshness(&self) -> Result<Vec<IndexFreshness>, Box<dyn std::error::Error>> {
+        let mut freshness = Vec::new();
+        for item in self.generation_tree.iter() {
+            let (k, v) = item?;
+            let collection_id = String::from_utf8_lossy(&k).to_string();
+            let generation = GenerationRecord::decode(&v).unwrap_or_default().generation;
+            let built_generation = self
+                .lock_index_cache()
+                .get(&format!("{}/{}", collection_id, DEFAULT_VECTOR_FIELD))
+                .map(|(built_at, _, _)| *built_at);
+            freshness.push(IndexFreshness {
+                stale: built_generation.is_some_and(|built| built != generation),
+                collection_id,
+                generation,
+                built_generation,
+            });
+        }
+        Ok(freshness)
+    }
+
+    pub(crate) fn lock_index_cache(&self) -> std::sync::MutexGuard<'_, IndexCache> {
+        self.index_cache.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// The cached primary index of a collection, as a handle taken before a write so
+    /// `extend_primary_index` can tell whether the entry was replaced while the write ran
+    pub(crate) fn primary_index_handle(&self, collection_id: &str) -> Option<Weak<VectorIndex>> {
+        let cache_key = format!("{}/{}", collection_id, DEFAULT_VECTOR_FIELD);
+        self.lock_index_cache().get(&cache_key).map(|(_, _, index)| Arc::downgrade(index))
+    }
+
+    /// Add `points`, written by the insert that made `bump`, to the collection's cached primary
+    /// index instead of leaving the index to be rebuilt. Only done while the entry is still the
+    /// one `handle` refers to and was current right before this write (built at the generation
+    /// before `bump`); otherwise another write or a rebuild got in between and the usual
+    /// generation check decides. Points are added to a copy (which shares the built graph), so
+    /// searches holding the entry are unaffected and a merge rebuild doesn't hold the cache lock.
+    /// Extensions of checkpointed indexes are logged to the index WAL (see `storage::wal`).
+    /// Returns whether the index was extended.
+    pub(crate) fn extend_primary_index(
+        &self,
+        collection_id: &str,
+        handle: Option<Weak<VectorIndex>>,
+        bump: GenerationBump,
+        points: Vec<(String, Vec<f32>)>,
+    ) -> bool {
+        let Some(handle) = handle else {
+            return false;
+        };
+        let generation = bump.generation;
+        let cache_key = format!("{}/{}", collection_id, DEFAULT_VECTOR_FIELD);
+        let unchanged = |entry: &(u64, Instant, Arc<VectorIndex>)| {
+            entry.0 + 1 == generation && std::ptr::eq(handle.as_ptr(), Arc::as_ptr(&entry.2))
+        };
+        let mut extended = match self.lock_index_cache().get(&cache_key) {
+            Some(entry) if unchanged(entry) => VectorIndex::clone(&entry.2),
+            _ => return false,
+        };
+        let added = points.len();
+        let logged = self.is_checkpointed(&cache_key).then(|| points.clone());
+        for (id, vector) in points {
+            extended.add(id, vector);
+        }
+        {
+            let mut cache = self.lock_index_cache();
+            match cache.get_mut(&cache_key) {
+                Some(entry) if unchanged(entry) => {
+                    entry.0 = generation;
+                    entry.2 = Arc::new(extended);
+                }
+                _ => return false,
+            }
+        }
+        debug!(cache_key = %cache_key, generation = generation, added = added, "Vector index extended in place of a rebuild");
+        if let Some(points) = logged {
+            // Without the record a restart rebuilds instead of replaying; nothing is lost
+            if let Err(e) = self.log_index_delta(&cache_key, bump, points) {
+                warn!(cache_key = %cache_key, generation = generation, error = %e, "Failed to log index delta");
+            }
+        }
+        true
+    }
+
+    /// Vector index over one field of a collection, reused while the collection's generation is
+    /// unchanged (or, with rebuild debouncing, while writes to it haven't settled)
+    pub(crate) fn cached_field_index(&self, collection_id: &str, field: &str) -> Result<Arc<VectorIndex>, Box<dyn std::error::Error>> {
+        Ok(self.cached_field_index_at(collection_id, field)?.1)
+    }
+
+    /// Stats of a collection's vector indexes: the primary one (built now if no search has built
+    /// it yet) and any named-field indexes currently cached, ordered by field
+    #[instrument(skip(self))]
+    pub fn index_stats(&self, collection_id: &str) -> Result<Vec<IndexStats>, Box<dyn std::error::Error>> {
+        let (generation, primary) = self.cached_field_index_at(collection_id, DEFAULT_VECTOR_FIELD)?;
+        let prefix = format!("{}/", collection_id);
+        let mut indexes: BTreeMap<String, (u64, Arc<VectorIndex>)> = self
+            .lock_index_cache()
+            .iter()
+            .filter_map(|(key, (built_at, _, index))| Some((key.strip_prefix(&prefix)?.to_string(), (*built_at, index.clone()))))
+            .filter(|(field, _)| !field.contains('/'))
+            .collect();
+        indexes.insert(DEFAULT_VECTOR_FIELD.to_string(), (generation, primary));
+
+        Ok(indexes
+            .into_iter()
+            .map(|(field, (generation, index))| IndexStats {
+                field,
+                backend: index.backend(),
+                metric: index.metric_name().to_string(),
+                vectors: index.len(),
+                pending: index.pending_len(),
+                approx_memory_bytes: index.approx_memory_bytes(),
+                build_ms: index.build_time().as_millis() as u64,
+                generation,
+            })
+            .collect())
+    }
+
+    /// Build a collection's primary vector index from its current documents, replacing the cached
+    /// one (and any checkpoint-loaded copy); indexes of its other fields are dropped and rebuilt
+    /// on their next search
+    #[instrument(skip(self))]
+    pub fn rebuild_index(&self, collection_id: &str) -> Result<Arc<VectorIndex>, Box<dyn std::error::Error>> {
+        // Generation first, as in cached_field_index_at
+        let generation = self.collection_generation(collection_id)?;
+        let vectors = self.get_vectors_in_collection(collection_id)?;
+        let index = Arc::new(self.build_index(vectors, self.collection_metric(collection_id)?));
+
+        let prefix = format!("{}/", collection_id);
+        let mut cache = self.lock_index_cache();
+        cache.retain(|key, _| !key.starts_with(&prefix));
+        cache.insert(format!("{}{}", prefix, DEFAULT_VECTOR_FIELD), (generation, Instant::now(), index.clone()));
+        info!(collection_id = %collection_id, generation = generation, vectors = index.len(), "Vector index rebuilt");
+        Ok(index)
+    }
+
+    /// `cached_field_index` plus the generation the returned index was built at
+    pub(crate) fn cached_field_index_at(&self, collection_id: &str, field: &str) -> Result<(u64, Arc<VectorIndex>), Box<dyn std::error::Error>> {
+        let cache_key = format!("{}/{}", collection_id, field);
+        loop {
+            // Read the generation before the vectors: a write racing the build then leaves the
+            // entry tagged with an already-outdated generation instead of hiding the write
+            let generation = self.collection_generation(collection_id)?;
+            let mut last_good = None;
+            if let Some((built_at, built, index)) = self.lock_index_cache().get(&cache_key) {
+                if *built_at == generation {
+                    debug!(collection_id = %collection_id, field = %field, generation = generation, "Vector index cache hit");
+                    return Ok((*built_at, index.clone()));
+                }
+                if self.defer_rebuild(collection_id, &cache_key, *built) {
+                    return Ok((*built_at, index.clone()));
+                }
+                last_good = Some((*built_at, index.clone()));
+            }
+
+            // One build per index at a time; concurrent misses share its result or, per
+            // `RebuildWait`, keep using the last-good index while it runs
+            let leader = match self.join_index_build(&cache_key) {
+                BuildRole::Leader(leader) => leader,
+                BuildRole::Waiter(flight) => {
+                    let timeout = match (self.rebuild_wait, &last_good) {
+                        (RebuildWait::ServeStale, Some(stale)) => {
+                            debug!(cache_key = %cache_key, built_at = stale.0, "Serving last-good index during rebuild");
+                            return Ok(stale.clone());
+                        }
+                        (RebuildWait::ServeStale, None) => None,
+                        (RebuildWait::Wait { timeout }, _) => timeout,
+                    };
+                    match flight.wait_for(timeout) {
+                        Ok(Some((built_at, index))) if built_at >= generation => return Ok((built_at, index)),
+                        Ok(_) => continue,
+                        Err(()) => {
+                            warn!(cache_key = %cache_key, ?timeout, "Timed out waiting for index rebuild");
+                            return last_good.ok_or_else(|| format!("Timed out waiting for the vector index of '{}' to build", cache_key).into());
+                        }
+                    }
+                }
+            };
+            let index = match self.load_index_checkpoint(collection_id, &cache_key, generation)? {
+                Some(index) => index,
+                None => {
+                    let vectors = self.get_field_vectors_in_collection(collection_id, field)?;
+                    // Last point to give up before the build itself, which can't be interrupted
+                    crate::cancel::check()?;
+                    Arc::new(self.build_index(vectors, self.collection_metric(collection_id)?))
+                }
+            };
+            self.lock_index_cache().insert(cache_key, (generation, Instant::now(), index.clone()));
+            leader.complete(generation, index.clone());
+            return Ok((generation, index));
+        }
+    }
+
+    /// Recompute every collection's document count and fingerprint from `doc_tree` and compare
+    /// them with the recorded generation state. With `repair`, drifted records are rewritten and
+    /// their generation bumped so cached indexes are rebuilt from current data.
+    /// Writes racing the scan can show up as drift; repairing them only costs an index rebuild.
+    #[instrument(skip(self))]
+    pub fn verify_generations(&self, repair: bool) -> Result<GenerationReport, Box<dyn std::error::Error>> {
+        debug!(repair = repair, "Verifying collection generations");
+        if repair {
+            self.ensure_writable()?;
+        }
+
+        let mut actual: BTreeMap<String, DocDelta> = BTreeMap::new();
+        for item in self.doc_tree.iter() {
+            let (k, v) = item?;
+            if let Some(collection_id) = collection_of(&k) {
+                actual.entry(collection_id.to_string()).or_default().record(&k, None, Some(&v));
+            }
+        }
+        let mut recorded: BTreeMap<String, GenerationRecord> = BTreeMap::new();
+        for item in self.generation_tree.iter() {
+            let (k, v) = item?;
+            let record = GenerationRecord::decode(&v).unwrap_or_default();
+            recorded.insert(String::from_utf8_lossy(&k).to_string(), record);
+        }
+
+        let mut report = GenerationReport::default();
+        let collections: Vec<String> = actual.keys().chain(recorded.keys()).cloned().collect::<std::collections::BTreeSet<_>>().into_iter().collect();
+        for collection_id in collections {
+            report.collections_checked += 1;
+            let expected = actual.get(&collection_id).copied().unwrap_or_default();
+            let record = recorded.get(&collection_id).copied().unwrap_or_default();
+            if record.doc_count == expected.count as u64 && record.fingerprint == expected.fingerprint {
+                continue;
+            }
+            warn!(
+                collection_id = %collection_id,
+                recorded_docs = record.doc_count,
+                actual_docs = expected.count,
+                "Collection generation out of sync with stored documents"
+            );
+            if repair {
+                self.replace_generation_contents(&collection_id, expected)?;
+            }
+            report.drifted.push(collection_id);
+        }
+
+        report.repaired = repair && !report.drifted.is_empty();
+        info!(
+            collections_checked = report.collections_checked,
+            drifted = report.drifted.len(),
+            repaired = report.repaired,
+            "Generation verification completed"
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexing::ScoreKind;
+    use crate::storage::{test_doc, DEFAULT_VECTOR_FIELD};
+    use std::fs;
+
+    fn top_hit(storage: &Storage) -> String {
+        storage
+            .vector_search_in_field("gen_col", DEFAULT_VECTOR_FIELD, &[10.0, 10.0], 1, ScoreKind::Distance)
+            .unwrap()
+            .ids[0]
+            .clone()
+    }
+
+    #[test]
+    fn desynced_generation_is_repaired_and_search_sees_current_data() {
+        let path = std::env::temp_dir().join("aidb_test_generation_verify");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap();
+
+        storage.insert_doc(test_doc("a", vec![0.0, 0.0]), "gen_col").unwrap();
+        storage.insert_doc(test_doc("b", vec![1.0, 1.0]), "gen_col").unwrap();
+        storage.delete_doc("gen_col", "b").unwrap();
+        storage.insert_doc(test_doc("b", vec![1.0, 1.0]), "gen_col").unwrap();
+        assert!(storage.verify_generations(false).unwrap().drifted.is_empty());
+        assert_eq!(top_hit(&storage), "b"); // caches the index at the current generation
+
+        // A write whose generation bump was lost: data changes, the counter does not
+        let near = test_doc("near", vec![10.0, 10.0]);
+        let key = "gen_col/near";
+        let generation = storage.collection_generation("gen_col").unwrap();
+        storage.doc_tree.insert(key.as_bytes(), storage.encode_doc(&near).unwrap()).unwrap();
+        let vector_bytes: Vec<u8> = near.vector.iter().flat_map(|f| f.to_le_bytes()).collect();
+        storage.vector_tree.insert(key.as_bytes(), vector_bytes).unwrap();
+        storage.metadata_tree.insert(key.as_bytes(), vec![]).unwrap();
+        assert_eq!(storage.collection_generation("gen_col").unwrap(), generation);
+        assert_eq!(top_hit(&storage), "b", "stale cached index is still served");
+
+        let report = storage.verify_generations(true).unwrap();
+        assert_eq!(report.drifted, vec!["gen_col".to_string()]);
+        assert!(report.repaired);
+        assert!(storage.collection_generation("gen_col").unwrap() > generation);
+        assert_eq!(top_hit(&storage), "near");
+        assert!(storage.verify_generations(false).unwrap().drifted.is_empty());
+
+        drop(storage);
+        let _ = fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,272 @@
+//! In-place appends to a document's metadata arrays
+//!
+//! Appending a tag through `update_doc` means reading the document, editing it client-side and
+//! writing it back, so two concurrent appends can overwrite each other. `append_metadata_values`
+//! does the read-modify-write inside Sled with compare-and-swap: if the stored document changed
+//! between the read and the write, the append is redone against the newer version, so every
+//! concurrent append survives.
+//!
+//! `patch_metadata_where` sets fields on every document of a collection that passes a predicate,
+//! writing all of them in one Sled transaction. The transaction checks that each document is still
+//! the version the patch was computed from; if any changed in the meantime, the scan and patch are
+//! redone, so a concurrent write is never silently overwritten.
+
+use serde_json::{Map, Value};
+use sled::transaction::{ConflictableTransactionError, TransactionError};
+use tracing::{info, debug, warn, instrument};
+
+use crate::storage::codec::decode_doc;
+use crate::storage::generation::DocDelta;
+use crate::storage::nosql::now_millis;
+use crate::storage::{Document, Storage};
+
+/// Why values could not be appended to a metadata field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataAppendError {
+    /// The document's `metadata` is neither an object nor null
+    NotAnObject,
+    /// The named field exists but holds something other than an array
+    NotAnArray(String),
+}
+
+impl std::fmt::Display for MetadataAppendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotAnObject => write!(f, "Document metadata is not an object"),
+            Self::NotAnArray(field) => write!(f, "Metadata field '{}' is not an array", field),
+        }
+    }
+}
+
+impl std::error::Error for MetadataAppendError {}
+
+/// Append `values` to `metadata[field]`, creating the array when the field is missing.
+/// With `unique`, values already present (or repeated within `values`) are skipped.
+/// Returns how many values were added.
+fn append_values(doc: &mut Document, field: &str, values: &[Value], unique: bool) -> Result<usize, MetadataAppendError> {
+    if doc.metadata.is_null() {
+        doc.metadata = Value::Object(serde_json::Map::new());
+    }
+    let metadata = doc.metadata.as_object_mut().ok_or(MetadataAppendError::NotAnObject)?;
+    let array = metadata
+        .entry(field.to_string())
+        .or_insert_with(|| Value::Array(Vec::new()))
+        .as_array_mut()
+        .ok_or_else(|| MetadataAppendError::NotAnArray(field.to_string()))?;
+    let before = array.len();
+    for value in values {
+        if !unique || !array.contains(value) {
+            array.push(value.clone());
+        }
+    }
+    Ok(array.len() - before)
+}
+
+/// Times `patch_metadata_where` redoes its scan after losing a race with other writes before giving up
+const MAX_PATCH_ATTEMPTS: usize = 5;
+
+/// Outcome of `patch_metadata_where`
+#[derive(Debug, Clone, Default)]
+pub struct MetadataPatchReport {
+    /// Documents that passed the predicate
+    pub matched: usize,
+    /// The matched documents whose metadata the patch changed, as written
+    pub updated: Vec<Document>,
+}
+
+/// Apply `patch` to `target` as a JSON merge patch (RFC 7386): fields are set, `null` removes a
+/// field, and nested objects are merged rather than replaced
+fn merge_patch(target: &mut Value, patch: &Map<String, Value>) {
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Some(object) = target.as_object_mut() else {
+        return;
+    };
+    for (field, value) in patch {
+        match value {
+            Value::Null => {
+                object.remove(field);
+            }
+            Value::Object(nested) => merge_patch(object.entry(field.clone()).or_insert(Value::Null), nested),
+            _ => {
+                object.insert(field.clone(), value.clone());
+            }
+        }
+    }
+}
+
+impl Storage {
+    /// Atomically append `values` to the array `metadata[field]` of a stored document (see
+    /// `MetadataAppendError` for rejected shapes). Returns the updated document, or `None` when
+    /// it doesn't exist.
+    #[instrument(skip(self, values), fields(collection_id, doc_id, count = values.len()))]
+    pub fn append_metadata_values(
+        &self,
+        collection_id: &str,
+        id: &str,
+        field: &str,
+        values: &[Value],
+        unique: bool,
+    ) -> Result<Option<Document>, Box<dyn std::error::Error>> {
+        let _shared = self.collection_read_guard();
+        let key = format!("{}/{}", collection_id, id);
+
+        let mut attempts = 0;
+        let (previous_bytes, previous, doc, json_bytes, added) = loop {
+            attempts += 1;
+            let Some(current) = self.doc_tree.get(key.as_bytes())? else {
+                debug!(key = %key, "Document not found for metadata append");
+                return Ok(None);
+            };
+            let previous = decode_doc(&current)?;
+            let mut doc = previous.clone();
+            let added = append_values(&mut doc, field, values, unique)?;
+            doc.updated_at = Some(now_millis());
+            let json_bytes = self.encode_doc(&doc)?;
+            match self.doc_tree.compare_and_swap(key.as_bytes(), Some(&current), Some(json_bytes.as_slice()))? {
+                Ok(()) => break (current, previous, doc, json_bytes, added),
+                Err(_) => debug!(key = %key, attempts = attempts, "Document changed during metadata append, retrying"),
+            }
+        };
+        self.note_doc_write(&key, Some(&previous_bytes), Some(&json_bytes))?;
+        // Only metadata changed: field vectors stay as they are, but the tags may not
+        self.sync_tag_centroids(collection_id, Some(&doc), Some(&previous))?;
+        // Evict rather than insert, so a slower concurrent append can't leave its older version cached
+        self.lock_cache().remove(&key);
+
+        info!(key = %key, field = %field, added = added, attempts = attempts, "Metadata values appended");
+        Ok(Some(doc))
+    }
+
+    /// Merge `patch` into the metadata of every document in `collection_id` for which `matches`
+    /// holds (see `merge_patch`), in one transaction. Documents the patch leaves unchanged are not
+    /// rewritten.
+    #[instrument(skip(self, matches, patch), fields(collection_id, fields = patch.len()))]
+    pub fn patch_metadata_where(
+        &self,
+        collection_id: &str,
+        matches: impl Fn(&Document) -> bool,
+        patch: &Map<String, Value>,
+    ) -> Result<MetadataPatchReport, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let _shared = self.collection_read_guard();
+        let prefix = format!("{}/", collection_id);
+
+        let mut attempts = 0;
+        let (report, previous_docs, generation_delta) = loop {
+            attempts += 1;
+            let mut report = MetadataPatchReport::default();
+            let mut previous_docs = Vec::new();
+            // (key, stored bytes, patched bytes) for every document the patch changes
+            let mut writes = Vec::new();
+            for item in self.doc_tree.scan_prefix(prefix.as_bytes()) {
+                let (key, current) = item?;
+                let previous = decode_doc(&current)?;
+                if !matches(&previous) {
+                    continue;
+                }
+                report.matched += 1;
+                let mut doc = previous.clone();
+                merge_patch(&mut doc.metadata, patch);
+                if doc.metadata == previous.metadata {
+                    continue;
+                }
+                doc.updated_at = Some(now_millis());
+                writes.push((key, current, self.encode_doc(&doc)?));
+                previous_docs.push(previous);
+                report.updated.push(doc);
+            }
+
+            let outcome: Result<(), TransactionError<()>> = self.doc_tree.transaction(|tree| {
+                for (key, current, patched) in &writes {
+                    if tree.get(key)?.as_ref() != Some(current) {
+                        return Err(ConflictableTransactionError::Abort(()));
+                    }
+                    tree.insert(key, patched.as_slice())?;
+                }
+                Ok(())
+            });
+            match outcome {
+                Ok(()) => {
+                    let mut generation_delta = DocDelta::default();
+                    for (key, current, patched) in &writes {
+                        generation_delta.record(key, Some(current), Some(patched.as_slice()));
+                    }
+                    break (report, previous_docs, generation_delta);
+                }
+                Err(TransactionError::Abort(())) if attempts < MAX_PATCH_ATTEMPTS => {
+                    debug!(collection_id = %collection_id, attempts = attempts, "Documents changed during metadata patch, retrying");
+                }
+                Err(TransactionError::Abort(())) => {
+                    warn!(collection_id = %collection_id, attempts = attempts, "Metadata patch kept racing other writes");
+                    return Err(format!("Documents in {} kept changing during the metadata patch; retry it", collection_id).into());
+                }
+                Err(TransactionError::Storage(e)) => return Err(e.into()),
+            }
+        };
+
+        if !report.updated.is_empty() {
+            self.note_doc_batch(collection_id, generation_delta)?;
+            // Only metadata changed: field vectors stay as they are, but the tags may not
+            for (doc, previous) in report.updated.iter().zip(&previous_docs) {
+                self.sync_tag_centroids(collection_id, Some(doc), Some(previous))?;
+            }
+            let mut cache = self.lock_cache();
+            for doc in &report.updated {
+                cache.remove(&format!("{}{}", prefix, doc.id));
+            }
+        }
+
+        info!(collection_id = %collection_id, matched = report.matched, updated = report.updated.len(), attempts = attempts, "Metadata patched by query");
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn concurrent_appends_all_survive() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_metadata_append");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Arc::new(Storage::open(temp_dir.to_str().unwrap()).expect("open storage"));
+        storage.insert_doc(Document {
+            metadata: serde_json::json!({"tags": ["seed"], "title": "x"}),
+            ..test_doc("doc", vec![1.0, 0.0])
+        }, "col").unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    storage
+                        .append_metadata_values("col", "doc", "tags", &[serde_json::json!(format!("tag{}", i))], false)
+                        .unwrap()
+                        .unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let tags = storage.get_doc("col", "doc").unwrap().metadata["tags"].as_array().unwrap().clone();
+        assert_eq!(tags.len(), 9);
+        for i in 0..8 {
+            assert!(tags.contains(&serde_json::json!(format!("tag{}", i))));
+        }
+
+        // Unique skips values already present; non-arrays and missing docs are reported
+        let doc = storage.append_metadata_values("col", "doc", "tags", &[serde_json::json!("seed")], true).unwrap().unwrap();
+        assert_eq!(doc.metadata["tags"].as_array().unwrap().len(), 9);
+        let err = storage.append_metadata_values("col", "doc", "title", &[serde_json::json!("y")], false).unwrap_err();
+        assert_eq!(err.downcast_ref::<MetadataAppendError>(), Some(&MetadataAppendError::NotAnArray("title".to_string())));
+        assert!(storage.append_metadata_values("col", "missing", "tags", &[], false).unwrap().is_none());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
@@ -1,17 +1,49 @@
 use serde::{Deserialize, Serialize};
 use serde_json;
 use sled::Db;
-use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use tracing::{info, debug, warn, error, instrument};
 
 use crate::cache::DocCache;
+use crate::config::StorageConfig;
+use crate::indexing::{DistanceMetric, HnswParams, IndexBuildLimiter, VectorIndex};
 
+pub mod audit;
+pub mod cache;
+pub mod checkpoint;
+pub mod classify;
+pub mod codec;
+pub mod debounce;
+pub mod export;
+pub mod flight;
+pub mod generation;
+pub mod metadata;
 pub mod nosql;
+pub mod retry;
+pub mod self_check;
+pub mod snapshot;
 pub mod sql;
+pub mod swap;
+pub mod tags;
 pub mod vector;
+pub mod wal;
+pub mod warm;
 
-pub use vector::create_metadata_batch;
-pub use nosql::RagStorageDocument;
+pub use vector::{create_metadata_batch, l2_normalize, DimensionMismatch, InvalidVector, DEFAULT_VECTOR_FIELD};
+pub use audit::{AuditFilter, AuditPage, AuditRecord};
+pub use checkpoint::{CheckpointReport, IncompatibleIndex, IndexCheckpointPolicy, IndexImport};
+pub use classify::{Classifier, KeywordClassifier, KeywordRule};
+pub use debounce::{RebuildDebounce, RebuildWait};
+pub use generation::{GenerationReport, IndexFreshness, IndexStats};
+pub use metadata::{MetadataAppendError, MetadataPatchReport};
+pub use nosql::{BatchInsertResult, BulkDeleteResult, DeleteStatus, DocLocation, InsertStatus, RagStorageDocument};
+pub use retry::{RetryPolicy, StorageError};
+pub use self_check::{DocResync, SelfCheckReport};
+pub use snapshot::SnapshotReport;
+pub use swap::CollectionSwap;
+pub use warm::WarmReport;
 
 /// Document struct for NoSQL/JSON support
 /// Enables schema-flexible storage in Sled (Serde-serialized).
@@ -24,12 +56,53 @@ pub struct Document {
     pub category: String,  // For SQL filtering (e.g., 'AI')
     pub vector: Vec<f32>,  // Embedded vector for ANN
     pub metadata: serde_json::Value,  // Flexible JSON for extra NoSQL fields
+    /// Named embeddings (e.g. "title", "body"), each indexed separately; `vector` stays the default field
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vectors: HashMap<String, Vec<f32>>,
+    /// Milliseconds since the Unix epoch of the last insert/update; set by storage on every write
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
+    /// Provenance: where the document came from (file path or URL, optionally with a `#offset`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_uri: Option<String>,
+    /// Provenance: the ingestion job or client that wrote the document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingested_by: Option<String>,
+}
+
+impl Document {
+    /// `source_uri`, or a `source_uri` string in `metadata` for documents that recorded
+    /// provenance there before it had its own field
+    pub fn provenance_source_uri(&self) -> Option<&str> {
+        self.source_uri.as_deref().or_else(|| self.metadata.get("source_uri")?.as_str())
+    }
+
+    /// `ingested_by`, or an `ingested_by` string in `metadata` (see `provenance_source_uri`)
+    pub fn provenance_ingested_by(&self) -> Option<&str> {
+        self.ingested_by.as_deref().or_else(|| self.metadata.get("ingested_by")?.as_str())
+    }
+}
+
+/// Test document `id` with `vector`, text `"<id> text"`, category `AI` and nothing else set
+#[cfg(test)]
+pub(crate) fn test_doc(id: &str, vector: Vec<f32>) -> Document {
+    Document {
+        id: id.to_string(),
+        text: format!("{} text", id),
+        category: "AI".to_string(),
+        vector,
+        metadata: serde_json::json!({}),
+        vectors: HashMap::new(),
+        updated_at: None,
+        source_uri: None,
+        ingested_by: None,
+    }
 }
 
 #[allow(dead_code)]  // db kept for future ops like flush/close on Sled
 #[derive(Clone)]  // Clone for sharing across gRPC/REST servers (Sled internals cheap to clone)
 pub struct Storage {
-    db: Db,
+    pub(crate) db: Db,
     // Trees for unified multi-model storage:
     // - metadata/vectors: existing vector + Arrow
     // - docs: NoSQL JSON documents (Serde-serialized for schema-flexible storage)
@@ -41,12 +114,34 @@ pub struct Storage {
     pub(crate) env_tree: sled::Tree,
     pub(crate) collection_tree: sled::Tree,
     pub(crate) rag_tree: sled::Tree,  // For RAG documents and chunks
+    pub(crate) field_vector_tree: sled::Tree,  // Named embeddings keyed "collection_id/field/doc_id"
+    pub(crate) tag_centroid_tree: sled::Tree,  // Per-tag vector sums keyed "collection_id/tag"
+    pub(crate) generation_tree: sled::Tree,  // Per-collection generation counters keyed by collection_id
+    pub(crate) index_checkpoint_tree: sled::Tree,  // Serialized vector indexes keyed "collection_id/field"
+    pub(crate) index_wal_tree: sled::Tree,  // Points added to checkpointed indexes since, keyed cache key + generation
+    pub(crate) audit_tree: sled::Tree,  // Audited API calls keyed by timestamp + ID
     pub(crate) doc_cache: Arc<Mutex<DocCache>>, // In-memory cache for docs
-}
-
-fn read_cache_capacity_mb() -> usize {
-    let raw = std::env::var("AIDB_CACHE_MB").unwrap_or_else(|_| "64".to_string());
-    raw.trim().parse::<usize>().unwrap_or(64)
+    pub(crate) index_cache: Arc<Mutex<generation::IndexCache>>, // Vector indexes reused until their collection's generation moves
+    pub(crate) build_flights: Arc<Mutex<flight::BuildFlights>>, // Index builds in progress, joined by concurrent cache misses
+    pub(crate) checkpoint_policy: IndexCheckpointPolicy, // When cached indexes are persisted (AIDB_INDEX_CHECKPOINT_SECS)
+    pub(crate) checkpointed: Arc<Mutex<checkpoint::CheckpointedGenerations>>, // Generation of each index's last checkpoint
+    pub(crate) rebuild_debounce: RebuildDebounce, // Quiet period before stale indexes rebuild (AIDB_REBUILD_DEBOUNCE_MS)
+    pub(crate) last_writes: Arc<Mutex<debounce::WriteTimes>>, // Last write per collection, for rebuild debouncing
+    pub(crate) rebuild_wait: RebuildWait, // Serve the last-good index or wait while another search rebuilds it (AIDB_REBUILD_WAIT)
+    pub(crate) query_timeout: Option<std::time::Duration>, // Default hybrid query deadline (AIDB_QUERY_TIMEOUT_MS)
+    pub(crate) sql_fallback: bool, // Query engines skip DataFusion (AIDB_SQL_FALLBACK)
+    pub(crate) index_builds: Arc<AtomicU64>, // Indexes built by build_index
+    pub(crate) histogram_cache: Arc<Mutex<crate::query::histogram::HistogramCache>>, // k-distance histograms, same invalidation
+    pub(crate) doc_compression: bool, // zstd-compress doc_tree values (AIDB_DOC_COMPRESSION)
+    pub(crate) index_build_limiter: Arc<IndexBuildLimiter>, // Bounds concurrent index builds
+    pub(crate) retry_policy: RetryPolicy, // Retries transient Sled write errors
+    pub(crate) parallel_decode_threshold: usize, // Scans at least this large decode on the rayon pool
+    pub(crate) read_only: bool, // Set by open_read_only; write paths fail with StorageError::ReadOnly
+    pub(crate) vector_max_abs: Option<f32>, // Component magnitude bound (AIDB_VECTOR_MAX_ABS); NaN/Inf always rejected
+    pub(crate) default_metric: DistanceMetric, // Metric of collections without one (AIDB_DEFAULT_METRIC)
+    pub(crate) hnsw_params: HnswParams, // Graph parameters of every HNSW build (AIDB_HNSW_*)
+    pub(crate) classifier: Option<Arc<dyn Classifier>>, // Derives `category` for inserts without one (AIDB_AUTO_CATEGORY)
+    pub(crate) collection_lock: Arc<RwLock<()>>, // Shared by scans and doc writes, exclusive during swap_collections
 }
 
 impl Storage {
@@ -57,7 +152,17 @@ impl Storage {
     /// - RAG tree for RAG documents and chunks
     #[instrument(skip(path), fields(path))]
     pub fn open(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open_with_config(path, &StorageConfig { read_only: false, ..StorageConfig::from_env() })
+    }
+
+    /// Open the database at `path` with settings parsed once at startup (see `crate::config`).
+    /// `config.read_only` opens an existing database the way `open_read_only` does.
+    #[instrument(skip(path, config), fields(path))]
+    pub fn open_with_config(path: &str, config: &StorageConfig) -> Result<Self, Box<dyn std::error::Error>> {
         debug!(path = %path, "Opening storage");
+        if config.read_only && !std::path::Path::new(path).exists() {
+            return Err(format!("Cannot open missing database '{}' read-only", path).into());
+        }
         
         let db = sled::open(path)?;
         let metadata_tree = db.open_tree("metadata")?;
@@ -68,16 +173,32 @@ impl Storage {
         let env_tree = db.open_tree("environments")?;
         let collection_tree = db.open_tree("collections")?;
         let rag_tree = db.open_tree("rag")?;  // RAG documents and chunks
-        let capacity_mb = read_cache_capacity_mb();
+        let field_vector_tree = db.open_tree("field_vectors")?;  // Named embeddings per document
+        let tag_centroid_tree = db.open_tree("tag_centroids")?;  // Query expansion by metadata tags
+        let generation_tree = db.open_tree("generations")?;  // Index cache invalidation
+        let index_checkpoint_tree = db.open_tree("index_checkpoints")?;  // Indexes persisted across restarts
+        let index_wal_tree = db.open_tree("index_wal")?;  // Index deltas written after a checkpoint
+        let audit_tree = db.open_tree("audit")?;  // State-changing API calls
+        let capacity_mb = config.cache_mb;
         let capacity_bytes = capacity_mb.saturating_mul(1024).saturating_mul(1024);
+        let doc_compression = config.doc_compression;
+        let mut doc_cache = DocCache::new(capacity_bytes);
+        doc_cache.set_log_evictions(config.cache_log_evictions);
+        let max_index_builds = config.max_index_builds;
+        let retry_policy = config.write_retry;
+        let parallel_decode_threshold = config.parallel_decode_threshold;
         
         info!(
             path = %path,
             cache_capacity_mb = capacity_mb,
+            doc_compression = doc_compression,
+            max_index_builds = max_index_builds,
+            write_retries = retry_policy.max_retries,
+            read_only = config.read_only,
             "Storage opened successfully"
         );
         
-        Ok(Self {
+        let storage = Self {
             db,
             metadata_tree,
             vector_tree,
@@ -87,8 +208,97 @@ impl Storage {
             env_tree,
             collection_tree,
             rag_tree,
-            doc_cache: Arc::new(Mutex::new(DocCache::new(capacity_bytes))),
-        })
+            field_vector_tree,
+            tag_centroid_tree,
+            generation_tree,
+            index_checkpoint_tree,
+            index_wal_tree,
+            audit_tree,
+            doc_cache: Arc::new(Mutex::new(doc_cache)),
+            index_cache: Arc::new(Mutex::new(HashMap::new())),
+            build_flights: Arc::new(Mutex::new(HashMap::new())),
+            checkpoint_policy: config.index_checkpoints.clone(),
+            checkpointed: Arc::new(Mutex::new(HashMap::new())),
+            rebuild_debounce: config.rebuild_debounce,
+            last_writes: Arc::new(Mutex::new(HashMap::new())),
+            rebuild_wait: config.rebuild_wait,
+            query_timeout: config.query_timeout,
+            sql_fallback: config.sql_fallback,
+            index_builds: Arc::new(AtomicU64::new(0)),
+            histogram_cache: Arc::new(Mutex::new(HashMap::new())),
+            doc_compression,
+            index_build_limiter: Arc::new(IndexBuildLimiter::new(max_index_builds)),
+            retry_policy,
+            parallel_decode_threshold,
+            read_only: config.read_only,
+            vector_max_abs: config.vector_max_abs,
+            default_metric: config.default_metric,
+            hnsw_params: config.hnsw,
+            classifier: config.auto_category.then(|| Arc::new(KeywordClassifier::default()) as Arc<dyn Classifier>),
+            collection_lock: Arc::new(RwLock::new(())),
+        };
+        // Persisted indexes that still match their collections serve the first searches
+        if let Err(e) = storage.load_index_sidecars() {
+            warn!(error = %e, "Failed to load index sidecars; indexes will be rebuilt on demand");
+        }
+        Ok(storage)
+    }
+
+    /// Open an existing database for reads only: every write method fails with
+    /// `StorageError::ReadOnly`, so the handle can back GET/search/SQL traffic safely.
+    ///
+    /// Sled is single-process: it locks its directory, so this cannot attach to a directory
+    /// another process has open. A read replica opens its own copy (e.g. a snapshot synced from
+    /// the primary) and is refreshed by reopening it.
+    #[instrument(skip(path), fields(path))]
+    pub fn open_read_only(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::open_with_config(path, &StorageConfig { read_only: true, ..StorageConfig::from_env() })
+    }
+
+    /// Whether this handle rejects writes (see `open_read_only`)
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Deadline of hybrid queries that don't set their own (AIDB_QUERY_TIMEOUT_MS)
+    pub fn default_query_timeout(&self) -> Option<std::time::Duration> {
+        self.query_timeout
+    }
+
+    /// Limit concurrent index builds to `permits` (overrides AIDB_MAX_INDEX_BUILDS)
+    pub fn with_max_index_builds(mut self, permits: usize) -> Self {
+        self.index_build_limiter = Arc::new(IndexBuildLimiter::new(permits));
+        self
+    }
+
+    /// Decode collection scans of at least `threshold` entries in parallel (overrides AIDB_PARALLEL_DECODE_MIN)
+    pub fn with_parallel_decode_threshold(mut self, threshold: usize) -> Self {
+        self.parallel_decode_threshold = threshold;
+        self
+    }
+
+    /// Shared limiter guarding index construction
+    pub fn index_build_limiter(&self) -> &IndexBuildLimiter {
+        &self.index_build_limiter
+    }
+
+    /// Build a vector index ranking by `metric` while holding a build permit; queues when all
+    /// permits are taken
+    pub fn build_index(&self, vectors: Vec<(String, Vec<f32>)>, metric: DistanceMetric) -> VectorIndex {
+        let _permit = self.index_build_limiter.acquire();
+        self.index_builds.fetch_add(1, Ordering::Relaxed);
+        VectorIndex::build_with_params(vectors, metric, self.hnsw_params)
+    }
+
+    /// HNSW parameters indexes are built with
+    pub fn hnsw_params(&self) -> HnswParams {
+        self.hnsw_params
+    }
+
+    /// Write every tree's dirty pages to disk (Sled otherwise flushes in the background);
+    /// returns the bytes written
+    pub fn flush(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        Ok(self.db.flush()?)
     }
 }
 
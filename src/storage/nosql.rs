@@ -1,30 +1,106 @@
+use crate::storage::codec::decode_doc;
+use crate::storage::generation::{entry_hash, DocDelta};
+use crate::storage::vector::PARALLEL_DECODE_CHUNK;
+use crate::storage::vector::DimensionMismatch;
 use crate::storage::{Document, Storage};
+use rayon::prelude::*;
+use serde::Serialize;
 use serde_json;
+use std::collections::HashMap;
 use tracing::{info, debug, warn, error, instrument};
 
+/// Collections containing a given document ID (see `Storage::locate_doc`)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DocLocation {
+    pub doc_id: String,
+    pub collections: Vec<String>,
+    /// Collections checked before returning
+    pub scanned: usize,
+    /// Whether the result cap was hit before every candidate collection was checked
+    pub truncated: bool,
+}
+
+/// Per-ID outcome of `Storage::delete_docs_batch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteStatus {
+    Deleted,
+    NotFound,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BulkDeleteResult {
+    pub id: String,
+    pub status: DeleteStatus,
+}
+
+/// Per-document outcome of `Storage::insert_docs_partial`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InsertStatus {
+    Inserted,
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BatchInsertResult {
+    pub id: String,
+    pub status: InsertStatus,
+    /// Why the document was rejected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether an index rebuild would list this document under `doc_id`: `get_vectors_in_collection`
+/// takes the ID from the second `/`-separated part of the key, so IDs it would cut short (or
+/// collection IDs containing `/`) are left to a rebuild rather than added to the cached index
+fn indexable_id(collection_id: &str, doc_id: &str) -> bool {
+    !collection_id.contains('/') && !doc_id.contains('/')
+}
+
+/// Current time as milliseconds since the Unix epoch, the unit of `Document::updated_at`
+pub fn now_millis() -> u64 {
+    chrono::Utc::now().timestamp_millis().max(0) as u64
+}
+
 impl Storage {
     /// Insert a NoSQL Document (JSON via Serde) into unified Sled storage
     /// This provides schema-flexible document storage. Automatically syncs
     /// vector/metadata for indexing. Core to unified KV layer.
     #[instrument(skip(self, doc), fields(id = %doc.id, collection_id))]
-    pub fn insert_doc(&self, doc: Document, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn insert_doc(&self, mut doc: Document, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
         debug!(id = %doc.id, collection_id = %collection_id, "Inserting NoSQL document");
-        
-        // Serialize to JSON bytes for NoSQL storage in Sled
-        let json_bytes = serde_json::to_vec(&doc)?;
+        self.validate_doc_vectors(&doc)?;
+        self.check_vector_dims(collection_id, std::slice::from_ref(&doc))?;
+        self.normalize_doc_vectors(collection_id, std::slice::from_mut(&mut doc))?;
+        doc.updated_at = Some(now_millis());
+        self.classify_if_uncategorized(&mut doc);
+        let _shared = self.collection_read_guard();
+        
+        // Serialize to JSON bytes for NoSQL storage in Sled (header byte + optional zstd)
+        let json_bytes = self.encode_doc(&doc)?;
         let key = format!("{}/{}", collection_id, doc.id);
+        let index = self.primary_index_handle(collection_id);
 
         // Store raw JSON doc (NoSQL)
-        self.doc_tree.insert(key.as_bytes(), json_bytes)?;
+        let previous = self.retry_write("insert_doc", || self.doc_tree.insert(key.as_bytes(), json_bytes.as_slice()))?;
 
         // Sync to existing vector/Arrow for compatibility (hybrid link)
         let metadata_batch = crate::storage::create_metadata_batch(&doc.id, &doc.text)?;
-        self.insert(&key, metadata_batch, doc.vector.clone())?;  // Reuses vector storage
+        self.write_vector_entry(&key, metadata_batch, &doc.vector)?;  // Reuses vector storage
+        let bump = self.note_doc_write(&key, previous.as_deref(), Some(&json_bytes))?;
+        // A new document only adds a point, so the cached index can take it instead of a rebuild
+        if let (None, Some(bump)) = (&previous, bump) {
+            if indexable_id(collection_id, &doc.id) {
+                self.extend_primary_index(collection_id, index, bump, vec![(doc.id.clone(), doc.vector.clone())]);
+            }
+        }
+        let previous = previous.and_then(|bytes| decode_doc(&bytes).ok());
+        self.sync_field_vectors(collection_id, &doc, previous.as_ref())?;
+        self.sync_tag_centroids(collection_id, Some(&doc), previous.as_ref())?;
 
         // Update cache
-        if let Ok(mut cache) = self.doc_cache.lock() {
-            cache.insert(key.clone(), doc.clone());
-        }
+        self.lock_cache().insert(key.clone(), doc.clone());
         
         info!(id = %doc.id, collection_id = %collection_id, "NoSQL document inserted successfully");
         Ok(())
@@ -32,17 +108,41 @@ impl Storage {
 
     /// Insert multiple NoSQL Documents (batch) into unified Sled storage
     #[instrument(skip(self, docs), fields(count = docs.len(), collection_id))]
-    pub fn insert_docs(&self, docs: Vec<Document>, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn insert_docs(&self, mut docs: Vec<Document>, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
         debug!(count = docs.len(), collection_id = %collection_id, "Inserting batch of NoSQL documents");
+        self.check_vector_dims(collection_id, &docs)?;
+        self.normalize_doc_vectors(collection_id, &mut docs)?;
+        let _shared = self.collection_read_guard();
+        let updated_at = now_millis();
+        for doc in &mut docs {
+            doc.updated_at = Some(updated_at);
+            self.classify_if_uncategorized(doc);
+        }
         
         let mut doc_batch = sled::Batch::default();
         let mut metadata_batch_op = sled::Batch::default();
         let mut vector_batch = sled::Batch::default();
+        let mut previous_docs = Vec::with_capacity(docs.len());
+        // Raw values written earlier in this batch, so repeated IDs diff against the right version
+        let mut batch_values: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut generation_delta = DocDelta::default();
+        let index = self.primary_index_handle(collection_id);
+        let mut only_new_docs = true;
 
         for doc in &docs {
-            let json_bytes = serde_json::to_vec(doc)?;
+            self.validate_doc_vectors(doc)?;
+            let json_bytes = self.encode_doc(doc)?;
             let key = format!("{}/{}", collection_id, doc.id);
-            doc_batch.insert(key.as_bytes(), json_bytes);
+            doc_batch.insert(key.as_bytes(), json_bytes.as_slice());
+            // Previous version, so named vectors it had but this one drops get removed
+            let previous = match batch_values.get(&key) {
+                Some(bytes) => Some(bytes.clone()),
+                None => self.doc_tree.get(key.as_bytes())?.map(|bytes| bytes.to_vec()),
+            };
+            generation_delta.record(key.as_bytes(), previous.as_deref(), Some(&json_bytes));
+            only_new_docs &= previous.is_none() && indexable_id(collection_id, &doc.id);
+            previous_docs.push(previous.and_then(|bytes| decode_doc(&bytes).ok()));
+            batch_values.insert(key.clone(), json_bytes);
 
             // Sync to vector/Arrow
             let metadata_batch = crate::storage::create_metadata_batch(&doc.id, &doc.text)?;
@@ -66,24 +166,88 @@ impl Storage {
         }
 
         // Apply batches
-        self.doc_tree.apply_batch(doc_batch)?;
-        self.metadata_tree.apply_batch(metadata_batch_op)?;
-        self.vector_tree.apply_batch(vector_batch)?;
+        self.retry_write("insert_docs", || self.doc_tree.apply_batch(doc_batch.clone()))?;
+        self.retry_write("insert_docs", || self.metadata_tree.apply_batch(metadata_batch_op.clone()))?;
+        self.retry_write("insert_docs", || self.vector_tree.apply_batch(vector_batch.clone()))?;
+        let bump = self.note_doc_batch(collection_id, generation_delta)?;
+        if only_new_docs {
+            let points = docs.iter().map(|doc| (doc.id.clone(), doc.vector.clone())).collect();
+            self.extend_primary_index(collection_id, index, bump, points);
+        }
+        for (doc, previous) in docs.iter().zip(&previous_docs) {
+            self.sync_field_vectors(collection_id, doc, previous.as_ref())?;
+            self.sync_tag_centroids(collection_id, Some(doc), previous.as_ref())?;
+        }
 
         let docs_len = docs.len();
 
         // Update cache
-        if let Ok(mut cache) = self.doc_cache.lock() {
-            for doc in docs {
-                let key = format!("{}/{}", collection_id, doc.id);
-                cache.insert(key, doc);
-            }
+        let mut cache = self.lock_cache();
+        for doc in docs {
+            let key = format!("{}/{}", collection_id, doc.id);
+            cache.insert(key, doc);
         }
         
         info!(count = docs_len, collection_id = %collection_id, "Batch insertion successful");
         Ok(())
     }
 
+    /// Insert a batch, skipping documents that fail validation instead of failing the whole
+    /// batch: a missing ID, invalid vector values, or a vector dimension that differs from the
+    /// collection's (or, for the first vector that sets it, from earlier ones in the batch). The
+    /// accepted documents go in with one `insert_docs` call. Results are in input order; an
+    /// `Err` means a storage failure, not a bad document.
+    #[instrument(skip(self, docs), fields(count = docs.len(), collection_id))]
+    pub fn insert_docs_partial(&self, docs: Vec<Document>, collection_id: &str) -> Result<Vec<BatchInsertResult>, Box<dyn std::error::Error>> {
+        let mut results = Vec::with_capacity(docs.len());
+        let mut accepted = Vec::with_capacity(docs.len());
+        let mut batch_dim: Option<usize> = None;
+        for doc in docs {
+            let rejection = if doc.id.is_empty() {
+                Some("missing document id".to_string())
+            } else if let Err(e) = self.validate_doc_vectors(&doc) {
+                Some(e.to_string())
+            } else if doc.vector.is_empty() {
+                None
+            } else if let Some(expected) = batch_dim {
+                (doc.vector.len() != expected).then(|| DimensionMismatch {
+                    collection_id: collection_id.to_string(),
+                    doc_id: doc.id.clone(),
+                    expected,
+                    actual: doc.vector.len(),
+                }.to_string())
+            } else {
+                match self.check_vector_dims(collection_id, std::slice::from_ref(&doc)) {
+                    Ok(()) => {
+                        batch_dim = Some(doc.vector.len());
+                        None
+                    }
+                    Err(e) if e.downcast_ref::<DimensionMismatch>().is_some() => Some(e.to_string()),
+                    Err(e) => return Err(e),
+                }
+            };
+            match rejection {
+                Some(error) => {
+                    debug!(doc_id = %doc.id, error = %error, "Rejected document in batch");
+                    results.push(BatchInsertResult { id: doc.id, status: InsertStatus::Rejected, error: Some(error) });
+                }
+                None => {
+                    results.push(BatchInsertResult { id: doc.id.clone(), status: InsertStatus::Inserted, error: None });
+                    accepted.push(doc);
+                }
+            }
+        }
+
+        let rejected = results.len() - accepted.len();
+        if !accepted.is_empty() {
+            self.insert_docs(accepted, collection_id)?;
+        }
+        if rejected > 0 {
+            warn!(rejected = rejected, total = results.len(), collection_id = %collection_id, "Batch insert skipped invalid documents");
+        }
+        Ok(results)
+    }
+
     /// Retrieve NoSQL Document by ID (deserializes JSON from Sled)
     /// Enables dynamic/unstructured access.
     #[instrument(skip(self), fields(key))]
@@ -102,19 +266,15 @@ impl Storage {
         key: &str,
     ) -> Result<(Document, bool), Box<dyn std::error::Error>> {
         // Check cache first
-        if let Ok(mut cache) = self.doc_cache.lock() {
-            if let Some(doc) = cache.get(key) {
-                debug!(key = %key, "Document served from cache");
-                return Ok((doc, true));
-            }
+        if let Some(doc) = self.lock_cache().get(key) {
+            debug!(key = %key, "Document served from cache");
+            return Ok((doc, true));
         }
 
         // Fetch from storage
         if let Some(doc_bytes) = self.doc_tree.get(key.as_bytes())? {
-            let doc: Document = serde_json::from_slice(&doc_bytes)?;
-            if let Ok(mut cache) = self.doc_cache.lock() {
-                cache.insert(key.to_string(), doc.clone());
-            }
+            let doc = decode_doc(&doc_bytes)?;
+            self.lock_cache().insert(key.to_string(), doc.clone());
             debug!(key = %key, "Document retrieved from storage");
             Ok((doc, false))
         } else {
@@ -123,18 +283,100 @@ impl Storage {
         }
     }
 
+    /// Document plus its ETag: a quoted hash of the stored value, so every write (which also
+    /// restamps `updated_at`) changes it. Reads Sled directly rather than the cache so the body
+    /// and the tag always come from the same bytes.
+    #[instrument(skip(self))]
+    pub fn get_doc_with_etag(&self, collection_id: &str, id: &str) -> Result<(Document, String), Box<dyn std::error::Error>> {
+        let key = format!("{}/{}", collection_id, id);
+        let doc_bytes = self.doc_tree.get(key.as_bytes())?.ok_or("Document not found")?;
+        let doc = decode_doc(&doc_bytes)?;
+        let etag = format!("\"{:016x}\"", entry_hash(key.as_bytes(), &doc_bytes));
+        debug!(key = %key, etag = %etag, "Document retrieved with ETag");
+        Ok((doc, etag))
+    }
+
+    /// Find which of `collections` hold a document with ID `doc_id` (key `collection/doc_id`).
+    /// Stops after `max_results` matches; `truncated` reports whether it stopped early.
+    #[instrument(skip(self, collections), fields(candidates = collections.len()))]
+    pub fn locate_doc(
+        &self,
+        doc_id: &str,
+        collections: &[String],
+        max_results: usize,
+    ) -> Result<DocLocation, Box<dyn std::error::Error>> {
+        let mut location = DocLocation { doc_id: doc_id.to_string(), ..DocLocation::default() };
+        for collection_id in collections {
+            if location.collections.len() >= max_results {
+                location.truncated = true;
+                break;
+            }
+            location.scanned += 1;
+            if self.doc_tree.contains_key(format!("{}/{}", collection_id, doc_id).as_bytes())? {
+                location.collections.push(collection_id.clone());
+            }
+        }
+        debug!(doc_id = %doc_id, found = location.collections.len(), scanned = location.scanned, "Document located");
+        Ok(location)
+    }
+
     /// Get all NoSQL docs (for hybrid planner/indexing)
     #[instrument(skip(self))]
     pub fn get_docs_in_collection(&self, collection_id: &str) -> Result<Vec<Document>, Box<dyn std::error::Error>> {
         debug!(collection_id = %collection_id, "Retrieving all documents in collection");
-        let mut docs = vec![];
         let prefix = format!("{}/", collection_id);
-        for item in self.doc_tree.scan_prefix(prefix.as_bytes()) {
-            let (_, v) = item?;
-            let doc: Document = serde_json::from_slice(&v)?;
-            docs.push(doc);
+        let _shared = self.collection_read_guard();
+        let values = self.doc_tree
+            .scan_prefix(prefix.as_bytes())
+            .values()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let parallel = values.len() >= self.parallel_decode_threshold;
+        let docs = if parallel {
+            values
+                .par_iter()
+                .with_min_len(PARALLEL_DECODE_CHUNK)
+                .map(|v| decode_doc(v).map_err(|e| e.to_string()))
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            values.iter().map(|v| decode_doc(v)).collect::<Result<Vec<_>, _>>()?
+        };
+        info!(collection_id = %collection_id, count = docs.len(), parallel = parallel, "Documents retrieved");
+        Ok(docs)
+    }
+
+    /// Number of documents in a collection, counted from `doc_tree` keys without reading or
+    /// decoding any document
+    #[instrument(skip(self))]
+    pub fn count_docs(&self, collection_id: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let prefix = format!("{}/", collection_id);
+        let _shared = self.collection_read_guard();
+        let mut count = 0;
+        for key in self.doc_tree.scan_prefix(prefix.as_bytes()).keys() {
+            key?;
+            count += 1;
         }
-        info!(collection_id = %collection_id, count = docs.len(), "Documents retrieved");
+        debug!(collection_id = %collection_id, count = count, "Documents counted");
+        Ok(count)
+    }
+
+    /// Documents whose `updated_at` lies within `[from, to]` (milliseconds since the epoch; either
+    /// bound may be open). Documents written before timestamps existed have none and never match.
+    #[instrument(skip(self))]
+    pub fn get_docs_updated_between(
+        &self,
+        collection_id: &str,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<Vec<Document>, Box<dyn std::error::Error>> {
+        let docs: Vec<Document> = self
+            .get_docs_in_collection(collection_id)?
+            .into_iter()
+            .filter(|doc| {
+                doc.updated_at.is_some_and(|t| from.is_none_or(|f| t >= f) && to.is_none_or(|u| t <= u))
+            })
+            .collect();
+        debug!(collection_id = %collection_id, ?from, ?to, count = docs.len(), "Documents filtered by update time");
         Ok(docs)
     }
 
@@ -192,23 +434,30 @@ impl Storage {
     /// Update NoSQL Document by ID (upsert JSON in Sled ; syncs metadata/vector)
     /// For edit capability in NoSQL layer.
     #[instrument(skip(self, doc), fields(id = %doc.id, collection_id))]
-    pub fn update_doc(&self, doc: Document, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn update_doc(&self, mut doc: Document, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
         debug!(id = %doc.id, collection_id = %collection_id, "Updating NoSQL document");
+        self.validate_doc_vectors(&doc)?;
+        self.check_vector_dims(collection_id, std::slice::from_ref(&doc))?;
+        self.normalize_doc_vectors(collection_id, std::slice::from_mut(&mut doc))?;
+        doc.updated_at = Some(now_millis());
+        let _shared = self.collection_read_guard();
         
         // Serialize updated JSON
-        let json_bytes = serde_json::to_vec(&doc)?;
+        let json_bytes = self.encode_doc(&doc)?;
         let key = format!("{}/{}", collection_id, doc.id);
 
         // Upsert in doc_tree (NoSQL)
-        self.doc_tree.insert(key.as_bytes(), json_bytes)?;
+        let previous = self.retry_write("update_doc", || self.doc_tree.insert(key.as_bytes(), json_bytes.as_slice()))?;
+        self.note_doc_write(&key, previous.as_deref(), Some(&json_bytes))?;
 
         // Sync to Arrow/metadata + vector trees for SQL/index consistency
         let metadata_batch = crate::storage::create_metadata_batch(&doc.id, &doc.text)?;
         self.insert(&key, metadata_batch, doc.vector.clone())?;
+        let previous = previous.and_then(|bytes| decode_doc(&bytes).ok());
+        self.sync_field_vectors(collection_id, &doc, previous.as_ref())?;
+        self.sync_tag_centroids(collection_id, Some(&doc), previous.as_ref())?;
 
-        if let Ok(mut cache) = self.doc_cache.lock() {
-            cache.insert(key, doc.clone());
-        }
+        self.lock_cache().insert(key, doc.clone());
         
         info!(id = %doc.id, collection_id = %collection_id, "Document updated successfully");
         Ok(())
@@ -218,24 +467,84 @@ impl Storage {
     #[instrument(skip(self), fields(collection_id, doc_id))]
     pub fn delete_doc(&self, collection_id: &str, id: &str) -> Result<(), Box<dyn std::error::Error>> {
         debug!(collection_id = %collection_id, doc_id = %id, "Deleting document");
+        let _shared = self.collection_read_guard();
         
         let key = format!("{}/{}", collection_id, id);
-        self.doc_tree.remove(key.as_bytes())?;
-        self.metadata_tree.remove(key.as_bytes())?;
-        self.vector_tree.remove(key.as_bytes())?;
-        
-        if let Ok(mut cache) = self.doc_cache.lock() {
-            cache.remove(&key);
+        let previous = self.retry_write("delete_doc", || self.doc_tree.remove(key.as_bytes()))?;
+        self.note_doc_write(&key, previous.as_deref(), None)?;
+        self.retry_write("delete_doc", || self.metadata_tree.remove(key.as_bytes()))?;
+        self.retry_write("delete_doc", || self.vector_tree.remove(key.as_bytes()))?;
+        if let Some(previous) = previous.and_then(|bytes| decode_doc(&bytes).ok()) {
+            self.remove_field_vectors(collection_id, &previous)?;
+            self.sync_tag_centroids(collection_id, None, Some(&previous))?;
         }
         
+        self.lock_cache().remove(&key);
+        
         info!(key = %key, "Document deleted successfully");
         Ok(())
     }
 
+    /// Delete many documents by ID with one Sled batch per tree. Returns each ID's status in request
+    /// order (a repeated ID is `NotFound` after its first occurrence); caches are evicted and the
+    /// collection generation is bumped once for the whole batch.
+    #[instrument(skip(self, ids), fields(collection_id, count = ids.len()))]
+    pub fn delete_docs_batch(
+        &self,
+        collection_id: &str,
+        ids: &[String],
+    ) -> Result<Vec<BulkDeleteResult>, Box<dyn std::error::Error>> {
+        debug!(collection_id = %collection_id, count = ids.len(), "Bulk deleting documents");
+        self.ensure_writable()?;
+        let _shared = self.collection_read_guard();
+
+        let mut key_batch = sled::Batch::default();
+        let mut generation_delta = DocDelta::default();
+        let mut removed_keys = Vec::new();
+        let mut removed_docs = Vec::new();
+        let mut results = Vec::with_capacity(ids.len());
+        let mut seen = std::collections::HashSet::new();
+        for id in ids {
+            let key = format!("{}/{}", collection_id, id);
+            let previous = if seen.insert(key.clone()) { self.doc_tree.get(key.as_bytes())? } else { None };
+            let status = match previous {
+                Some(bytes) => {
+                    key_batch.remove(key.as_bytes());
+                    generation_delta.record(key.as_bytes(), Some(&bytes), None);
+                    removed_docs.extend(decode_doc(&bytes).ok());
+                    removed_keys.push(key);
+                    DeleteStatus::Deleted
+                }
+                None => DeleteStatus::NotFound,
+            };
+            results.push(BulkDeleteResult { id: id.clone(), status });
+        }
+
+        if !removed_keys.is_empty() {
+            self.retry_write("delete_docs_batch", || self.doc_tree.apply_batch(key_batch.clone()))?;
+            self.retry_write("delete_docs_batch", || self.metadata_tree.apply_batch(key_batch.clone()))?;
+            self.retry_write("delete_docs_batch", || self.vector_tree.apply_batch(key_batch.clone()))?;
+            self.note_doc_batch(collection_id, generation_delta)?;
+            for previous in &removed_docs {
+                self.remove_field_vectors(collection_id, previous)?;
+                self.sync_tag_centroids(collection_id, None, Some(previous))?;
+            }
+            let mut cache = self.lock_cache();
+            for key in &removed_keys {
+                cache.remove(key);
+            }
+        }
+
+        info!(collection_id = %collection_id, requested = ids.len(), deleted = removed_keys.len(), "Bulk delete completed");
+        Ok(results)
+    }
+
     /// Delete an entire collection and its documents
     #[instrument(skip(self), fields(env_id, col_id))]
     pub fn delete_collection(&self, env_id: &str, col_id: &str) -> Result<(), Box<dyn std::error::Error>> {
         debug!(env_id = %env_id, col_id = %col_id, "Deleting collection");
+        self.ensure_writable()?;
+        let _shared = self.collection_read_guard();
         
         // 1. Remove all docs in collection from doc_tree, metadata_tree, vector_tree
         let prefix = format!("{}/", col_id);
@@ -249,13 +558,21 @@ impl Storage {
 
             // Cleanup cache if needed
             if let Ok(k_str) = String::from_utf8(k.to_vec()) {
-                if let Ok(mut cache) = self.doc_cache.lock() {
-                    cache.remove(&k_str);
-                }
+                self.lock_cache().remove(&k_str);
             }
             deleted_count += 1;
         }
 
+        // Named vectors and tag centroids share the "col_id/" prefix
+        for tree in [&self.field_vector_tree, &self.tag_centroid_tree] {
+            for item in tree.scan_prefix(prefix.as_bytes()) {
+                let (k, _) = item?;
+                tree.remove(&k)?;
+            }
+        }
+
+        self.reset_generation(col_id)?;
+
         // 2. Remove collection metadata
         self.collection_tree.remove(col_id.as_bytes())?;
 
@@ -304,6 +621,7 @@ impl Storage {
         collection_id: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
         debug!(id = %doc.id, collection_id = %collection_id, "Inserting RAG document");
+        self.ensure_writable()?;
         
         // Serialize to JSON
         let json_bytes = serde_json::to_vec(doc)?;
@@ -326,6 +644,10 @@ impl Storage {
                 "created_at": doc.created_at,
                 "custom": doc.metadata,
             }),
+            vectors: HashMap::new(),
+            updated_at: None,
+            source_uri: None,
+            ingested_by: None,
         };
         self.insert_doc(storage_doc, collection_id)?;
         
@@ -395,6 +717,8 @@ impl Storage {
         doc_id: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
         debug!(collection_id = %collection_id, doc_id = %doc_id, "Deleting RAG document");
+        self.ensure_writable()?;
+        let _shared = self.collection_read_guard();
         
         // Get all chunks first
         let chunks = self.get_rag_doc_chunks(collection_id, doc_id)?;
@@ -407,14 +731,13 @@ impl Storage {
             self.rag_tree.remove(key.as_bytes())?;
             
             // Also delete from doc_tree and vector_tree
-            self.doc_tree.remove(key.as_bytes())?;
+            let previous = self.doc_tree.remove(key.as_bytes())?;
+            self.note_doc_write(&key, previous.as_deref(), None)?;
             self.metadata_tree.remove(key.as_bytes())?;
             self.vector_tree.remove(key.as_bytes())?;
             
             // Remove from cache
-            if let Ok(mut cache) = self.doc_cache.lock() {
-                cache.remove(&key);
-            }
+            self.lock_cache().remove(&key);
         }
         
         info!(collection_id = %collection_id, doc_id = %doc_id, chunks_deleted = deleted_count, "RAG document deleted");
@@ -484,3 +807,103 @@ impl Storage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use std::fs;
+
+    #[test]
+    fn count_docs_counts_only_the_collections_own_keys() {
+        let path = std::env::temp_dir().join("aidb_test_count_docs");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap();
+        let doc = |id: &str| test_doc(id, vec![1.0, 0.0]);
+        storage.insert_docs(vec![doc("a"), doc("b"), doc("c")], "col").unwrap();
+        // A collection whose name extends "col" and other trees' entries aren't counted
+        storage.insert_doc(doc("x"), "col2").unwrap();
+        storage.insert_doc(doc("a"), "col").unwrap();
+        assert_eq!(storage.count_docs("col").unwrap(), 3);
+        assert_eq!(storage.count_docs("col2").unwrap(), 1);
+        assert_eq!(storage.count_docs("missing").unwrap(), 0);
+
+        storage.delete_doc("col", "b").unwrap();
+        assert_eq!(storage.count_docs("col").unwrap(), 2);
+
+        let _ = fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn bulk_delete_reports_each_id_and_bumps_generation_once() {
+        let path = std::env::temp_dir().join("aidb_test_bulk_delete");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap();
+        let docs = (0..4)
+            .map(|i| Document {
+                text: format!("doc {}", i),
+                metadata: serde_json::json!({"tags": ["t"]}),
+                vectors: HashMap::from([("title".to_string(), vec![i as f32])]),
+                ..test_doc(&format!("d{}", i), vec![i as f32, 1.0])
+            })
+            .collect();
+        storage.insert_docs(docs, "col").unwrap();
+        assert_eq!(storage.get_doc("col", "d1").unwrap().text, "doc 1");
+        let before = storage.collection_generation("col").unwrap();
+
+        let ids: Vec<String> = ["d1", "missing", "d3", "d1"].iter().map(|s| s.to_string()).collect();
+        let results = storage.delete_docs_batch("col", &ids).unwrap();
+        let statuses: Vec<DeleteStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![DeleteStatus::Deleted, DeleteStatus::NotFound, DeleteStatus::Deleted, DeleteStatus::NotFound]);
+        assert_eq!(storage.collection_generation("col").unwrap(), before + 1);
+
+        assert!(storage.get_doc("col", "d1").is_err(), "cached copy evicted");
+        let remaining: Vec<String> = storage.get_vectors_in_collection("col").unwrap().into_iter().map(|(id, _)| id).collect();
+        assert_eq!(remaining, vec!["d0", "d2"]);
+        assert_eq!(storage.get_field_vectors_in_collection("col", "title").unwrap().len(), 2);
+        assert!(storage.verify_generations(false).unwrap().drifted.is_empty());
+
+        // Nothing to delete leaves the generation alone
+        storage.delete_docs_batch("col", &["missing".to_string()]).unwrap();
+        assert_eq!(storage.collection_generation("col").unwrap(), before + 1);
+
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn new_documents_extend_the_cached_index_instead_of_rebuilding_it() {
+        let path = std::env::temp_dir().join("aidb_test_incremental_insert");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap();
+        let point = |i: usize| crate::storage::test_doc(&format!("d{}", i), vec![i as f32, 1.0]);
+        storage.insert_docs((0..crate::indexing::FLAT_INDEX_THRESHOLD + 10).map(point).collect(), "col").unwrap();
+        let search = |v: &[f32]| storage.vector_search("col", v, 1).unwrap();
+        assert_eq!(search(&[3.0, 1.0]), vec!["d3"]);
+        assert_eq!(storage.index_builds(), 1);
+
+        // New documents, alone or in a batch, are searchable without a rebuild
+        storage.insert_doc(crate::storage::test_doc("single", vec![-50.0, 1.0]), "col").unwrap();
+        storage.insert_docs(vec![point(1_000), point(2_000)], "col").unwrap();
+        assert_eq!(search(&[-49.0, 1.0]), vec!["single"]);
+        assert_eq!(search(&[1_999.0, 1.0]), vec!["d2000"]);
+        assert_eq!(storage.index_builds(), 1);
+
+        // A replaced document isn't just an added point, so the next search rebuilds
+        storage.insert_doc(crate::storage::test_doc("d3", vec![3.0, 2.0]), "col").unwrap();
+        search(&[3.0, 2.0]);
+        assert_eq!(storage.index_builds(), 2);
+        storage.insert_docs(vec![point(3_000), point(4)], "col").unwrap();
+        search(&[3.0, 2.0]);
+        assert_eq!(storage.index_builds(), 3);
+
+        // The extended index holds exactly what a rebuild would
+        storage.insert_doc(point(5_000), "col").unwrap();
+        assert_eq!(search(&[4_999.0, 1.0]), vec!["d5000"]);
+        let index = storage.cached_field_index("col", crate::storage::DEFAULT_VECTOR_FIELD).unwrap();
+        assert_eq!(storage.index_builds(), 3);
+        index.check_contents(&storage.get_vectors_in_collection("col").unwrap()).unwrap();
+
+        drop(storage);
+        let _ = fs::remove_dir_all(&path);
+    }
+}
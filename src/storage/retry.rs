@@ -0,0 +1,221 @@
+use std::fmt;
+use std::io::ErrorKind;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::storage::Storage;
+
+/// Retries after the first attempt when `AIDB_WRITE_RETRIES` is unset
+pub const DEFAULT_WRITE_RETRIES: u32 = 3;
+/// Delay before the first retry when `AIDB_WRITE_RETRY_BASE_MS` is unset
+pub const DEFAULT_WRITE_RETRY_BASE_MS: u64 = 10;
+/// Upper bound on a single backoff delay
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Storage failure classified by whether retrying can help
+#[derive(Debug)]
+pub enum StorageError {
+    /// Likely to succeed on retry (interrupted/timed-out I/O, e.g. while Sled compacts)
+    Transient(sled::Error),
+    /// Retrying will not help (corruption, unsupported operation, missing tree, ...)
+    Permanent(sled::Error),
+    /// The storage was opened with `Storage::open_read_only`
+    ReadOnly,
+}
+
+impl StorageError {
+    pub fn classify(err: sled::Error) -> Self {
+        let transient = match &err {
+            sled::Error::Io(io) => matches!(
+                io.kind(),
+                ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut
+            ),
+            _ => false,
+        };
+        if transient {
+            StorageError::Transient(err)
+        } else {
+            StorageError::Permanent(err)
+        }
+    }
+
+    pub fn is_transient(&self) -> bool {
+        matches!(self, StorageError::Transient(_))
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Transient(e) => write!(f, "transient storage error: {}", e),
+            StorageError::Permanent(e) => write!(f, "storage error: {}", e),
+            StorageError::ReadOnly => write!(f, "storage is opened read-only"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StorageError::Transient(e) | StorageError::Permanent(e) => Some(e),
+            StorageError::ReadOnly => None,
+        }
+    }
+}
+
+/// How many times, and how patiently, transient write failures are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 disables retrying)
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled on each further retry up to one second
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_WRITE_RETRIES,
+            base_delay: Duration::from_millis(DEFAULT_WRITE_RETRY_BASE_MS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Parse the retry count and base delay in milliseconds (invalid values keep the defaults)
+    pub fn parse(retries: Option<&str>, base_ms: Option<&str>) -> Self {
+        let number = |raw: Option<&str>| raw.and_then(|raw| raw.trim().parse::<u64>().ok());
+        Self {
+            max_retries: number(retries)
+                .map(|n| n.min(u32::MAX as u64) as u32)
+                .unwrap_or(DEFAULT_WRITE_RETRIES),
+            base_delay: Duration::from_millis(number(base_ms).unwrap_or(DEFAULT_WRITE_RETRY_BASE_MS)),
+        }
+    }
+
+    fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_RETRY_DELAY)
+    }
+
+    /// Run `op`, retrying transient failures with exponential backoff until the budget runs out
+    pub fn run<T>(&self, op_name: &str, mut op: impl FnMut() -> sled::Result<T>) -> Result<T, StorageError> {
+        let mut retry = 0;
+        loop {
+            match op().map_err(StorageError::classify) {
+                Ok(value) => {
+                    if retry > 0 {
+                        debug!(op = op_name, retries = retry, "Storage write succeeded after retry");
+                    }
+                    return Ok(value);
+                }
+                Err(err) if err.is_transient() && retry < self.max_retries => {
+                    let delay = self.delay(retry);
+                    warn!(op = op_name, retry = retry + 1, delay_ms = delay.as_millis() as u64, error = %err, "Retrying transient storage error");
+                    std::thread::sleep(delay);
+                    retry += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Storage {
+    /// Override the write retry policy (defaults come from AIDB_WRITE_RETRIES / AIDB_WRITE_RETRY_BASE_MS)
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Run one Sled write under the storage's retry policy
+    pub(crate) fn retry_write<T>(&self, op_name: &str, op: impl FnMut() -> sled::Result<T>) -> Result<T, StorageError> {
+        self.ensure_writable()?;
+        self.retry_policy.run(op_name, op)
+    }
+
+    /// Fail with `StorageError::ReadOnly` on a read-only storage; every write path checks this first
+    pub fn ensure_writable(&self) -> Result<(), StorageError> {
+        if self.read_only {
+            return Err(StorageError::ReadOnly);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use std::io;
+
+    fn transient() -> sled::Error {
+        sled::Error::Io(io::Error::new(io::ErrorKind::Interrupted, "injected"))
+    }
+
+    #[test]
+    fn transient_failures_are_retried_within_budget() {
+        let policy = RetryPolicy { max_retries: 3, base_delay: Duration::from_millis(1) };
+
+        let mut attempts = 0;
+        let value = policy
+            .run("test", || {
+                attempts += 1;
+                if attempts < 3 { Err(transient()) } else { Ok(42) }
+            })
+            .expect("succeeds on third attempt");
+        assert_eq!(value, 42);
+        assert_eq!(attempts, 3);
+
+        // Budget exhausted: the transient error surfaces
+        let mut attempts = 0;
+        let err = policy
+            .run("test", || -> sled::Result<()> {
+                attempts += 1;
+                Err(transient())
+            })
+            .unwrap_err();
+        assert!(err.is_transient());
+        assert_eq!(attempts, 4);
+
+        // Permanent errors are not retried
+        let mut attempts = 0;
+        let err = policy
+            .run("test", || -> sled::Result<()> {
+                attempts += 1;
+                Err(sled::Error::Unsupported("nope".to_string()))
+            })
+            .unwrap_err();
+        assert!(!err.is_transient());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn read_only_storage_serves_reads_and_rejects_writes() {
+        let path = std::env::temp_dir().join("aidb_test_read_only");
+        let _ = std::fs::remove_dir_all(&path);
+        let doc = |id: &str| test_doc(id, vec![1.0, 0.0]);
+        {
+            let primary = Storage::open(path.to_str().unwrap()).unwrap();
+            primary.insert_doc(doc("a"), "col").unwrap();
+            primary.db.flush().unwrap();
+        }
+
+        let replica = Storage::open_read_only(path.to_str().unwrap()).unwrap();
+        assert!(replica.is_read_only());
+        assert_eq!(replica.get_doc("col", "a").unwrap().text, "a text");
+        assert_eq!(replica.vector_search("col", &[1.0, 0.0], 1).unwrap(), vec!["a".to_string()]);
+
+        let err = replica.insert_doc(doc("b"), "col").unwrap_err();
+        assert!(matches!(err.downcast_ref::<StorageError>(), Some(StorageError::ReadOnly)));
+        assert!(replica.delete_doc("col", "a").is_err());
+        assert!(replica.self_check_and_repair().is_err());
+        assert!(replica.get_doc("col", "b").is_err());
+        assert!(replica.get_doc("col", "a").is_ok());
+
+        drop(replica);
+        let _ = std::fs::remove_dir_all(&path);
+        assert!(Storage::open_read_only(path.to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use tracing::{info, debug, warn, instrument};
+
+use crate::storage::codec::decode_doc;
+use crate::storage::{create_metadata_batch, Storage};
+
+/// Parse `AIDB_SELF_CHECK`: `1`/`true` runs a report-only check at startup, `repair` also fixes orphans
+pub fn parse_self_check_mode(raw: Option<&str>) -> Option<bool> {
+    match raw?.trim().to_lowercase().as_str() {
+        "1" | "true" => Some(false),
+        "repair" => Some(true),
+        _ => None,
+    }
+}
+
+/// Outcome of `Storage::self_check`: keys that disagree across the doc/vector/metadata trees.
+/// Only collection-scoped keys (`collection_id/doc_id`) are checked; bare IDs written through
+/// the legacy vector `insert` API have no document by design.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SelfCheckReport {
+    pub docs_scanned: usize,
+    /// Vector entries with no document
+    pub orphan_vectors: Vec<String>,
+    /// Arrow metadata entries with no document
+    pub orphan_metadata: Vec<String>,
+    /// Documents missing their vector and/or metadata entry
+    pub docs_missing_index_data: Vec<String>,
+    /// Document values that failed to decode (never repaired automatically)
+    pub corrupt_docs: Vec<String>,
+    /// Whether orphans were repaired during this run
+    pub repaired: bool,
+}
+
+impl SelfCheckReport {
+    pub fn is_consistent(&self) -> bool {
+        self.orphan_vectors.is_empty()
+            && self.orphan_metadata.is_empty()
+            && self.docs_missing_index_data.is_empty()
+            && self.corrupt_docs.is_empty()
+    }
+
+    pub fn issue_count(&self) -> usize {
+        self.orphan_vectors.len()
+            + self.orphan_metadata.len()
+            + self.docs_missing_index_data.len()
+            + self.corrupt_docs.len()
+    }
+}
+
+/// Outcome of `Storage::resync_doc`
+#[derive(Debug, Clone, Serialize)]
+pub struct DocResync {
+    pub key: String,
+    pub vector_dim: usize,
+    /// Whether the stored vector bytes differed from (or were missing for) the document
+    pub vector_changed: bool,
+    /// Whether the Arrow metadata entry was missing before the rewrite
+    pub metadata_was_missing: bool,
+    pub named_vectors: usize,
+}
+
+impl Storage {
+    /// Rewrite one document's vector, metadata and named-vector entries from its stored
+    /// `doc_tree` value. Returns `None` if the document does not exist.
+    #[instrument(skip(self))]
+    pub fn resync_doc(&self, collection_id: &str, doc_id: &str) -> Result<Option<DocResync>, Box<dyn std::error::Error>> {
+        let key = format!("{}/{}", collection_id, doc_id);
+        // Read the tree directly: the stored value, not a cached copy, is the source of truth
+        let doc = match self.doc_tree.get(key.as_bytes())? {
+            Some(bytes) => decode_doc(&bytes)?,
+            None => return Ok(None),
+        };
+
+        let expected: Vec<u8> = doc.vector.iter().flat_map(|f| f.to_le_bytes()).collect();
+        let vector_changed = self.vector_tree.get(key.as_bytes())?.as_deref() != Some(expected.as_slice());
+        let metadata_was_missing = !self.metadata_tree.contains_key(key.as_bytes())?;
+
+        let metadata_batch = create_metadata_batch(&doc.id, &doc.text)?;
+        self.insert(&key, metadata_batch, doc.vector.clone())?;
+        self.sync_field_vectors(collection_id, &doc, None)?;
+
+        let resync = DocResync {
+            key,
+            vector_dim: doc.vector.len(),
+            vector_changed,
+            metadata_was_missing,
+            named_vectors: doc.vectors.len(),
+        };
+        info!(
+            key = %resync.key,
+            vector_changed = vector_changed,
+            metadata_was_missing = metadata_was_missing,
+            "Document resynced from stored value"
+        );
+        Ok(Some(resync))
+    }
+
+    /// Scan for keys present in one of doc/vector/metadata trees but not the others (report only)
+    pub fn self_check(&self) -> Result<SelfCheckReport, Box<dyn std::error::Error>> {
+        self.run_self_check(false)
+    }
+
+    /// Like `self_check`, but also repairs what it finds: orphan vector/metadata entries are
+    /// removed, and documents missing index data get it re-derived from the stored document
+    pub fn self_check_and_repair(&self) -> Result<SelfCheckReport, Box<dyn std::error::Error>> {
+        self.run_self_check(true)
+    }
+
+    #[instrument(skip(self))]
+    fn run_self_check(&self, repair: bool) -> Result<SelfCheckReport, Box<dyn std::error::Error>> {
+        debug!(repair = repair, "Running storage self-check");
+        if repair {
+            self.ensure_writable()?;
+        }
+        let mut report = SelfCheckReport::default();
+
+        let mut doc_keys = BTreeSet::new();
+        for item in self.doc_tree.iter() {
+            let (k, v) = item?;
+            let key = String::from_utf8_lossy(&k).to_string();
+            report.docs_scanned += 1;
+
+            let doc = match decode_doc(&v) {
+                Ok(doc) => doc,
+                Err(e) => {
+                    warn!(key = %key, error = %e, "Self-check found undecodable document");
+                    report.corrupt_docs.push(key.clone());
+                    doc_keys.insert(key);
+                    continue;
+                }
+            };
+
+            if !self.vector_tree.contains_key(&k)? || !self.metadata_tree.contains_key(&k)? {
+                report.docs_missing_index_data.push(key.clone());
+                if repair {
+                    let metadata_batch = create_metadata_batch(&doc.id, &doc.text)?;
+                    self.insert(&key, metadata_batch, doc.vector.clone())?;
+                }
+            }
+            doc_keys.insert(key);
+        }
+
+        for (tree, orphans) in [
+            (&self.vector_tree, &mut report.orphan_vectors),
+            (&self.metadata_tree, &mut report.orphan_metadata),
+        ] {
+            for item in tree.iter() {
+                let (k, _) = item?;
+                let key = String::from_utf8_lossy(&k).to_string();
+                if !key.contains('/') || doc_keys.contains(&key) {
+                    continue;
+                }
+                if repair {
+                    tree.remove(&k)?;
+                    self.bump_generation_for_key(&key)?;
+                }
+                orphans.push(key);
+            }
+        }
+
+        report.repaired = repair && !report.is_consistent();
+        if report.is_consistent() {
+            info!(docs_scanned = report.docs_scanned, "Storage self-check passed");
+        } else {
+            warn!(
+                docs_scanned = report.docs_scanned,
+                orphan_vectors = report.orphan_vectors.len(),
+                orphan_metadata = report.orphan_metadata.len(),
+                docs_missing_index_data = report.docs_missing_index_data.len(),
+                corrupt_docs = report.corrupt_docs.len(),
+                repaired = report.repaired,
+                "Storage self-check found inconsistencies"
+            );
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{test_doc, Document};
+    use std::fs;
+
+    #[test]
+    fn self_check_detects_and_repairs_orphan_vector() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_self_check");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        storage.insert_doc(Document {
+            text: "consistent doc".to_string(),
+            ..test_doc("kept", vec![0.1, 0.2])
+        }, "col").expect("insert");
+        // Orphan: vector without a document
+        storage.vector_tree.insert(b"col/ghost", 1.0f32.to_le_bytes().to_vec()).unwrap();
+        // Legacy bare-ID vector records are not orphans
+        storage.vector_tree.insert(b"legacy", 1.0f32.to_le_bytes().to_vec()).unwrap();
+
+        let report = storage.self_check().expect("self check");
+        assert!(!report.is_consistent());
+        assert_eq!(report.docs_scanned, 1);
+        assert_eq!(report.orphan_vectors, vec!["col/ghost".to_string()]);
+        assert!(storage.vector_tree.contains_key(b"col/ghost").unwrap());
+
+        let repaired = storage.self_check_and_repair().expect("repair");
+        assert!(repaired.repaired);
+        assert!(!storage.vector_tree.contains_key(b"col/ghost").unwrap());
+        assert!(storage.self_check().unwrap().is_consistent());
+        assert_eq!(storage.vector_search("col", &[0.1, 0.2], 5).unwrap(), vec!["kept".to_string()]);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn resync_restores_corrupted_vector() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_resync_doc");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+
+        for (id, vector) in [("near", vec![1.0, 0.0]), ("far", vec![-5.0, -5.0])] {
+            storage.insert_doc(Document { text: "resync".to_string(), ..test_doc(id, vector) }, "col").expect("insert");
+        }
+        // Drift: the vector tree no longer matches the stored document
+        let bogus: Vec<u8> = [-9.0f32, -9.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        storage.vector_tree.insert(b"col/near", bogus).unwrap();
+        storage.metadata_tree.remove(b"col/near").unwrap();
+        assert_eq!(storage.vector_search("col", &[1.0, 0.0], 1).unwrap(), vec!["far".to_string()]);
+
+        let resync = storage.resync_doc("col", "near").expect("resync").expect("doc exists");
+        assert!(resync.vector_changed);
+        assert!(resync.metadata_was_missing);
+        assert_eq!(resync.vector_dim, 2);
+        assert_eq!(storage.vector_search("col", &[1.0, 0.0], 1).unwrap(), vec!["near".to_string()]);
+
+        // A second resync is a no-op; unknown docs report None
+        assert!(!storage.resync_doc("col", "near").unwrap().unwrap().vector_changed);
+        assert!(storage.resync_doc("col", "missing").unwrap().is_none());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
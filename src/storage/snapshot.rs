@@ -0,0 +1,283 @@
+//! Point-in-time snapshots of the whole database, restorable into a fresh directory
+//!
+//! A snapshot is one zstd-compressed NDJSON file: a header line, one line per entry of every
+//! Sled tree (key and value hex-encoded), and a footer carrying the entry count so a truncated
+//! archive is rejected on restore. While the snapshot is written it holds `collection_lock`
+//! exclusively, so no document write or collection swap lands halfway through: documents,
+//! vectors, metadata, generations and index checkpoints all describe the same moment.
+//! Tenant, user and session records don't take the lock; a write to them racing a snapshot may
+//! or may not be included.
+//!
+//! Sled locks its directory, so a snapshot can't be loaded into the running database:
+//! `Storage::restore_snapshot` fills a new, empty directory that a server is then started on.
+//! It loads the archive into a sibling staging directory first and renames that into place, so
+//! a failed restore leaves nothing behind and can simply be retried.
+//!
+//! Over REST, archives and restore targets are named relative to the snapshot directory
+//! (`AIDB_SNAPSHOT_DIR`, see `snapshot_path`), so callers can't write anywhere else.
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::{info, debug, warn, instrument};
+
+use crate::storage::nosql::now_millis;
+use crate::storage::Storage;
+
+/// `format` of the header line
+pub const SNAPSHOT_FORMAT: &str = "aidb-snapshot";
+/// Archive layout version written by this build
+const SNAPSHOT_VERSION: u32 = 1;
+/// Entries applied per Sled batch on restore
+const RESTORE_BATCH_SIZE: usize = 1000;
+
+/// One line of a snapshot archive
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SnapshotLine {
+    Header { format: String, version: u32, created_at: u64 },
+    Entry { tree: String, key: String, value: String },
+    Footer { entries: u64 },
+}
+
+/// What a snapshot or restore covered
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnapshotReport {
+    /// Archive written or read
+    pub path: String,
+    /// Milliseconds since the epoch when the snapshot was taken
+    pub created_at: u64,
+    pub trees: usize,
+    pub entries: u64,
+}
+
+/// `name` inside `dir`. Fails unless `name` is a non-empty relative path of plain components
+/// (no `..`, root or drive prefix), so it can't point outside `dir`.
+pub fn snapshot_path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name.trim());
+    if relative.as_os_str().is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("'{}' must be a relative path inside the snapshot directory", name));
+    }
+    Ok(dir.join(relative))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err("Odd-length hex in snapshot entry".into());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&hex[i..i + 2], 16)?))
+        .collect()
+}
+
+impl Storage {
+    /// Write every tree to the archive at `path` (replaced atomically) as of one point in time
+    #[instrument(skip(self, path))]
+    pub fn snapshot(&self, path: &Path) -> Result<SnapshotReport, Box<dyn std::error::Error>> {
+        let _exclusive = self.collection_write_guard();
+        self.db.flush()?;
+        let created_at = now_millis();
+        debug!(path = %path.display(), "Writing snapshot");
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut out = zstd::Encoder::new(BufWriter::new(std::fs::File::create(&tmp)?), 3)?;
+        let mut write_line = |line: &SnapshotLine| -> Result<(), Box<dyn std::error::Error>> {
+            serde_json::to_writer(&mut out, line)?;
+            out.write_all(b"\n")?;
+            Ok(())
+        };
+        write_line(&SnapshotLine::Header { format: SNAPSHOT_FORMAT.to_string(), version: SNAPSHOT_VERSION, created_at })?;
+        let names = self.db.tree_names();
+        let mut entries = 0u64;
+        for name in &names {
+            let tree_name = String::from_utf8(name.to_vec())?;
+            for item in self.db.open_tree(name)?.iter() {
+                let (key, value) = item?;
+                write_line(&SnapshotLine::Entry { tree: tree_name.clone(), key: to_hex(&key), value: to_hex(&value) })?;
+                entries += 1;
+            }
+        }
+        write_line(&SnapshotLine::Footer { entries })?;
+        out.finish()?.flush()?;
+        std::fs::rename(&tmp, path)?;
+
+        info!(path = %path.display(), trees = names.len(), entries = entries, "Snapshot written");
+        Ok(SnapshotReport { path: path.display().to_string(), created_at, trees: names.len(), entries })
+    }
+
+    /// Load the archive at `archive` into a new database at `data_path`, which must not exist or
+    /// be an empty directory. Open it with `Storage::open` afterwards.
+    ///
+    /// The database is built in `<data_path>.restoring` and renamed to `data_path` once complete;
+    /// on failure the staging directory is removed and `data_path` is left as it was.
+    #[instrument(skip(archive))]
+    pub fn restore_snapshot(archive: &Path, data_path: &str) -> Result<SnapshotReport, Box<dyn std::error::Error>> {
+        let target = Path::new(data_path);
+        if target.exists() && std::fs::read_dir(target)?.next().is_some() {
+            return Err(format!("Cannot restore into non-empty directory '{}'", data_path).into());
+        }
+        let Some(name) = target.file_name() else {
+            return Err(format!("Cannot restore into '{}'", data_path).into());
+        };
+        let mut staging_name = name.to_owned();
+        staging_name.push(".restoring");
+        let staging = target.with_file_name(staging_name);
+        // Left over from an interrupted restore
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+
+        let (created_at, trees, entries) = match Self::restore_into(archive, &staging) {
+            Ok(restored) => restored,
+            Err(e) => {
+                if let Err(cleanup) = std::fs::remove_dir_all(&staging) {
+                    if cleanup.kind() != std::io::ErrorKind::NotFound {
+                        warn!(staging = %staging.display(), error = %cleanup, "Failed to remove restore staging directory");
+                    }
+                }
+                return Err(e);
+            }
+        };
+        if target.exists() {
+            std::fs::remove_dir(target)?;
+        }
+        std::fs::rename(&staging, target)?;
+
+        info!(path = %archive.display(), data_path = %data_path, trees = trees, entries = entries, "Snapshot restored");
+        Ok(SnapshotReport { path: archive.display().to_string(), created_at, trees, entries })
+    }
+
+    /// Load `archive` into a new database at `dir`: its creation time, trees and entries
+    fn restore_into(archive: &Path, dir: &Path) -> Result<(u64, usize, u64), Box<dyn std::error::Error>> {
+        let mut lines = BufReader::new(zstd::Decoder::new(std::fs::File::open(archive)?)?).lines();
+        let created_at = match lines.next().transpose()?.map(|line| serde_json::from_str(&line)).transpose()? {
+            Some(SnapshotLine::Header { format, version, created_at }) if format == SNAPSHOT_FORMAT && version == SNAPSHOT_VERSION => created_at,
+            _ => return Err(format!("'{}' is not a version {} aidb snapshot", archive.display(), SNAPSHOT_VERSION).into()),
+        };
+
+        let db = sled::open(dir)?;
+        let mut batches: Vec<(String, sled::Batch, usize)> = Vec::new();
+        let mut restored = 0u64;
+        let mut footer = None;
+        for line in lines {
+            match serde_json::from_str(&line?)? {
+                SnapshotLine::Entry { tree, key, value } => {
+                    let pos = match batches.iter().position(|(name, _, _)| *name == tree) {
+                        Some(pos) => pos,
+                        None => {
+                            batches.push((tree, sled::Batch::default(), 0));
+                            batches.len() - 1
+                        }
+                    };
+                    let (name, batch, pending) = &mut batches[pos];
+                    batch.insert(from_hex(&key)?, from_hex(&value)?);
+                    *pending += 1;
+                    if *pending >= RESTORE_BATCH_SIZE {
+                        db.open_tree(name.as_bytes())?.apply_batch(std::mem::take(batch))?;
+                        *pending = 0;
+                    }
+                    restored += 1;
+                }
+                SnapshotLine::Footer { entries } => {
+                    footer = Some(entries);
+                    break;
+                }
+                SnapshotLine::Header { .. } => return Err("Unexpected second snapshot header".into()),
+            }
+        }
+        if footer != Some(restored) {
+            return Err(format!(
+                "Snapshot '{}' is truncated or corrupt: read {} entries, footer says {:?}",
+                archive.display(), restored, footer
+            ).into());
+        }
+        for (name, batch, _) in batches.iter_mut() {
+            db.open_tree(name.as_bytes())?.apply_batch(std::mem::take(batch))?;
+        }
+        db.flush()?;
+        Ok((created_at, batches.len(), restored))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::snapshot_path;
+    use crate::storage::{test_doc, Storage};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn snapshot_restores_into_a_fresh_database() {
+        let base = std::env::temp_dir().join("aidb_test_snapshot");
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        let storage = Storage::open(base.join("source").to_str().unwrap()).unwrap();
+        storage.insert_docs((0..20).map(|i| test_doc(&format!("doc{}", i), vec![i as f32, 1.0])).collect(), "col").unwrap();
+        storage.insert_doc(test_doc("other", vec![0.0, 0.0]), "second").unwrap();
+
+        let archive = base.join("backup.aidbsnap");
+        let taken = storage.snapshot(&archive).unwrap();
+        assert!(taken.entries > 0);
+
+        let restored_path = base.join("restored");
+        let restored = Storage::restore_snapshot(&archive, restored_path.to_str().unwrap()).unwrap();
+        assert_eq!((restored.entries, restored.created_at), (taken.entries, taken.created_at));
+        // Refuses to overwrite a database
+        assert!(Storage::restore_snapshot(&archive, restored_path.to_str().unwrap()).is_err());
+        assert!(!base.join("restored.restoring").exists());
+
+        let copy = Storage::open(restored_path.to_str().unwrap()).unwrap();
+        assert_eq!(copy.get_docs_in_collection("col").unwrap().len(), 20);
+        assert_eq!(copy.get_docs_in_collection("second").unwrap().len(), 1);
+        let (original, restored_doc) = (storage.get_doc("col", "doc7").unwrap(), copy.get_doc("col", "doc7").unwrap());
+        assert_eq!(serde_json::to_value(&original).unwrap(), serde_json::to_value(&restored_doc).unwrap());
+        assert_eq!(copy.vector_search("col", &[7.0, 1.0], 1).unwrap(), vec!["doc7".to_string()]);
+
+        drop((storage, copy));
+        let _ = fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn failed_restore_leaves_nothing_behind_and_can_be_retried() {
+        let base = std::env::temp_dir().join("aidb_test_snapshot_retry");
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        let storage = Storage::open(base.join("source").to_str().unwrap()).unwrap();
+        storage.insert_docs((0..5).map(|i| test_doc(&format!("doc{}", i), vec![i as f32, 1.0])).collect(), "col").unwrap();
+        let archive = base.join("backup.aidbsnap");
+        storage.snapshot(&archive).unwrap();
+
+        // The same archive without its footer line
+        let text = String::from_utf8(zstd::decode_all(fs::File::open(&archive).unwrap()).unwrap()).unwrap();
+        let truncated_text = text.trim_end().rsplit_once('\n').unwrap().0.to_string();
+        let truncated = base.join("truncated.aidbsnap");
+        fs::write(&truncated, zstd::encode_all(truncated_text.as_bytes(), 3).unwrap()).unwrap();
+
+        let target = base.join("restored");
+        let err = Storage::restore_snapshot(&truncated, target.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{}", err);
+        assert!(!target.exists() && !base.join("restored.restoring").exists());
+
+        Storage::restore_snapshot(&archive, target.to_str().unwrap()).unwrap();
+        let copy = Storage::open(target.to_str().unwrap()).unwrap();
+        assert_eq!(copy.get_docs_in_collection("col").unwrap().len(), 5);
+
+        drop((storage, copy));
+        let _ = fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn snapshot_paths_stay_inside_the_directory() {
+        let dir = Path::new("/srv/snapshots");
+        assert_eq!(snapshot_path(dir, "nightly/aidb.snap").unwrap(), dir.join("nightly/aidb.snap"));
+        for name in ["", "  ", "/etc/passwd", "../aidb.snap", "nightly/../../aidb.snap", "."] {
+            assert!(snapshot_path(dir, name).is_err(), "{:?}", name);
+        }
+    }
+}
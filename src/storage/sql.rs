@@ -1,75 +1,251 @@
-use arrow::array::{ArrayRef, StringArray};
-use arrow::datatypes::{DataType, Field, Schema};
+use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, TimestampMillisecondArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::error::ArrowError;
 use arrow::record_batch::RecordBatch;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tracing::{info, debug, warn, instrument};
 
+use crate::storage::codec::decode_doc;
 use crate::storage::{Document, Storage};
 
+/// Schema of the Arrow projection registered as the SQL `docs` table
+pub fn docs_schema() -> SchemaRef {
+    // Build simple Arrow schema for SQL (avoids type errors , ensures response)
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("text", DataType::Utf8, false),
+        Field::new("category", DataType::Utf8, false),
+        Field::new("vector", DataType::Utf8, false),  // Stringified for compat
+        // Provenance (NULL when unknown)
+        Field::new("source_uri", DataType::Utf8, true),
+        Field::new("ingested_by", DataType::Utf8, true),
+        // Last write; a timestamp rather than raw millis so it sorts and compares as time (NULL if never stamped)
+        Field::new("updated_at", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+    ]))
+}
+
+/// Column type of one top-level metadata key across the documents that set it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetadataKind {
+    Boolean,
+    Int64,
+    Float64,
+    Utf8,
+    /// Only objects/arrays seen so far: not a column unless a scalar turns up too
+    Nested,
+}
+
+impl MetadataKind {
+    fn of(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::Bool(_) => Some(MetadataKind::Boolean),
+            serde_json::Value::Number(n) if n.is_i64() => Some(MetadataKind::Int64),
+            serde_json::Value::Number(_) => Some(MetadataKind::Float64),
+            serde_json::Value::String(_) => Some(MetadataKind::Utf8),
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => Some(MetadataKind::Nested),
+        }
+    }
+
+    /// Integers widen to floats; any other disagreement falls back to text
+    fn unify(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (MetadataKind::Int64, MetadataKind::Float64) | (MetadataKind::Float64, MetadataKind::Int64) => MetadataKind::Float64,
+            _ => MetadataKind::Utf8,
+        }
+    }
+
+    fn data_type(self) -> Option<DataType> {
+        match self {
+            MetadataKind::Boolean => Some(DataType::Boolean),
+            MetadataKind::Int64 => Some(DataType::Int64),
+            MetadataKind::Float64 => Some(DataType::Float64),
+            MetadataKind::Utf8 => Some(DataType::Utf8),
+            MetadataKind::Nested => None,
+        }
+    }
+}
+
+/// Nullable columns for the top-level scalar `metadata` keys of `docs`, sorted by name. A key's
+/// type is the one all its non-null values share (Boolean, Int64, Float64 or Utf8); integers mixed
+/// with floats become Float64 and any other mix Utf8. Keys holding only objects/arrays, and keys
+/// named like a `docs_schema()` column, are left out.
+pub fn metadata_fields(docs: &[Document]) -> Vec<Field> {
+    let base = docs_schema();
+    let mut kinds: BTreeMap<&str, MetadataKind> = BTreeMap::new();
+    for doc in docs {
+        let Some(metadata) = doc.metadata.as_object() else { continue };
+        for (key, value) in metadata {
+            if let Some(kind) = MetadataKind::of(value) {
+                kinds.entry(key.as_str()).and_modify(|k| *k = k.unify(kind)).or_insert(kind);
+            }
+        }
+    }
+    kinds
+        .into_iter()
+        .filter(|(key, _)| base.index_of(key).is_err())
+        .filter_map(|(key, kind)| Some(Field::new(key, kind.data_type()?, true)))
+        .collect()
+}
+
+/// One metadata column: each document's value for `field`, NULL where missing or null
+fn metadata_column(docs: &[Document], field: &Field) -> ArrayRef {
+    let values = || docs.iter().map(|d| d.metadata.get(field.name()).filter(|v| !v.is_null()));
+    match field.data_type() {
+        DataType::Boolean => Arc::new(BooleanArray::from(values().map(|v| v.and_then(|v| v.as_bool())).collect::<Vec<_>>())),
+        DataType::Int64 => Arc::new(Int64Array::from(values().map(|v| v.and_then(|v| v.as_i64())).collect::<Vec<_>>())),
+        DataType::Float64 => Arc::new(Float64Array::from(values().map(|v| v.and_then(|v| v.as_f64())).collect::<Vec<_>>())),
+        _ => Arc::new(StringArray::from(
+            values()
+                .map(|v| v.map(|v| match v {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                }))
+                .collect::<Vec<_>>(),
+        )),
+    }
+}
+
+/// Project documents into one `docs_schema()` batch, preserving their order
+pub fn docs_to_arrow(docs: &[Document]) -> Result<RecordBatch, ArrowError> {
+    let ids: Vec<&str> = docs.iter().map(|d| d.id.as_str()).collect();
+    let texts: Vec<&str> = docs.iter().map(|d| d.text.as_str()).collect();
+    let categories: Vec<&str> = docs.iter().map(|d| d.category.as_str()).collect();
+    // Stringify vector for placeholder (enables SQL , hybrid join)
+    let vector_strs: Vec<String> = docs
+        .iter()
+        .map(|d| serde_json::to_string(&d.vector).unwrap_or_default())
+        .collect();
+    let source_uris: Vec<Option<&str>> = docs.iter().map(|d| d.provenance_source_uri()).collect();
+    let ingested_by: Vec<Option<&str>> = docs.iter().map(|d| d.provenance_ingested_by()).collect();
+    let updated_at: Vec<Option<i64>> = docs.iter().map(|d| d.updated_at.map(|t| t as i64)).collect();
+
+    RecordBatch::try_new(
+        docs_schema(),
+        vec![
+            Arc::new(StringArray::from(ids)) as ArrayRef,
+            Arc::new(StringArray::from(texts)) as ArrayRef,
+            Arc::new(StringArray::from(categories)) as ArrayRef,
+            Arc::new(StringArray::from(vector_strs)) as ArrayRef,
+            Arc::new(StringArray::from(source_uris)) as ArrayRef,
+            Arc::new(StringArray::from(ingested_by)) as ArrayRef,
+            Arc::new(TimestampMillisecondArray::from(updated_at)) as ArrayRef,
+        ],
+    )
+}
+
+/// `docs_to_arrow` plus one typed column per top-level scalar metadata key (see `metadata_fields`)
+pub fn docs_to_arrow_with_metadata(docs: &[Document]) -> Result<RecordBatch, ArrowError> {
+    let base = docs_to_arrow(docs)?;
+    let extra = metadata_fields(docs);
+    if extra.is_empty() {
+        return Ok(base);
+    }
+    let mut fields: Vec<Field> = base.schema().fields().iter().map(|f| f.as_ref().clone()).collect();
+    let mut columns = base.columns().to_vec();
+    for field in extra {
+        columns.push(metadata_column(docs, &field));
+        fields.push(field);
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
 impl Storage {
     /// Project NoSQL docs from Sled into Arrow RecordBatch
     /// This is the hybrid link: Enables SQL queries via DataFusion on
     /// structured view of JSON data (high-perf vectorized scans).
     /// Supports push-down filters for category, text, etc.
-    /// Fixed schema: basic columns to ensure DataFusion table register/query success
-    /// (vector stringified for hybrid; full List for prod).
+    /// Schema: the `docs_schema()` columns (vector stringified for hybrid; full List for prod),
+    /// then one typed column per top-level scalar `metadata` key found in the collection, so SQL
+    /// can filter on e.g. `score > 40`. Documents without a key read NULL there.
     #[instrument(skip(self))]
     pub fn project_collection_to_arrow(&self, collection_id: &str) -> Result<RecordBatch, Box<dyn std::error::Error>> {
         debug!(collection_id = %collection_id, "Projecting collection to Arrow");
         
-        let mut ids = vec![];
-        let mut texts = vec![];
-        let mut categories = vec![];
-        let mut vector_strs = vec![];  // Stringify vectors for SQL compat
-
         let prefix = format!("{}/", collection_id);
 
         // Scan NoSQL docs from Sled
+        let mut docs = vec![];
         for item in self.doc_tree.scan_prefix(prefix.as_bytes()) {
             let (_, value) = item?;
-            let doc: Document = serde_json::from_slice(&value)?;
-            ids.push(doc.id);
-            texts.push(doc.text);
-            categories.push(doc.category);
-            // Stringify vector for placeholder (enables SQL , hybrid join)
-            vector_strs.push(serde_json::to_string(&doc.vector).unwrap_or_default());
+            docs.push(decode_doc(&value)?);
         }
 
-        if ids.is_empty() {
+        if docs.is_empty() {
             // Empty batch fallback for SQL register (prevents query fail on no data)
             debug!(collection_id = %collection_id, "No documents found, creating empty batch");
-            ids.push("".to_string());
-            texts.push("".to_string());
-            categories.push("".to_string());
-            vector_strs.push("[]".to_string());
+            docs.push(Document {
+                id: String::new(),
+                text: String::new(),
+                category: String::new(),
+                vector: vec![],
+                metadata: serde_json::Value::Null,
+                vectors: HashMap::new(),
+                updated_at: None,
+                source_uri: None,
+                ingested_by: None,
+            });
         }
 
-        // Build simple Arrow schema for SQL (avoids type errors , ensures response)
-        let schema = Arc::new(Schema::new(vec![
-            Field::new("id", DataType::Utf8, false),
-            Field::new("text", DataType::Utf8, false),
-            Field::new("category", DataType::Utf8, false),
-            Field::new("vector", DataType::Utf8, false),  // Stringified for compat
-        ]));
-
-        // Convert to Arrow arrays (vectorized ; ids moved handled by len capture)
-        let _num_rows = ids.len();  // Unused after simplification; prefix for warning
-        let id_array = StringArray::from(ids);
-        let text_array = StringArray::from(texts);
-        let cat_array = StringArray::from(categories);
-        let vec_str_array = StringArray::from(vector_strs);
-
-        let batch = RecordBatch::try_new(
-            schema,
-            vec![
-                Arc::new(id_array) as ArrayRef,
-                Arc::new(text_array) as ArrayRef,
-                Arc::new(cat_array) as ArrayRef,
-                Arc::new(vec_str_array) as ArrayRef,
-            ],
-        )?;
+        let batch = docs_to_arrow_with_metadata(&docs)?;
         
         info!(collection_id = %collection_id, rows = batch.num_rows(), "Collection projected to Arrow");
         Ok(batch)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{test_doc, Document, Storage};
+    use arrow::array::{Array, BooleanArray, Float64Array, Int64Array, StringArray};
+    use arrow::datatypes::DataType;
+    use std::fs;
+
+    fn doc(id: &str, metadata: serde_json::Value) -> Document {
+        Document { metadata, ..test_doc(id, vec![1.0, 0.0]) }
+    }
+
+    #[test]
+    fn metadata_keys_become_typed_nullable_columns() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_metadata_columns");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).unwrap();
+        storage.insert_doc(doc("a", serde_json::json!({
+            "score": 42, "ratio": 1, "label": "x", "flag": true, "mixed": 7, "nested": {"k": 1}, "id": "shadow"
+        })), "col").unwrap();
+        storage.insert_doc(doc("b", serde_json::json!({"score": 17, "ratio": 0.5, "mixed": "seven", "flag": null})), "col").unwrap();
+        storage.insert_doc(doc("c", serde_json::json!({})), "col").unwrap();
+
+        let batch = storage.project_collection_to_arrow("col").unwrap();
+        let schema = batch.schema();
+        let types: Vec<(&str, &DataType)> = schema.fields().iter().skip(7).map(|f| (f.name().as_str(), f.data_type())).collect();
+        assert_eq!(types, vec![
+            ("flag", &DataType::Boolean),
+            ("label", &DataType::Utf8),
+            ("mixed", &DataType::Utf8),
+            ("ratio", &DataType::Float64),
+            ("score", &DataType::Int64),
+        ]);
+
+        let column = |name: &str| batch.column(schema.index_of(name).unwrap()).clone();
+        let score = column("score");
+        let score = score.as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!((score.value(0), score.value(1), score.is_null(2)), (42, 17, true));
+        let ratio = column("ratio");
+        let ratio = ratio.as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!((ratio.value(0), ratio.value(1)), (1.0, 0.5));
+        let mixed = column("mixed");
+        let mixed = mixed.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!((mixed.value(0), mixed.value(1)), ("7", "seven"));
+        let flag = column("flag");
+        let flag = flag.as_any().downcast_ref::<BooleanArray>().unwrap();
+        assert!(flag.value(0) && flag.is_null(1) && flag.is_null(2));
+        // The document's own id wins over a metadata key of the same name
+        let ids = column("id");
+        assert_eq!(ids.as_any().downcast_ref::<StringArray>().unwrap().value(0), "a");
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
@@ -0,0 +1,185 @@
+//! Atomic exchange of two collections' contents
+//!
+//! Supports rebuild-then-swap: load a fresh copy into a staging collection, then swap it with the
+//! live one. Every per-collection entry (documents, Arrow metadata, vectors, named vectors, tag
+//! centroids, RAG chunks) is re-keyed under the other collection's prefix in a single Sled
+//! transaction, and the collections' vector settings (dim/metric) follow their data.
+//! Collection scans and document writes hold `collection_lock` shared while the swap holds it
+//! exclusively, so searches see either the old or the new contents, never a mix.
+
+use serde::Serialize;
+use sled::transaction::{ConflictableTransactionError, TransactionError, Transactional};
+use sled::IVec;
+use std::sync::{RwLockReadGuard, RwLockWriteGuard};
+use tracing::{info, debug, instrument};
+
+use crate::storage::generation::DocDelta;
+use crate::storage::Storage;
+use crate::tenants::Collection;
+
+/// Outcome of `Storage::swap_collections`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CollectionSwap {
+    pub first: String,
+    pub second: String,
+    /// Documents now stored under `first` (formerly `second`'s)
+    pub first_docs: usize,
+    /// Documents now stored under `second` (formerly `first`'s)
+    pub second_docs: usize,
+}
+
+/// Entries of one tree under `from/`, re-keyed under `to/`
+fn rekeyed(tree: &sled::Tree, from: &str, to: &str) -> sled::Result<Vec<(IVec, IVec, IVec)>> {
+    let prefix = format!("{}/", from);
+    tree.scan_prefix(prefix.as_bytes())
+        .map(|item| {
+            let (k, v) = item?;
+            let mut new_key = format!("{}/", to).into_bytes();
+            new_key.extend_from_slice(&k[prefix.len()..]);
+            Ok((k, IVec::from(new_key), v))
+        })
+        .collect()
+}
+
+impl Storage {
+    /// Shared side of the collection lock: collection scans and document writes
+    pub(crate) fn collection_read_guard(&self) -> RwLockReadGuard<'_, ()> {
+        self.collection_lock.read().unwrap_or_else(|p| p.into_inner())
+    }
+
+    pub(crate) fn collection_write_guard(&self) -> RwLockWriteGuard<'_, ()> {
+        self.collection_lock.write().unwrap_or_else(|p| p.into_inner())
+    }
+
+    /// Atomically exchange the contents of two collections. Both IDs keep their tenancy
+    /// (name, environment); only data and vector settings move. The whole swap is one Sled
+    /// transaction, so both collections' entries must fit in memory.
+    #[instrument(skip(self))]
+    pub fn swap_collections(&self, first: &str, second: &str) -> Result<CollectionSwap, Box<dyn std::error::Error>> {
+        if first == second {
+            return Err("Cannot swap a collection with itself".into());
+        }
+        if first.contains('/') || second.contains('/') {
+            return Err("Collection IDs must not contain '/'".into());
+        }
+        self.ensure_writable()?;
+        let _exclusive = self.collection_write_guard();
+        debug!(first = %first, second = %second, "Swapping collections");
+
+        let data_trees = [
+            &self.doc_tree,
+            &self.metadata_tree,
+            &self.vector_tree,
+            &self.field_vector_tree,
+            &self.tag_centroid_tree,
+            &self.rag_tree,
+        ];
+        // Per data tree: entries leaving `first` for `second`, then the reverse
+        let mut moves = Vec::with_capacity(data_trees.len());
+        for tree in data_trees {
+            moves.push((rekeyed(tree, first, second)?, rekeyed(tree, second, first)?));
+        }
+        let (to_second, to_first) = &moves[0];
+        let first_docs = to_first.len();
+        let second_docs = to_second.len();
+
+        let mut first_config = self.get_collection(first)?;
+        let mut second_config = self.get_collection(second)?;
+        if let (Some(a), Some(b)) = (first_config.as_mut(), second_config.as_mut()) {
+            std::mem::swap(&mut a.vector_dim, &mut b.vector_dim);
+            std::mem::swap(&mut a.metric, &mut b.metric);
+        }
+        let configs: Vec<Collection> = first_config.into_iter().chain(second_config).collect();
+        let config_values = configs
+            .iter()
+            .map(|c| serde_json::to_vec(c).map(|v| (c.id.clone(), v)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut trees: Vec<sled::Tree> = data_trees.iter().map(|t| (*t).clone()).collect();
+        trees.push(self.collection_tree.clone());
+        let result: Result<(), TransactionError<()>> = trees.as_slice().transaction(|views| {
+            for (view, (to_second, to_first)) in views.iter().zip(&moves) {
+                for (old_key, _, _) in to_second.iter().chain(to_first) {
+                    view.remove(old_key.clone())?;
+                }
+                for (_, new_key, value) in to_second.iter().chain(to_first) {
+                    view.insert(new_key.clone(), value.clone())?;
+                }
+            }
+            let collections = &views[views.len() - 1];
+            for (id, value) in &config_values {
+                collections.insert(id.as_bytes(), value.clone())?;
+            }
+            Ok::<_, ConflictableTransactionError<()>>(())
+        });
+        result.map_err(|e| format!("Collection swap transaction failed: {:?}", e))?;
+
+        // Fingerprints hash the full key, so each side's record is recomputed for its new keys
+        for (collection_id, entries) in [(first, to_first), (second, to_second)] {
+            let mut delta = DocDelta::default();
+            for (_, new_key, value) in entries {
+                delta.record(new_key, None, Some(value));
+            }
+            self.replace_generation_contents(collection_id, delta)?;
+        }
+        let mut cache = self.lock_cache();
+        for (old_key, new_key, _) in to_first.iter().chain(to_second.iter()) {
+            cache.remove(&String::from_utf8_lossy(old_key));
+            cache.remove(&String::from_utf8_lossy(new_key));
+        }
+
+        info!(first = %first, second = %second, first_docs = first_docs, second_docs = second_docs, "Collections swapped");
+        Ok(CollectionSwap {
+            first: first.to_string(),
+            second: second.to_string(),
+            first_docs,
+            second_docs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{test_doc, Document};
+    use std::collections::HashMap;
+    use std::fs;
+
+    /// `test_doc` with a tag and a named `title` vector, so swaps have every tree to move
+    fn doc(id: &str, vector: Vec<f32>) -> Document {
+        Document {
+            metadata: serde_json::json!({"tags": ["t"]}),
+            vectors: HashMap::from([("title".to_string(), vec![1.0])]),
+            ..test_doc(id, vector)
+        }
+    }
+
+    #[test]
+    fn swapped_collections_answer_with_each_others_data() {
+        let path = std::env::temp_dir().join("aidb_test_swap_collections");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap();
+
+        storage.insert_doc(doc("live1", vec![0.0, 0.0]), "live").unwrap();
+        storage.insert_doc(doc("live2", vec![1.0, 1.0]), "live").unwrap();
+        storage.insert_doc(doc("staged", vec![5.0, 5.0]), "staging").unwrap();
+        // Cache an index and a document for both sides before swapping
+        assert_eq!(storage.vector_search("live", &[0.0, 0.0], 1).unwrap(), vec!["live1"]);
+        assert_eq!(storage.vector_search("staging", &[0.0, 0.0], 1).unwrap(), vec!["staged"]);
+        assert!(storage.get_doc("live", "live1").is_ok());
+
+        let swap = storage.swap_collections("live", "staging").unwrap();
+        assert_eq!((swap.first_docs, swap.second_docs), (1, 2));
+
+        assert_eq!(storage.vector_search("live", &[0.0, 0.0], 5).unwrap(), vec!["staged"]);
+        assert_eq!(storage.vector_search("staging", &[0.0, 0.0], 5).unwrap(), vec!["live1", "live2"]);
+        assert!(storage.get_doc("live", "live1").is_err());
+        assert_eq!(storage.get_doc("staging", "live1").unwrap().text, "live1 text");
+        assert_eq!(storage.get_field_vectors_in_collection("live", "title").unwrap().len(), 1);
+        assert!(storage.tag_centroid("staging", "t").unwrap().is_some());
+        assert!(storage.verify_generations(false).unwrap().drifted.is_empty());
+        assert!(storage.swap_collections("live", "live").is_err());
+
+        let _ = fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeSet;
+use tracing::{info, debug, instrument};
+
+use crate::storage::{Document, Storage};
+
+/// Tags of a document: the string entries of its `metadata.tags` array
+pub fn doc_tags(doc: &Document) -> BTreeSet<String> {
+    doc.metadata
+        .get("tags")
+        .and_then(|tags| tags.as_array())
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|t| t.as_str())
+                .map(|t| t.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn centroid_key(collection_id: &str, tag: &str) -> String {
+    format!("{}/{}", collection_id, tag)
+}
+
+/// Running sum of the vectors carrying a tag: `count` (u64 LE) followed by one f64 LE per dimension.
+/// Sums (not means) make adding and removing a document an O(dim) update.
+struct CentroidSum {
+    count: u64,
+    sum: Vec<f64>,
+}
+
+impl CentroidSum {
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < 8 || (bytes.len() - 8) % 8 != 0 {
+            return None;
+        }
+        let count = u64::from_le_bytes(bytes[..8].try_into().ok()?);
+        let sum = bytes[8..]
+            .chunks_exact(8)
+            .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        Some(Self { count, sum })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8 + self.sum.len() * 8);
+        out.extend_from_slice(&self.count.to_le_bytes());
+        for v in &self.sum {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+        out
+    }
+
+    fn mean(&self) -> Vec<f32> {
+        self.sum.iter().map(|s| (s / self.count as f64) as f32).collect()
+    }
+}
+
+impl Storage {
+    /// Keep per-tag centroid sums in step with a document write or delete.
+    /// `doc` is the new version (None on delete), `previous` the version it replaced.
+    #[instrument(skip(self, doc, previous), fields(collection_id))]
+    pub(crate) fn sync_tag_centroids(
+        &self,
+        collection_id: &str,
+        doc: Option<&Document>,
+        previous: Option<&Document>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(prev) = previous {
+            for tag in doc_tags(prev) {
+                self.apply_centroid_delta(collection_id, &tag, &prev.vector, -1.0)?;
+            }
+        }
+        if let Some(doc) = doc {
+            for tag in doc_tags(doc) {
+                self.apply_centroid_delta(collection_id, &tag, &doc.vector, 1.0)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Atomically add (`sign` = 1) or remove (`sign` = -1) one vector from a tag's sum
+    fn apply_centroid_delta(
+        &self,
+        collection_id: &str,
+        tag: &str,
+        vector: &[f32],
+        sign: f64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if vector.is_empty() {
+            return Ok(());
+        }
+        let key = centroid_key(collection_id, tag);
+        self.tag_centroid_tree.update_and_fetch(key.as_bytes(), |old| {
+            let mut centroid = old
+                .and_then(CentroidSum::decode)
+                .unwrap_or(CentroidSum { count: 0, sum: vec![0.0; vector.len()] });
+            if centroid.sum.len() != vector.len() {
+                // Dimension mismatch with the tag's existing members: leave the sum untouched
+                debug!(tag = %tag, "Skipping centroid update for vector of different dimension");
+                return old.map(|bytes| bytes.to_vec());
+            }
+            if sign < 0.0 && centroid.count == 0 {
+                return old.map(|bytes| bytes.to_vec());
+            }
+            centroid.count = if sign > 0.0 { centroid.count + 1 } else { centroid.count - 1 };
+            if centroid.count == 0 {
+                return None;
+            }
+            for (s, v) in centroid.sum.iter_mut().zip(vector) {
+                *s += sign * *v as f64;
+            }
+            Some(centroid.encode())
+        })?;
+        Ok(())
+    }
+
+    /// Mean vector of the documents in a collection tagged `tag`, if any
+    pub fn tag_centroid(&self, collection_id: &str, tag: &str) -> Result<Option<Vec<f32>>, Box<dyn std::error::Error>> {
+        Ok(self.tag_centroid_tree
+            .get(centroid_key(collection_id, tag).as_bytes())?
+            .and_then(|bytes| CentroidSum::decode(&bytes))
+            .map(|centroid| centroid.mean()))
+    }
+
+    /// Blend a query vector toward the centroids of `tags`:
+    /// `(1 - weight) * query + weight * mean(centroids)`. Unknown tags and centroids of a
+    /// different dimension are ignored; with none left the query is returned unchanged.
+    #[instrument(skip(self, query_vector), fields(collection_id))]
+    pub fn expand_query_with_tags(
+        &self,
+        collection_id: &str,
+        query_vector: &[f32],
+        tags: &[String],
+        weight: f32,
+    ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+        let mut centroids = Vec::new();
+        for tag in tags {
+            match self.tag_centroid(collection_id, tag)? {
+                Some(c) if c.len() == query_vector.len() => centroids.push(c),
+                _ => debug!(tag = %tag, "No usable centroid for expansion tag"),
+            }
+        }
+        if centroids.is_empty() {
+            return Ok(query_vector.to_vec());
+        }
+
+        let weight = weight.clamp(0.0, 1.0);
+        let n = centroids.len() as f32;
+        let expanded = query_vector
+            .iter()
+            .enumerate()
+            .map(|(i, q)| {
+                let centroid_mean = centroids.iter().map(|c| c[i]).sum::<f32>() / n;
+                (1.0 - weight) * q + weight * centroid_mean
+            })
+            .collect();
+
+        info!(collection_id = %collection_id, tags_used = centroids.len(), weight = weight, "Query expanded with tag centroids");
+        Ok(expanded)
+    }
+}
@@ -3,11 +3,14 @@ use arrow::datatypes::{DataType, Field, Schema};
 use arrow::ipc::reader::FileReader;
 use arrow::ipc::writer::FileWriter;
 use arrow::record_batch::RecordBatch;
+use rayon::prelude::*;
+use std::borrow::Cow;
 use std::io::Cursor;
 use std::sync::Arc;
 use tracing::{info, debug, warn, error, instrument};
 
-use crate::storage::Storage;
+use crate::cancel;
+use crate::storage::{Document, Storage};
 
 impl Storage {
     /// Insert an Arrow RecordBatch (metadata) and a vector for a given ID
@@ -17,8 +20,20 @@ impl Storage {
         id: &str,
         metadata_batch: RecordBatch,
         vector: Vec<f32>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.write_vector_entry(id, metadata_batch, &vector)?;
+        self.bump_generation_for_key(id)
+    }
+
+    /// `insert` without the generation bump, for callers that bump once for the whole write
+    pub(crate) fn write_vector_entry(
+        &self,
+        id: &str,
+        metadata_batch: RecordBatch,
+        vector: &[f32],
     ) -> Result<(), Box<dyn std::error::Error>> {
         debug!(id = %id, vector_len = vector.len(), "Inserting vector and metadata");
+        self.check_vector(DEFAULT_VECTOR_FIELD, vector)?;
         
         // Serialize metadata RecordBatch to IPC bytes
         let mut metadata_buf = Vec::new();
@@ -35,8 +50,8 @@ impl Storage {
             .collect();
 
         // Store with id as key in respective trees
-        self.metadata_tree.insert(id.as_bytes(), metadata_buf)?;
-        self.vector_tree.insert(id.as_bytes(), vector_bytes)?;
+        self.retry_write("insert_metadata", || self.metadata_tree.insert(id.as_bytes(), metadata_buf.as_slice()))?;
+        self.retry_write("insert_vector", || self.vector_tree.insert(id.as_bytes(), vector_bytes.as_slice()))?;
         
         debug!(id = %id, "Vector and metadata inserted successfully");
         Ok(())
@@ -80,30 +95,283 @@ impl Storage {
     }
 
     /// Get all vectors for indexing purposes (returns id and vector)
+    /// The Sled scan is sequential; decoding runs on the rayon pool once the scan reaches
+    /// `parallel_decode_threshold` entries. Output order matches key order either way.
     #[instrument(skip(self))]
     pub fn get_vectors_in_collection(&self, collection_id: &str) -> Result<Vec<(String, Vec<f32>)>, Box<dyn std::error::Error>> {
         debug!(collection_id = %collection_id, "Retrieving all vectors in collection");
         
-        let mut vectors = Vec::new();
         let prefix = format!("{}/", collection_id);
+        let _shared = self.collection_read_guard();
         // Vectors are in vector_tree. The key is same as doc key: col_id/doc_id
+        let mut entries = Vec::new();
         for item in self.vector_tree.scan_prefix(prefix.as_bytes()) {
-            let (k, v) = item?;
+            if entries.len().is_multiple_of(cancel::CHECK_INTERVAL) {
+                cancel::check()?;
+            }
+            entries.push(item?);
+        }
+
+        let decode = |(k, v): &(sled::IVec, sled::IVec)| -> Result<(String, Vec<f32>), std::string::FromUtf8Error> {
             let key_str = String::from_utf8(k.to_vec())?;
             // Extract doc_id from key "col_id/doc_id"
             let parts: Vec<&str> = key_str.split('/').collect();
-            let id = if parts.len() > 1 { parts[1].to_string() } else { key_str }; // fallback
+            let id = if parts.len() > 1 { parts[1].to_string() } else { key_str.clone() }; // fallback
+            Ok((id, decode_vector(v)))
+        };
+        let parallel = entries.len() >= self.parallel_decode_threshold;
+        let vectors = if parallel {
+            entries.par_iter().with_min_len(PARALLEL_DECODE_CHUNK).map(decode).collect::<Result<Vec<_>, _>>()?
+        } else {
+            entries.iter().map(decode).collect::<Result<Vec<_>, _>>()?
+        };
+        
+        info!(collection_id = %collection_id, count = vectors.len(), parallel = parallel, "Vectors retrieved");
+        Ok(vectors)
+    }
+}
+
+/// Default `parallel_decode_threshold`: smaller scans decode serially
+pub const DEFAULT_PARALLEL_DECODE_THRESHOLD: usize = 4096;
+/// Fewest entries one rayon task decodes, so tiny splits don't outweigh the work
+pub(crate) const PARALLEL_DECODE_CHUNK: usize = 512;
+
+/// Name of the implicit vector field backed by `Document::vector`
+pub const DEFAULT_VECTOR_FIELD: &str = "vector";
+
+/// Named vector fields must be non-empty, must not shadow the default field and must not
+/// contain '/', which separates the parts of a `field_vectors` key
+pub fn validate_vector_field(field: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if field.is_empty() || field == DEFAULT_VECTOR_FIELD || field.contains('/') {
+        return Err(format!("Invalid vector field name '{}'", field).into());
+    }
+    Ok(())
+}
+
+/// A vector component that is NaN/infinite, or beyond the configured magnitude bound
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidVector {
+    pub field: String,
+    pub index: usize,
+    pub value: f32,
+    /// The `AIDB_VECTOR_MAX_ABS` bound, when the component is finite but exceeds it
+    pub max_abs: Option<f32>,
+}
+
+impl std::fmt::Display for InvalidVector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.max_abs {
+            Some(max) => write!(f, "Vector '{}' component {} is {}, beyond the allowed magnitude {}", self.field, self.index, self.value, max),
+            None => write!(f, "Vector '{}' component {} is {}; components must be finite", self.field, self.index, self.value),
+        }
+    }
+}
+
+impl std::error::Error for InvalidVector {}
+
+/// A document vector whose length differs from its collection's vector dimension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DimensionMismatch {
+    pub collection_id: String,
+    pub doc_id: String,
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl std::fmt::Display for DimensionMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Vector of doc '{}' doesn't fit collection '{}': expected dim {}, got {}",
+            self.doc_id, self.collection_id, self.expected, self.actual
+        )
+    }
+}
 
-            let vec_bytes = v.to_vec();
-            let mut vector = Vec::new();
-            for chunk in vec_bytes.chunks_exact(4) {
-                let f = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
-                vector.push(f);
+impl std::error::Error for DimensionMismatch {}
+
+/// Scale `vector` to unit L2 norm; zero (and empty) vectors are left as they are
+pub fn l2_normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+}
+
+fn field_vector_key(collection_id: &str, field: &str, doc_id: &str) -> String {
+    format!("{}/{}/{}", collection_id, field, doc_id)
+}
+
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+impl Storage {
+    /// Override the component magnitude bound (defaults come from AIDB_VECTOR_MAX_ABS; `None` only rejects NaN/Inf)
+    pub fn with_vector_max_abs(mut self, max_abs: Option<f32>) -> Self {
+        self.vector_max_abs = max_abs;
+        self
+    }
+
+    /// Reject vectors with NaN/infinite components (or components beyond `AIDB_VECTOR_MAX_ABS`):
+    /// they poison distance computations and break index ordering
+    pub fn check_vector(&self, field: &str, vector: &[f32]) -> Result<(), InvalidVector> {
+        let invalid = |index: usize, value: f32, max_abs: Option<f32>| InvalidVector {
+            field: field.to_string(),
+            index,
+            value,
+            max_abs,
+        };
+        for (index, value) in vector.iter().copied().enumerate() {
+            if !value.is_finite() {
+                return Err(invalid(index, value, None));
+            }
+            if let Some(max) = self.vector_max_abs.filter(|max| value.abs() > *max) {
+                return Err(invalid(index, value, Some(max)));
             }
-            vectors.push((id, vector));
         }
-        
-        info!(collection_id = %collection_id, count = vectors.len(), "Vectors retrieved");
+        Ok(())
+    }
+
+    /// Reject documents whose vector length differs from the collection's dimension: the one
+    /// declared at creation or recorded by the first insert (this call records it for a registered
+    /// collection that has none yet), or for ad-hoc collection IDs the length of a vector already
+    /// stored by another document. Within `docs`, every vector must also match the first one.
+    /// Empty vectors (documents without an embedding) are not checked.
+    pub fn check_vector_dims(&self, collection_id: &str, docs: &[Document]) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(first) = docs.iter().find(|doc| !doc.vector.is_empty()) else {
+            return Ok(());
+        };
+        let expected = self.expected_vector_dim(collection_id, &first.id, first.vector.len())?;
+        for doc in docs.iter().filter(|doc| !doc.vector.is_empty()) {
+            if doc.vector.len() != expected {
+                return Err(Box::new(DimensionMismatch {
+                    collection_id: collection_id.to_string(),
+                    doc_id: doc.id.clone(),
+                    expected,
+                    actual: doc.vector.len(),
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Dimension `collection_id` requires, given a first vector of `dim` for `doc_id`
+    fn expected_vector_dim(&self, collection_id: &str, doc_id: &str, dim: usize) -> Result<usize, Box<dyn std::error::Error>> {
+        if let Some(col) = self.get_collection(collection_id)? {
+            return match col.vector_dim {
+                Some(existing) => Ok(existing),
+                // Read-only handles can't record; their writes fail anyway
+                None if self.read_only => Ok(dim),
+                None => Ok(self.record_vector_dim(collection_id, dim)?.dim),
+            };
+        }
+        let prefix = format!("{}/", collection_id);
+        let own_key = format!("{}{}", prefix, doc_id);
+        for item in self.vector_tree.scan_prefix(prefix.as_bytes()) {
+            let (key, value) = item?;
+            if !value.is_empty() && key.as_ref() != own_key.as_bytes() {
+                return Ok(value.len() / 4);
+            }
+        }
+        Ok(dim)
+    }
+
+    /// Validate every vector of a document: named field names, then component values
+    pub fn validate_doc_vectors(&self, doc: &Document) -> Result<(), Box<dyn std::error::Error>> {
+        self.check_vector(DEFAULT_VECTOR_FIELD, &doc.vector)?;
+        for (field, vector) in &doc.vectors {
+            validate_vector_field(field)?;
+            self.check_vector(field, vector)?;
+        }
+        Ok(())
+    }
+
+    /// L2-normalize a document's primary and named vectors in place if the collection asks for it
+    pub(crate) fn normalize_doc_vectors(&self, collection_id: &str, docs: &mut [Document]) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.collection_normalizes(collection_id)? {
+            return Ok(());
+        }
+        for doc in docs {
+            l2_normalize(&mut doc.vector);
+            doc.vectors.values_mut().for_each(|vector| l2_normalize(vector));
+        }
+        Ok(())
+    }
+
+    /// A query vector as the collection's indexes expect it: unit length if the collection
+    /// normalizes, otherwise unchanged
+    pub fn prepare_query_vector<'a>(&self, collection_id: &str, query_vector: &'a [f32]) -> Result<Cow<'a, [f32]>, Box<dyn std::error::Error>> {
+        if !self.collection_normalizes(collection_id)? {
+            return Ok(Cow::Borrowed(query_vector));
+        }
+        let mut normalized = query_vector.to_vec();
+        l2_normalize(&mut normalized);
+        Ok(Cow::Owned(normalized))
+    }
+
+    /// Write a document's named vectors, dropping fields the previous version had but this one lacks
+    #[instrument(skip(self, doc, previous), fields(id = %doc.id, collection_id))]
+    pub(crate) fn sync_field_vectors(
+        &self,
+        collection_id: &str,
+        doc: &Document,
+        previous: Option<&Document>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut batch = sled::Batch::default();
+        if let Some(prev) = previous {
+            for field in prev.vectors.keys().filter(|f| !doc.vectors.contains_key(*f)) {
+                batch.remove(field_vector_key(collection_id, field, &prev.id).as_bytes());
+            }
+        }
+        for (field, vector) in &doc.vectors {
+            batch.insert(field_vector_key(collection_id, field, &doc.id).as_bytes(), encode_vector(vector));
+        }
+        self.retry_write("sync_field_vectors", || self.field_vector_tree.apply_batch(batch.clone()))?;
+        debug!(id = %doc.id, fields = doc.vectors.len(), "Named vectors synced");
+        Ok(())
+    }
+
+    /// Remove every named vector of a document
+    pub(crate) fn remove_field_vectors(&self, collection_id: &str, doc: &Document) -> Result<(), Box<dyn std::error::Error>> {
+        for field in doc.vectors.keys() {
+            self.field_vector_tree.remove(field_vector_key(collection_id, field, &doc.id).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Get all (doc_id, vector) pairs of one named field; the default field reads `Document::vector`
+    #[instrument(skip(self))]
+    pub fn get_field_vectors_in_collection(
+        &self,
+        collection_id: &str,
+        field: &str,
+    ) -> Result<Vec<(String, Vec<f32>)>, Box<dyn std::error::Error>> {
+        if field == DEFAULT_VECTOR_FIELD {
+            return self.get_vectors_in_collection(collection_id);
+        }
+        validate_vector_field(field)?;
+
+        let prefix = format!("{}/{}/", collection_id, field);
+        let _shared = self.collection_read_guard();
+        let mut vectors = Vec::new();
+        for item in self.field_vector_tree.scan_prefix(prefix.as_bytes()) {
+            if vectors.len().is_multiple_of(cancel::CHECK_INTERVAL) {
+                cancel::check()?;
+            }
+            let (k, v) = item?;
+            let doc_id = String::from_utf8(k[prefix.len()..].to_vec())?;
+            vectors.push((doc_id, decode_vector(&v)));
+        }
+
+        info!(collection_id = %collection_id, field = %field, count = vectors.len(), "Field vectors retrieved");
         Ok(vectors)
     }
 }
@@ -129,3 +397,173 @@ pub fn create_metadata_batch(id: &str, text: &str) -> Result<RecordBatch, Box<dy
         ],
     )?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::test_doc;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::time::Instant;
+
+    #[test]
+    fn non_finite_vectors_are_rejected_on_insert_and_query() {
+        let path = std::env::temp_dir().join("aidb_test_non_finite_vectors");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap().with_vector_max_abs(None);
+        storage.insert_doc(test_doc("ok", vec![1.0, 0.0]), "col").unwrap();
+
+        let err = storage.insert_doc(test_doc("nan", vec![1.0, f32::NAN]), "col").unwrap_err();
+        let invalid = err.downcast_ref::<InvalidVector>().expect("typed rejection");
+        assert_eq!((invalid.field.as_str(), invalid.index), (DEFAULT_VECTOR_FIELD, 1));
+        assert!(storage.get_doc("col", "nan").is_err(), "nothing written");
+
+        let mut named = test_doc("named", vec![1.0, 0.0]);
+        named.vectors.insert("title".to_string(), vec![f32::NEG_INFINITY]);
+        assert!(storage.insert_docs(vec![named], "col").unwrap_err().downcast_ref::<InvalidVector>().is_some());
+
+        let err = storage.vector_search("col", &[f32::INFINITY, 0.0], 1).unwrap_err();
+        assert!(err.downcast_ref::<InvalidVector>().is_some());
+        assert_eq!(storage.vector_search("col", &[1.0, 0.0], 1).unwrap(), vec!["ok".to_string()]);
+
+        // An optional magnitude bound also rejects finite but oversized components
+        let bounded = storage.with_vector_max_abs(Some(100.0));
+        let err = bounded.insert_doc(test_doc("huge", vec![1e6, 0.0]), "col").unwrap_err();
+        assert_eq!(err.downcast_ref::<InvalidVector>().unwrap().max_abs, Some(100.0));
+
+        drop(bounded);
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn vectors_must_match_the_collection_dimension() {
+        let path = std::env::temp_dir().join("aidb_test_vector_dimension");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap();
+        let doc = |id: &str, dim: usize| test_doc(id, vec![0.5; dim]);
+        let collection = |id: &str, vector_dim: Option<usize>| crate::tenants::Collection {
+            id: id.to_string(),
+            name: id.to_string(),
+            environment_id: "env".to_string(),
+            vector_dim,
+            metric: Default::default(),
+            normalize: false,
+        };
+
+        // Declared at creation
+        storage.create_collection(collection("declared", Some(384))).unwrap();
+        let err = storage.insert_doc(doc("wide", 768), "declared").unwrap_err();
+        let mismatch = err.downcast_ref::<DimensionMismatch>().expect("typed rejection");
+        assert_eq!((mismatch.expected, mismatch.actual), (384, 768));
+        assert!(err.to_string().contains("expected dim 384, got 768"));
+        assert!(storage.get_doc("declared", "wide").is_err(), "nothing written");
+        storage.insert_doc(doc("fits", 384), "declared").unwrap();
+
+        // Recorded by the first insert, then enforced on inserts and updates
+        storage.create_collection(collection("recorded", None)).unwrap();
+        storage.insert_doc(doc("first", 3), "recorded").unwrap();
+        assert_eq!(storage.get_collection("recorded").unwrap().unwrap().vector_dim, Some(3));
+        assert!(storage.update_doc(doc("first", 4), "recorded").unwrap_err().is::<DimensionMismatch>());
+        assert!(storage.insert_docs(vec![doc("a", 3), doc("b", 2)], "recorded").unwrap_err().is::<DimensionMismatch>());
+
+        // Ad-hoc collection IDs follow the vectors already stored
+        storage.insert_doc(doc("first", 2), "adhoc").unwrap();
+        assert!(storage.insert_doc(doc("second", 5), "adhoc").unwrap_err().is::<DimensionMismatch>());
+        storage.insert_doc(doc("no_vector", 0), "adhoc").unwrap();
+
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn normalized_collections_rank_by_direction_not_magnitude() {
+        let path = std::env::temp_dir().join("aidb_test_vector_normalize");
+        let _ = fs::remove_dir_all(&path);
+        let storage = Storage::open(path.to_str().unwrap()).unwrap();
+        let doc = |id: &str, vector: Vec<f32>| Document {
+            vectors: HashMap::from([("title".to_string(), vector.clone())]),
+            ..test_doc(id, vector)
+        };
+        for (id, normalize) in [("raw", false), ("unit", true)] {
+            storage.create_collection(crate::tenants::Collection {
+                id: id.to_string(),
+                name: id.to_string(),
+                environment_id: "env".to_string(),
+                vector_dim: None,
+                metric: Default::default(),
+                normalize,
+            }).unwrap();
+            storage.insert_docs(vec![doc("long", vec![10.0, 0.0]), doc("short", vec![0.6, 0.8])], id).unwrap();
+        }
+
+        // Raw L2 is dominated by the long vector's magnitude; normalized, the query points its way
+        let query = [0.9, 0.1];
+        assert_eq!(storage.vector_search("raw", &query, 2).unwrap(), vec!["short".to_string(), "long".to_string()]);
+        assert_eq!(storage.vector_search("unit", &query, 2).unwrap(), vec!["long".to_string(), "short".to_string()]);
+
+        // Stored vectors (primary and named, on insert and update) are unit length
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let stored = storage.get_doc("unit", "long").unwrap();
+        assert_eq!(stored.vector, vec![1.0, 0.0]);
+        assert!((norm(&stored.vectors["title"]) - 1.0).abs() < 1e-6);
+        storage.update_doc(doc("short", vec![3.0, 4.0]), "unit").unwrap();
+        assert!((norm(&storage.get_doc("unit", "short").unwrap().vector) - 1.0).abs() < 1e-6);
+        assert_eq!(storage.get_doc("raw", "long").unwrap().vector, vec![10.0, 0.0]);
+
+        let _ = fs::remove_dir_all(&path);
+    }
+
+    fn open_with_vectors(name: &str, count: usize, dim: usize) -> (Storage, std::path::PathBuf) {
+        let temp_dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).expect("open storage");
+        let docs = (0..count)
+            .map(|i| test_doc(&format!("doc{:06}", i), (0..dim).map(|d| (i * dim + d) as f32).collect()))
+            .collect();
+        storage.insert_docs(docs, "col").expect("insert");
+        (storage, temp_dir)
+    }
+
+    #[test]
+    fn parallel_decode_matches_serial() {
+        let (storage, temp_dir) = open_with_vectors("aidb_test_parallel_decode", 3000, 8);
+        let serial = storage.clone().with_parallel_decode_threshold(usize::MAX);
+        let parallel = storage.with_parallel_decode_threshold(0);
+
+        let serial_vectors = serial.get_vectors_in_collection("col").unwrap();
+        assert_eq!(serial_vectors.len(), 3000);
+        assert_eq!(parallel.get_vectors_in_collection("col").unwrap(), serial_vectors);
+        assert_eq!(serial_vectors[42], ("doc000042".to_string(), (336..344).map(|v| v as f32).collect::<Vec<_>>()));
+
+        let serial_docs = serial.get_docs_in_collection("col").unwrap();
+        let parallel_docs = parallel.get_docs_in_collection("col").unwrap();
+        let ids = |docs: &[Document]| docs.iter().map(|d| d.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&parallel_docs), ids(&serial_docs));
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    /// Timing-sensitive, so opt-in: `cargo test --release -- --ignored parallel_decode_speedup`
+    #[test]
+    #[ignore]
+    fn parallel_decode_speedup_on_large_collection() {
+        if std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) < 2 {
+            return;
+        }
+        let (storage, temp_dir) = open_with_vectors("aidb_test_parallel_decode_bench", 50_000, 256);
+        let serial = storage.clone().with_parallel_decode_threshold(usize::MAX);
+        let parallel = storage.with_parallel_decode_threshold(0);
+        // Warm Sled's page cache so both paths measure decoding, not I/O
+        serial.get_docs_in_collection("col").unwrap();
+
+        let time = |s: &Storage| {
+            let start = Instant::now();
+            s.get_docs_in_collection("col").unwrap();
+            start.elapsed()
+        };
+        let serial_time = (0..3).map(|_| time(&serial)).min().unwrap();
+        let parallel_time = (0..3).map(|_| time(&parallel)).min().unwrap();
+        assert!(parallel_time < serial_time, "parallel {:?} vs serial {:?}", parallel_time, serial_time);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
@@ -0,0 +1,108 @@
+//! Write-ahead log of index deltas
+//!
+//! Documents inserted under new IDs are added to the cached primary index instead of making it
+//! stale (`Storage::extend_primary_index`), so once an index has been checkpointed the cached copy
+//! runs ahead of the persisted one. Each such extension of a checkpointed index is also appended
+//! to the `index_wal` tree, keyed by the index's cache key and the generation the insert moved its
+//! collection to, with the added points and the collection's content fingerprint before and after.
+//!
+//! When a checkpoint older than its collection is loaded, the records after it are replayed into
+//! the loaded index's `pending` buffer, but only if they form an unbroken chain: one record per
+//! generation up to the current one, each starting from the fingerprint the previous one (or the
+//! checkpoint) ended at and the last ending at the collection's current fingerprint. Any other
+//! write in between (an update, a delete, an insert the cache couldn't take or whose record was
+//! lost in a crash) breaks the chain and the index is rebuilt from Sled as before, so a record can
+//! save a rebuild but never change what a search returns. Writing a checkpoint drops the records
+//! it covers, and deleting a collection drops all of its records.
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::storage::generation::GenerationBump;
+use crate::storage::Storage;
+
+/// (id, vector) pairs added to an index
+pub(crate) type IndexDelta = Vec<(String, Vec<f32>)>;
+
+/// Points one insert added to an index, and the collection fingerprints around it
+#[derive(Debug, Serialize, Deserialize)]
+struct WalRecord {
+    previous_fingerprint: u64,
+    fingerprint: u64,
+    points: IndexDelta,
+}
+
+/// Record key: the index cache key, a NUL (so `col/vector` records never share a prefix with
+/// `col/vector2` ones) and the generation (big-endian, so records scan in generation order)
+fn wal_prefix(cache_key: &str) -> Vec<u8> {
+    let mut prefix = cache_key.as_bytes().to_vec();
+    prefix.push(0);
+    prefix
+}
+
+fn wal_key(cache_key: &str, generation: u64) -> Vec<u8> {
+    let mut key = wal_prefix(cache_key);
+    key.extend_from_slice(&generation.to_be_bytes());
+    key
+}
+
+impl Storage {
+    /// Log the points an insert added to the cached index `cache_key`
+    pub(crate) fn log_index_delta(
+        &self,
+        cache_key: &str,
+        bump: GenerationBump,
+        points: IndexDelta,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let record = WalRecord { previous_fingerprint: bump.previous_fingerprint, fingerprint: bump.fingerprint, points };
+        let value = serde_json::to_vec(&record)?;
+        self.retry_write("log_index_delta", || self.index_wal_tree.insert(wal_key(cache_key, bump.generation), value.as_slice()))?;
+        Ok(())
+    }
+
+    /// Points logged for `cache_key` between a checkpoint taken at generation `from` with
+    /// fingerprint `from_fingerprint` and the collection's current `to` / `to_fingerprint`, in
+    /// write order; `None` unless the records cover every generation in between as one chain
+    pub(crate) fn replay_index_wal(
+        &self,
+        cache_key: &str,
+        (from, from_fingerprint): (u64, u64),
+        (to, to_fingerprint): (u64, u64),
+    ) -> Result<Option<IndexDelta>, Box<dyn std::error::Error>> {
+        let mut points = Vec::new();
+        let (mut generation, mut fingerprint) = (from, from_fingerprint);
+        for item in self.index_wal_tree.range(wal_key(cache_key, from + 1)..=wal_key(cache_key, to)) {
+            let (key, value) = item?;
+            let Ok(record) = serde_json::from_slice::<WalRecord>(&value) else {
+                warn!(cache_key = %cache_key, "Ignoring unreadable index WAL record");
+                return Ok(None);
+            };
+            generation += 1;
+            let logged_at = key.len().checked_sub(8).and_then(|at| key[at..].try_into().ok()).map(u64::from_be_bytes);
+            if logged_at != Some(generation) || record.previous_fingerprint != fingerprint {
+                debug!(cache_key = %cache_key, generation = generation, "Index WAL chain broken");
+                return Ok(None);
+            }
+            fingerprint = record.fingerprint;
+            points.extend(record.points);
+        }
+        Ok((generation == to && fingerprint == to_fingerprint).then_some(points))
+    }
+
+    /// Drop the records of `cache_key` up to and including `generation` (covered by a checkpoint)
+    pub(crate) fn prune_index_wal(&self, cache_key: &str, generation: u64) -> Result<(), Box<dyn std::error::Error>> {
+        for key in self.index_wal_tree.range(wal_prefix(cache_key)..=wal_key(cache_key, generation)).keys() {
+            self.index_wal_tree.remove(key?)?;
+        }
+        Ok(())
+    }
+
+    /// Drop every record of a collection's indexes
+    pub(crate) fn remove_index_wal(&self, collection_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let prefix = format!("{}/", collection_id);
+        for key in self.index_wal_tree.scan_prefix(prefix.as_bytes()).keys() {
+            self.index_wal_tree.remove(key?)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+//! Boot-time index warm-up
+//!
+//! Indexes are built (or loaded from a checkpoint) on the first search of each collection, so
+//! the first queries after a restart pay for it. With `AIDB_WARM_INDEXES=1` the server warms every
+//! collection's primary index in the background right after startup, the same way a search
+//! would: a current checkpoint is loaded, anything else is built. Searches arriving while a
+//! collection is being warmed join that build instead of starting their own.
+//!
+//! Warm-up stops starting new collections once the indexes it warmed reach
+//! `AIDB_WARM_BUDGET_MB` (estimated from each index's `approx_memory_bytes`); collections in
+//! flight at that point still finish, so the budget can be overshot by up to
+//! `AIDB_WARM_CONCURRENCY - 1` indexes. Collections are warmed in ID order, and builds also
+//! count against `AIDB_MAX_INDEX_BUILDS`.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tracing::{info, debug, warn, error, instrument};
+
+use crate::storage::{Storage, DEFAULT_VECTOR_FIELD};
+
+/// Worker threads used when `AIDB_WARM_CONCURRENCY` is unset
+pub const DEFAULT_WARM_CONCURRENCY: usize = 2;
+
+/// Outcome of one `Storage::warm_indexes` pass
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WarmReport {
+    /// Collections whose primary index is now cached
+    pub warmed: Vec<String>,
+    /// Collections left cold because the memory budget was used up
+    pub skipped: Vec<String>,
+    /// Collections whose index could not be loaded or built (logged)
+    pub failed: Vec<String>,
+    /// Estimated memory of the warmed indexes
+    pub approx_memory_bytes: u64,
+}
+
+impl Storage {
+    /// Load or build the primary index of every collection with documents, `concurrency`
+    /// collections at a time, until the warmed indexes reach `budget_bytes` (`None` = no budget)
+    #[instrument(skip(self))]
+    pub fn warm_indexes(&self, budget_bytes: Option<u64>, concurrency: usize) -> Result<WarmReport, Box<dyn std::error::Error>> {
+        let collections: VecDeque<String> = self.collections_with_docs()?.into_iter().map(|(id, _)| id).collect();
+        debug!(collections = collections.len(), "Warming vector indexes");
+        let pending = Mutex::new(collections);
+        let report = Mutex::new(WarmReport::default());
+
+        std::thread::scope(|scope| {
+            for _ in 0..concurrency.max(1) {
+                scope.spawn(|| loop {
+                    let Some(collection_id) = pending.lock().unwrap_or_else(|p| p.into_inner()).pop_front() else {
+                        return;
+                    };
+                    let over_budget = budget_bytes
+                        .is_some_and(|budget| report.lock().unwrap_or_else(|p| p.into_inner()).approx_memory_bytes >= budget);
+                    if over_budget {
+                        report.lock().unwrap_or_else(|p| p.into_inner()).skipped.push(collection_id);
+                        continue;
+                    }
+                    let outcome = self.cached_field_index(&collection_id, DEFAULT_VECTOR_FIELD);
+                    let mut report = report.lock().unwrap_or_else(|p| p.into_inner());
+                    match outcome {
+                        Ok(index) => {
+                            debug!(collection_id = %collection_id, vectors = index.len(), "Vector index warmed");
+                            report.approx_memory_bytes += index.approx_memory_bytes() as u64;
+                            report.warmed.push(collection_id);
+                        }
+                        Err(e) => {
+                            warn!(collection_id = %collection_id, error = %e, "Failed to warm vector index");
+                            report.failed.push(collection_id);
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut report = report.into_inner().unwrap_or_else(|p| p.into_inner());
+        report.warmed.sort();
+        report.skipped.sort();
+        report.failed.sort();
+        info!(
+            warmed = report.warmed.len(),
+            skipped = report.skipped.len(),
+            failed = report.failed.len(),
+            approx_memory_bytes = report.approx_memory_bytes,
+            "Index warm-up completed"
+        );
+        Ok(report)
+    }
+
+    /// Run `warm_indexes` on a blocking thread of the current Tokio runtime
+    pub fn spawn_index_warmer(&self, budget_bytes: Option<u64>, concurrency: usize) -> tokio::task::JoinHandle<()> {
+        let storage = self.clone();
+        info!(budget_bytes = ?budget_bytes, concurrency = concurrency, "Index warm-up starting");
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = storage.warm_indexes(budget_bytes, concurrency) {
+                error!(error = %e, "Index warm-up failed");
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::{test_doc, Storage, DEFAULT_VECTOR_FIELD};
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn warmed_indexes_serve_the_first_search_without_a_build() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_warm_indexes");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Storage::open(temp_dir.to_str().unwrap()).unwrap();
+        storage.insert_docs(vec![test_doc("a", vec![1.0, 0.0]), test_doc("b", vec![0.0, 1.0])], "col_a").unwrap();
+        storage.insert_docs(vec![test_doc("c", vec![1.0, 1.0])], "col_b").unwrap();
+        assert_eq!(storage.index_builds(), 0, "nothing is indexed before the first search");
+
+        let report = storage.warm_indexes(None, 2).unwrap();
+        assert_eq!(report.warmed, vec!["col_a".to_string(), "col_b".to_string()]);
+        assert!(report.skipped.is_empty() && report.failed.is_empty());
+        assert!(report.approx_memory_bytes > 0);
+        assert_eq!(storage.index_builds(), 2);
+
+        // The first search uses the warmed index as is
+        let warmed = storage.cached_field_index("col_a", DEFAULT_VECTOR_FIELD).unwrap();
+        assert_eq!(storage.vector_search("col_a", &[1.0, 0.1], 1).unwrap(), vec!["a".to_string()]);
+        assert_eq!(storage.index_builds(), 2, "no build on the first search");
+        assert!(Arc::ptr_eq(&warmed, &storage.cached_field_index("col_a", DEFAULT_VECTOR_FIELD).unwrap()));
+
+        // A used-up budget leaves the remaining collections cold
+        storage.lock_index_cache().clear();
+        let report = storage.warm_indexes(Some(1), 1).unwrap();
+        assert_eq!((report.warmed, report.skipped), (vec!["col_a".to_string()], vec!["col_b".to_string()]));
+        assert_eq!(storage.index_builds(), 3);
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
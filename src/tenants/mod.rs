@@ -1,12 +1,64 @@
 use serde::{Deserialize, Serialize};
 
+use std::collections::BTreeMap;
+
+use crate::indexing::{DistanceMetric, HnswParams};
+
 pub mod storage;
 
+/// What a user may do; each role includes the ones below it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Searches, queries and reads
+    Reader,
+    /// Plus document inserts, updates and deletes
+    Writer,
+    /// Plus tenant/environment/collection management and `/admin` endpoints
+    Admin,
+}
+
+impl Role {
+    /// Whether holding `self` allows what `required` allows
+    pub fn grants(self, required: Role) -> bool {
+        self >= required
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
     pub username: String,
     pub password_hash: String,
     pub tenants: Vec<String>,
+    /// Roles granted to the user; records written before roles existed have none (see `effective_roles`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<Role>,
+    /// Current workspace used when a request omits the tenant/environment/collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<WorkspaceContext>,
+}
+
+impl User {
+    /// Roles to put in the user's tokens. Users created before roles existed keep writing, but
+    /// administering (snapshots, restores, role changes) takes an explicit grant.
+    pub fn effective_roles(&self) -> Vec<Role> {
+        if self.roles.is_empty() {
+            vec![Role::Writer]
+        } else {
+            self.roles.clone()
+        }
+    }
+}
+
+/// A user's current workspace (set via `PUT /me/context`); each level is optional
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceContext {
+    #[serde(default)]
+    pub tenant_id: Option<String>,
+    #[serde(default)]
+    pub environment_id: Option<String>,
+    #[serde(default)]
+    pub collection_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,11 +82,150 @@ pub struct Collection {
     pub id: String,
     pub name: String,
     pub environment_id: String,
+    /// Vector dimension, recorded by the first insert when not set at creation
+    #[serde(default)]
+    pub vector_dim: Option<usize>,
+    /// Similarity metric configured for the collection
+    #[serde(default)]
+    pub metric: DistanceMetric,
+    /// L2-normalize document vectors on write and query vectors on search; fixed at creation so
+    /// stored and query vectors are always treated alike
+    #[serde(default)]
+    pub normalize: bool,
 }
 
+/// Effective vector configuration of a collection, echoed back on insert
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectionVectorConfig {
+    pub dim: usize,
+    pub metric: DistanceMetric,
+}
+
+/// Where an effective collection setting comes from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    /// Stored on the collection record
+    Collection,
+    /// Not set per collection; inherited from the server-wide default
+    Server,
+}
+
+/// Resolved configuration of a collection (`GET /environments/:env_id/collections/:col_id/config`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EffectiveCollectionConfig {
+    pub collection_id: String,
+    pub environment_id: String,
+    /// `None` until set at creation or recorded by the first insert
+    pub vector_dim: Option<usize>,
+    pub metric: DistanceMetric,
+    pub hnsw: HnswParams,
+    pub read_only: bool,
+    /// Whether vectors are L2-normalized before storage and search
+    pub normalize: bool,
+    /// Source of each reported setting, keyed by field name (`vector_dim` only once known)
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+/// Longest tenant, environment or collection ID accepted on create
+pub const MAX_HIERARCHY_ID_LEN: usize = 64;
+
+/// A tenant, environment or collection ID that can't serve as a Sled key segment (keys are
+/// `collection/doc`) or a URL path segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidHierarchyId {
+    /// `tenant`, `environment` or `collection`
+    pub kind: &'static str,
+    pub id: String,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for InvalidHierarchyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid {} ID '{}': {}", self.kind, self.id, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidHierarchyId {}
+
+/// Normalize (trim surrounding whitespace) and check an ID being created: 1 to
+/// `MAX_HIERARCHY_ID_LEN` ASCII letters, digits, `_`, `-` or `.`, starting with a letter or digit
+pub fn validate_hierarchy_id(kind: &'static str, id: &str) -> Result<String, InvalidHierarchyId> {
+    let normalized = id.trim();
+    let invalid = |reason| InvalidHierarchyId { kind, id: id.to_string(), reason };
+    if normalized.is_empty() {
+        return Err(invalid("must not be empty"));
+    }
+    if normalized.len() > MAX_HIERARCHY_ID_LEN {
+        return Err(invalid("longer than 64 characters"));
+    }
+    if !normalized.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        return Err(invalid("must start with a letter or digit"));
+    }
+    if !normalized.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')) {
+        return Err(invalid("only letters, digits, '_', '-' and '.' are allowed"));
+    }
+    Ok(normalized.to_string())
+}
+
+/// Why a get-or-create of a collection can't be answered with an existing or new collection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollectionUpsertError {
+    /// The environment the collection would be created in doesn't exist
+    UnknownEnvironment { environment_id: String },
+    /// A collection with this ID already exists in another environment
+    OtherEnvironment { collection_id: String, environment_id: String },
+}
+
+impl std::fmt::Display for CollectionUpsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownEnvironment { environment_id } => write!(f, "Environment '{}' not found", environment_id),
+            Self::OtherEnvironment { collection_id, environment_id } => {
+                write!(f, "Collection '{}' already exists in environment '{}'", collection_id, environment_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CollectionUpsertError {}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AuthPayload {
     pub sub: String, // username
     pub exp: usize,
     pub session_id: Option<String>, // Session ID for log tracking
+    /// Issue time (seconds since the epoch), for auditing; absent on tokens minted before it existed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    /// Roles of the user when the token was issued; a token without any only reads
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<Role>,
+}
+
+impl AuthPayload {
+    /// Whether any of the token's roles allows what `role` allows (every token may read)
+    pub fn has_role(&self, role: Role) -> bool {
+        role == Role::Reader || self.roles.iter().any(|held| held.grants(role))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hierarchy_ids_are_slugs() {
+        for valid in ["docs", "team-a", "prod_2024", "v1.2", "A1"] {
+            assert_eq!(validate_hierarchy_id("collection", valid).as_deref(), Ok(valid));
+        }
+        assert_eq!(validate_hierarchy_id("tenant", "  acme \n").as_deref(), Ok("acme"));
+        assert_eq!(validate_hierarchy_id("collection", &"a".repeat(MAX_HIERARCHY_ID_LEN)).map(|id| id.len()), Ok(64));
+
+        for invalid in ["", "   ", "a/b", "my docs", "../etc", ".hidden", "-flag", "naïve", "tab\tid"] {
+            let err = validate_hierarchy_id("environment", invalid).unwrap_err();
+            assert_eq!((err.kind, err.id.as_str()), ("environment", invalid));
+        }
+        assert!(validate_hierarchy_id("collection", &"a".repeat(MAX_HIERARCHY_ID_LEN + 1)).is_err());
+    }
 }
@@ -1,21 +1,27 @@
+use crate::indexing::DistanceMetric;
 use crate::storage::Storage;
-use crate::tenants::{Collection, Environment, Tenant, User};
+use crate::tenants::{
+    Collection, CollectionUpsertError, CollectionVectorConfig, ConfigSource, EffectiveCollectionConfig, Environment, Role, Tenant, User,
+    WorkspaceContext,
+};
 use serde_json;
 use tracing::{info, debug, warn, instrument};
 
+/// Key in the default tree naming the user who bootstrapped the database as its admin
+const BOOTSTRAP_ADMIN_KEY: &[u8] = b"bootstrap_admin";
+
 impl Storage {
     // User CRUD
     #[instrument(skip(self, user), fields(username = %user.username))]
     pub fn create_user(&self, user: User) -> Result<(), Box<dyn std::error::Error>> {
         debug!(username = %user.username, "Creating user");
+        self.ensure_writable()?;
         
-        let key = user.username.as_bytes();
-        if self.user_tree.contains_key(key)? {
+        let value = serde_json::to_vec(&user)?;
+        if self.user_tree.compare_and_swap(user.username.as_bytes(), None as Option<&[u8]>, Some(value))?.is_err() {
             warn!(username = %user.username, "User already exists");
             return Err("User already exists".into());
         }
-        let value = serde_json::to_vec(&user)?;
-        self.user_tree.insert(key, value)?;
         
         info!(username = %user.username, "User created successfully");
         Ok(())
@@ -41,6 +47,7 @@ impl Storage {
     #[instrument(skip(self, user), fields(username = %user.username))]
     pub fn update_user(&self, user: User) -> Result<(), Box<dyn std::error::Error>> {
         debug!(username = %user.username, "Updating user");
+        self.ensure_writable()?;
         
         let value = serde_json::to_vec(&user)?;
         self.user_tree.insert(user.username.as_bytes(), value)?;
@@ -49,10 +56,47 @@ impl Storage {
         Ok(())
     }
 
+    /// Create a self-registered user (any `roles` are replaced) and return it. The first user of
+    /// an empty database administers it; everyone after reads until an admin grants more. The
+    /// admin is claimed with a compare-and-swap on a marker key, so of several concurrent first
+    /// registrations exactly one becomes admin.
+    #[instrument(skip(self, user), fields(username = %user.username))]
+    pub fn register_user(&self, mut user: User) -> Result<User, Box<dyn std::error::Error>> {
+        self.ensure_writable()?;
+        let bootstrap = self.user_tree.is_empty()
+            && self.db.compare_and_swap(BOOTSTRAP_ADMIN_KEY, None as Option<&[u8]>, Some(user.username.as_bytes()))?.is_ok();
+        user.roles = vec![if bootstrap { Role::Admin } else { Role::Reader }];
+        if let Err(e) = self.create_user(user.clone()) {
+            if bootstrap {
+                self.db.remove(BOOTSTRAP_ADMIN_KEY)?;
+            }
+            return Err(e);
+        }
+        if bootstrap {
+            info!(username = %user.username, "First user registered as admin");
+        }
+        Ok(user)
+    }
+
+    /// Replace a user's roles (effective from their next login); `None` if there is no such user
+    #[instrument(skip(self))]
+    pub fn set_user_roles(&self, username: &str, roles: Vec<Role>) -> Result<Option<User>, Box<dyn std::error::Error>> {
+        let Some(mut user) = self.get_user(username)? else {
+            return Ok(None);
+        };
+        if roles.is_empty() {
+            return Err("A user needs at least one role".into());
+        }
+        user.roles = roles;
+        self.update_user(user.clone())?;
+        Ok(Some(user))
+    }
+
     // Tenant CRUD
     #[instrument(skip(self, tenant), fields(tenant_id = %tenant.id))]
     pub fn create_tenant(&self, tenant: Tenant) -> Result<(), Box<dyn std::error::Error>> {
         debug!(tenant_id = %tenant.id, name = %tenant.name, "Creating tenant");
+        self.ensure_writable()?;
         
         let value = serde_json::to_vec(&tenant)?;
         self.tenant_tree.insert(tenant.id.as_bytes(), value)?;
@@ -81,6 +125,7 @@ impl Storage {
     #[instrument(skip(self, tenant), fields(tenant_id = %tenant.id))]
     pub fn update_tenant(&self, tenant: Tenant) -> Result<(), Box<dyn std::error::Error>> {
         debug!(tenant_id = %tenant.id, "Updating tenant");
+        self.ensure_writable()?;
         
         let value = serde_json::to_vec(&tenant)?;
         self.tenant_tree.insert(tenant.id.as_bytes(), value)?;
@@ -93,6 +138,7 @@ impl Storage {
     #[instrument(skip(self, env), fields(env_id = %env.id))]
     pub fn create_environment(&self, env: Environment) -> Result<(), Box<dyn std::error::Error>> {
         debug!(env_id = %env.id, tenant_id = %env.tenant_id, "Creating environment");
+        self.ensure_writable()?;
         
         let value = serde_json::to_vec(&env)?;
         self.env_tree.insert(env.id.as_bytes(), value)?;
@@ -121,6 +167,7 @@ impl Storage {
     #[instrument(skip(self, env), fields(env_id = %env.id))]
     pub fn update_environment(&self, env: Environment) -> Result<(), Box<dyn std::error::Error>> {
         debug!(env_id = %env.id, "Updating environment");
+        self.ensure_writable()?;
         
         let value = serde_json::to_vec(&env)?;
         self.env_tree.insert(env.id.as_bytes(), value)?;
@@ -133,6 +180,7 @@ impl Storage {
     #[instrument(skip(self, col), fields(collection_id = %col.id))]
     pub fn create_collection(&self, col: Collection) -> Result<(), Box<dyn std::error::Error>> {
         debug!(collection_id = %col.id, env_id = %col.environment_id, "Creating collection");
+        self.ensure_writable()?;
         
         let value = serde_json::to_vec(&col)?;
         self.collection_tree.insert(col.id.as_bytes(), value)?;
@@ -141,6 +189,224 @@ impl Storage {
         Ok(())
     }
 
+    /// Return the collection if it exists, otherwise create it.
+    /// Uses a Sled compare-and-swap on the collection key so concurrent callers
+    /// converge on a single stored collection; only the winner links it into its environment.
+    /// Returns the stored collection and whether this call created it; fails with a
+    /// `CollectionUpsertError` if the environment doesn't exist or the collection lives in another one.
+    #[instrument(skip(self, col), fields(collection_id = %col.id))]
+    pub fn get_or_create_collection(&self, col: Collection) -> Result<(Collection, bool), Box<dyn std::error::Error>> {
+        debug!(collection_id = %col.id, env_id = %col.environment_id, "Get or create collection");
+        self.ensure_writable()?;
+        if self.get_environment(&col.environment_id)?.is_none() {
+            return Err(CollectionUpsertError::UnknownEnvironment { environment_id: col.environment_id }.into());
+        }
+
+        let value = serde_json::to_vec(&col)?;
+        match self.collection_tree.compare_and_swap(col.id.as_bytes(), None as Option<&[u8]>, Some(value))? {
+            Ok(()) => {
+                if let Some(mut env) = self.get_environment(&col.environment_id)? {
+                    if !env.collections.contains(&col.id) {
+                        env.collections.push(col.id.clone());
+                        self.update_environment(env)?;
+                    }
+                }
+                info!(collection_id = %col.id, "Collection created via get_or_create");
+                Ok((col, true))
+            }
+            Err(cas_err) => {
+                let current = cas_err.current.ok_or("Collection vanished during get_or_create")?;
+                let existing: Collection = serde_json::from_slice(&current)?;
+                if existing.environment_id != col.environment_id {
+                    warn!(collection_id = %existing.id, env_id = %existing.environment_id, "Collection exists in another environment");
+                    return Err(CollectionUpsertError::OtherEnvironment {
+                        collection_id: existing.id,
+                        environment_id: existing.environment_id,
+                    }
+                    .into());
+                }
+                debug!(collection_id = %existing.id, "Collection already exists");
+                Ok((existing, false))
+            }
+        }
+    }
+
+    /// Record `dim` as the collection's vector dimension if none is set yet (first insert wins,
+    /// via compare-and-swap) and return the now-effective vector config.
+    /// Collections without a stored record (ad-hoc collection IDs) report the inserted dimension
+    /// and the configured default metric, which their indexes rank by.
+    #[instrument(skip(self), fields(collection_id))]
+    pub fn record_vector_dim(&self, collection_id: &str, dim: usize) -> Result<CollectionVectorConfig, Box<dyn std::error::Error>> {
+        loop {
+            let Some(current) = self.collection_tree.get(collection_id.as_bytes())? else {
+                return Ok(CollectionVectorConfig { dim, metric: self.default_metric });
+            };
+            let mut col: Collection = serde_json::from_slice(&current)?;
+            if let Some(existing) = col.vector_dim {
+                return Ok(CollectionVectorConfig { dim: existing, metric: col.metric });
+            }
+            if dim == 0 {
+                return Ok(CollectionVectorConfig { dim, metric: col.metric });
+            }
+
+            col.vector_dim = Some(dim);
+            let updated = serde_json::to_vec(&col)?;
+            if self.collection_tree
+                .compare_and_swap(collection_id.as_bytes(), Some(current), Some(updated))?
+                .is_ok()
+            {
+                info!(collection_id = %collection_id, dim = dim, "Collection vector dimension recorded");
+                return Ok(CollectionVectorConfig { dim, metric: col.metric });
+            }
+            debug!(collection_id = %collection_id, "Collection changed concurrently, retrying dim record");
+        }
+    }
+
+    /// Metric the collection's indexes rank by; the configured default (`AIDB_DEFAULT_METRIC`, L2
+    /// unless set) for ad-hoc collection IDs
+    pub fn collection_metric(&self, collection_id: &str) -> Result<DistanceMetric, Box<dyn std::error::Error>> {
+        Ok(self.get_collection(collection_id)?.map(|col| col.metric).unwrap_or(self.default_metric))
+    }
+
+    /// Whether the collection L2-normalizes stored and query vectors; never for ad-hoc collection IDs
+    pub fn collection_normalizes(&self, collection_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.get_collection(collection_id)?.is_some_and(|col| col.normalize))
+    }
+
+    /// Collection IDs reachable from a user's tenants and their environments (deduplicated,
+    /// in hierarchy order), stopping after `limit`. Unknown users have no collections.
+    #[instrument(skip(self))]
+    pub fn accessible_collections(&self, username: &str, limit: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut collections = Vec::new();
+        let Some(user) = self.get_user(username)? else {
+            return Ok(collections);
+        };
+        for tenant_id in &user.tenants {
+            let Some(tenant) = self.get_tenant(tenant_id)? else { continue };
+            for env_id in &tenant.environments {
+                let Some(env) = self.get_environment(env_id)? else { continue };
+                for col_id in env.collections {
+                    if collections.len() >= limit {
+                        return Ok(collections);
+                    }
+                    if !collections.contains(&col_id) {
+                        collections.push(col_id);
+                    }
+                }
+            }
+        }
+        debug!(username = %username, count = collections.len(), "Accessible collections resolved");
+        Ok(collections)
+    }
+
+    /// Tenant owning a collection, found by walking collection → environment → tenant. `None`
+    /// for ad-hoc collection IDs without a record and for records whose environment or tenant
+    /// no longer exists; such collections are unowned.
+    #[instrument(skip(self))]
+    pub fn collection_owner(&self, collection_id: &str) -> Result<Option<Tenant>, Box<dyn std::error::Error>> {
+        let Some(col) = self.get_collection(collection_id)? else {
+            return Ok(None);
+        };
+        let Some(env) = self.get_environment(&col.environment_id)? else {
+            debug!(collection_id = %collection_id, env_id = %col.environment_id, "Collection environment missing; treating as unowned");
+            return Ok(None);
+        };
+        self.get_tenant(&env.tenant_id)
+    }
+
+    /// Whether `username` may use a collection: it is unowned (see `collection_owner`), the user
+    /// owns its tenant, or the tenant is among the user's granted `tenants`
+    pub fn can_access_collection(&self, username: &str, collection_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(tenant) = self.collection_owner(collection_id)? else {
+            return Ok(true);
+        };
+        if tenant.owner_id == username {
+            return Ok(true);
+        }
+        Ok(self.get_user(username)?.is_some_and(|user| user.tenants.contains(&tenant.id)))
+    }
+
+    /// Validate and store a user's workspace context. Each level that is set must be reachable
+    /// by the user and belong to the level above it (when that level is set too).
+    #[instrument(skip(self, context))]
+    pub fn set_user_context(&self, username: &str, context: WorkspaceContext) -> Result<WorkspaceContext, Box<dyn std::error::Error>> {
+        let mut user = self.get_user(username)?.ok_or("User not found")?;
+
+        if let Some(tenant_id) = &context.tenant_id {
+            if !user.tenants.contains(tenant_id) {
+                return Err(format!("Tenant '{}' is not accessible", tenant_id).into());
+            }
+        }
+        if let Some(env_id) = &context.environment_id {
+            let env = self.get_environment(env_id)?.ok_or_else(|| format!("Environment '{}' not found", env_id))?;
+            let owning_tenant_ok = match &context.tenant_id {
+                Some(tenant_id) => &env.tenant_id == tenant_id,
+                None => user.tenants.contains(&env.tenant_id),
+            };
+            if !owning_tenant_ok {
+                return Err(format!("Environment '{}' is not in the selected tenant", env_id).into());
+            }
+        }
+        if let Some(col_id) = &context.collection_id {
+            let in_env = match &context.environment_id {
+                Some(env_id) => self.get_environment(env_id)?.is_some_and(|env| env.collections.contains(col_id)),
+                None => self.accessible_collections(username, usize::MAX)?.contains(col_id),
+            };
+            if !in_env {
+                return Err(format!("Collection '{}' is not in the selected workspace", col_id).into());
+            }
+        }
+
+        user.context = Some(context.clone());
+        self.update_user(user)?;
+        info!(username = %username, collection_id = ?context.collection_id, "User workspace context set");
+        Ok(context)
+    }
+
+    /// Collection a request targets: the explicit one if given, else the user's context collection
+    pub fn resolve_collection(&self, username: &str, explicit: Option<&str>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        if let Some(collection_id) = explicit {
+            return Ok(Some(collection_id.to_string()));
+        }
+        Ok(self
+            .get_user(username)?
+            .and_then(|user| user.context)
+            .and_then(|context| context.collection_id))
+    }
+
+    /// Resolved configuration of a collection in `env_id`: settings stored on the collection plus
+    /// server-wide ones it inherits. `None` if the collection doesn't exist in that environment.
+    #[instrument(skip(self))]
+    pub fn collection_config(
+        &self,
+        env_id: &str,
+        collection_id: &str,
+    ) -> Result<Option<EffectiveCollectionConfig>, Box<dyn std::error::Error>> {
+        let col = match self.get_collection(collection_id)? {
+            Some(col) if col.environment_id == env_id => col,
+            _ => return Ok(None),
+        };
+        let mut sources = std::collections::BTreeMap::new();
+        if col.vector_dim.is_some() {
+            sources.insert("vector_dim".to_string(), ConfigSource::Collection);
+        }
+        sources.insert("metric".to_string(), ConfigSource::Collection);
+        sources.insert("normalize".to_string(), ConfigSource::Collection);
+        for inherited in ["hnsw", "read_only"] {
+            sources.insert(inherited.to_string(), ConfigSource::Server);
+        }
+        Ok(Some(EffectiveCollectionConfig {
+            collection_id: col.id,
+            environment_id: col.environment_id,
+            vector_dim: col.vector_dim,
+            metric: col.metric,
+            hnsw: self.hnsw_params(),
+            read_only: self.is_read_only(),
+            normalize: col.normalize,
+            sources,
+        }))
+    }
+
     #[instrument(skip(self), fields(collection_id))]
     pub fn get_collection(&self, id: &str) -> Result<Option<Collection>, Box<dyn std::error::Error>> {
         debug!(collection_id = %id, "Retrieving collection");
@@ -158,3 +424,99 @@ impl Storage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn concurrent_get_or_create_yields_one_collection() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_get_or_create");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Arc::new(Storage::open(temp_dir.to_str().unwrap()).expect("open storage"));
+        storage.create_environment(Environment {
+            id: "env1".to_string(),
+            name: "env1".to_string(),
+            tenant_id: "t1".to_string(),
+            collections: vec![],
+        }).unwrap();
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let storage = storage.clone();
+                std::thread::spawn(move || {
+                    storage.get_or_create_collection(Collection {
+                        id: "shared".to_string(),
+                        name: format!("caller-{}", i),
+                        environment_id: "env1".to_string(),
+                        vector_dim: None,
+                        metric: Default::default(),
+                        normalize: false,
+                    }).unwrap()
+                })
+            })
+            .collect();
+        let results: Vec<(Collection, bool)> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(results.iter().filter(|(_, created)| *created).count(), 1);
+        let winner = storage.get_collection("shared").unwrap().unwrap();
+        assert!(results.iter().all(|(col, _)| col.name == winner.name));
+        let env = storage.get_environment("env1").unwrap().unwrap();
+        assert_eq!(env.collections, vec!["shared".to_string()]);
+
+        // Unknown environments and other environments' collections are errors, not silent successes
+        let elsewhere = |env: &str| Collection {
+            id: "shared".to_string(),
+            name: "elsewhere".to_string(),
+            environment_id: env.to_string(),
+            vector_dim: None,
+            metric: Default::default(),
+            normalize: false,
+        };
+        let err = storage.get_or_create_collection(elsewhere("missing")).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CollectionUpsertError>(),
+            Some(&CollectionUpsertError::UnknownEnvironment { environment_id: "missing".to_string() })
+        );
+        storage.create_environment(Environment {
+            id: "env2".to_string(),
+            name: "env2".to_string(),
+            tenant_id: "t1".to_string(),
+            collections: vec![],
+        }).unwrap();
+        let err = storage.get_or_create_collection(elsewhere("env2")).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<CollectionUpsertError>(),
+            Some(&CollectionUpsertError::OtherEnvironment { collection_id: "shared".to_string(), environment_id: "env1".to_string() })
+        );
+        assert!(storage.get_environment("env2").unwrap().unwrap().collections.is_empty());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+
+    #[test]
+    fn concurrent_first_registrations_yield_one_admin() {
+        let temp_dir = std::env::temp_dir().join("aidb_test_bootstrap_admin");
+        let _ = fs::remove_dir_all(&temp_dir);
+        let storage = Arc::new(Storage::open(temp_dir.to_str().unwrap()).expect("open storage"));
+        let user = |username: String| User { username, password_hash: String::new(), tenants: vec![], roles: vec![Role::Admin], context: None };
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let storage = storage.clone();
+                std::thread::spawn(move || storage.register_user(user(format!("user{}", i))).unwrap().roles)
+            })
+            .collect();
+        let roles: Vec<Vec<Role>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(roles.iter().filter(|r| **r == [Role::Admin]).count(), 1);
+        assert_eq!(roles.iter().filter(|r| **r == [Role::Reader]).count(), 7, "requested roles are ignored");
+
+        // Later registrations read, and a taken name is refused without touching the admin
+        assert_eq!(storage.register_user(user("late".to_string())).unwrap().roles, vec![Role::Reader]);
+        assert!(storage.register_user(user("late".to_string())).is_err());
+
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}